@@ -1,32 +1,27 @@
 extern crate qstore;
-use qstore::StorageEngine;
+use qstore::store::StorageEngine;
 use qstore::identifiers::{InternalID};
 
 fn main() {
     let mut s = StorageEngine::default();
     let t1 = "http://default.org/graph";
 
-    let tid = s.uri_to_internal_ID(t1).unwrap();
-
-
     let t2 = "http://default.com/testtwo";
     let t3 = "http://default.com/another/test";
     let t4 = "http://default.com/third#test";
-    let r1 = s.uri_to_internal_uri_ID(t1).unwrap();
-    let r2 = s.uri_to_internal_uri_ID(t2).unwrap();
-    let r3 = s.uri_to_internal_uri_ID(t3).unwrap();
-    let r4 = s.uri_to_internal_uri_ID(t4).unwrap();
-    let u1 = s.internal_uri_ID_to_uri(r1).unwrap();
+    let r1 = s.uri_to_internal_uri_id(t1).unwrap();
+    let r2 = s.uri_to_internal_uri_id(t2).unwrap();
+    let r3 = s.uri_to_internal_uri_id(t3).unwrap();
+    let r4 = s.uri_to_internal_uri_id(t4).unwrap();
+    let u1 = s.internal_uri_id_to_uri(&r1).unwrap();
     println!("{}", u1);
-    let u2 = s.internal_uri_ID_to_uri(r2).unwrap();
+    let u2 = s.internal_uri_id_to_uri(&r2).unwrap();
     println!("{}", u2);
-    let u3 = s.internal_uri_ID_to_uri(r3).unwrap();
+    let u3 = s.internal_uri_id_to_uri(&r3).unwrap();
     println!("{}", u3);
-    let u4 = s.internal_uri_ID_to_uri(r4).unwrap();
+    let u4 = s.internal_uri_id_to_uri(&r4).unwrap();
     println!("{}", u4);
 
-    //test_me();
-
     s.add_internal_quad(InternalID(1.into()), InternalID(2.into()), InternalID(3.into()), InternalID(4.into()));
     s.add_internal_quad(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(7.into()));
     s.add_internal_quad(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(8.into()));
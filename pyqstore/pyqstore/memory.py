@@ -32,8 +32,8 @@ class QStoreMemory(RdflibStore):
 
     def __init__(self, configuration=None, identifier=None):
         super(QStoreMemory, self).__init__()
-        # prefix/namespace map (and reverse) are still implemented as python dicts.
-        # because qstore does not have any facility to do this itself.
+        # prefix/namespace map (and reverse) are still implemented as python dicts,
+        # until the namespace methods of _PyQStore are built and tested.
         self.__prefix = dict()
         self.__namespace = dict()
         self._qstore = _PyQStore(True, True)
@@ -0,0 +1,84 @@
+extern crate qstore;
+
+use std::env;
+use std::net::TcpListener;
+use std::process;
+use std::time::Duration;
+use qstore::limits::Limits;
+use qstore::server::{Endpoint, DEFAULT_MAX_REQUEST_SIZE};
+use qstore::sparql::entailment::EntailmentRegime;
+use qstore::store::StorageEngine;
+
+const USAGE: &str = "Usage: qstore-server [--bind ADDR] [--data FILE] [--read-only] [--max-request-size BYTES]
+                     [--timeout SECONDS] [--max-rows N] [--entailment NAME] [--allow-service URL]...
+
+Serves the SPARQL 1.1 Protocol at http://ADDR/sparql and the Graph Store HTTP Protocol
+at http://ADDR/data?graph=IRI (or ?default).
+
+  --bind ADDR                Address to listen on (default 127.0.0.1:7878)
+  --data FILE                N-Quads file to load, and to save the dataset to after each update
+  --read-only                Refuse updates
+  --max-request-size BYTES   Largest request body accepted (default 1048576)
+  --timeout SECONDS          Stop queries and updates running longer, with 503 Service Unavailable
+  --max-rows N               Stop queries and updates whose intermediate results exceed N rows
+  --entailment NAME          Answer queries under simple, rdfs or owl-rl entailment by default;
+                             a request may choose another with the entailment parameter
+  --allow-service URL        Let SERVICE patterns query the endpoint at URL; may be repeated.
+                             Without it, queries cannot make the server contact other hosts";
+
+fn fail(message: &str) -> ! {
+    eprintln!("qstore-server: {}\n\n{}", message, USAGE);
+    process::exit(2)
+}
+
+fn main() {
+    let mut bind = "127.0.0.1:7878".to_owned();
+    let mut data = None;
+    let mut read_only = false;
+    let mut max_request_size = DEFAULT_MAX_REQUEST_SIZE;
+    let mut limits = Limits::new();
+    let mut entailment = EntailmentRegime::Simple;
+    let mut services = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bind" => bind = args.next().unwrap_or_else(|| fail("--bind needs an address")),
+            "--data" => data = Some(args.next().unwrap_or_else(|| fail("--data needs a file"))),
+            "--read-only" => read_only = true,
+            "--max-request-size" => {
+                let value = args.next().unwrap_or_else(|| fail("--max-request-size needs a number of bytes"));
+                max_request_size = value.parse().unwrap_or_else(|_| fail(&format!("Invalid size '{}'", value)));
+            }
+            "--timeout" => {
+                let value = args.next().unwrap_or_else(|| fail("--timeout needs a number of seconds"));
+                let seconds: f64 = value.parse().ok().filter(|s: &f64| *s >= 0.0).unwrap_or_else(|| fail(&format!("Invalid timeout '{}'", value)));
+                limits = limits.with_timeout(Duration::from_millis((seconds * 1000.0) as u64));
+            }
+            "--max-rows" => {
+                let value = args.next().unwrap_or_else(|| fail("--max-rows needs a number"));
+                limits = limits.with_max_rows(value.parse().unwrap_or_else(|_| fail(&format!("Invalid row limit '{}'", value))));
+            }
+            "--entailment" => {
+                let value = args.next().unwrap_or_else(|| fail("--entailment needs a regime"));
+                entailment = EntailmentRegime::from_name(&value).unwrap_or_else(|| fail(&format!("Unknown entailment regime '{}'", value)));
+            }
+            "--allow-service" => services.push(args.next().unwrap_or_else(|| fail("--allow-service needs an endpoint URL"))),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => fail(&format!("Unknown argument '{}'", other)),
+        }
+    }
+    let endpoint = match data {
+        Some(path) => Endpoint::open(&path).unwrap_or_else(|e| fail(&e)),
+        None => Endpoint::new(StorageEngine::default()),
+    };
+    let mut endpoint = endpoint.with_read_only(read_only).with_limits(limits).with_entailment(entailment).with_allowed_services(services);
+    let listener = TcpListener::bind(&bind).unwrap_or_else(|e| fail(&format!("Could not bind {}: {}", bind, e)));
+    eprintln!("Serving SPARQL at http://{}/sparql{}", bind, if read_only { " (read-only)" } else { "" });
+    if let Err(e) = endpoint.serve(&listener, max_request_size) {
+        eprintln!("qstore-server: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,389 @@
+extern crate qstore;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+use qstore::blank::BlankNodeScope;
+use qstore::identifiers::InternalID;
+use qstore::limits::Limits;
+use qstore::nquads::{self, Quad, Term};
+use qstore::patch::{Patch, PatchQuad, PatchRow};
+use qstore::sparql::{parser, QueryResult};
+use qstore::sparql::entailment::EntailmentRegime;
+use qstore::sparql::eval::Evaluator;
+use qstore::sparql::results::{self, ResultFormat};
+use qstore::store::{GraphID, StorageEngine};
+use qstore::turtle;
+
+const USAGE: &str = "Usage: qstore COMMAND [OPTIONS]
+
+STORE is an N-Quads file holding a persisted store. FILE may be - for standard input, and
+QUERY or UPDATE may be the text itself, @PATH to read it from a file, or - for standard input.
+
+Commands:
+  load STORE FILE...     Load RDF files into the store, creating it if needed
+  dump STORE             Write the store, or one graph of it, to standard output
+  query STORE QUERY      Run a SPARQL query
+  update STORE UPDATE    Run a SPARQL update and save the store, creating it if needed
+  explain STORE QUERY    Run a SPARQL query and print the plan it was evaluated with
+  graphs STORE           List the graphs with their quad counts
+  stats STORE            Print dictionary and index statistics
+  validate FILE...       Check that files parse, without loading them
+
+Options:
+  --graph IRI            load: put triples in this graph; dump: write only this graph
+  --default              dump: write only the default graph
+  --format NAME          load, validate and dump: nquads, ntriples or turtle (by default from the
+                         file extension, or nquads for the dataset and turtle for one graph);
+                         query: json, xml, csv, tsv, turtle or ntriples (by default tsv for
+                         solutions, json for booleans and turtle for graphs);
+                         explain: text or json (by default text)
+  --entailment NAME      query: also match what simple, rdfs or owl-rl entailment infers at query
+                         time, without materializing it (default simple)
+  --timeout SECONDS      query and update: stop after this long
+  --max-rows N           query and update: stop when an intermediate result exceeds N rows
+  --allow-service URL    query and explain: let SERVICE patterns query the endpoint at URL;
+                         may be repeated. Without it, no other endpoint is contacted";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RdfFormat {
+    NQuads,
+    NTriples,
+    Turtle,
+}
+
+impl RdfFormat {
+    fn from_name(name: &str) -> Result<RdfFormat, String> {
+        match name.to_lowercase().as_str() {
+            "nquads" | "nq" => Ok(RdfFormat::NQuads),
+            "ntriples" | "nt" => Ok(RdfFormat::NTriples),
+            "turtle" | "ttl" => Ok(RdfFormat::Turtle),
+            _ => Err(format!("Unknown RDF format '{}'; expected nquads, ntriples or turtle.", name)),
+        }
+    }
+
+    fn from_path(path: &str) -> Option<RdfFormat> {
+        match Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()) {
+            Some(ref e) if e == "nq" => Some(RdfFormat::NQuads),
+            Some(ref e) if e == "nt" => Some(RdfFormat::NTriples),
+            Some(ref e) if e == "ttl" => Some(RdfFormat::Turtle),
+            _ => None,
+        }
+    }
+}
+
+struct Options {
+    positional: Vec<String>,
+    graph: Option<String>,
+    default_graph: bool,
+    format: Option<String>,
+    limits: Limits,
+    entailment: EntailmentRegime,
+    services: Vec<String>,
+}
+
+fn parse_options<I: Iterator<Item=String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options { positional: Vec::new(), graph: None, default_graph: false, format: None, limits: Limits::new(),
+        entailment: EntailmentRegime::Simple, services: Vec::new() };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--graph" => options.graph = Some(args.next().ok_or("--graph needs an IRI")?),
+            "--default" => options.default_graph = true,
+            "--format" => options.format = Some(args.next().ok_or("--format needs a name")?),
+            "--entailment" => {
+                let value = args.next().ok_or("--entailment needs a regime")?;
+                options.entailment = EntailmentRegime::from_name(&value)
+                    .ok_or_else(|| format!("Unknown entailment regime '{}'; expected simple, rdfs or owl-rl.", value))?;
+            }
+            "--timeout" => {
+                let value = args.next().ok_or("--timeout needs a number of seconds")?;
+                let seconds: f64 = value.parse().ok().filter(|s: &f64| *s >= 0.0).ok_or_else(|| format!("Invalid timeout '{}'", value))?;
+                options.limits = options.limits.with_timeout(Duration::from_millis((seconds * 1000.0) as u64));
+            }
+            "--max-rows" => {
+                let value = args.next().ok_or("--max-rows needs a number")?;
+                options.limits = options.limits.with_max_rows(value.parse().map_err(|_| format!("Invalid row limit '{}'", value))?);
+            }
+            "--allow-service" => options.services.push(args.next().ok_or("--allow-service needs an endpoint URL")?),
+            other if other.starts_with("--") => return Err(format!("Unknown option '{}'", other)),
+            _ => options.positional.push(arg),
+        }
+    }
+    if options.graph.is_some() && options.default_graph {
+        return Err("Give only one of --graph and --default".to_owned());
+    }
+    Ok(options)
+}
+
+fn read_input(name: &str) -> Result<String, String> {
+    if name == "-" {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text).map_err(|e| format!("Could not read standard input: {}", e))?;
+        Ok(text)
+    } else {
+        fs::read_to_string(name).map_err(|e| format!("Could not read {}: {}", name, e))
+    }
+}
+
+/* The text of a QUERY or UPDATE argument. */
+fn read_request(argument: &str) -> Result<String, String> {
+    if argument == "-" {
+        read_input("-")
+    } else if let Some(path) = argument.strip_prefix('@') {
+        read_input(path)
+    } else {
+        Ok(argument.to_owned())
+    }
+}
+
+fn parse_file(name: &str, format: Option<RdfFormat>) -> Result<Vec<Quad>, String> {
+    let format = match format.or_else(|| RdfFormat::from_path(name)) {
+        Some(format) => format,
+        None if name == "-" => RdfFormat::NQuads,
+        None => return Err(format!("Cannot tell the format of {} from its extension; use --format.", name)),
+    };
+    let text = read_input(name)?;
+    match format {
+        RdfFormat::NQuads | RdfFormat::NTriples => {
+            let quads = nquads::parse(&text)?;
+            if format == RdfFormat::NTriples && quads.iter().any(|q| q.3.is_some()) {
+                return Err("N-Triples cannot name graphs; use --format nquads.".to_owned());
+            }
+            Ok(quads)
+        }
+        RdfFormat::Turtle => {
+            /* Relative IRIs resolve against the file's own location. */
+            let base = if name == "-" {
+                None
+            } else {
+                let path = fs::canonicalize(name).map_err(|e| format!("Could not resolve {}: {}", name, e))?;
+                Some(format!("file://{}", path.display()))
+            };
+            let triples = turtle::parse(&text, base.as_deref())?;
+            Ok(triples.into_iter().map(|(s, p, o)| (s, p, o, None)).collect())
+        }
+    }
+}
+
+fn open_store(path: &str, create: bool) -> Result<StorageEngine, String> {
+    if !create && !Path::new(path).exists() {
+        return Err(format!("There is no store at {}.", path));
+    }
+    nquads::open_file(Path::new(path))
+}
+
+fn positional<'a>(options: &'a Options, count: usize, what: &str) -> Result<&'a [String], String> {
+    if options.positional.len() < count {
+        Err(format!("Missing {}", what))
+    } else {
+        Ok(&options.positional)
+    }
+}
+
+fn load(options: &Options) -> Result<(), String> {
+    let args = positional(options, 2, "STORE and FILE arguments")?;
+    let format = match options.format {
+        Some(ref name) => Some(RdfFormat::from_name(name)?),
+        None => None,
+    };
+    let mut store = open_store(&args[0], true)?;
+    let target = options.graph.as_ref().map(|iri| Term::Iri(iri.clone()));
+    for file in args[1..].iter() {
+        let quads = parse_file(file, format).map_err(|e| format!("{}: {}", file, e))?;
+        let mut patch = Patch::new();
+        let mut scope = BlankNodeScope::new();
+        for (subject, predicate, object, graph) in quads {
+            if graph.is_some() && target.is_some() {
+                return Err(format!("{}: --graph cannot be used with quads that name their graph.", file));
+            }
+            patch.push(PatchRow::Add(PatchQuad { subject: subject.scoped(&mut scope), predicate,
+                object: object.scoped(&mut scope), graph: graph.or_else(|| target.clone()).map(|g| g.scoped(&mut scope)) }));
+        }
+        let added = store.apply_patch(&patch, false).map_err(|e| format!("{}: {}", file, e))?;
+        eprintln!("{}: {} quads", file, added);
+    }
+    nquads::save_file(&store, Path::new(&args[0]))
+}
+
+/* The graph selected by --graph or --default, or `None` for the whole dataset. */
+fn selected_graph(store: &StorageEngine, options: &Options) -> Result<Option<GraphID>, String> {
+    match options.graph {
+        Some(ref iri) => Term::Iri(iri.clone()).find(store).map(Some).ok_or_else(|| format!("There is no graph <{}>.", iri)),
+        None if options.default_graph => Ok(Some(InternalID(0.into()))),
+        None => Ok(None),
+    }
+}
+
+fn dump(options: &Options) -> Result<(), String> {
+    let args = positional(options, 1, "STORE argument")?;
+    let store = open_store(&args[0], false)?;
+    let graph = selected_graph(&store, options)?;
+    let format = match options.format {
+        Some(ref name) => RdfFormat::from_name(name)?,
+        None if graph.is_some() => RdfFormat::Turtle,
+        None => RdfFormat::NQuads,
+    };
+    let text = match (format, graph) {
+        (RdfFormat::NQuads, graph) => nquads::serialize(&store, graph.as_ref())?,
+        (RdfFormat::NTriples, Some(graph)) => nquads::serialize(&store, Some(&graph))?,
+        (RdfFormat::Turtle, Some(graph)) => turtle::serialize(&turtle::graph_triples(&store, &graph)?, store.borrow_namespace_manager()),
+        (_, None) => return Err("Only N-Quads can hold the whole dataset; choose a graph with --graph or --default.".to_owned()),
+    };
+    io::stdout().write_all(text.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn query(options: &Options) -> Result<(), String> {
+    let args = positional(options, 2, "STORE and QUERY arguments")?;
+    let mut store = open_store(&args[0], false)?;
+    store.set_allowed_services(Some(options.services.clone()));
+    let parsed = parser::parse_query(&read_request(&args[1])?, store.borrow_namespace_manager())?;
+    let evaluator = Evaluator::new(&store).with_limits(options.limits.clone()).with_entailment(options.entailment);
+    let result = evaluator.execute(&parsed).map_err(|e| evaluator.borrow_guard().error(e).to_string())?;
+    let format = match options.format {
+        Some(ref name) => {
+            let format = ResultFormat::from_name(name).ok_or_else(|| format!("Unknown result format '{}'.", name))?;
+            if !ResultFormat::available(&result).contains(&format) {
+                return Err(format!("This result cannot be written as {}.", format.media_type()));
+            }
+            format
+        }
+        None => match result {
+            QueryResult::Solutions { .. } => ResultFormat::Tsv,
+            QueryResult::Boolean(_) => ResultFormat::Json,
+            QueryResult::Graph(_) => ResultFormat::Turtle,
+        },
+    };
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    match (format, &result) {
+        (ResultFormat::Turtle, QueryResult::Graph(triples)) =>
+            out.write_all(turtle::serialize(triples, store.borrow_namespace_manager()).as_bytes()),
+        _ => results::write(&result, format, &mut out),
+    }.map_err(|e| e.to_string())
+}
+
+fn explain(options: &Options) -> Result<(), String> {
+    let args = positional(options, 2, "STORE and QUERY arguments")?;
+    let mut store = open_store(&args[0], false)?;
+    store.set_allowed_services(Some(options.services.clone()));
+    let plan = store.explain_query(&read_request(&args[1])?)?;
+    match options.format.as_deref().unwrap_or("text") {
+        "text" => print!("{}", plan.to_text()),
+        "json" => println!("{}", plan.to_json()),
+        other => return Err(format!("Unknown plan format '{}'; expected text or json.", other)),
+    }
+    Ok(())
+}
+
+fn update(options: &Options) -> Result<(), String> {
+    let args = positional(options, 2, "STORE and UPDATE arguments")?;
+    let mut store = open_store(&args[0], true)?;
+    let changed = store.update_with_limits(&read_request(&args[1])?, &options.limits).map_err(|e| e.to_string())?;
+    nquads::save_file(&store, Path::new(&args[0]))?;
+    eprintln!("{} quads changed", changed);
+    Ok(())
+}
+
+fn graphs(options: &Options) -> Result<(), String> {
+    let args = positional(options, 1, "STORE argument")?;
+    let store = open_store(&args[0], false)?;
+    let mut listing = Vec::new();
+    for graph in store.graph_ids() {
+        let name = if graph == InternalID(0.into()) { "DEFAULT".to_owned() } else { Term::from_node(&store, &graph)?.to_ntriples() };
+        listing.push((name, store.search_engine_internal(Some(graph), None, None, None).count()));
+    }
+    listing.sort();
+    for (name, count) in listing {
+        println!("{}\t{}", count, name);
+    }
+    Ok(())
+}
+
+fn stats(options: &Options) -> Result<(), String> {
+    let args = positional(options, 1, "STORE argument")?;
+    let statistics = open_store(&args[0], false)?.statistics();
+    println!("quads\t{}", statistics.quad_count);
+    println!("inferred quads\t{}", statistics.inferred_quad_count);
+    println!("graphs\t{}", statistics.graph_count);
+    println!("IRIs\t{}", statistics.iri_count);
+    println!("blank nodes\t{}", statistics.blank_node_count);
+    println!("literals\t{}", statistics.literal_count);
+    println!("free node ids\t{}", statistics.free_node_ids);
+    println!("IRI prefixes\t{}", statistics.iri_prefix_count);
+    println!("IRI suffixes\t{}", statistics.iri_suffix_count);
+    println!("namespaces\t{}", statistics.namespace_count);
+    for &(name, entries) in statistics.index_entries.iter() {
+        println!("{} index entries\t{}", name, entries);
+    }
+    if let Some((literals, terms)) = statistics.fulltext_index {
+        println!("full-text literals\t{}", literals);
+        println!("full-text terms\t{}", terms);
+    }
+    Ok(())
+}
+
+fn validate(options: &Options) -> Result<(), String> {
+    let files = positional(options, 1, "FILE arguments")?;
+    let format = match options.format {
+        Some(ref name) => Some(RdfFormat::from_name(name)?),
+        None => None,
+    };
+    let mut failures = 0;
+    for file in files.iter() {
+        match parse_file(file, format) {
+            Ok(quads) => println!("{}: valid, {} statements", file, quads.len()),
+            Err(e) => {
+                println!("{}: invalid: {}", file, e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        Err(format!("{} of {} files are invalid.", failures, files.len()))
+    } else {
+        Ok(())
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let command = match args.next() {
+        Some(command) => command,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("qstore: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+    let outcome = match command.as_str() {
+        "load" => load(&options),
+        "dump" => dump(&options),
+        "query" => query(&options),
+        "update" => update(&options),
+        "explain" => explain(&options),
+        "graphs" => graphs(&options),
+        "stats" => stats(&options),
+        "validate" => validate(&options),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => {
+            eprintln!("qstore: Unknown command '{}'\n\n{}", other, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(e) = outcome {
+        eprintln!("qstore: {}", e);
+        process::exit(1);
+    }
+}
@@ -1,62 +1,93 @@
-
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
 use uuid::Uuid;
+use iri::IriRef;
 use store::{StoreNode, StorageEngine};
-use identifiers::InternalID;
-use literal::Literal;
 
 static BLANK_HASH_PREFIX: &'static str = "B:";
-static BLANK_NODE_IDENTIFIER_TYPE: &'static str = "http://internal/blank";
+pub static SKOLEM_PATH: &'static str = "/.well-known/genid/";
 
 #[derive(PartialEq, PartialOrd, Clone, Debug)]
 pub struct BlankNode {
-    id: InternalID
+    label: String
 }
 
 impl Hash for BlankNode {
     fn hash<H: Hasher>(&self, state: &mut H) {
         BLANK_HASH_PREFIX.hash(state);
-        self.id.0.hash(state)
+        self.label.hash(state)
     }
 }
 
 impl BlankNode {
-    pub fn new(store: &mut StorageEngine, identifier: Option<&str>) -> BlankNode {
-        let lit = if let Some(ref i) = identifier {
-            Literal::new(store, i, Some(BLANK_NODE_IDENTIFIER_TYPE), None)
+    /// A blank node with the given store-wide label, or a fresh unique label when `None`.
+    /// Use a `BlankNodeScope` for labels that are only meaningful within one document.
+    pub fn new(identifier: Option<&str>) -> BlankNode {
+        let label = if let Some(i) = identifier {
+            i.to_owned()
         } else {
-            let new_uuid = Uuid::new_v4().to_string();
-            Literal::new(store, &new_uuid, Some(BLANK_NODE_IDENTIFIER_TYPE), None)
+            Uuid::new_v4().to_string()
         };
-        let internal_id = store.find_or_add_internal_id(StoreNode::Literal(lit)).unwrap();
-        BlankNode { id: internal_id }
+        BlankNode { label }
     }
 
+    /* Err(()) means no blank node has been issued for the identifier. */
+    #[allow(clippy::result_unit_err)]
     pub fn find_by_idenfier_if_exist(store: &StorageEngine, identifier: Option<&str>) -> Result<BlankNode, ()> {
-        let lit = if let Some(ref i) = identifier {
-            if let Ok(l) = Literal::construct_if_exist(store, i, Some(BLANK_NODE_IDENTIFIER_TYPE), None) { l }
-                else { return Result::Err(()); }
-        } else {
-            return Result::Err(());
-        };
-        let internal_id = if let Ok(i) = store.find_internal_id(&StoreNode::Literal(lit)) { i }
+        let bnode = if let Some(i) = identifier { BlankNode::new(Some(i)) }
             else { return Result::Err(()); };
-        Ok(BlankNode { id: internal_id })
-    }
-
-    pub fn lookup_identifier<'a>(&self, store: &'a StorageEngine) -> &'a str {
-        let maybe_node = store.lookup_node_by_iid(&self.id);
-        if let Ok(node) = maybe_node {
-            match node {
-                &StoreNode::URIRef(_) => { unimplemented!("Blank node with uriref identifier is not yet supported.") },
-                &StoreNode::Blank(_) => { panic!("Blank node with BNode identifier is impossible.") },
-                &StoreNode::Literal(ref lit) => {
-                    lit.borrow_lexical_form()
-                },
-            }
+        if store.find_internal_id(&StoreNode::Blank(bnode.clone())).is_ok() {
+            Ok(bnode)
         } else {
-            panic!("Identifier for that blank node does not exist!")
+            Err(())
+        }
+    }
+
+    pub fn borrow_label<'a>(&'a self) -> &'a str {
+        &self.label
+    }
+
+    /// The skolem IRI for this blank node, ie `<authority>/.well-known/genid/<label>`.
+    pub fn to_skolem_iri(&self, authority: &str) -> String {
+        format!("{}{}{}", authority.trim_end_matches('/'), SKOLEM_PATH, self.label)
+    }
+
+    /// The blank node a skolem IRI stands for, if the IRI is one: an absolute IRI whose path is
+    /// `SKOLEM_PATH` followed by the label, with no query or fragment.
+    pub fn from_skolem_iri(iri: &str) -> Option<BlankNode> {
+        let parsed = IriRef::parse_absolute(iri).ok()?;
+        if parsed.borrow_authority().is_none() || parsed.borrow_query().is_some() || parsed.borrow_fragment().is_some() {
+            return None;
+        }
+        match parsed.borrow_path().strip_prefix(SKOLEM_PATH) {
+            Some(label) if !label.is_empty() && !label.contains('/') => Some(BlankNode::new(Some(label))),
+            _ => None,
         }
     }
-}
\ No newline at end of file
+}
+
+/// Maps the blank node labels of a single document (or load) onto fresh store-wide blank nodes,
+/// so `_:b0` in two different files does not become the same node.
+#[derive(Default)]
+pub struct BlankNodeScope {
+    labels: BTreeMap<String, BlankNode>,
+}
+
+impl BlankNodeScope {
+    pub fn new() -> BlankNodeScope {
+        BlankNodeScope::default()
+    }
+
+    pub fn blank_node(&mut self, label: &str) -> BlankNode {
+        self.labels.entry(label.to_owned()).or_insert_with(|| BlankNode::new(None)).clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+}
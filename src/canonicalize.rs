@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use identifiers::InternalID;
+use nquads::quad_to_line;
+use sha256::sha256_hex;
+use store::{StorageEngine, StoreNode, InternalQuad, GraphID};
+
+/// Default bound on the work spent distinguishing blank nodes with identical neighbourhoods, counted
+/// in Hash N-Degree Quads calls plus permutations tried. Ordinary data needs a handful; crafted
+/// worst cases grow factorially and are rejected instead of running unbounded.
+pub static DEFAULT_WORK_LIMIT: usize = 100000;
+
+#[derive(Clone, Debug)]
+struct IdentifierIssuer {
+    prefix: &'static str,
+    issued: Vec<InternalID>,
+    identifiers: BTreeMap<InternalID, String>,
+}
+
+impl IdentifierIssuer {
+    fn new(prefix: &'static str) -> IdentifierIssuer {
+        IdentifierIssuer { prefix, issued: Vec::new(), identifiers: BTreeMap::new() }
+    }
+
+    fn get(&self, id: &InternalID) -> Option<&String> {
+        self.identifiers.get(id)
+    }
+
+    fn issue(&mut self, id: &InternalID) -> String {
+        if let Some(existing) = self.identifiers.get(id) {
+            return existing.clone();
+        }
+        let identifier = format!("{}{}", self.prefix, self.issued.len());
+        self.issued.push(id.clone());
+        self.identifiers.insert(id.clone(), identifier.clone());
+        identifier
+    }
+}
+
+/// The canonical form of a dataset: sorted canonical N-Quads, their SHA-256 and the canonical
+/// label (`c14n0`, `c14n1`, ...) issued to each blank node.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CanonicalDataset {
+    nquads: String,
+    hash: String,
+    labels: BTreeMap<InternalID, String>,
+}
+
+impl CanonicalDataset {
+    pub fn borrow_nquads<'a>(&'a self) -> &'a str {
+        &self.nquads
+    }
+
+    pub fn borrow_hash<'a>(&'a self) -> &'a str {
+        &self.hash
+    }
+
+    pub fn borrow_labels<'a>(&'a self) -> &'a BTreeMap<InternalID, String> {
+        &self.labels
+    }
+}
+
+/// RDF Dataset Canonicalization (RDFC-1.0) with SHA-256.
+#[derive(Clone, Debug)]
+pub struct Canonicalizer {
+    work_limit: usize,
+}
+
+impl Default for Canonicalizer {
+    fn default() -> Canonicalizer {
+        Canonicalizer { work_limit: DEFAULT_WORK_LIMIT }
+    }
+}
+
+impl Canonicalizer {
+    pub fn new() -> Canonicalizer {
+        Canonicalizer::default()
+    }
+
+    pub fn with_work_limit(mut self, work_limit: usize) -> Canonicalizer {
+        self.work_limit = work_limit;
+        self
+    }
+
+    /// Canonicalizes the asserted quads of `graph`, or of the whole dataset. A single graph is
+    /// canonicalized as triples, so its hash does not depend on the graph's name.
+    pub fn canonicalize(&self, store: &StorageEngine, graph: Option<&GraphID>) -> Result<CanonicalDataset, String> {
+        let default_graph = InternalID(0.into());
+        let quads: Vec<InternalQuad> = store.search_engine_internal(graph.cloned(), None, None, None)
+            .map(|(g, s, p, o)| (s, p, o, if graph.is_some() { default_graph.clone() } else { g }))
+            .collect();
+        let mut state = CanonicalizationState {
+            store,
+            quads,
+            include_graph: graph.is_none(),
+            blank_quads: BTreeMap::new(),
+            first_degree: BTreeMap::new(),
+            canonical: IdentifierIssuer::new("c14n"),
+            work: 0,
+            work_limit: self.work_limit,
+        };
+        state.run()
+    }
+}
+
+struct CanonicalizationState<'a> {
+    store: &'a StorageEngine,
+    quads: Vec<InternalQuad>,
+    include_graph: bool,
+    blank_quads: BTreeMap<InternalID, Vec<usize>>,
+    first_degree: BTreeMap<InternalID, String>,
+    canonical: IdentifierIssuer,
+    work: usize,
+    work_limit: usize,
+}
+
+/* The lexicographically next permutation of `indexes`, or false once they are in descending order. */
+fn next_permutation(indexes: &mut [usize]) -> bool {
+    let n = indexes.len();
+    if n < 2 {
+        return false;
+    }
+    let mut i = n - 1;
+    while i > 0 && indexes[i - 1] >= indexes[i] {
+        i -= 1;
+    }
+    if i == 0 {
+        return false;
+    }
+    let mut j = n - 1;
+    while indexes[j] <= indexes[i - 1] {
+        j -= 1;
+    }
+    indexes.swap(i - 1, j);
+    indexes[i..].reverse();
+    true
+}
+
+impl<'a> CanonicalizationState<'a> {
+    fn is_blank(&self, id: &InternalID) -> bool {
+        matches!(self.store.lookup_node_by_iid(id), Ok(&StoreNode::Blank(_)))
+    }
+
+    fn spend(&mut self) -> Result<(), String> {
+        self.work += 1;
+        if self.work > self.work_limit {
+            return Err(format!("Canonicalization exceeded the work limit of {} steps.", self.work_limit));
+        }
+        Ok(())
+    }
+
+    fn components<'q>(&self, quad: &'q InternalQuad) -> Vec<(&'q InternalID, &'static str)> {
+        let mut components = vec![(&quad.0, "s"), (&quad.2, "o")];
+        if self.include_graph {
+            components.push((&quad.3, "g"));
+        }
+        components
+    }
+
+    fn run(&mut self) -> Result<CanonicalDataset, String> {
+        for (index, quad) in self.quads.iter().enumerate() {
+            for (component, _) in self.components(quad) {
+                if self.is_blank(component) {
+                    let entry = self.blank_quads.entry(component.clone()).or_default();
+                    if entry.last() != Some(&index) {
+                        entry.push(index);
+                    }
+                }
+            }
+        }
+        let blanks: Vec<InternalID> = self.blank_quads.keys().cloned().collect();
+        let mut by_hash: BTreeMap<String, Vec<InternalID>> = BTreeMap::new();
+        for blank in blanks.iter() {
+            let hash = self.hash_first_degree(blank)?;
+            self.first_degree.insert(blank.clone(), hash.clone());
+            by_hash.entry(hash).or_default().push(blank.clone());
+        }
+        for (_, nodes) in by_hash.iter().filter(|&(_, nodes)| nodes.len() == 1) {
+            self.canonical.issue(&nodes[0]);
+        }
+        for (_, nodes) in by_hash.iter().filter(|&(_, nodes)| nodes.len() > 1) {
+            let mut results = Vec::new();
+            for node in nodes.iter() {
+                if self.canonical.get(node).is_some() {
+                    continue;
+                }
+                let mut temporary = IdentifierIssuer::new("b");
+                temporary.issue(node);
+                results.push(self.hash_n_degree(node, temporary)?);
+            }
+            results.sort_by(|a, b| a.0.cmp(&b.0));
+            for (_, issuer) in results {
+                for id in issuer.issued.iter() {
+                    self.canonical.issue(id);
+                }
+            }
+        }
+        let mut lines = Vec::new();
+        for quad in self.quads.iter() {
+            let canonical = &self.canonical;
+            lines.push(quad_to_line(self.store, quad, self.include_graph, |id, _| canonical.get(id).cloned().unwrap_or_default())?);
+        }
+        lines.sort();
+        lines.dedup();
+        let nquads = lines.concat();
+        let hash = sha256_hex(nquads.as_bytes());
+        Ok(CanonicalDataset { nquads, hash, labels: self.canonical.identifiers.clone() })
+    }
+
+    fn hash_first_degree(&self, reference: &InternalID) -> Result<String, String> {
+        let mut lines = Vec::new();
+        for &index in self.blank_quads[reference].iter() {
+            lines.push(quad_to_line(self.store, &self.quads[index], self.include_graph,
+                |id, _| if id == reference { "a".to_owned() } else { "z".to_owned() })?);
+        }
+        lines.sort();
+        Ok(sha256_hex(lines.concat().as_bytes()))
+    }
+
+    fn hash_related(&self, related: &InternalID, quad: &InternalQuad, issuer: &IdentifierIssuer, position: &str) -> Result<String, String> {
+        let identifier = if let Some(c) = self.canonical.get(related) {
+            format!("_:{}", c)
+        } else if let Some(i) = issuer.get(related) {
+            format!("_:{}", i)
+        } else {
+            self.first_degree[related].clone()
+        };
+        let mut input = position.to_owned();
+        if position != "g" {
+            let predicate = self.store.lookup_node_by_iid(&quad.1).map_err(|_| format!("No node for internal id {:?}.", quad.1))?;
+            input.push_str(&predicate.to_ntriples(self.store));
+        }
+        input.push_str(&identifier);
+        Ok(sha256_hex(input.as_bytes()))
+    }
+
+    fn hash_n_degree(&mut self, identifier: &InternalID, issuer: IdentifierIssuer) -> Result<(String, IdentifierIssuer), String> {
+        self.spend()?;
+        let mut related_hashes: BTreeMap<String, Vec<InternalID>> = BTreeMap::new();
+        for &index in self.blank_quads[identifier].iter() {
+            let quad = &self.quads[index];
+            for (component, position) in self.components(quad) {
+                if component != identifier && self.is_blank(component) {
+                    let hash = self.hash_related(component, quad, &issuer, position)?;
+                    related_hashes.entry(hash).or_default().push(component.clone());
+                }
+            }
+        }
+        let mut issuer = issuer;
+        let mut data = String::new();
+        for (hash, related) in related_hashes {
+            data.push_str(&hash);
+            let mut chosen_path = String::new();
+            let mut chosen_issuer = None;
+            let mut order: Vec<usize> = (0..related.len()).collect();
+            loop {
+                self.spend()?;
+                if let Some((path, candidate)) = self.try_permutation(&related, &order, &issuer, &chosen_path)? {
+                    if chosen_issuer.is_none() || path < chosen_path {
+                        chosen_path = path;
+                        chosen_issuer = Some(candidate);
+                    }
+                }
+                if !next_permutation(&mut order) {
+                    break;
+                }
+            }
+            data.push_str(&chosen_path);
+            issuer = chosen_issuer.unwrap_or(issuer);
+        }
+        Ok((sha256_hex(data.as_bytes()), issuer))
+    }
+
+    /* The path for one ordering of related blank nodes, or `None` once it can no longer beat `chosen_path`. */
+    fn try_permutation(&mut self, related: &[InternalID], order: &[usize], issuer: &IdentifierIssuer, chosen_path: &str) -> Result<Option<(String, IdentifierIssuer)>, String> {
+        let worse = |path: &str| !chosen_path.is_empty() && path.len() >= chosen_path.len() && path > chosen_path;
+        let mut issuer_copy = issuer.clone();
+        let mut path = String::new();
+        let mut recursion = Vec::new();
+        for &i in order.iter() {
+            let node = &related[i];
+            if let Some(c) = self.canonical.get(node) {
+                path.push_str("_:");
+                path.push_str(c);
+            } else {
+                if issuer_copy.get(node).is_none() {
+                    recursion.push(node.clone());
+                }
+                path.push_str("_:");
+                path.push_str(&issuer_copy.issue(node));
+            }
+            if worse(&path) {
+                return Ok(None);
+            }
+        }
+        for node in recursion {
+            let (hash, result_issuer) = self.hash_n_degree(&node, issuer_copy.clone())?;
+            path.push_str("_:");
+            path.push_str(&issuer_copy.issue(&node));
+            path.push('<');
+            path.push_str(&hash);
+            path.push('>');
+            issuer_copy = result_issuer;
+            if worse(&path) {
+                return Ok(None);
+            }
+        }
+        Ok(Some((path, issuer_copy)))
+    }
+}
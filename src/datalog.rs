@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use identifiers::InternalID;
+use inference::{RulePattern, RuleTerm, Bindings};
+use iri::IriRef;
+use lexer::{Token, TokenStream};
+use literal::Literal;
+use store::{StorageEngine, StoreNode, InternalQuad, GraphID};
+use vocab;
+
+/// Default graph for quads derived by user rules.
+pub static DEFAULT_RULES_GRAPH_URI: &'static str = "http://internal/graph/rules";
+
+/// One N3-style rule, `{ body } => { head } .`. The body is a conjunction of triple patterns,
+/// optionally with negated groups written `not { ... }`.
+#[derive(Clone, Debug)]
+pub struct DatalogRule {
+    index: usize,
+    body: Vec<RulePattern>,
+    negated: Vec<Vec<RulePattern>>,
+    head: Vec<RulePattern>,
+    variables: Vec<String>,
+    stratum: usize,
+}
+
+impl DatalogRule {
+    pub fn borrow_body<'a>(&'a self) -> &'a [RulePattern] {
+        &self.body
+    }
+
+    pub fn borrow_negated<'a>(&'a self) -> &'a [Vec<RulePattern>] {
+        &self.negated
+    }
+
+    pub fn borrow_head<'a>(&'a self) -> &'a [RulePattern] {
+        &self.head
+    }
+
+    pub fn borrow_variables<'a>(&'a self) -> &'a [String] {
+        &self.variables
+    }
+
+    pub fn stratum(&self) -> usize {
+        self.stratum
+    }
+}
+
+/// A parsed and stratified rule set. Rule bodies match quads in every graph of the store, including
+/// the output graph, so rules can build on each other's conclusions.
+#[derive(Clone, Debug)]
+pub struct RuleProgram {
+    rules: Vec<DatalogRule>,
+    strata: usize,
+}
+
+struct RuleParser<'s> {
+    tokens: TokenStream,
+    store: &'s mut StorageEngine,
+    prefixes: BTreeMap<String, String>,
+    variables: Vec<String>,
+}
+
+impl<'s> RuleParser<'s> {
+    fn resolve_prefixed(&self, prefix: &str, local: &str) -> Result<String, String> {
+        if let Some(namespace) = self.prefixes.get(prefix) {
+            return Ok(format!("{}{}", namespace, local));
+        }
+        self.store.borrow_namespace_manager().expand_curie(&format!("{}:{}", prefix, local))
+    }
+
+    fn literal(&mut self, lexical_form: &str, datatype: Option<&str>, lang: Option<&str>) -> Result<RuleTerm, String> {
+        if let Some(datatype) = datatype {
+            if lang.is_some() {
+                return self.tokens.error("A literal cannot have both a language tag and a datatype");
+            }
+            IriRef::parse_absolute(datatype)?;
+        }
+        let literal = Literal::new(self.store, lexical_form, datatype, lang);
+        Ok(RuleTerm::Const(self.store.find_or_add_internal_id(StoreNode::Literal(literal))?))
+    }
+
+    fn variable(&mut self, name: &str) -> RuleTerm {
+        if let Some(i) = self.variables.iter().position(|v| v == name) {
+            return RuleTerm::Var(i);
+        }
+        self.variables.push(name.to_owned());
+        RuleTerm::Var(self.variables.len() - 1)
+    }
+
+    fn term(&mut self) -> Result<RuleTerm, String> {
+        let token = if let Some(t) = self.tokens.next() { t } else { return self.tokens.error("Expected a term"); };
+        match token {
+            Token::Var(name) => Ok(self.variable(&name)),
+            Token::IriRef(iri) => Ok(RuleTerm::Const(self.store.uri_str_to_internal_id(&iri)?)),
+            Token::PrefixedName(prefix, local) => {
+                let iri = self.resolve_prefixed(&prefix, &local)?;
+                Ok(RuleTerm::Const(self.store.uri_str_to_internal_id(&iri)?))
+            }
+            Token::Word(ref w) if w == "a" => Ok(RuleTerm::Const(self.store.uri_str_to_internal_id(vocab::RDF_TYPE)?)),
+            Token::Word(ref w) if w == "true" || w == "false" => self.literal(w, Some(vocab::XSD_BOOLEAN), None),
+            Token::Integer(n) => self.literal(&n, Some(vocab::XSD_INTEGER), None),
+            Token::Decimal(n) => self.literal(&n, Some(vocab::XSD_DECIMAL), None),
+            Token::Double(n) => self.literal(&n, Some(vocab::XSD_DOUBLE), None),
+            Token::Str(value) => {
+                if let Some(Token::LangTag(lang)) = self.tokens.peek().cloned() {
+                    self.tokens.next();
+                    return self.literal(&value, None, Some(&lang));
+                }
+                if self.tokens.eat_punct("^^") {
+                    let datatype = match self.tokens.next() {
+                        Some(Token::IriRef(iri)) => iri,
+                        Some(Token::PrefixedName(prefix, local)) => self.resolve_prefixed(&prefix, &local)?,
+                        _ => return self.tokens.error("Expected a datatype IRI"),
+                    };
+                    return self.literal(&value, Some(&datatype), None);
+                }
+                self.literal(&value, None, None)
+            }
+            Token::Blank(_) => Err("Blank nodes are not supported in rules, use a variable instead.".to_owned()),
+            other => Err(format!("Unexpected {:?} where a term was expected at offset {}.", other, self.tokens.offset())),
+        }
+    }
+
+    /* Triple patterns up to the closing brace, separated by dots. */
+    fn group(&mut self, negated: Option<&mut Vec<Vec<RulePattern>>>) -> Result<Vec<RulePattern>, String> {
+        let mut patterns = Vec::new();
+        let mut negated = negated;
+        self.tokens.expect_punct("{")?;
+        while !self.tokens.eat_punct("}") {
+            if self.tokens.is_word("not") {
+                self.tokens.next();
+                let inner = self.group(None)?;
+                if let Some(ref mut groups) = negated {
+                    groups.push(inner);
+                } else {
+                    return Err("Negation is only allowed in rule bodies, and cannot be nested.".to_owned());
+                }
+            } else {
+                let s = self.term()?;
+                loop {
+                    let p = self.term()?;
+                    loop {
+                        let o = self.term()?;
+                        patterns.push(RulePattern(s.clone(), p.clone(), o));
+                        if !self.tokens.eat_punct(",") {
+                            break;
+                        }
+                    }
+                    if !self.tokens.eat_punct(";") || self.tokens.is_punct(".") || self.tokens.is_punct("}") {
+                        break;
+                    }
+                }
+            }
+            if !self.tokens.eat_punct(".") && !self.tokens.is_punct("}") {
+                return self.tokens.error("Expected \".\" or \"}\"");
+            }
+        }
+        Ok(patterns)
+    }
+
+    fn prefix_declaration(&mut self) -> Result<(), String> {
+        let prefix = match self.tokens.next() {
+            Some(Token::PrefixedName(ref prefix, ref local)) if local.is_empty() => prefix.clone(),
+            _ => return self.tokens.error("Expected a prefix name"),
+        };
+        let namespace = match self.tokens.next() {
+            Some(Token::IriRef(iri)) => iri,
+            _ => return self.tokens.error("Expected a namespace IRI"),
+        };
+        self.prefixes.insert(prefix, namespace);
+        Ok(())
+    }
+
+    fn rule(&mut self, index: usize) -> Result<DatalogRule, String> {
+        self.variables.clear();
+        let mut negated = Vec::new();
+        let body = self.group(Some(&mut negated))?;
+        self.tokens.expect_punct("=>")?;
+        let head = self.group(None)?;
+        self.tokens.eat_punct(".");
+        let rule = DatalogRule { index, body, negated, head, variables: self.variables.clone(), stratum: 0 };
+        check_safety(&rule)?;
+        Ok(rule)
+    }
+
+    fn program(&mut self) -> Result<Vec<DatalogRule>, String> {
+        let mut rules = Vec::new();
+        while !self.tokens.is_at_end() {
+            if let Some(Token::LangTag(tag)) = self.tokens.peek().cloned() {
+                if tag != "prefix" {
+                    return self.tokens.error("Unexpected directive");
+                }
+                self.tokens.next();
+                self.prefix_declaration()?;
+                self.tokens.expect_punct(".")?;
+            } else if self.tokens.eat_word("prefix") {
+                self.prefix_declaration()?;
+            } else {
+                let index = rules.len();
+                rules.push(self.rule(index)?);
+            }
+        }
+        Ok(rules)
+    }
+}
+
+fn pattern_variables(patterns: &[RulePattern], found: &mut Vec<usize>) {
+    for pattern in patterns {
+        for term in [&pattern.0, &pattern.1, &pattern.2].iter() {
+            if let &&RuleTerm::Var(v) = term {
+                found.push(v);
+            }
+        }
+    }
+}
+
+/* Every head variable must be bound by the positive body, or the rule would derive open quads. */
+fn check_safety(rule: &DatalogRule) -> Result<(), String> {
+    let mut bound = Vec::new();
+    pattern_variables(&rule.body, &mut bound);
+    let mut in_head = Vec::new();
+    pattern_variables(&rule.head, &mut in_head);
+    if let Some(v) = in_head.iter().find(|v| !bound.contains(v)) {
+        return Err(format!("Rule {}: head variable ?{} does not occur in a positive body pattern.", rule.index + 1, rule.variables[*v]));
+    }
+    Ok(())
+}
+
+fn predicate_key(pattern: &RulePattern) -> Option<&InternalID> {
+    if let RuleTerm::Const(ref p) = pattern.1 { Some(p) } else { None }
+}
+
+/* A body pattern can match a head pattern's conclusions unless both predicates are known to differ. */
+fn may_depend(body: &RulePattern, head: &RulePattern) -> bool {
+    match (predicate_key(body), predicate_key(head)) {
+        (Some(b), Some(h)) => b == h,
+        _ => true,
+    }
+}
+
+/* Assigns each rule the lowest stratum above everything it negates and at least that of what it uses. */
+fn stratify(rules: &mut [DatalogRule]) -> Result<usize, String> {
+    let mut strata = vec![0; rules.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for r in 0..rules.len() {
+            for q in 0..rules.len() {
+                for head in rules[q].head.iter() {
+                    let mut required = strata[r];
+                    if rules[r].body.iter().any(|b| may_depend(b, head)) {
+                        required = required.max(strata[q]);
+                    }
+                    if rules[r].negated.iter().any(|group| group.iter().any(|b| may_depend(b, head))) {
+                        required = required.max(strata[q] + 1);
+                    }
+                    if required > strata[r] {
+                        if required > rules.len() {
+                            return Err(format!("Rules are not stratifiable: rule {} depends negatively on itself through recursion.", r + 1));
+                        }
+                        strata[r] = required;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    for (rule, stratum) in rules.iter_mut().zip(strata.iter()) {
+        rule.stratum = *stratum;
+    }
+    Ok(strata.iter().max().map(|m| m + 1).unwrap_or(0))
+}
+
+fn resolve(term: &RuleTerm, bindings: &Bindings) -> Option<InternalID> {
+    match term {
+        &RuleTerm::Const(ref c) => Some(c.clone()),
+        &RuleTerm::Var(v) => bindings[v].clone(),
+    }
+}
+
+/// Extends `bindings` through `remaining`, most-bound pattern first, over quads in any graph.
+fn join(store: &StorageEngine, remaining: &[RulePattern], bindings: Bindings, results: &mut Vec<Bindings>) {
+    if remaining.is_empty() {
+        results.push(bindings);
+        return;
+    }
+    let next = (0..remaining.len()).max_by_key(|&i| remaining[i].bound_count(&bindings)).unwrap();
+    let pattern = &remaining[next];
+    let rest: Vec<RulePattern> = remaining.iter().enumerate()
+        .filter(|&(i, _)| i != next).map(|(_, r)| r.clone()).collect();
+    let s = resolve(&pattern.0, &bindings);
+    let p = resolve(&pattern.1, &bindings);
+    let o = resolve(&pattern.2, &bindings);
+    for (_, qs, qp, qo) in store.search_engine_internal(None, s, p, o) {
+        if let Some(extended) = pattern.unify(&qs, &qp, &qo, &bindings) {
+            join(store, &rest, extended, results);
+        }
+    }
+}
+
+fn has_match(store: &StorageEngine, patterns: &[RulePattern], bindings: &Bindings) -> bool {
+    let mut results = Vec::new();
+    join(store, patterns, bindings.clone(), &mut results);
+    !results.is_empty()
+}
+
+impl RuleProgram {
+    /// Parses N3-style rules. Prefixes come from `@prefix`/`PREFIX` declarations in the text, falling
+    /// back to the store's namespace bindings. Terms used in the rules are interned into `store`.
+    pub fn parse(store: &mut StorageEngine, text: &str) -> Result<RuleProgram, String> {
+        let mut parser = RuleParser { tokens: TokenStream::new(text)?, store, prefixes: BTreeMap::new(), variables: Vec::new() };
+        let mut rules = parser.program()?;
+        let strata = stratify(&mut rules)?;
+        Ok(RuleProgram { rules, strata })
+    }
+
+    pub fn borrow_rules<'a>(&'a self) -> &'a [DatalogRule] {
+        &self.rules
+    }
+
+    pub fn strata_count(&self) -> usize {
+        self.strata
+    }
+
+    fn conclusions(&self, rule: &DatalogRule, store: &StorageEngine, matches: Vec<Bindings>, output_graph: &GraphID, derived: &mut Vec<InternalQuad>) {
+        for bindings in matches {
+            if rule.negated.iter().any(|group| has_match(store, group, &bindings)) {
+                continue;
+            }
+            for pattern in rule.head.iter() {
+                if let Some(q) = pattern.instantiate(&bindings, output_graph) {
+                    derived.push(q);
+                }
+            }
+        }
+    }
+
+    /* Adds the derived quads that are new to the output graph and returns them as the next delta. */
+    fn assert_new(store: &mut StorageEngine, derived: Vec<InternalQuad>) -> Vec<InternalQuad> {
+        let mut delta = Vec::new();
+        for (s, p, o, g) in derived {
+            if store.search_engine_internal(Some(g.clone()), Some(s.clone()), Some(p.clone()), Some(o.clone())).next().is_none() {
+                store.add_internal_quad(g.clone(), s.clone(), p.clone(), o.clone());
+                delta.push((s, p, o, g));
+            }
+        }
+        delta
+    }
+
+    /// Replaces the contents of `output_graph` with everything the rules derive, stratum by stratum,
+    /// each evaluated semi-naively to a fixpoint. Returns the number of quads derived.
+    ///
+    /// Rules read every graph, the output graph included, so its previous contents are removed first
+    /// to keep conclusions of earlier runs from feeding this one. Every quad in it is removed, not
+    /// just earlier conclusions, so it should be a graph kept for rule output. The default graph
+    /// cannot be the output graph.
+    pub fn evaluate(&self, store: &mut StorageEngine, output_graph: &GraphID) -> Result<usize, String> {
+        if *output_graph == InternalID(0.into()) {
+            return Err("Rules cannot write their conclusions into the default graph.".to_owned());
+        }
+        let stale: Vec<InternalQuad> = store.search_engine_internal(Some(output_graph.clone()), None, None, None)
+            .map(|(g, s, p, o)| (s, p, o, g)).collect();
+        for (s, p, o, g) in stale {
+            store.remove_internal_quad(g, s, p, o);
+        }
+        let mut total = 0;
+        for stratum in 0..self.strata {
+            let rules: Vec<&DatalogRule> = self.rules.iter().filter(|r| r.stratum == stratum).collect();
+            let mut derived = Vec::new();
+            for rule in rules.iter() {
+                let mut matches = Vec::new();
+                join(store, &rule.body, vec![None; rule.variables.len()], &mut matches);
+                self.conclusions(rule, store, matches, output_graph, &mut derived);
+            }
+            let mut delta = RuleProgram::assert_new(store, derived);
+            while !delta.is_empty() {
+                total += delta.len();
+                let mut derived = Vec::new();
+                for rule in rules.iter() {
+                    let mut matches = Vec::new();
+                    for (i, pattern) in rule.body.iter().enumerate() {
+                        let rest: Vec<RulePattern> = rule.body.iter().enumerate()
+                            .filter(|&(j, _)| j != i).map(|(_, r)| r.clone()).collect();
+                        for &(ref s, ref p, ref o, _) in delta.iter() {
+                            if let Some(bindings) = pattern.unify(s, p, o, &vec![None; rule.variables.len()]) {
+                                join(store, &rest, bindings, &mut matches);
+                            }
+                        }
+                    }
+                    self.conclusions(rule, store, matches, output_graph, &mut derived);
+                }
+                delta = RuleProgram::assert_new(store, derived);
+            }
+        }
+        Ok(total)
+    }
+}
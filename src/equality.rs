@@ -0,0 +1,142 @@
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use store::{GraphID, InternalQuad};
+
+/// Union-find over internal IDs. Classes are kept flat, so every member points straight at its
+/// representative and lookups need no path compression; unions move the smaller class.
+#[derive(Default, Clone, Debug)]
+pub struct EquivalenceClasses {
+    representatives: BTreeMap<InternalID, InternalID>,
+    members: BTreeMap<InternalID, Vec<InternalID>>,
+}
+
+impl EquivalenceClasses {
+    pub fn find(&self, id: &InternalID) -> InternalID {
+        self.representatives.get(id).cloned().unwrap_or_else(|| id.clone())
+    }
+
+    /// Merges the classes of `a` and `b`. Returns the representative that was absorbed and the one
+    /// that now stands for the merged class, or `None` if they were already equivalent.
+    pub fn union(&mut self, a: &InternalID, b: &InternalID) -> Option<(InternalID, InternalID)> {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a == root_b {
+            return None;
+        }
+        let size_a = self.members.get(&root_a).map(|m| m.len()).unwrap_or(1);
+        let size_b = self.members.get(&root_b).map(|m| m.len()).unwrap_or(1);
+        let (absorbed, root) = if size_a < size_b { (root_a, root_b) } else { (root_b, root_a) };
+        let moved = self.members.remove(&absorbed).unwrap_or_else(|| vec![absorbed.clone()]);
+        for member in moved.iter() {
+            self.representatives.insert(member.clone(), root.clone());
+        }
+        self.representatives.insert(root.clone(), root.clone());
+        self.members.entry(root.clone()).or_insert_with(|| vec![root.clone()]).extend(moved);
+        Some((absorbed, root))
+    }
+
+    /// Makes each of `members` a class of its own again.
+    pub fn remove_class(&mut self, members: &[InternalID]) {
+        for member in members.iter() {
+            self.representatives.remove(member);
+            self.members.remove(member);
+        }
+    }
+
+    /// Every ID equivalent to `id`, including `id` itself.
+    pub fn aliases(&self, id: &InternalID) -> Vec<InternalID> {
+        self.members.get(&self.find(id)).cloned().unwrap_or_else(|| vec![id.clone()])
+    }
+
+    /// The number of classes with more than one member.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+/// The owl:sameAs links asserted while equality mode is on, and the classes they induce.
+/// Links are kept so a class can be rebuilt when one is removed, as union-find cannot split.
+/// The quads as they were asserted are kept too, for the stored quads that stand for quads asserted
+/// about other members of a class, so they can be told apart again when the class splits.
+#[derive(Clone, Debug)]
+pub struct EqualityState {
+    same_as: InternalID,
+    classes: EquivalenceClasses,
+    links: BTreeSet<(GraphID, InternalID, InternalID)>,
+    neighbours: BTreeMap<InternalID, BTreeSet<InternalID>>,
+    /* Keyed by the stored quad. A stored quad without an entry was asserted just as it is stored. */
+    originals: BTreeMap<InternalQuad, BTreeSet<InternalQuad>>,
+}
+
+impl EqualityState {
+    pub fn new(same_as: InternalID) -> EqualityState {
+        EqualityState { same_as, classes: EquivalenceClasses::default(), links: BTreeSet::new(), neighbours: BTreeMap::new(), originals: BTreeMap::new() }
+    }
+
+    pub fn borrow_same_as<'a>(&'a self) -> &'a InternalID {
+        &self.same_as
+    }
+
+    pub fn borrow_classes<'a>(&'a self) -> &'a EquivalenceClasses {
+        &self.classes
+    }
+
+    pub fn add_link(&mut self, graph: &GraphID, a: &InternalID, b: &InternalID) -> Option<(InternalID, InternalID)> {
+        self.links.insert((graph.clone(), a.clone(), b.clone()));
+        self.neighbours.entry(a.clone()).or_default().insert(b.clone());
+        self.neighbours.entry(b.clone()).or_default().insert(a.clone());
+        self.classes.union(a, b)
+    }
+
+    /// Removes the link between `a` and `b` in `graph`, in either direction, and rebuilds their
+    /// class from the links left between its members. Returns false if there was no such link.
+    pub fn remove_link(&mut self, graph: &GraphID, a: &InternalID, b: &InternalID) -> bool {
+        let forward = self.links.remove(&(graph.clone(), a.clone(), b.clone()));
+        let backward = self.links.remove(&(graph.clone(), b.clone(), a.clone()));
+        if !(forward || backward) {
+            return false;
+        }
+        if !self.is_linked(a, b) {
+            for &(x, y) in [(a, b), (b, a)].iter() {
+                let now_empty = self.neighbours.get_mut(x).map(|n| { n.remove(y); n.is_empty() }).unwrap_or(false);
+                if now_empty {
+                    self.neighbours.remove(x);
+                }
+            }
+        }
+        let members = self.classes.aliases(a);
+        self.classes.remove_class(&members);
+        for member in members.iter() {
+            for other in self.neighbours.get(member).cloned().unwrap_or_default() {
+                self.classes.union(member, &other);
+            }
+        }
+        true
+    }
+
+    /* Whether a link between `a` and `b` is left in some graph. */
+    fn is_linked(&self, a: &InternalID, b: &InternalID) -> bool {
+        self.links.iter().any(|&(_, ref x, ref y)| (x == a && y == b) || (x == b && y == a))
+    }
+
+    /// The quads asserted that `stored` stands for, which is `stored` itself unless other forms were.
+    pub fn originals(&self, stored: &InternalQuad) -> BTreeSet<InternalQuad> {
+        self.originals.get(stored).cloned().unwrap_or_else(|| vec![stored.clone()].into_iter().collect())
+    }
+
+    pub fn set_originals(&mut self, stored: InternalQuad, originals: BTreeSet<InternalQuad>) {
+        if originals.len() == 1 && originals.contains(&stored) {
+            self.originals.remove(&stored);
+        } else {
+            self.originals.insert(stored, originals);
+        }
+    }
+
+    /// Like `originals`, and forgets them.
+    pub fn take_originals(&mut self, stored: &InternalQuad) -> BTreeSet<InternalQuad> {
+        self.originals.remove(stored).unwrap_or_else(|| vec![stored.clone()].into_iter().collect())
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use literal::Literal;
+use store::{ObjectID, SubjectID};
+
+/// Magic predicate for query evaluators: `?s <...fulltext#match> "query"` binds subjects of matching literals.
+pub static FULLTEXT_MATCH_PREDICATE: &'static str = "http://qstore.internal/fulltext#match";
+
+static BM25_K1: f64 = 1.2;
+static BM25_B: f64 = 0.75;
+static MIN_STEM_LEN: usize = 3;
+
+/* Light suffix-stripping stemmers, longest suffixes first. */
+static EN_SUFFIXES: &'static [&'static str] = &["ational", "ization", "fulness", "iveness", "ousness",
+    "ations", "ation", "ments", "ingly", "ness", "ment", "ings", "ably", "ibly", "edly", "ing", "ies",
+    "ied", "ers", "est", "ly", "ed", "er", "es", "s"];
+static FR_SUFFIXES: &'static [&'static str] = &["issements", "issement", "atrices", "ements", "ations",
+    "atrice", "ateurs", "ement", "ation", "ateur", "euses", "ités", "euse", "ment", "ité", "eux", "ées",
+    "es", "ée", "er", "s", "e"];
+static DE_SUFFIXES: &'static [&'static str] = &["ungen", "heit", "keit", "lich", "isch", "ung", "ern",
+    "end", "em", "en", "er", "es", "e", "s"];
+static ES_SUFFIXES: &'static [&'static str] = &["amientos", "imientos", "amiento", "imiento", "aciones",
+    "ación", "mente", "adora", "ador", "ante", "anza", "idad", "ismo", "able", "ible", "ista", "osos",
+    "osas", "oso", "osa", "es", "as", "os", "a", "o", "s"];
+static IT_SUFFIXES: &'static [&'static str] = &["amenti", "imenti", "amento", "imento", "azioni",
+    "azione", "mente", "atore", "abile", "ibile", "ista", "ismo", "oso", "osa", "i", "e", "a", "o"];
+static NL_SUFFIXES: &'static [&'static str] = &["heden", "ingen", "heid", "lijk", "baar", "ing", "en",
+    "e", "s"];
+static PT_SUFFIXES: &'static [&'static str] = &["amentos", "imentos", "amento", "imento", "ações",
+    "ação", "mente", "ador", "ante", "idade", "ismo", "ável", "ível", "ista", "oso", "osa", "es", "as",
+    "os", "a", "o", "s"];
+
+fn suffixes_for_lang(lang: Option<&str>) -> &'static [&'static str] {
+    let primary = if let Some(l) = lang {
+        l.split('-').next().unwrap_or("").to_lowercase()
+    } else {
+        return &[];
+    };
+    match primary.as_str() {
+        "en" => EN_SUFFIXES,
+        "fr" => FR_SUFFIXES,
+        "de" => DE_SUFFIXES,
+        "es" => ES_SUFFIXES,
+        "it" => IT_SUFFIXES,
+        "nl" => NL_SUFFIXES,
+        "pt" => PT_SUFFIXES,
+        _ => &[],
+    }
+}
+
+/// Splits text on anything that is not alphanumeric and case-folds each token.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Stems a case-folded token using the light stemmer for the primary subtag of `lang`.
+/// Tokens in untagged or unsupported languages are returned unchanged.
+pub fn stem(token: &str, lang: Option<&str>) -> String {
+    for suffix in suffixes_for_lang(lang) {
+        if let Some(stem_part) = token.strip_suffix(suffix) {
+            if stem_part.chars().count() >= MIN_STEM_LEN {
+                return undouble(stem_part, lang);
+            }
+        }
+    }
+    token.to_owned()
+}
+
+/* English "running" -> "runn" -> "run", but "falling" keeps its "ll". */
+fn undouble(stem_part: &str, lang: Option<&str>) -> String {
+    let is_english = suffixes_for_lang(lang).as_ptr() == EN_SUFFIXES.as_ptr();
+    let mut chars = stem_part.chars().rev();
+    if let (true, Some(last), Some(prev)) = (is_english, chars.next(), chars.next()) {
+        if last == prev && !"aeioulsz".contains(last) && stem_part.chars().count() > MIN_STEM_LEN {
+            return stem_part[..stem_part.len() - last.len_utf8()].to_owned();
+        }
+    }
+    stem_part.to_owned()
+}
+
+pub fn analyze(text: &str, lang: Option<&str>) -> Vec<String> {
+    tokenize(text).iter().map(|t| stem(t, lang)).collect()
+}
+
+/* The case-folded primary subtag of a language tag, which picks the stemmer. */
+fn primary_subtag(lang: Option<&str>) -> Option<String> {
+    lang.map(|l| l.split('-').next().unwrap_or("").to_lowercase())
+}
+
+#[derive(Default)]
+pub struct FullTextIndex {
+    postings: BTreeMap<String, BTreeMap<InternalID, u32>>,
+    doc_terms: BTreeMap<InternalID, Vec<String>>,
+    /* The primary language subtag of each literal, with the number of literals in each. */
+    doc_langs: BTreeMap<InternalID, Option<String>>,
+    lang_counts: BTreeMap<Option<String>, usize>,
+    total_terms: u64,
+}
+
+impl FullTextIndex {
+    pub fn contains_literal(&self, id: &ObjectID) -> bool {
+        self.doc_terms.contains_key(id)
+    }
+
+    pub fn literal_count(&self) -> usize {
+        self.doc_terms.len()
+    }
+
+    pub fn term_count(&self) -> usize {
+        self.postings.len()
+    }
+
+    pub fn index_literal(&mut self, id: &ObjectID, literal: &Literal) {
+        if self.contains_literal(id) {
+            return;
+        }
+        let terms = analyze(literal.borrow_lexical_form(), literal.borrow_lang());
+        for term in terms.iter() {
+            let docs = self.postings.entry(term.clone()).or_default();
+            *docs.entry(id.clone()).or_insert(0) += 1;
+        }
+        self.total_terms += terms.len() as u64;
+        self.doc_terms.insert(id.clone(), terms);
+        let lang = primary_subtag(literal.borrow_lang());
+        *self.lang_counts.entry(lang.clone()).or_insert(0) += 1;
+        self.doc_langs.insert(id.clone(), lang);
+    }
+
+    pub fn unindex_literal(&mut self, id: &ObjectID) {
+        let terms = if let Some(t) = self.doc_terms.remove(id) { t } else { return; };
+        self.total_terms -= terms.len() as u64;
+        if let Some(lang) = self.doc_langs.remove(id) {
+            let now_empty = if let Some(count) = self.lang_counts.get_mut(&lang) {
+                *count -= 1;
+                *count == 0
+            } else { false };
+            if now_empty {
+                self.lang_counts.remove(&lang);
+            }
+        }
+        let unique_terms: BTreeSet<String> = terms.into_iter().collect();
+        for term in unique_terms {
+            let now_empty = if let Some(docs) = self.postings.get_mut(&term) {
+                docs.remove(id);
+                docs.is_empty()
+            } else { false };
+            if now_empty {
+                self.postings.remove(&term);
+            }
+        }
+    }
+
+    /// Returns the IDs of indexed literals matching any term of `query`, ranked by BM25 score.
+    /// The query is stemmed in the language of each literal it is matched against, so "running"
+    /// finds "runs"@en. When `lang` is given, only literals in that language match.
+    pub fn search(&self, query: &str, lang: Option<&str>) -> Vec<(ObjectID, f64)> {
+        let doc_count = self.doc_terms.len() as f64;
+        if doc_count == 0.0 {
+            return Vec::new();
+        }
+        let avg_len = self.total_terms as f64 / doc_count;
+        let only = primary_subtag(lang);
+        let mut scores: BTreeMap<InternalID, f64> = BTreeMap::new();
+        for doc_lang in self.lang_counts.keys().filter(|l| only.is_none() || **l == only) {
+            let query_terms: BTreeSet<String> = analyze(query, doc_lang.as_deref()).into_iter().collect();
+            for term in query_terms.iter() {
+                let docs = if let Some(d) = self.postings.get(term) { d } else { continue; };
+                /* Document frequency counts every literal with the term, whatever its language. */
+                let df = docs.len() as f64;
+                let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                for (doc, tf) in docs.iter() {
+                    if self.doc_langs.get(doc) != Some(doc_lang) {
+                        continue;
+                    }
+                    let tf = *tf as f64;
+                    let doc_len = self.doc_terms.get(doc).map(|t| t.len()).unwrap_or(0) as f64;
+                    let norm = tf + BM25_K1 * (1.0 - BM25_B + BM25_B * doc_len / avg_len);
+                    *scores.entry(doc.clone()).or_insert(0.0) += idf * tf * (BM25_K1 + 1.0) / norm;
+                }
+            }
+        }
+        let mut ranked: Vec<(ObjectID, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+        ranked
+    }
+}
+
+pub fn rank_subjects(scores: BTreeMap<SubjectID, f64>) -> Vec<(SubjectID, f64)> {
+    let mut ranked: Vec<(SubjectID, f64)> = scores.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then_with(|| a.0.cmp(&b.0)));
+    ranked
+}
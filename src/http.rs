@@ -0,0 +1,402 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::num::IntErrorKind;
+use std::time::{Duration, Instant};
+
+/// Largest request head (request line and headers) that will be read.
+pub const MAX_HEAD_SIZE: usize = 65536;
+/// Longest chunk-size line, with any chunk extensions, that will be read.
+pub const MAX_CHUNK_LINE_SIZE: usize = 4096;
+/// Largest response body the built-in client accepts by default.
+pub const DEFAULT_MAX_RESPONSE_SIZE: usize = 64 << 20;
+
+/// A parsed HTTP/1.x request. Header names are lower-cased and the query string is percent-decoded.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers.iter().find(|&&(ref n, _)| *n == name).map(|&(_, ref v)| v.as_str())
+    }
+
+    /// The first value of the query string parameter `name`.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| v.as_str())
+    }
+
+    pub fn content_type(&self) -> Option<String> {
+        media_type(&self.headers)
+    }
+}
+
+/// The media type of a message body, lower-cased and without parameters, from its headers.
+pub fn media_type(headers: &[(String, String)]) -> Option<String> {
+    headers.iter().find(|&&(ref n, _)| n.eq_ignore_ascii_case("content-type"))
+        .map(|&(_, ref t)| t.split(';').next().unwrap_or("").trim().to_lowercase())
+}
+
+/// Decodes `%XX` escapes, and `+` as a space in form data. Invalid escapes are kept as written.
+pub fn percent_decode(s: &str, plus_as_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                match s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(b) => {
+                        decoded.push(b);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => decoded.push(b' '),
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Splits `application/x-www-form-urlencoded` data, or a query string, into decoded pairs.
+pub fn parse_form(s: &str) -> Vec<(String, String)> {
+    s.split('&').filter(|p| !p.is_empty()).map(|pair| {
+        let mut parts = pair.splitn(2, '=');
+        let name = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("");
+        (percent_decode(name, true), percent_decode(value, true))
+    }).collect()
+}
+
+/* Reads one CRLF- or LF-terminated line, counting it against the head limit. */
+fn read_line<R: BufRead>(reader: &mut R, remaining: &mut usize) -> Result<Option<String>, (u16, String)> {
+    let mut line = Vec::new();
+    let read = reader.by_ref().take(*remaining as u64 + 1).read_until(b'\n', &mut line)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => (408, "Timed out before the whole message arrived.".to_owned()),
+            _ => (400, format!("Could not read request: {}", e)),
+        })?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read > *remaining {
+        return Err((431, "Request header fields too large.".to_owned()));
+    }
+    *remaining -= read;
+    while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map(Some).map_err(|_| (400, "Request head is not valid UTF-8.".to_owned()))
+}
+
+/* Reads a chunk-size line, or the line break after chunk data, each line having its own limit. */
+fn read_chunk_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, (u16, String)> {
+    let mut remaining = MAX_CHUNK_LINE_SIZE;
+    read_line(reader, &mut remaining).map_err(|(status, message)| match status {
+        431 => (400, format!("Chunk size line exceeds {} bytes.", MAX_CHUNK_LINE_SIZE)),
+        _ => (status, message),
+    })
+}
+
+/* The chunk data is bounded by `max_body`, and the trailer fields by what is left of the head limit. */
+fn read_chunked<R: BufRead>(reader: &mut R, max_body: usize, remaining: &mut usize) -> Result<Vec<u8>, (u16, String)> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_chunk_line(reader)?.ok_or((400, "Truncated chunked body.".to_owned()))?;
+        let size_text = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_text, 16).map_err(|e| match *e.kind() {
+            IntErrorKind::PosOverflow => (413, format!("Body exceeds the limit of {} bytes.", max_body)),
+            _ => (400, format!("Invalid chunk size '{}'.", size_text)),
+        })?;
+        if size == 0 {
+            /* Skip any trailer fields. */
+            while let Some(trailer) = read_line(reader, remaining)? {
+                if trailer.is_empty() {
+                    break;
+                }
+            }
+            return Ok(body);
+        }
+        if body.len().checked_add(size).map(|total| total > max_body).unwrap_or(true) {
+            return Err((413, format!("Body exceeds the limit of {} bytes.", max_body)));
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..]).map_err(|_| (400, "Truncated chunked body.".to_owned()))?;
+        if read_chunk_line(reader)? != Some(String::new()) {
+            return Err((400, "Chunk data is not followed by a line break.".to_owned()));
+        }
+    }
+}
+
+/* Header fields up to the blank line ending the head, with lower-cased names. */
+fn read_headers<R: BufRead>(reader: &mut R, remaining: &mut usize) -> Result<Vec<(String, String)>, (u16, String)> {
+    let mut headers = Vec::new();
+    loop {
+        let line = read_line(reader, remaining)?.ok_or((400, "Truncated message head.".to_owned()))?;
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        let colon = line.find(':').ok_or((400, format!("Malformed header '{}'.", line)))?;
+        headers.push((line[..colon].trim().to_lowercase(), line[colon + 1..].trim().to_owned()));
+    }
+}
+
+/// Reads a request from `reader`, refusing bodies larger than `max_body` bytes. Returns `None`
+/// when the connection closes before a request starts, and on failure the status code to reply with.
+pub fn read_request<R: BufRead>(reader: &mut R, max_body: usize) -> Result<Option<Request>, (u16, String)> {
+    let mut remaining = MAX_HEAD_SIZE;
+    let request_line = match read_line(reader, &mut remaining)? {
+        Some(line) => line,
+        None => return Ok(None),
+    };
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    if parts.len() != 3 || !parts[2].starts_with("HTTP/1.") {
+        return Err((400, format!("Malformed request line '{}'.", request_line)));
+    }
+    let headers = read_headers(reader, &mut remaining)?;
+    let (path, query) = match parts[1].find('?') {
+        Some(i) => (&parts[1][..i], parse_form(&parts[1][i + 1..])),
+        None => (parts[1], Vec::new()),
+    };
+    let mut request = Request {
+        method: parts[0].to_uppercase(),
+        path: percent_decode(path, false),
+        query: query,
+        headers: headers,
+        body: Vec::new(),
+    };
+    let chunked = request.header("transfer-encoding").map(|t| t.to_lowercase().contains("chunked")).unwrap_or(false);
+    if chunked {
+        request.body = read_chunked(reader, max_body, &mut remaining)?;
+    } else if let Some(length) = request.header("content-length").map(|l| l.to_owned()) {
+        let length = length.parse::<usize>().map_err(|e| match *e.kind() {
+            IntErrorKind::PosOverflow => (413, format!("Request body exceeds the limit of {} bytes.", max_body)),
+            _ => (400, format!("Invalid Content-Length '{}'.", length)),
+        })?;
+        if length > max_body {
+            return Err((413, format!("Request body exceeds the limit of {} bytes.", max_body)));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).map_err(|_| (400, "Truncated request body.".to_owned()))?;
+        request.body = body;
+    }
+    Ok(Some(request))
+}
+
+/// A TCP stream whose reads share one deadline, so that a peer sending a byte at a time cannot
+/// hold the connection past it.
+pub struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl DeadlineStream {
+    pub fn new(stream: TcpStream, timeout: Duration) -> DeadlineStream {
+        DeadlineStream::until(stream, Instant::now() + timeout)
+    }
+
+    pub fn until(stream: TcpStream, deadline: Instant) -> DeadlineStream {
+        DeadlineStream { stream: stream, deadline: deadline }
+    }
+
+    /// The time left before the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.remaining();
+        if remaining == Duration::from_secs(0) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the deadline passed"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        406 => "Not Acceptable",
+        408 => "Request Timeout",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|&&(ref n, _)| n.eq_ignore_ascii_case(name)).map(|&(_, ref v)| v.as_str())
+    }
+
+    pub fn content_type(&self) -> Option<String> {
+        media_type(&self.headers)
+    }
+
+    pub fn new(status: u16) -> Response {
+        Response { status: status, headers: Vec::new(), body: Vec::new() }
+    }
+
+    /// A plain text response, used for errors.
+    pub fn text(status: u16, message: &str) -> Response {
+        Response::new(status).with_header("Content-Type", "text/plain; charset=utf-8").with_body(format!("{}\n", message).into_bytes())
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn with_body(mut self, body: Vec<u8>) -> Response {
+        self.body = body;
+        self
+    }
+
+    /// Writes the response, leaving out the body for `HEAD` requests. Connections are not reused.
+    pub fn write_to<W: Write>(&self, out: &mut W, head_only: bool) -> io::Result<()> {
+        write!(out, "HTTP/1.1 {} {}\r\n", self.status, reason_phrase(self.status))?;
+        for &(ref name, ref value) in self.headers.iter() {
+            write!(out, "{}: {}\r\n", name, value)?;
+        }
+        write!(out, "Content-Length: {}\r\nConnection: close\r\n\r\n", self.body.len())?;
+        if !head_only {
+            out.write_all(&self.body)?;
+        }
+        out.flush()
+    }
+}
+
+/// Reads a response from `reader`, refusing bodies larger than `max_body` bytes. Without a length
+/// or chunked encoding, the body runs to the end of the connection.
+pub fn read_response<R: BufRead>(reader: &mut R, max_body: usize) -> Result<Response, String> {
+    let mut remaining = MAX_HEAD_SIZE;
+    let status_line = read_line(reader, &mut remaining).map_err(|(_, e)| e)?.ok_or("The connection closed without a response.")?;
+    let parts: Vec<&str> = status_line.splitn(3, ' ').collect();
+    let status = match (parts.first(), parts.get(1).and_then(|s| s.parse::<u16>().ok())) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/1.") => status,
+        _ => return Err(format!("Malformed status line '{}'.", status_line)),
+    };
+    let mut response = Response::new(status);
+    response.headers = read_headers(reader, &mut remaining).map_err(|(_, e)| e)?;
+    let chunked = response.header("transfer-encoding").map(|t| t.to_lowercase().contains("chunked")).unwrap_or(false);
+    if chunked {
+        response.body = read_chunked(reader, max_body, &mut remaining).map_err(|(_, e)| e)?;
+    } else if let Some(length) = response.header("content-length").map(|l| l.to_owned()) {
+        let length = length.parse::<usize>().map_err(|_| format!("Invalid Content-Length '{}'.", length))?;
+        if length > max_body {
+            return Err(format!("Body exceeds the limit of {} bytes.", max_body));
+        }
+        response.body = vec![0; length];
+        reader.read_exact(&mut response.body).map_err(|_| "Truncated response body.".to_owned())?;
+    } else {
+        reader.by_ref().take(max_body as u64 + 1).read_to_end(&mut response.body).map_err(|e| format!("Could not read response: {}", e))?;
+        if response.body.len() > max_body {
+            return Err(format!("Body exceeds the limit of {} bytes.", max_body));
+        }
+    }
+    Ok(response)
+}
+
+/// Splits an absolute URL into its scheme, authority, and path with query, which is `/` if empty.
+pub fn split_url(url: &str) -> Result<(String, String, String), String> {
+    let colon = url.find("://").ok_or_else(|| format!("'{}' is not an absolute URL.", url))?;
+    let rest = &url[colon + 3..];
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let target = rest[end..].split('#').next().unwrap_or("");
+    let target = if target.starts_with('/') { target.to_owned() } else { format!("/{}", target) };
+    Ok((url[..colon].to_lowercase(), rest[..end].to_owned(), target))
+}
+
+/// Sends HTTP requests for the store, such as the sub-queries of SPARQL `SERVICE` patterns.
+/// Give the store another client to route them elsewhere, as to a stand-in endpoint in tests.
+pub trait HttpClient {
+    /// Sends a request to an absolute URL, returning the response whatever its status.
+    fn send(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8]) -> Result<Response, String>;
+
+    /// Sends a request like `send`, giving up after `timeout` where the client can. The default
+    /// ignores the timeout.
+    fn send_with_timeout(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8], timeout: Duration) -> Result<Response, String> {
+        let _ = timeout;
+        self.send(method, url, headers, body)
+    }
+}
+
+/// The default client: HTTP/1.1 over plain TCP, one connection per request. It cannot fetch
+/// `https` URLs; supply a client of your own for those.
+#[derive(Clone, Debug)]
+pub struct TcpHttpClient {
+    pub timeout: Duration,
+    pub max_response_size: usize,
+}
+
+impl Default for TcpHttpClient {
+    fn default() -> TcpHttpClient {
+        TcpHttpClient { timeout: Duration::from_secs(60), max_response_size: DEFAULT_MAX_RESPONSE_SIZE }
+    }
+}
+
+impl HttpClient for TcpHttpClient {
+    fn send(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8]) -> Result<Response, String> {
+        self.send_with_timeout(method, url, headers, body, self.timeout)
+    }
+
+    /// Uses the shorter of `timeout` and the client's own timeout as a deadline for the whole
+    /// exchange, from connecting to reading the last byte of the response.
+    fn send_with_timeout(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8], timeout: Duration) -> Result<Response, String> {
+        let deadline = Instant::now() + ::std::cmp::min(timeout, self.timeout);
+        /* Sockets refuse a zero timeout, so one that has run out is an error of its own. */
+        let remaining = || match deadline.saturating_duration_since(Instant::now()) {
+            left if left > Duration::from_secs(0) => Ok(left),
+            _ => Err(format!("Timed out waiting for {}.", url)),
+        };
+        let (scheme, authority, target) = split_url(url)?;
+        if scheme != "http" {
+            return Err(format!("The built-in HTTP client cannot fetch {} URLs.", scheme));
+        }
+        let host = authority.rsplit('@').next().unwrap_or("");
+        let has_port = host.rsplit(':').next().map(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()) && p.len() < host.len()).unwrap_or(false);
+        let address = if has_port && !host.ends_with(']') { host.to_owned() } else { format!("{}:80", host) };
+        let failed = |e: io::Error| format!("Could not reach {}: {}", url, e);
+        let socket = address.to_socket_addrs().map_err(&failed)?.next().ok_or_else(|| format!("Could not resolve {}.", host))?;
+        let mut stream = TcpStream::connect_timeout(&socket, remaining()?).map_err(&failed)?;
+        stream.set_write_timeout(Some(remaining()?)).map_err(&failed)?;
+        let mut head = format!("{} {} HTTP/1.1\r\nHost: {}\r\n", method, target, host);
+        for &(ref name, ref value) in headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", body.len()));
+        stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(body)).and_then(|_| stream.flush()).map_err(&failed)?;
+        read_response(&mut BufReader::new(DeadlineStream::until(stream, deadline)), self.max_response_size)
+    }
+}
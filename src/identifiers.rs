@@ -3,7 +3,7 @@ use std::hash::{Hash};
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Hash, Debug)]
 pub struct InternalUriID(pub ThirtyTwoBitID, pub ThirtyTwoBitID);
 
-#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Hash, Debug)]
 pub struct InternalID(pub SixtyFourBitID);
 
 impl InternalID {
@@ -25,7 +25,7 @@ impl ThirtyTwoBitID {
         ThirtyTwoBitID(0)
     }
     pub const fn max_value() -> Self {
-        ThirtyTwoBitID(u32::max_value())
+        ThirtyTwoBitID(u32::MAX)
     }
     pub const MIN: Self = Self::min_value();
     pub const MAX: Self = Self::max_value();
@@ -55,7 +55,7 @@ impl SixtyFourBitID {
         SixtyFourBitID(0)
     }
     pub const fn max_value() -> Self {
-        SixtyFourBitID(u64::max_value())
+        SixtyFourBitID(u64::MAX)
     }
     pub const MIN: Self = Self::min_value();
     pub const MAX: Self = Self::max_value();
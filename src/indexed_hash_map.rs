@@ -2,7 +2,6 @@ use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 use std::collections::BTreeMap;
 use std::borrow::Borrow;
-use std::ops::Deref;
 
 pub type HashResult = u64;
 
@@ -32,8 +31,18 @@ impl<K, V> Default for IndexedIDHashMap<K, V> {
 
 impl<K, V: IndexedID> IndexedIDHashMap<K, V> {
     #[inline]
-    pub fn get_id_by_key<Q: ?Sized>(&self, key: &Q) -> Option<&V>
-        where K: Borrow<Q>, Q: Hash {
+    pub fn len(&self) -> usize {
+        self.inner_map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner_map.is_empty()
+    }
+
+    #[inline]
+    pub fn get_id_by_key<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: Hash + ?Sized {
         let key_hash = make_hash(key);
         self.inner_map.get(&key_hash)
     }
@@ -50,8 +59,8 @@ impl<K, V: IndexedID> IndexedIDHashMap<K, V> {
     }
 
     #[inline]
-    pub fn remove_by_key<Q: ?Sized>(&mut self, key: &Q) -> Result<(), String>
-        where K: Borrow<Q>, Q: Hash {
+    pub fn remove_by_key<Q>(&mut self, key: &Q) -> Result<(), String>
+        where K: Borrow<Q>, Q: Hash + ?Sized {
         let key_hash = make_hash(key);
         self.remove_by_key_hash(key_hash)
     }
@@ -62,6 +71,16 @@ impl<K, V: IndexedID> IndexedIDHashMap<K, V> {
         self.inner_index.get(Into::<usize>::into(id_copy))
     }
 
+    /// The keys currently stored, in no particular order.
+    pub fn keys<'a>(&'a self) -> Box<Iterator<Item=&'a K> + 'a> {
+        Box::new(self.inner_map.values().filter_map(move |id| self.get_key_ref_by_id(id)))
+    }
+
+    /// The number of freed IDs waiting to be reused.
+    pub fn free_id_count(&self) -> usize {
+        self.reuse_pool.len()
+    }
+
 }
 
 
@@ -69,7 +88,7 @@ impl <K: Hash, V: IndexedID> IndexedIDHashMap<K, V> {
 
     pub fn remove_by_id(&mut self, id: &V) -> Result<(),String> {
         let key_hash = {
-            let maybe_key_ref = self.get_key_ref_by_id(id.into());
+            let maybe_key_ref = self.get_key_ref_by_id(id);
             if let Some(key_ref) = maybe_key_ref {
                 make_hash(key_ref)
             } else {
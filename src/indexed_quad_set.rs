@@ -1,11 +1,11 @@
-use std::ops::{RangeInclusive, Range};
+use std::ops::RangeInclusive;
 use std::marker::PhantomData;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::collections::btree_set::Range as BTreeSetRange;
-use std::borrow::Borrow;
+use std::iter;
 
 use identifiers::{InternalID};
-use store::{InternalQuad, SubjectID, PredicateID, ObjectID, GraphID};
+use store::{SubjectID, PredicateID, ObjectID, GraphID};
 
 pub trait IndexOrder<A, B, C, D>: Eq+Ord+Clone+Sized {
     fn make_full_range() -> RangeInclusive<Self>;
@@ -24,27 +24,27 @@ impl IndexOrder<SubjectID, PredicateID, ObjectID, GraphID> for SPOG {
     fn make_full_range() -> RangeInclusive<SPOG> {
         let min_spog = SPOG(SubjectID::MIN, PredicateID::MIN, ObjectID::MIN, GraphID::MIN);
         let max_spog = SPOG(SubjectID::MAX, PredicateID::MAX, ObjectID::MAX, GraphID::MAX);
-        RangeInclusive { start: min_spog, end: max_spog }
+        RangeInclusive::new(min_spog, max_spog)
     }
     fn make_one_part_range(part1: &SubjectID) -> RangeInclusive<SPOG> {
         let min_spog = SPOG(part1.clone(), PredicateID::MIN, ObjectID::MIN, GraphID::MIN);
         let max_spog = SPOG(part1.clone(), PredicateID::MAX, ObjectID::MAX, GraphID::MAX);
-        RangeInclusive { start: min_spog, end: max_spog }
+        RangeInclusive::new(min_spog, max_spog)
     }
     fn make_two_part_range(part1: &SubjectID, part2: &ObjectID) -> RangeInclusive<SPOG> {
         let min_spog = SPOG(part1.clone(), part2.clone(), ObjectID::MIN, GraphID::MIN);
         let max_spog = SPOG(part1.clone(), part2.clone(), ObjectID::MAX, GraphID::MAX);
-        RangeInclusive { start: min_spog, end: max_spog }
+        RangeInclusive::new(min_spog, max_spog)
     }
     fn make_three_part_range(part1: &SubjectID, part2: &ObjectID, part3: &PredicateID) -> RangeInclusive<SPOG> {
         let min_spog = SPOG(part1.clone(), part2.clone(), part3.clone(), GraphID::MIN);
         let max_spog = SPOG(part1.clone(), part2.clone(), part3.clone(), GraphID::MAX);
-        RangeInclusive { start: min_spog, end: max_spog }
+        RangeInclusive::new(min_spog, max_spog)
     }
     fn make_four_part_range(part1: &SubjectID, part2: &ObjectID, part3: &PredicateID, part4: &GraphID) -> RangeInclusive<SPOG> {
         let min_spog = SPOG(part1.clone(), part2.clone(), part3.clone(), part4.clone());
         let max_spog = min_spog.clone();
-        RangeInclusive { start: min_spog, end: max_spog }
+        RangeInclusive::new(min_spog, max_spog)
     }
     fn build_from_ref_parts(part1: &SubjectID, part2: &ObjectID, part3: &PredicateID, part4: &GraphID) -> SPOG {
         SPOG(part1.clone(), part2.clone(), part3.clone(), part4.clone())
@@ -63,27 +63,27 @@ impl IndexOrder<GraphID, SubjectID, PredicateID, ObjectID> for GSPO {
     fn make_full_range() -> RangeInclusive<GSPO> {
         let min = GSPO(GraphID::MIN, SubjectID::MIN, PredicateID::MIN, ObjectID::MIN);
         let max = GSPO(GraphID::MAX, SubjectID::MAX, PredicateID::MAX, ObjectID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_one_part_range(part1: &GraphID) -> RangeInclusive<GSPO> {
         let min = GSPO(part1.clone(), SubjectID::MIN, PredicateID::MIN, ObjectID::MIN);
         let max = GSPO(part1.clone(), SubjectID::MAX, PredicateID::MAX, ObjectID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_two_part_range(part1: &GraphID, part2: &SubjectID) -> RangeInclusive<GSPO> {
         let min = GSPO(part1.clone(), part2.clone(), PredicateID::MIN, ObjectID::MIN);
         let max = GSPO(part1.clone(), part2.clone(), PredicateID::MAX, ObjectID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_three_part_range(part1: &GraphID, part2: &SubjectID, part3: &PredicateID) -> RangeInclusive<GSPO> {
         let min = GSPO(part1.clone(), part2.clone(), part3.clone(), ObjectID::MIN);
         let max = GSPO(part1.clone(), part2.clone(), part3.clone(), ObjectID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_four_part_range(part1: &GraphID, part2: &SubjectID, part3: &PredicateID, part4: &ObjectID) -> RangeInclusive<GSPO> {
         let min = GSPO(part1.clone(), part2.clone(), part3.clone(), part4.clone());
         let max = min.clone();
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn build_from_ref_parts(part1: &GraphID, part2: &SubjectID, part3: &PredicateID, part4: &ObjectID) -> GSPO {
         GSPO(part1.clone(), part2.clone(), part3.clone(), part4.clone())
@@ -102,27 +102,27 @@ impl IndexOrder<PredicateID, ObjectID, SubjectID, GraphID> for POSG {
     fn make_full_range() -> RangeInclusive<POSG> {
         let min = POSG(PredicateID::MIN, ObjectID::MIN, SubjectID::MIN, GraphID::MIN);
         let max = POSG(PredicateID::MAX, ObjectID::MAX, SubjectID::MAX, GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_one_part_range(part1: &PredicateID) -> RangeInclusive<POSG> {
         let min = POSG(part1.clone(), ObjectID::MIN, SubjectID::MIN, GraphID::MIN);
         let max = POSG(part1.clone(), ObjectID::MAX, SubjectID::MAX, GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_two_part_range(part1: &PredicateID, part2: &ObjectID) -> RangeInclusive<POSG> {
         let min = POSG(part1.clone(), part2.clone(), SubjectID::MIN, GraphID::MIN);
         let max = POSG(part1.clone(), part2.clone(), SubjectID::MAX, GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_three_part_range(part1: &PredicateID, part2: &ObjectID, part3: &SubjectID) -> RangeInclusive<POSG> {
         let min = POSG(part1.clone(), part2.clone(), part3.clone(), GraphID::MIN);
         let max = POSG(part1.clone(), part2.clone(), part3.clone(), GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_four_part_range(part1: &PredicateID, part2: &ObjectID, part3: &SubjectID, part4: &GraphID) -> RangeInclusive<POSG> {
         let min = POSG(part1.clone(), part2.clone(), part3.clone(), part4.clone());
         let max = min.clone();
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn build_from_ref_parts(part1: &PredicateID, part2: &ObjectID, part3: &SubjectID, part4: &GraphID) -> POSG {
         POSG(part1.clone(), part2.clone(), part3.clone(), part4.clone())
@@ -141,27 +141,27 @@ impl IndexOrder<ObjectID, SubjectID, PredicateID, GraphID> for OSPG {
     fn make_full_range() -> RangeInclusive<OSPG> {
         let min = OSPG(ObjectID::MIN, SubjectID::MIN, PredicateID::MIN, GraphID::MIN);
         let max = OSPG(ObjectID::MAX, SubjectID::MAX, PredicateID::MAX, GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_one_part_range(part1: &ObjectID) -> RangeInclusive<OSPG> {
         let min = OSPG(part1.clone(), SubjectID::MIN, PredicateID::MIN, GraphID::MIN);
         let max = OSPG(part1.clone(), SubjectID::MAX, PredicateID::MAX, GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_two_part_range(part1: &ObjectID, part2: &SubjectID) -> RangeInclusive<OSPG> {
         let min = OSPG(part1.clone(), part2.clone(), PredicateID::MIN, GraphID::MIN);
         let max = OSPG(part1.clone(), part2.clone(), PredicateID::MAX, GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_three_part_range(part1: &ObjectID, part2: &SubjectID, part3: &PredicateID) -> RangeInclusive<OSPG> {
         let min = OSPG(part1.clone(), part2.clone(), part3.clone(), GraphID::MIN);
         let max = OSPG(part1.clone(), part2.clone(), part3.clone(), GraphID::MAX);
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn make_four_part_range(part1: &ObjectID, part2: &SubjectID, part3: &PredicateID, part4: &GraphID) -> RangeInclusive<OSPG> {
         let min = OSPG(part1.clone(), part2.clone(), part3.clone(), part4.clone());
         let max = min.clone();
-        RangeInclusive { start: min, end: max }
+        RangeInclusive::new(min, max)
     }
     fn build_from_ref_parts(part1: &ObjectID, part2: &SubjectID, part3: &PredicateID, part4: &GraphID) -> OSPG {
         OSPG(part1.clone(), part2.clone(), part3.clone(), part4.clone())
@@ -212,14 +212,18 @@ impl<A, B, C, D, Q: IndexOrder<A, B, C, D>> IndexedQuadSet<A, B, C, D, Q> {
     pub fn remove_entry(&mut self, entry: &Q) -> bool {
         self.inner_map.remove(entry)
     }
+    pub fn len(&self) -> usize {
+        self.inner_map.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inner_map.is_empty()
+    }
 }
 
 impl<A, B, C, D, Q:IndexOrder<A, B, C, D>> SearchableIndex<A, B, C, D, Q> for IndexedQuadSet<A, B, C, D, Q> {
     fn find_exact_struct(&self, struct_param: Q)  -> Option<Q> {
-        let range: RangeInclusive<Q> = RangeInclusive{ start: struct_param.clone(), end: struct_param.clone() };
-        return if let Some(s) = self.inner_map.range(range).next() {
-            Some(struct_param)
-        } else { None }
+        let range: RangeInclusive<Q> = RangeInclusive::new(struct_param.clone(), struct_param.clone());
+        self.inner_map.range(range).next().map(|_s| struct_param)
     }
     fn find_exact_match(&self, param1: &A, param2: &B, param3: &C, param4: &D) -> Option<Q> {
         let index_struct = Q::build_from_ref_parts(param1, param2, param3, param4);
@@ -245,32 +249,351 @@ pub type GSPOIndex = IndexedQuadSet<GraphID, SubjectID, PredicateID, ObjectID, G
 pub type POSGIndex = IndexedQuadSet<PredicateID, ObjectID, SubjectID, GraphID, POSG>;
 pub type OSPGIndex = IndexedQuadSet<ObjectID, SubjectID, PredicateID, GraphID, OSPG>;
 
-pub fn test_me() -> () {
-    let mut SPOGSet = SPOGIndex::default();
-
-    SPOGSet.add_entry(SPOG(InternalID(1.into()), InternalID(2.into()), InternalID(3.into()), InternalID(4.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(7.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(8.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(9.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(10.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(11.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(12.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(13.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(14.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(15.into())));
-    SPOGSet.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(16.into())));
-    SPOGSet.add_entry(SPOG(InternalID(99.into()), InternalID(100.into()), InternalID(101.into()), InternalID(102.into())));
-    SPOGSet.add_entry(SPOG(InternalID(105.into()), InternalID(106.into()), InternalID(107.into()), InternalID(108.into())));
-    SPOGSet.add_entry(SPOG(InternalID(7.into()), InternalID(8.into()), InternalID(9.into()), InternalID(10.into())));
-    SPOGSet.add_entry(SPOG(InternalID(10.into()), InternalID(11.into()), InternalID(12.into()), InternalID(13.into())));
-
-    let res = SPOGSet.find_exact_match(&InternalID(99.into()), &InternalID(100.into()), &InternalID(101.into()), &InternalID(102.into()));
+/// One of the four orderings kept by `QuadIndexes`, named by the order of its key parts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IndexKind {
+    GSPO,
+    SPOG,
+    POSG,
+    OSPG,
+}
+
+pub static INDEX_KINDS: [IndexKind; 4] = [IndexKind::GSPO, IndexKind::SPOG, IndexKind::POSG, IndexKind::OSPG];
+
+impl IndexKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            IndexKind::GSPO => "GSPO",
+            IndexKind::SPOG => "SPOG",
+            IndexKind::POSG => "POSG",
+            IndexKind::OSPG => "OSPG",
+        }
+    }
+
+    /// The key parts in order, as positions in a (graph, subject, predicate, object) quad.
+    pub fn key_order(&self) -> [usize; 4] {
+        match *self {
+            IndexKind::GSPO => [0, 1, 2, 3],
+            IndexKind::SPOG => [1, 2, 3, 0],
+            IndexKind::POSG => [2, 3, 1, 0],
+            IndexKind::OSPG => [3, 1, 2, 0],
+        }
+    }
+}
+
+/// Quads per predicate, with the number of distinct subjects and objects it links.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PredicateStatistics {
+    pub quads: usize,
+    pub subjects: usize,
+    pub objects: usize,
+}
+
+/// Quad counts per graph, predicate, subject and object, kept up to date by `QuadIndexes` for
+/// estimating how many quads a pattern matches.
+#[derive(Clone, Debug, Default)]
+pub struct QuadStatistics {
+    graphs: BTreeMap<GraphID, usize>,
+    predicates: BTreeMap<PredicateID, PredicateStatistics>,
+    subjects: BTreeMap<SubjectID, usize>,
+    objects: BTreeMap<ObjectID, usize>,
+}
+
+fn increment(counts: &mut BTreeMap<InternalID, usize>, key: &InternalID) {
+    *counts.entry(key.clone()).or_insert(0) += 1;
+}
+
+fn decrement(counts: &mut BTreeMap<InternalID, usize>, key: &InternalID) {
+    let empty = match counts.get_mut(key) {
+        Some(count) => {
+            *count -= 1;
+            *count == 0
+        }
+        None => false,
+    };
+    if empty {
+        counts.remove(key);
+    }
+}
+
+impl QuadStatistics {
+    pub fn graph_quads(&self, graph: &GraphID) -> usize {
+        self.graphs.get(graph).cloned().unwrap_or(0)
+    }
+
+    pub fn predicate(&self, predicate: &PredicateID) -> Option<&PredicateStatistics> {
+        self.predicates.get(predicate)
+    }
+
+    pub fn subject_quads(&self, subject: &SubjectID) -> usize {
+        self.subjects.get(subject).cloned().unwrap_or(0)
+    }
+
+    pub fn object_quads(&self, object: &ObjectID) -> usize {
+        self.objects.get(object).cloned().unwrap_or(0)
+    }
+
+    pub fn graph_count(&self) -> usize {
+        self.graphs.len()
+    }
+
+    pub fn predicate_count(&self) -> usize {
+        self.predicates.len()
+    }
+
+    pub fn subject_count(&self) -> usize {
+        self.subjects.len()
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn predicates<'a>(&'a self) -> Box<Iterator<Item=(&'a PredicateID, &'a PredicateStatistics)> + 'a> {
+        Box::new(self.predicates.iter())
+    }
+
+    /* `new_subject` and `new_object` tell whether the quad is the first linking its subject or object by its predicate. */
+    fn record(&mut self, graph: &GraphID, subject: &SubjectID, predicate: &PredicateID, object: &ObjectID, new_subject: bool, new_object: bool) {
+        increment(&mut self.graphs, graph);
+        increment(&mut self.subjects, subject);
+        increment(&mut self.objects, object);
+        let entry = self.predicates.entry(predicate.clone()).or_default();
+        entry.quads += 1;
+        entry.subjects += new_subject as usize;
+        entry.objects += new_object as usize;
+    }
+
+    fn forget(&mut self, graph: &GraphID, subject: &SubjectID, predicate: &PredicateID, object: &ObjectID, last_subject: bool, last_object: bool) {
+        decrement(&mut self.graphs, graph);
+        decrement(&mut self.subjects, subject);
+        decrement(&mut self.objects, object);
+        let empty = match self.predicates.get_mut(predicate) {
+            Some(entry) => {
+                entry.quads -= 1;
+                entry.subjects -= last_subject as usize;
+                entry.objects -= last_object as usize;
+                entry.quads == 0
+            }
+            None => false,
+        };
+        if empty {
+            self.predicates.remove(predicate);
+        }
+    }
+}
+
+/* The quads in the range of `index` whose first `prefix` key parts are those of `key`. */
+fn scan_prefix<'a, Q>(index: &'a IndexedQuadSet<InternalID, InternalID, InternalID, InternalID, Q>, key: &[Option<InternalID>], prefix: usize)
+                      -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a>
+    where Q: IndexOrder<InternalID, InternalID, InternalID, InternalID> + 'a {
+    let part = |i: usize| key[i].clone().unwrap();
+    match prefix {
+        0 => Box::new(index.full_range().map(|r: &Q| r.clone().deconstruct())),
+        1 => Box::new(index.find_by_first_one(&part(0)).map(|r: &Q| r.clone().deconstruct())),
+        2 => Box::new(index.find_by_first_two(&part(0), &part(1)).map(|r: &Q| r.clone().deconstruct())),
+        3 => Box::new(index.find_by_first_three(&part(0), &part(1), &part(2)).map(|r: &Q| r.clone().deconstruct())),
+        _ => Box::new(index.find_exact_match(&part(0), &part(1), &part(2), &part(3)).into_iter().map(|r: Q| r.deconstruct())),
+    }
+}
+
+/// The four orderings of a set of quads, kept in step so any pattern can be answered by a range scan.
+#[derive(Default)]
+pub struct QuadIndexes {
+    spog_index: SPOGIndex,
+    gspo_index: GSPOIndex,
+    posg_index: POSGIndex,
+    ospg_index: OSPGIndex,
+    statistics: QuadStatistics,
+    len: usize,
+}
+
+impl QuadIndexes {
+    pub fn add_quad(&mut self, graph: &GraphID, subject: &SubjectID, predicate: &PredicateID, object: &ObjectID) -> bool {
+        let added = self.gspo_index.add_entry(GSPO::build_from_ref_parts(graph, subject, predicate, object));
+        if added {
+            self.spog_index.add_entry(SPOG::build_from_ref_parts(subject, predicate, object, graph));
+            self.posg_index.add_entry(POSG::build_from_ref_parts(predicate, object, subject, graph));
+            self.ospg_index.add_entry(OSPG::build_from_ref_parts(object, subject, predicate, graph));
+            let new_subject = self.spog_index.find_by_first_two(subject, predicate).nth(1).is_none();
+            let new_object = self.posg_index.find_by_first_two(predicate, object).nth(1).is_none();
+            self.statistics.record(graph, subject, predicate, object, new_subject, new_object);
+            self.len += 1;
+        }
+        added
+    }
+
+    pub fn remove_quad(&mut self, graph: &GraphID, subject: &SubjectID, predicate: &PredicateID, object: &ObjectID) -> bool {
+        let removed = self.gspo_index.remove_entry(&GSPO::build_from_ref_parts(graph, subject, predicate, object));
+        if removed {
+            self.spog_index.remove_entry(&SPOG::build_from_ref_parts(subject, predicate, object, graph));
+            self.posg_index.remove_entry(&POSG::build_from_ref_parts(predicate, object, subject, graph));
+            self.ospg_index.remove_entry(&OSPG::build_from_ref_parts(object, subject, predicate, graph));
+            let last_subject = self.spog_index.find_by_first_two(subject, predicate).next().is_none();
+            let last_object = self.posg_index.find_by_first_two(predicate, object).next().is_none();
+            self.statistics.forget(graph, subject, predicate, object, last_subject, last_object);
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn contains_quad(&self, graph: &GraphID, subject: &SubjectID, predicate: &PredicateID, object: &ObjectID) -> bool {
+        self.gspo_index.find_exact_match(graph, subject, predicate, object).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = QuadIndexes::default();
+    }
+
+    pub fn borrow_statistics<'a>(&'a self) -> &'a QuadStatistics {
+        &self.statistics
+    }
+
+    pub fn borrow_spog_index<'a>(&'a self) -> &'a SPOGIndex {
+        &self.spog_index
+    }
+
+    pub fn borrow_gspo_index<'a>(&'a self) -> &'a GSPOIndex {
+        &self.gspo_index
+    }
+
+    pub fn borrow_posg_index<'a>(&'a self) -> &'a POSGIndex {
+        &self.posg_index
+    }
+
+    pub fn borrow_ospg_index<'a>(&'a self) -> &'a OSPGIndex {
+        &self.ospg_index
+    }
+
+    pub fn search<'a>(&'a self, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
+                      -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
+        match (graph, subject, predicate, object) {
+            (Some(g), Some(s), Some(p), Some(o)) => {
+                if let Some(_f) = self.gspo_index.find_exact_match(&g, &s, &p, &o) {
+                    Box::new(iter::once((g, s, p, o)))
+                } else {
+                    Box::new(iter::empty())
+                }
+            },
+            (None, None, None, None) => {
+                Box::new(self.gspo_index.full_range().map(|r: &GSPO| { r.clone().deconstruct() }))
+            },
+            (Some(g), Some(s), Some(p), None) => {
+                Box::new(self.gspo_index.find_by_first_three(&g, &s, &p).map(|r: &GSPO| { r.clone().deconstruct() }))
+            },
+            (Some(g), Some(s), None, None) => {
+                Box::new(self.gspo_index.find_by_first_two(&g, &s).map(|r: &GSPO| { r.clone().deconstruct() }))
+            },
+            (Some(g), None, None, None) => {
+                Box::new(self.gspo_index.find_by_first_one(&g).map(|r: &GSPO| { r.clone().deconstruct() }))
+            },
+            (Some(g), Some(s), None, Some(o)) => {
+                let o_copy = o.clone();
+                Box::new(self.gspo_index.find_by_first_two(&g, &s)
+                    .filter(move |&r| r.object_refs().3.eq(&o_copy))
+                    .map(|r: &GSPO|  r.clone().deconstruct() ))
+            },
+            (Some(g), None, Some(p), Some(o)) => {
+                let g_copy = g.clone();
+                Box::new(self.posg_index.find_by_first_two(&p, &o)
+                    .filter(move |&r| r.object_refs().0.eq(&g_copy))
+                    .map(|r: &POSG|  r.clone().deconstruct() ))
+            },
+            (Some(g), None, Some(p), None) => {
+                /* This is assuming there are more different p's in the graph than different g's */
+                let g_copy = g.clone();
+                Box::new(self.posg_index.find_by_first_one(&p)
+                    .filter(move |&r| r.object_refs().0.eq(&g_copy))
+                    .map(|r: &POSG|  r.clone().deconstruct() ))
+            },
+            (Some(g), None, None, Some(o)) => {
+                /* This is assuming there are more different o's in the graph than different g's */
+                let g_copy = g.clone();
+                Box::new(self.ospg_index.find_by_first_one(&o)
+                    .filter(move |&r| r.object_refs().0.eq(&g_copy))
+                    .map(|r: &OSPG|  r.clone().deconstruct() ))
+            },
+            (None, Some(s), Some(p), Some(o)) => {
+                Box::new(self.spog_index.find_by_first_three(&s, &p, &o).map(|r: &SPOG| { r.clone().deconstruct() }))
+            },
+            (None, Some(s), Some(p), None) => {
+                Box::new(self.spog_index.find_by_first_two(&s, &p).map(|r: &SPOG| { r.clone().deconstruct() }))
+            },
+            (None, Some(s), None, None) => {
+                Box::new(self.spog_index.find_by_first_one(&s).map(|r: &SPOG| { r.clone().deconstruct() }))
+            },
+            (None, None, Some(p), Some(o)) => {
+                Box::new(self.posg_index.find_by_first_two(&p, &o).map(|r: &POSG| { r.clone().deconstruct() }))
+            },
+            (None, None, Some(p), None) => {
+                Box::new(self.posg_index.find_by_first_one(&p).map(|r: &POSG| { r.clone().deconstruct() }))
+            },
+            (None, Some(s), None, Some(o)) => {
+                Box::new(self.ospg_index.find_by_first_two(&o, &s).map(|r: &OSPG| { r.clone().deconstruct() }))
+            },
+            (None, None, None, Some(o)) => {
+                Box::new(self.ospg_index.find_by_first_one(&o).map(|r: &OSPG| { r.clone().deconstruct() }))
+            },
+            //_ => unimplemented!()
+        }
+    }
+
+    /// Searches like `search`, but always reads `kind`: its range is narrowed by the longest bound
+    /// prefix of its key, and the remaining bound parts are filtered.
+    pub fn scan<'a>(&'a self, kind: IndexKind, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
+                    -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
+        let parts = [graph, subject, predicate, object];
+        let key: Vec<Option<InternalID>> = kind.key_order().iter().map(|&i| parts[i].clone()).collect();
+        let prefix = key.iter().take_while(|k| k.is_some()).count();
+        let results = match kind {
+            IndexKind::GSPO => scan_prefix(&self.gspo_index, &key, prefix),
+            IndexKind::SPOG => scan_prefix(&self.spog_index, &key, prefix),
+            IndexKind::POSG => scan_prefix(&self.posg_index, &key, prefix),
+            IndexKind::OSPG => scan_prefix(&self.ospg_index, &key, prefix),
+        };
+        if key[prefix..].iter().all(|k| k.is_none()) {
+            return results;
+        }
+        Box::new(results.filter(move |&(ref g, ref s, ref p, ref o)| {
+            [g, s, p, o].iter().zip(parts.iter()).all(|(found, bound)| bound.as_ref().map(|b| b == *found).unwrap_or(true))
+        }))
+    }
+}
+
+pub fn test_me() {
+    let mut spog_set = SPOGIndex::default();
+
+    spog_set.add_entry(SPOG(InternalID(1.into()), InternalID(2.into()), InternalID(3.into()), InternalID(4.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(7.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(8.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(9.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(10.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(11.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(12.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(13.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(14.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(15.into())));
+    spog_set.add_entry(SPOG(InternalID(4.into()), InternalID(5.into()), InternalID(6.into()), InternalID(16.into())));
+    spog_set.add_entry(SPOG(InternalID(99.into()), InternalID(100.into()), InternalID(101.into()), InternalID(102.into())));
+    spog_set.add_entry(SPOG(InternalID(105.into()), InternalID(106.into()), InternalID(107.into()), InternalID(108.into())));
+    spog_set.add_entry(SPOG(InternalID(7.into()), InternalID(8.into()), InternalID(9.into()), InternalID(10.into())));
+    spog_set.add_entry(SPOG(InternalID(10.into()), InternalID(11.into()), InternalID(12.into()), InternalID(13.into())));
+
+    let res = spog_set.find_exact_match(&InternalID(99.into()), &InternalID(100.into()), &InternalID(101.into()), &InternalID(102.into()));
     println!("{:?}", res);
-    let res2 = SPOGSet.find_exact_match(&InternalID(100.into()), &InternalID(100.into()), &InternalID(101.into()), &InternalID(102.into()));
+    let res2 = spog_set.find_exact_match(&InternalID(100.into()), &InternalID(100.into()), &InternalID(101.into()), &InternalID(102.into()));
     println!("{:?}", res2);
     assert!(res.is_some());
     assert!(res2.is_none());
-    let res3 = SPOGSet.find_by_first_three(&InternalID(4.into()), &InternalID(5.into()), &InternalID(6.into()));
+    let res3 = spog_set.find_by_first_three(&InternalID(4.into()), &InternalID(5.into()), &InternalID(6.into()));
     for f in res3 {
         println!("{:?}", f);
     }
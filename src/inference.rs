@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use identifiers::InternalID;
+use indexed_quad_set::QuadIndexes;
+use store::{StorageEngine, InternalQuad, GraphID};
+
+/// A set of quads that cannot all hold, as found by a consistency-checking rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inconsistency {
+    pub rule: &'static str,
+    pub graph: GraphID,
+    pub premises: Vec<InternalQuad>,
+}
+
+/// A quad derived by a rule, with the quads it was derived from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Derivation {
+    pub quad: InternalQuad,
+    pub premises: Vec<InternalQuad>,
+}
+
+impl Derivation {
+    pub fn new(quad: InternalQuad, premises: Vec<InternalQuad>) -> Derivation {
+        Derivation { quad, premises }
+    }
+}
+
+/// How an inferred quad was first derived.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Justification {
+    pub rule: &'static str,
+    pub premises: Vec<InternalQuad>,
+}
+
+/// A proof tree for a quad: asserted quads are leaves, inferred ones carry the rule that derived them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProofStep {
+    Asserted,
+    Inferred { rule: &'static str, premises: Vec<Proof> },
+    /// The quad already occurs further up this branch of the proof.
+    Circular,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Proof {
+    pub quad: InternalQuad,
+    pub step: ProofStep,
+}
+
+/// A forward-chaining rule. `apply` is handed each newly asserted or derived quad in turn and
+/// pushes every quad that follows from it together with quads already in the store. Each derivation
+/// carries the premises it used, which are kept as the derived quad's justification. Handing over
+/// one quad at a time gives semi-naive evaluation: each quad is joined against the rest of the store
+/// exactly once.
+pub trait InferenceRule {
+    fn name(&self) -> &'static str;
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>);
+    /// A derivation of `quad` by this rule in one step from the quads now in the store, if there is
+    /// one. Used to rederive quads whose support a deletion may have taken away.
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation>;
+    fn check(&self, _delta: &InternalQuad, _store: &StorageEngine, _violations: &mut Vec<Inconsistency>) {}
+}
+
+/// The rules enabled on a store, and the quads they have materialized so far. Inferred quads are
+/// kept out of the asserted indexes, so queries can choose to see them or not.
+#[derive(Default)]
+pub struct InferenceState {
+    rules: Vec<Box<InferenceRule>>,
+    inferred: QuadIndexes,
+    justifications: BTreeMap<InternalQuad, Justification>,
+    inconsistencies: Vec<Inconsistency>,
+}
+
+impl InferenceState {
+    pub fn add_rules(&mut self, rules: Vec<Box<InferenceRule>>) {
+        self.rules.extend(rules);
+    }
+
+    pub fn borrow_rules<'a>(&'a self) -> &'a [Box<InferenceRule>] {
+        &self.rules
+    }
+
+    pub fn borrow_inferred<'a>(&'a self) -> &'a QuadIndexes {
+        &self.inferred
+    }
+
+    pub fn borrow_inferred_mut<'a>(&'a mut self) -> &'a mut QuadIndexes {
+        &mut self.inferred
+    }
+
+    /// Keeps only the first justification found for a quad; its premises were all known before it.
+    pub fn record_justification(&mut self, quad: InternalQuad, justification: Justification) {
+        self.justifications.entry(quad).or_insert(justification);
+    }
+
+    pub fn forget_justification(&mut self, quad: &InternalQuad) {
+        self.justifications.remove(quad);
+    }
+
+    pub fn justification<'a>(&'a self, quad: &InternalQuad) -> Option<&'a Justification> {
+        self.justifications.get(quad)
+    }
+
+    pub fn borrow_inconsistencies<'a>(&'a self) -> &'a [Inconsistency] {
+        &self.inconsistencies
+    }
+
+    pub fn record_inconsistency(&mut self, inconsistency: Inconsistency) {
+        if !self.inconsistencies.contains(&inconsistency) {
+            self.inconsistencies.push(inconsistency);
+        }
+    }
+
+    /// Drops the inconsistencies with any premise `gone` holds for.
+    pub fn forget_inconsistencies<F: Fn(&InternalQuad) -> bool>(&mut self, gone: F) {
+        self.inconsistencies.retain(|i| !i.premises.iter().any(&gone));
+    }
+
+    pub fn clear_inconsistencies(&mut self, graph: Option<&GraphID>) {
+        if let Some(g) = graph {
+            self.inconsistencies.retain(|i| i.graph != *g);
+        } else {
+            self.inconsistencies.clear();
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuleTerm {
+    Var(usize),
+    Const(InternalID),
+}
+
+/// A subject, predicate, object pattern. Rules match within one graph, so there is no graph term.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RulePattern(pub RuleTerm, pub RuleTerm, pub RuleTerm);
+
+#[derive(Clone, Debug)]
+pub enum RuleConclusion {
+    Quads(Vec<RulePattern>),
+    Inconsistent,
+}
+
+pub type Bindings = Vec<Option<InternalID>>;
+
+fn bind_term(term: &RuleTerm, value: &InternalID, bindings: &mut Bindings) -> bool {
+    match term {
+        &RuleTerm::Const(ref c) => c == value,
+        &RuleTerm::Var(v) => {
+            if let Some(ref bound) = bindings[v] {
+                return bound == value;
+            }
+            bindings[v] = Some(value.clone());
+            true
+        }
+    }
+}
+
+fn resolve_term(term: &RuleTerm, bindings: &Bindings) -> Option<InternalID> {
+    match term {
+        &RuleTerm::Const(ref c) => Some(c.clone()),
+        &RuleTerm::Var(v) => bindings[v].clone()
+    }
+}
+
+impl RulePattern {
+    pub fn unify(&self, s: &InternalID, p: &InternalID, o: &InternalID, bindings: &Bindings) -> Option<Bindings> {
+        let mut extended = bindings.clone();
+        if bind_term(&self.0, s, &mut extended) && bind_term(&self.1, p, &mut extended) && bind_term(&self.2, o, &mut extended) {
+            Some(extended)
+        } else {
+            None
+        }
+    }
+
+    /// The subject, predicate and object, where bound.
+    pub fn resolve(&self, bindings: &Bindings) -> (Option<InternalID>, Option<InternalID>, Option<InternalID>) {
+        (resolve_term(&self.0, bindings), resolve_term(&self.1, bindings), resolve_term(&self.2, bindings))
+    }
+
+    pub fn bound_count(&self, bindings: &Bindings) -> usize {
+        [&self.0, &self.1, &self.2].iter().filter(|t| resolve_term(t, bindings).is_some()).count()
+    }
+
+    pub fn instantiate(&self, bindings: &Bindings, graph: &GraphID) -> Option<InternalQuad> {
+        Some((resolve_term(&self.0, bindings)?, resolve_term(&self.1, bindings)?, resolve_term(&self.2, bindings)?, graph.clone()))
+    }
+}
+
+/// Extends `bindings` through every remaining pattern, most-bound pattern first, matching
+/// against asserted and inferred quads in `graph`.
+pub fn join_patterns(store: &StorageEngine, graph: &GraphID, remaining: &[RulePattern], bindings: Bindings, results: &mut Vec<Bindings>) {
+    if remaining.is_empty() {
+        results.push(bindings);
+        return;
+    }
+    let next = (0..remaining.len()).max_by_key(|&i| remaining[i].bound_count(&bindings)).unwrap();
+    let pattern = &remaining[next];
+    let rest: Vec<RulePattern> = remaining.iter().enumerate()
+        .filter(|&(i, _)| i != next).map(|(_, r)| r.clone()).collect();
+    let s = resolve_term(&pattern.0, &bindings);
+    let p = resolve_term(&pattern.1, &bindings);
+    let o = resolve_term(&pattern.2, &bindings);
+    for (_, qs, qp, qo) in store.search_engine_entailed(Some(graph.clone()), s, p, o) {
+        if let Some(extended) = pattern.unify(&qs, &qp, &qo, &bindings) {
+            join_patterns(store, graph, &rest, extended, results);
+        }
+    }
+}
+
+/// A Horn rule over triple patterns, evaluated semi-naively: the delta quad is unified with each
+/// body pattern in turn and the rest of the body is joined against the store.
+pub struct PatternRule {
+    name: &'static str,
+    body: Vec<RulePattern>,
+    conclusion: RuleConclusion,
+    var_count: usize,
+    distinct_vars: Vec<(usize, usize)>,
+}
+
+impl PatternRule {
+    pub fn new(name: &'static str, body: Vec<RulePattern>, conclusion: RuleConclusion) -> PatternRule {
+        let mut var_count = 0;
+        for pattern in body.iter() {
+            for term in [&pattern.0, &pattern.1, &pattern.2].iter() {
+                if let &&RuleTerm::Var(v) = term {
+                    var_count = var_count.max(v + 1);
+                }
+            }
+        }
+        PatternRule { name, body, conclusion, var_count, distinct_vars: Vec::new() }
+    }
+
+    /// Only fire when the two variables are bound to different terms.
+    pub fn with_distinct(mut self, a: usize, b: usize) -> PatternRule {
+        self.distinct_vars.push((a, b));
+        self
+    }
+
+    pub fn borrow_body(&self) -> &[RulePattern] {
+        &self.body
+    }
+
+    /// The patterns the rule concludes, none for a consistency check.
+    pub fn borrow_head(&self) -> &[RulePattern] {
+        match self.conclusion {
+            RuleConclusion::Quads(ref head) => head,
+            RuleConclusion::Inconsistent => &[],
+        }
+    }
+
+    /// Unbound bindings for the rule's variables.
+    pub fn empty_bindings(&self) -> Bindings {
+        vec![None; self.var_count]
+    }
+
+    /// Whether the rule may fire with `bindings`, given the variables it needs to be distinct.
+    pub fn allows(&self, bindings: &Bindings) -> bool {
+        self.distinct_vars.iter().all(|&(x, y)| bindings[x] != bindings[y])
+    }
+
+    fn matches(&self, delta: &InternalQuad, store: &StorageEngine) -> Vec<Bindings> {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        let mut results = Vec::new();
+        let empty: Bindings = vec![None; self.var_count];
+        for (i, pattern) in self.body.iter().enumerate() {
+            if let Some(bindings) = pattern.unify(s, p, o, &empty) {
+                let rest: Vec<RulePattern> = self.body.iter().enumerate()
+                    .filter(|&(j, _)| j != i).map(|(_, r)| r.clone()).collect();
+                join_patterns(store, g, &rest, bindings, &mut results);
+            }
+        }
+        results.retain(|b| self.allows(b));
+        results
+    }
+}
+
+impl InferenceRule for PatternRule {
+    fn name(&self) -> &'static str { self.name }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let head = if let RuleConclusion::Quads(ref h) = self.conclusion { h } else { return; };
+        for bindings in self.matches(delta, store) {
+            let premises: Vec<InternalQuad> = self.body.iter().filter_map(|p| p.instantiate(&bindings, &delta.3)).collect();
+            for pattern in head.iter() {
+                if let Some(q) = pattern.instantiate(&bindings, &delta.3) {
+                    derived.push(Derivation::new(q, premises.clone()));
+                }
+            }
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref s, ref p, ref o, ref g) = quad;
+        for pattern in self.borrow_head() {
+            let bindings = if let Some(b) = pattern.unify(s, p, o, &self.empty_bindings()) { b } else { continue; };
+            let mut results = Vec::new();
+            join_patterns(store, g, &self.body, bindings, &mut results);
+            if let Some(bindings) = results.into_iter().find(|b| self.allows(b)) {
+                let premises = self.body.iter().filter_map(|p| p.instantiate(&bindings, g)).collect();
+                return Some(Derivation::new(quad.clone(), premises));
+            }
+        }
+        None
+    }
+
+    fn check(&self, delta: &InternalQuad, store: &StorageEngine, violations: &mut Vec<Inconsistency>) {
+        if let RuleConclusion::Quads(_) = self.conclusion { return; }
+        for bindings in self.matches(delta, store) {
+            let premises = self.body.iter().filter_map(|p| p.instantiate(&bindings, &delta.3)).collect();
+            violations.push(Inconsistency { rule: self.name, graph: delta.3.clone(), premises });
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::fmt;
+
+/* RFC 3987 IRI references, split into components following RFC 3986 Appendix B. */
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct IriRef {
+    scheme: Option<String>,
+    authority: Option<String>,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+fn is_unreserved(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' || c == '~' || is_ucschar(c)
+}
+
+fn is_sub_delim(c: char) -> bool {
+    "!$&'()*+,;=".contains(c)
+}
+
+fn is_ucschar(c: char) -> bool {
+    matches!(c as u32,
+        0xA0..=0xD7FF | 0xF900..=0xFDCF | 0xFDF0..=0xFFEF |
+        0x10000..=0x1FFFD | 0x20000..=0x2FFFD | 0x30000..=0x3FFFD |
+        0x40000..=0x4FFFD | 0x50000..=0x5FFFD | 0x60000..=0x6FFFD |
+        0x70000..=0x7FFFD | 0x80000..=0x8FFFD | 0x90000..=0x9FFFD |
+        0xA0000..=0xAFFFD | 0xB0000..=0xBFFFD | 0xC0000..=0xCFFFD |
+        0xD0000..=0xDFFFD | 0xE1000..=0xEFFFD)
+}
+
+fn is_iprivate(c: char) -> bool {
+    matches!(c as u32, 0xE000..=0xF8FF | 0xF0000..=0xFFFFD | 0x100000..=0x10FFFD)
+}
+
+fn is_pchar(c: char) -> bool {
+    is_unreserved(c) || is_sub_delim(c) || c == ':' || c == '@'
+}
+
+/* Checks every char against `allowed`, letting well-formed %XX escapes through. */
+fn validate_component(component: &str, name: &str, allowed: &Fn(char) -> bool) -> Result<(), String> {
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            let h1 = chars.next();
+            let h2 = chars.next();
+            match (h1, h2) {
+                (Some(a), Some(b)) if a.is_ascii_hexdigit() && b.is_ascii_hexdigit() => continue,
+                _ => return Err(format!("Malformed percent-encoding in IRI {}.", name))
+            }
+        }
+        if !allowed(c) {
+            return Err(format!("Character {:?} is not allowed in IRI {}.", c, name));
+        }
+    }
+    Ok(())
+}
+
+fn validate_authority(authority: &str) -> Result<(), String> {
+    let (userinfo, hostport) = match authority.rfind('@') {
+        Some(i) => (Some(&authority[..i]), &authority[i + 1..]),
+        None => (None, authority)
+    };
+    if let Some(u) = userinfo {
+        validate_component(u, "userinfo", &|c| is_unreserved(c) || is_sub_delim(c) || c == ':')?;
+    }
+    let (host, port) = if hostport.starts_with('[') {
+        let close = if let Some(i) = hostport.find(']') { i }
+            else { return Err("Unterminated IP literal in IRI host.".to_string()); };
+        let literal = &hostport[1..close];
+        if literal.is_empty() || !literal.chars().all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.' || is_unreserved(c) || is_sub_delim(c)) {
+            return Err("Invalid IP literal in IRI host.".to_string());
+        }
+        let rest = &hostport[close + 1..];
+        if !rest.is_empty() && !rest.starts_with(':') {
+            return Err("Unexpected characters after IP literal in IRI host.".to_string());
+        }
+        (&hostport[..close + 1], if rest.is_empty() { None } else { Some(&rest[1..]) })
+    } else {
+        match hostport.rfind(':') {
+            Some(i) => (&hostport[..i], Some(&hostport[i + 1..])),
+            None => (hostport, None)
+        }
+    };
+    if !host.starts_with('[') {
+        validate_component(host, "host", &|c| is_unreserved(c) || is_sub_delim(c))?;
+    }
+    if let Some(p) = port {
+        if !p.chars().all(|c| c.is_ascii_digit()) {
+            return Err("IRI port must be numeric.".to_string());
+        }
+    }
+    Ok(())
+}
+
+/* RFC 3986 section 5.2.4 */
+pub fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output: Vec<&str> = Vec::new();
+    let absolute = path.starts_with('/');
+    while !input.is_empty() {
+        if input.starts_with("../") {
+            input = &input[3..];
+        } else if input.starts_with("./") || input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            output.pop();
+        } else if input == "/.." {
+            input = "/";
+            output.pop();
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = if input.starts_with('/') { 1 } else { 0 };
+            let end = input[start..].find('/').map(|i| i + start).unwrap_or(input.len());
+            output.push(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    let joined: String = output.concat();
+    if absolute && !joined.starts_with('/') {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
+fn normalize_percent_encoding(component: &str) -> String {
+    let mut result = String::with_capacity(component.len());
+    let bytes = component.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() {
+            let hex = &component[i + 1..i + 3];
+            if let Ok(value) = u8::from_str_radix(hex, 16) {
+                let decoded = value as char;
+                if value < 0x80 && (decoded.is_ascii_alphanumeric() || "-._~".contains(decoded)) {
+                    result.push(decoded);
+                } else {
+                    result.push('%');
+                    result.push_str(&hex.to_uppercase());
+                }
+                i += 3;
+                continue;
+            }
+        }
+        let c = component[i..].chars().next().unwrap();
+        result.push(c);
+        i += c.len_utf8();
+    }
+    result
+}
+
+impl IriRef {
+    /// Parses and validates an IRI reference, which may be relative.
+    pub fn parse(reference: &str) -> Result<IriRef, String> {
+        let mut rest = reference;
+        let mut scheme = None;
+        if let Some(i) = rest.find([':', '/', '?', '#']) {
+            if rest[i..].starts_with(':') {
+                let candidate = &rest[..i];
+                let mut cs = candidate.chars();
+                let valid = match cs.next() {
+                    Some(first) => first.is_ascii_alphabetic() && cs.all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.'),
+                    None => false
+                };
+                if !valid {
+                    return Err(format!("Invalid IRI scheme {:?}.", candidate));
+                }
+                scheme = Some(candidate.to_owned());
+                rest = &rest[i + 1..];
+            }
+        }
+        let mut authority = None;
+        if rest.starts_with("//") {
+            let after = &rest[2..];
+            let end = after.find(['/', '?', '#']).unwrap_or(after.len());
+            validate_authority(&after[..end])?;
+            authority = Some(after[..end].to_owned());
+            rest = &after[end..];
+        }
+        let path_end = rest.find(['?', '#']).unwrap_or(rest.len());
+        let path = &rest[..path_end];
+        validate_component(path, "path", &|c| is_pchar(c) || c == '/')?;
+        rest = &rest[path_end..];
+        let mut query = None;
+        if rest.starts_with('?') {
+            let end = rest.find('#').unwrap_or(rest.len());
+            let q = &rest[1..end];
+            validate_component(q, "query", &|c| is_pchar(c) || c == '/' || c == '?' || is_iprivate(c))?;
+            query = Some(q.to_owned());
+            rest = &rest[end..];
+        }
+        let mut fragment = None;
+        if let Some(f) = rest.strip_prefix('#') {
+            validate_component(f, "fragment", &|c| is_pchar(c) || c == '/' || c == '?')?;
+            fragment = Some(f.to_owned());
+        }
+        Ok(IriRef { scheme, authority, path: path.to_owned(), query, fragment })
+    }
+
+    /// Parses an IRI reference and requires it to be absolute (ie to have a scheme).
+    pub fn parse_absolute(iri: &str) -> Result<IriRef, String> {
+        let parsed = Self::parse(iri)?;
+        if parsed.scheme.is_none() {
+            return Err(format!("IRI {:?} is relative, an absolute IRI is required.", iri));
+        }
+        Ok(parsed)
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.scheme.is_some()
+    }
+
+    pub fn borrow_scheme<'a>(&'a self) -> Option<&'a str> {
+        self.scheme.as_deref()
+    }
+
+    pub fn borrow_authority<'a>(&'a self) -> Option<&'a str> {
+        self.authority.as_deref()
+    }
+
+    pub fn borrow_path<'a>(&'a self) -> &'a str {
+        &self.path
+    }
+
+    pub fn borrow_query<'a>(&'a self) -> Option<&'a str> {
+        self.query.as_deref()
+    }
+
+    pub fn borrow_fragment<'a>(&'a self) -> Option<&'a str> {
+        self.fragment.as_deref()
+    }
+
+    /// Syntax-based normalization: lower-cases the scheme and host, upper-cases percent-encoding
+    /// hex digits, decodes percent-encoded unreserved characters and removes dot segments.
+    pub fn normalize(&self) -> IriRef {
+        let authority = self.authority.as_ref().map(|a| {
+            let (userinfo, hostport) = match a.rfind('@') {
+                Some(i) => (&a[..i + 1], &a[i + 1..]),
+                None => ("", a.as_str())
+            };
+            format!("{}{}", normalize_percent_encoding(userinfo), normalize_percent_encoding(&hostport.to_lowercase()))
+        });
+        let path = normalize_percent_encoding(&self.path);
+        let path = if self.scheme.is_some() { remove_dot_segments(&path) } else { path };
+        IriRef {
+            scheme: self.scheme.as_ref().map(|s| s.to_lowercase()),
+            authority,
+            path,
+            query: self.query.as_ref().map(|q| normalize_percent_encoding(q)),
+            fragment: self.fragment.as_ref().map(|f| normalize_percent_encoding(f)),
+        }
+    }
+
+    /// Resolves `reference` against this IRI as base, following RFC 3986 section 5.2.2.
+    pub fn resolve(&self, reference: &IriRef) -> Result<IriRef, String> {
+        if self.scheme.is_none() {
+            return Err("Cannot resolve against a relative base IRI.".to_string());
+        }
+        let r = reference;
+        let (scheme, authority, path, query);
+        if r.scheme.is_some() {
+            scheme = r.scheme.clone();
+            authority = r.authority.clone();
+            path = remove_dot_segments(&r.path);
+            query = r.query.clone();
+        } else {
+            scheme = self.scheme.clone();
+            if r.authority.is_some() {
+                authority = r.authority.clone();
+                path = remove_dot_segments(&r.path);
+                query = r.query.clone();
+            } else {
+                authority = self.authority.clone();
+                if r.path.is_empty() {
+                    path = self.path.clone();
+                    query = if r.query.is_some() { r.query.clone() } else { self.query.clone() };
+                } else {
+                    if r.path.starts_with('/') {
+                        path = remove_dot_segments(&r.path);
+                    } else {
+                        path = remove_dot_segments(&self.merge_path(&r.path));
+                    }
+                    query = r.query.clone();
+                }
+            }
+        }
+        Ok(IriRef { scheme, authority, path, query, fragment: r.fragment.clone() })
+    }
+
+    /* RFC 3986 section 5.2.3 */
+    fn merge_path(&self, reference_path: &str) -> String {
+        if self.authority.is_some() && self.path.is_empty() {
+            format!("/{}", reference_path)
+        } else {
+            match self.path.rfind('/') {
+                Some(i) => format!("{}{}", &self.path[..i + 1], reference_path),
+                None => reference_path.to_owned()
+            }
+        }
+    }
+}
+
+impl fmt::Display for IriRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref s) = self.scheme {
+            write!(f, "{}:", s)?;
+        }
+        if let Some(ref a) = self.authority {
+            write!(f, "//{}", a)?;
+        }
+        write!(f, "{}", self.path)?;
+        if let Some(ref q) = self.query {
+            write!(f, "?{}", q)?;
+        }
+        if let Some(ref fr) = self.fragment {
+            write!(f, "#{}", fr)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a possibly-relative IRI reference string against an absolute base IRI string.
+pub fn resolve_iri(base: &str, reference: &str) -> Result<String, String> {
+    let base_iri = IriRef::parse_absolute(base)?;
+    let reference_iri = IriRef::parse(reference)?;
+    Ok(base_iri.resolve(&reference_iri)?.to_string())
+}
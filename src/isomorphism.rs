@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use canonicalize::Canonicalizer;
+use sha256::sha256_hex;
+use store::{StorageEngine, StoreNode, InternalQuad, GraphID};
+
+/// The outcome of comparing two graphs or datasets. `isomorphic` is `None` when that could not be
+/// decided within the canonicalization work limit. `mapping` takes blank nodes of the first to
+/// the blank nodes of the second they were matched with; the quads that still differ under that
+/// mapping are listed per side, in each store's own IDs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub isomorphic: Option<bool>,
+    pub mapping: BTreeMap<InternalID, InternalID>,
+    pub only_in_first: Vec<InternalQuad>,
+    pub only_in_second: Vec<InternalQuad>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Term {
+    Ground(String),
+    Blank(InternalID),
+}
+
+type TermQuad = [Term; 4];
+
+/// One side of a comparison. Ground terms are compared by their N-Triples form, so the two sides
+/// may live in different stores.
+struct Side {
+    quads: Vec<InternalQuad>,
+    terms: Vec<TermQuad>,
+    by_blank: BTreeMap<InternalID, Vec<usize>>,
+}
+
+impl Side {
+    fn new(store: &StorageEngine, graph: Option<&GraphID>) -> Result<Side, String> {
+        let default_graph = InternalID(0.into());
+        let term = |id: &InternalID| -> Result<Term, String> {
+            match store.lookup_node_by_iid(id) {
+                Ok(&StoreNode::Blank(_)) => Ok(Term::Blank(id.clone())),
+                Ok(node) => Ok(Term::Ground(node.to_ntriples(store))),
+                Err(_) => Err(format!("No node for internal id {:?}.", id)),
+            }
+        };
+        let mut side = Side { quads: Vec::new(), terms: Vec::new(), by_blank: BTreeMap::new() };
+        for (g, s, p, o) in store.search_engine_internal(graph.cloned(), None, None, None) {
+            /* Graph names only matter when whole datasets are compared. */
+            let graph_term = if graph.is_some() || g == default_graph { Term::Ground(String::new()) } else { term(&g)? };
+            let quad_terms = [term(&s)?, term(&p)?, term(&o)?, graph_term];
+            let index = side.quads.len();
+            for t in quad_terms.iter() {
+                if let &Term::Blank(ref b) = t {
+                    let entry = side.by_blank.entry(b.clone()).or_default();
+                    if entry.last() != Some(&index) {
+                        entry.push(index);
+                    }
+                }
+            }
+            side.quads.push((s, p, o, g));
+            side.terms.push(quad_terms);
+        }
+        Ok(side)
+    }
+
+    /* A colour for `blank` from its quads, with neighbouring blank nodes shown by their current colour. */
+    fn refine(&self, blank: &InternalID, colours: &BTreeMap<InternalID, String>) -> String {
+        let mut lines: Vec<String> = self.by_blank[blank].iter().map(|&i| {
+            self.terms[i].iter().map(|t| match t {
+                &Term::Ground(ref g) => g.clone(),
+                &Term::Blank(ref b) if b == blank => "@".to_owned(),
+                &Term::Blank(ref b) => format!("_:{}", colours[b]),
+            }).collect::<Vec<String>>().join(" ")
+        }).collect();
+        lines.sort();
+        sha256_hex(lines.join("\n").as_bytes())
+    }
+}
+
+/* Successive colour refinements of both sides, coarsest first, until the partitions stop splitting. */
+fn colour_rounds(first: &Side, second: &Side) -> Vec<(BTreeMap<InternalID, String>, BTreeMap<InternalID, String>)> {
+    let mut rounds = Vec::new();
+    let mut colours_a: BTreeMap<InternalID, String> = first.by_blank.keys().map(|b| (b.clone(), String::new())).collect();
+    let mut colours_b: BTreeMap<InternalID, String> = second.by_blank.keys().map(|b| (b.clone(), String::new())).collect();
+    let distinct = |a: &BTreeMap<InternalID, String>, b: &BTreeMap<InternalID, String>| a.values().chain(b.values()).collect::<BTreeSet<_>>().len();
+    let mut classes = 0;
+    loop {
+        let next_a: BTreeMap<InternalID, String> = colours_a.keys().map(|b| (b.clone(), first.refine(b, &colours_a))).collect();
+        let next_b: BTreeMap<InternalID, String> = colours_b.keys().map(|b| (b.clone(), second.refine(b, &colours_b))).collect();
+        let next_classes = distinct(&next_a, &next_b);
+        rounds.push((next_a.clone(), next_b.clone()));
+        if next_classes <= classes {
+            break;
+        }
+        classes = next_classes;
+        colours_a = next_a;
+        colours_b = next_b;
+    }
+    rounds
+}
+
+fn translate(quad: &TermQuad, mapping: &BTreeMap<InternalID, InternalID>) -> Option<TermQuad> {
+    let mut translated = quad.clone();
+    for t in translated.iter_mut() {
+        if let Term::Blank(ref mut b) = *t {
+            *b = mapping.get(b)?.clone();
+        }
+    }
+    Some(translated)
+}
+
+/* Matches blank nodes with a colour that is unique on both sides, finest colours first, then pairs
+   the rest greedily by how many of their quads the pairing would match. */
+fn match_blanks(first: &Side, second: &Side) -> BTreeMap<InternalID, InternalID> {
+    let mut mapping = BTreeMap::new();
+    let mut used = BTreeSet::new();
+    for &(ref colours_a, ref colours_b) in colour_rounds(first, second).iter().rev() {
+        let mut groups: BTreeMap<&String, (Vec<&InternalID>, Vec<&InternalID>)> = BTreeMap::new();
+        for (b, c) in colours_a.iter().filter(|&(b, _)| !mapping.contains_key(b)) {
+            groups.entry(c).or_insert_with(|| (Vec::new(), Vec::new())).0.push(b);
+        }
+        for (b, c) in colours_b.iter().filter(|&(b, _)| !used.contains(b)) {
+            groups.entry(c).or_insert_with(|| (Vec::new(), Vec::new())).1.push(b);
+        }
+        for (_, (a, b)) in groups {
+            if a.len() == 1 && b.len() == 1 {
+                mapping.insert(a[0].clone(), b[0].clone());
+                used.insert(b[0].clone());
+            }
+        }
+    }
+    let second_quads: BTreeSet<&TermQuad> = second.terms.iter().collect();
+    let remaining: Vec<InternalID> = first.by_blank.keys().filter(|b| !mapping.contains_key(*b)).cloned().collect();
+    for a in remaining {
+        let mut best: Option<(usize, InternalID)> = None;
+        for b in second.by_blank.keys().filter(|b| !used.contains(*b)) {
+            mapping.insert(a.clone(), b.clone());
+            let score = first.by_blank[&a].iter()
+                .filter(|&&i| translate(&first.terms[i], &mapping).map(|t| second_quads.contains(&t)).unwrap_or(false))
+                .count();
+            if best.as_ref().map(|&(s, _)| score > s).unwrap_or(true) {
+                best = Some((score, b.clone()));
+            }
+        }
+        mapping.remove(&a);
+        if let Some((_, b)) = best {
+            used.insert(b.clone());
+            mapping.insert(a, b);
+        }
+    }
+    mapping
+}
+
+fn mapping_from_labels(first: &BTreeMap<InternalID, String>, second: &BTreeMap<InternalID, String>) -> BTreeMap<InternalID, InternalID> {
+    let by_label: BTreeMap<&String, &InternalID> = second.iter().map(|(id, label)| (label, id)).collect();
+    first.iter().filter_map(|(id, label)| by_label.get(label).map(|b| (id.clone(), (*b).clone()))).collect()
+}
+
+/// Compares `first_graph` of `first` with `second_graph` of `second`, or whole datasets when the
+/// graphs are `None`. Isomorphic inputs are recognised through their RDFC-1.0 canonical forms.
+/// Otherwise blank nodes are paired by colour refinement and the quads left unmatched are reported;
+/// this is the smallest difference whenever the mismatch leaves blank nodes distinguishable, and a
+/// close approximation when it makes them ambiguous, as finding the true minimum is NP-hard.
+/// If canonicalization runs out of work, the inputs are isomorphic only when the pairing found
+/// matches every quad, and are not when their sizes differ; otherwise the answer is left open.
+pub fn compare(first: &StorageEngine, first_graph: Option<&GraphID>, second: &StorageEngine, second_graph: Option<&GraphID>) -> Result<Comparison, String> {
+    let canonicalizer = Canonicalizer::new();
+    let canonical = (canonicalizer.canonicalize(first, first_graph), canonicalizer.canonicalize(second, second_graph));
+    let decided = if let (&Ok(ref a), &Ok(ref b)) = (&canonical.0, &canonical.1) {
+        if a.borrow_nquads() == b.borrow_nquads() {
+            let mapping = mapping_from_labels(a.borrow_labels(), b.borrow_labels());
+            return Ok(Comparison { isomorphic: Some(true), mapping, only_in_first: Vec::new(), only_in_second: Vec::new() });
+        }
+        true
+    } else {
+        false
+    };
+    let side_a = Side::new(first, first_graph)?;
+    let side_b = Side::new(second, second_graph)?;
+    let mapping = match_blanks(&side_a, &side_b);
+    let mut unmatched_b: BTreeMap<&TermQuad, usize> = side_b.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+    let mut only_in_first = Vec::new();
+    for (i, quad) in side_a.terms.iter().enumerate() {
+        let matched = translate(quad, &mapping).and_then(|t| unmatched_b.remove(&t));
+        if matched.is_none() {
+            only_in_first.push(side_a.quads[i].clone());
+        }
+    }
+    let mut rest: Vec<usize> = unmatched_b.values().cloned().collect();
+    rest.sort();
+    let only_in_second: Vec<InternalQuad> = rest.into_iter().map(|i| side_b.quads[i].clone()).collect();
+    let isomorphic = if only_in_first.is_empty() && only_in_second.is_empty() {
+        Some(true)
+    } else if decided || side_a.quads.len() != side_b.quads.len() || side_a.by_blank.len() != side_b.by_blank.len() {
+        Some(false)
+    } else {
+        None
+    };
+    Ok(Comparison { isomorphic, mapping, only_in_first, only_in_second })
+}
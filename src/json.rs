@@ -0,0 +1,228 @@
+use std::char;
+use std::collections::BTreeMap;
+
+/// A parsed JSON value, as read from SPARQL JSON results. Numbers keep their source text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    pub fn get<'a>(&'a self, key: &str) -> Option<&'a Json> {
+        if let &Json::Object(ref members) = self { members.get(key) } else { None }
+    }
+
+    pub fn as_str<'a>(&'a self) -> Option<&'a str> {
+        if let &Json::String(ref s) = self { Some(s) } else { None }
+    }
+
+    pub fn as_array<'a>(&'a self) -> Option<&'a [Json]> {
+        if let &Json::Array(ref items) = self { Some(items) } else { None }
+    }
+}
+
+/// Writes `value` as a quoted JSON string.
+pub fn quote(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c < '\u{20}' => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            other => escaped.push(other),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+/// Parses a complete JSON document.
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = JsonParser { chars: text.chars().collect(), position: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position < parser.chars.len() {
+        return parser.error("Unexpected text after the JSON value");
+    }
+    Ok(value)
+}
+
+impl JsonParser {
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("{} at character {}.", message, self.position))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.position < self.chars.len() && self.chars[self.position].is_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.position += 1;
+            Ok(())
+        } else {
+            self.error(&format!("Expected '{}'", c))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            if self.peek() != Some(expected) {
+                return self.error(&format!("Expected '{}'", word));
+            }
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => {
+                self.position += 1;
+                let mut members = BTreeMap::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    let value = self.value()?;
+                    members.insert(key, value);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.position += 1,
+                        Some('}') => {
+                            self.position += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return self.error("Expected ',' or '}'"),
+                    }
+                }
+            }
+            Some('[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.position += 1,
+                        Some(']') => {
+                            self.position += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return self.error("Expected ',' or ']'"),
+                    }
+                }
+            }
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.position;
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' {
+                        self.position += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let number: String = self.chars[start..self.position].iter().collect();
+                if number.parse::<f64>().is_err() {
+                    return self.error(&format!("Invalid number {}", number));
+                }
+                Ok(Json::Number(number))
+            }
+            _ => self.error("Expected a JSON value"),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        if self.position + 4 > self.chars.len() {
+            return self.error("Truncated \\u escape");
+        }
+        let digits: String = self.chars[self.position..self.position + 4].iter().collect();
+        self.position += 4;
+        u32::from_str_radix(&digits, 16).or_else(|_| self.error("Invalid \\u escape"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            return self.error("Expected a string");
+        }
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return self.error("Unterminated string"),
+            };
+            self.position += 1;
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(e) => e,
+                        None => return self.error("Unterminated string"),
+                    };
+                    self.position += 1;
+                    match escaped {
+                        '"' | '\\' | '/' => value.push(escaped),
+                        'b' => value.push('\u{8}'),
+                        'f' => value.push('\u{c}'),
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        't' => value.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            /* A high surrogate followed by an escaped low surrogate. */
+                            if (0xd800..0xdc00).contains(&code) && self.chars.get(self.position) == Some(&'\\') &&
+                                self.chars.get(self.position + 1) == Some(&'u') {
+                                self.position += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            match char::from_u32(code) {
+                                Some(c) => value.push(c),
+                                None => return self.error("Invalid \\u escape"),
+                            }
+                        }
+                        _ => return self.error(&format!("Invalid escape \\{}", escaped)),
+                    }
+                }
+                other => value.push(other),
+            }
+        }
+    }
+}
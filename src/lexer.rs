@@ -0,0 +1,361 @@
+use std::char;
+
+/// Tokens shared by the Turtle-like text syntaxes (rules, shape languages and queries).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    IriRef(String),
+    PrefixedName(String, String),
+    Var(String),
+    Blank(String),
+    Str(String),
+    LangTag(String),
+    Integer(String),
+    Decimal(String),
+    Double(String),
+    Word(String),
+    Punct(&'static str),
+    /// A ShExC `/pattern/flags` regular expression, only produced by `lex_with_regex_literals`.
+    Regex(String, String),
+}
+
+static PUNCTUATION: &'static [&'static str] = &["^^", "=>", "<=", ">=", "!=", "&&", "||",
+    "{", "}", "(", ")", "[", "]", ".", ",", ";", "=", "!", "<", ">", "*", "+", "-", "/", "|", "?", "^", "$", "@", "&", "~"];
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum NameKind {
+    /* Variable names and keywords. */
+    Plain,
+    /* Prefixes and blank node labels, which may contain hyphens and inner dots. */
+    Prefix,
+    /* Local parts of prefixed names, which may also contain colons and percent escapes. */
+    Local,
+}
+
+/* Takes name characters from `i`, never ending on a dot, and returns the end index. */
+fn scan_name(chars: &[(usize, char)], mut i: usize, kind: NameKind) -> usize {
+    let mut end = i;
+    while i < chars.len() {
+        let c = chars[i].1;
+        let accepted = is_name_char(c) || (kind != NameKind::Plain && c == '-') ||
+            (kind == NameKind::Local && (c == '%' || c == ':'));
+        if accepted {
+            i += 1;
+            end = i;
+        } else if kind != NameKind::Plain && c == '.' {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    end
+}
+
+fn offset_at(chars: &[(usize, char)], i: usize, input: &str) -> usize {
+    if i < chars.len() { chars[i].0 } else { input.len() }
+}
+
+fn parse_hex(chars: &[(usize, char)], i: usize, digits: usize) -> Result<char, String> {
+    if i + digits > chars.len() {
+        return Err("Truncated unicode escape.".to_owned());
+    }
+    let hex: String = chars[i..i + digits].iter().map(|&(_, c)| c).collect();
+    u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+        .ok_or_else(|| format!("Invalid unicode escape \\u{}.", hex))
+}
+
+/// Splits `input` into `(byte offset, token)` pairs. `#` starts a comment outside IRIs and strings.
+/// `<` starts an IRI only when a `>` follows before any whitespace, so it can also be an operator.
+pub fn lex(input: &str) -> Result<Vec<(usize, Token)>, String> {
+    lex_tokens(input, false)
+}
+
+/// Like `lex`, but `/` starts a regular expression literal running to the next unescaped `/`.
+pub fn lex_with_regex_literals(input: &str) -> Result<Vec<(usize, Token)>, String> {
+    lex_tokens(input, true)
+}
+
+fn lex_tokens(input: &str, regex_literals: bool) -> Result<Vec<(usize, Token)>, String> {
+    let chars: Vec<(usize, char)> = input.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (offset, c) = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '#' {
+            while i < chars.len() && chars[i].1 != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '<' {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j].1 != '>' && !chars[j].1.is_whitespace() && chars[j].1 != '<' && chars[j].1 != '"' {
+                j += 1;
+            }
+            if j < chars.len() && chars[j].1 == '>' {
+                let iri: String = chars[i + 1..j].iter().map(|&(_, c)| c).collect();
+                tokens.push((offset, Token::IriRef(iri)));
+                i = j + 1;
+                continue;
+            }
+        }
+        if c == '"' || c == '\'' {
+            let long = i + 2 < chars.len() && chars[i + 1].1 == c && chars[i + 2].1 == c;
+            let mut j = if long { i + 3 } else { i + 1 };
+            let mut value = String::new();
+            loop {
+                if j >= chars.len() {
+                    return Err(format!("Unterminated string at offset {}.", offset));
+                }
+                let d = chars[j].1;
+                if d == '\\' {
+                    let escaped = if j + 1 < chars.len() { chars[j + 1].1 } else { ' ' };
+                    j += 2;
+                    match escaped {
+                        't' => value.push('\t'),
+                        'n' => value.push('\n'),
+                        'r' => value.push('\r'),
+                        'b' => value.push('\u{8}'),
+                        'f' => value.push('\u{c}'),
+                        '"' | '\'' | '\\' => value.push(escaped),
+                        'u' => { value.push(parse_hex(&chars, j, 4)?); j += 4; }
+                        'U' => { value.push(parse_hex(&chars, j, 8)?); j += 8; }
+                        _ => return Err(format!("Invalid escape \\{} at offset {}.", escaped, chars[j - 2].0)),
+                    }
+                    continue;
+                }
+                if long {
+                    if d == c && j + 2 < chars.len() && chars[j + 1].1 == c && chars[j + 2].1 == c {
+                        j += 3;
+                        break;
+                    }
+                } else if d == c {
+                    j += 1;
+                    break;
+                } else if d == '\n' || d == '\r' {
+                    return Err(format!("Line break in string at offset {}.", offset));
+                }
+                value.push(d);
+                j += 1;
+            }
+            tokens.push((offset, Token::Str(value)));
+            i = j;
+            continue;
+        }
+        if (c == '?' || c == '$') && i + 1 < chars.len() && is_name_char(chars[i + 1].1) {
+            let end = scan_name(&chars, i + 1, NameKind::Plain);
+            tokens.push((offset, Token::Var(input[chars[i + 1].0..offset_at(&chars, end, input)].to_owned())));
+            i = end;
+            continue;
+        }
+        if c == '_' && i + 1 < chars.len() && chars[i + 1].1 == ':' {
+            let end = scan_name(&chars, i + 2, NameKind::Prefix);
+            tokens.push((offset, Token::Blank(input[chars[i + 1].0 + 1..offset_at(&chars, end, input)].to_owned())));
+            i = end;
+            continue;
+        }
+        if regex_literals && c == '/' {
+            let mut j = i + 1;
+            let mut pattern = String::new();
+            while j < chars.len() && chars[j].1 != '/' {
+                if chars[j].1 == '\\' && j + 1 < chars.len() {
+                    /* Only the delimiter escape is resolved here; the rest belong to the pattern. */
+                    if chars[j + 1].1 != '/' {
+                        pattern.push('\\');
+                    }
+                    j += 1;
+                }
+                pattern.push(chars[j].1);
+                j += 1;
+            }
+            if j >= chars.len() {
+                return Err(format!("Unterminated regular expression at offset {}.", offset));
+            }
+            j += 1;
+            let flags_start = j;
+            while j < chars.len() && "smix".contains(chars[j].1) {
+                j += 1;
+            }
+            let flags: String = chars[flags_start..j].iter().map(|&(_, c)| c).collect();
+            tokens.push((offset, Token::Regex(pattern, flags)));
+            i = j;
+            continue;
+        }
+        if c == '@' && i + 1 < chars.len() && chars[i + 1].1.is_alphabetic() {
+            let mut j = i + 1;
+            while j < chars.len() && (chars[j].1.is_alphanumeric() || chars[j].1 == '-') {
+                j += 1;
+            }
+            /* `@ex:Shape` is a shape reference in ShExC, not a language tag. */
+            if j < chars.len() && chars[j].1 == ':' {
+                tokens.push((offset, Token::Punct("@")));
+                i += 1;
+                continue;
+            }
+            tokens.push((offset, Token::LangTag(input[chars[i + 1].0..offset_at(&chars, j, input)].to_owned())));
+            i = j;
+            continue;
+        }
+        if c.is_ascii_digit() || (c == '.' && i + 1 < chars.len() && chars[i + 1].1.is_ascii_digit()) {
+            let mut j = i;
+            while j < chars.len() && chars[j].1.is_ascii_digit() {
+                j += 1;
+            }
+            let mut decimal = false;
+            if j + 1 < chars.len() && chars[j].1 == '.' && chars[j + 1].1.is_ascii_digit() {
+                decimal = true;
+                j += 1;
+                while j < chars.len() && chars[j].1.is_ascii_digit() {
+                    j += 1;
+                }
+            }
+            let mut double = false;
+            if j < chars.len() && (chars[j].1 == 'e' || chars[j].1 == 'E') {
+                let mut k = j + 1;
+                if k < chars.len() && (chars[k].1 == '+' || chars[k].1 == '-') {
+                    k += 1;
+                }
+                if k < chars.len() && chars[k].1.is_ascii_digit() {
+                    double = true;
+                    j = k;
+                    while j < chars.len() && chars[j].1.is_ascii_digit() {
+                        j += 1;
+                    }
+                }
+            }
+            let text = input[offset..offset_at(&chars, j, input)].to_owned();
+            tokens.push((offset, if double { Token::Double(text) } else if decimal { Token::Decimal(text) } else { Token::Integer(text) }));
+            i = j;
+            continue;
+        }
+        if is_name_start(c) || c == ':' {
+            let end = if c == ':' { i } else { scan_name(&chars, i, NameKind::Prefix) };
+            if end < chars.len() && chars[end].1 == ':' {
+                let prefix = input[offset..chars[end].0].to_owned();
+                let local_end = if end + 1 < chars.len() && (is_name_char(chars[end + 1].1) || chars[end + 1].1 == '%' || chars[end + 1].1 == ':') {
+                    scan_name(&chars, end + 1, NameKind::Local)
+                } else {
+                    end + 1
+                };
+                let local = input[chars[end].0 + 1..offset_at(&chars, local_end, input)].to_owned();
+                tokens.push((offset, Token::PrefixedName(prefix, local)));
+                i = local_end;
+                continue;
+            }
+            let word_end = scan_name(&chars, i, NameKind::Plain);
+            tokens.push((offset, Token::Word(input[offset..offset_at(&chars, word_end, input)].to_owned())));
+            i = word_end;
+            continue;
+        }
+        let rest = &input[offset..];
+        if let Some(p) = PUNCTUATION.iter().find(|p| rest.starts_with(*p)) {
+            tokens.push((offset, Token::Punct(p)));
+            i += p.chars().count();
+            continue;
+        }
+        return Err(format!("Unexpected character {:?} at offset {}.", c, offset));
+    }
+    Ok(tokens)
+}
+
+/// A cursor over lexed tokens with the lookahead helpers the text parsers share.
+pub struct TokenStream {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    input_len: usize,
+}
+
+impl TokenStream {
+    pub fn new(input: &str) -> Result<TokenStream, String> {
+        Ok(TokenStream { tokens: lex(input)?, position: 0, input_len: input.len() })
+    }
+
+    pub fn with_regex_literals(input: &str) -> Result<TokenStream, String> {
+        Ok(TokenStream { tokens: lex_with_regex_literals(input)?, position: 0, input_len: input.len() })
+    }
+
+    pub fn peek<'a>(&'a self) -> Option<&'a Token> {
+        self.tokens.get(self.position).map(|&(_, ref t)| t)
+    }
+
+    pub fn peek_at<'a>(&'a self, ahead: usize) -> Option<&'a Token> {
+        self.tokens.get(self.position + ahead).map(|&(_, ref t)| t)
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.tokens.get(self.position).map(|&(o, _)| o).unwrap_or(self.input_len)
+    }
+
+    pub fn error<T>(&self, message: &str) -> Result<T, String> {
+        let found = self.peek().map(|t| format!("{:?}", t)).unwrap_or("end of input".to_owned());
+        Err(format!("{} at offset {}, found {}.", message, self.offset(), found))
+    }
+
+    pub fn is_punct(&self, punct: &str) -> bool {
+        if let Some(&Token::Punct(p)) = self.peek() { p == punct } else { false }
+    }
+
+    /// Words are matched case-insensitively, as keywords are in SPARQL.
+    pub fn is_word(&self, word: &str) -> bool {
+        if let Some(&Token::Word(ref w)) = self.peek() { w.eq_ignore_ascii_case(word) } else { false }
+    }
+
+    pub fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub fn eat_word(&mut self, word: &str) -> bool {
+        let found = self.is_word(word);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub fn expect_punct(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat_punct(punct) { Ok(()) } else { self.error(&format!("Expected {:?}", punct)) }
+    }
+
+    pub fn expect_word(&mut self, word: &str) -> Result<(), String> {
+        if self.eat_word(word) { Ok(()) } else { self.error(&format!("Expected {}", word)) }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn reset(&mut self, position: usize) {
+        self.position = position;
+    }
+}
+
+impl Iterator for TokenStream {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|&(_, ref t)| t.clone());
+        if token.is_some() {
+            self.position += 1;
+        }
+        token
+    }
+}
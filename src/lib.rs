@@ -1,15 +1,11 @@
-//#![feature(nll)]
-//#![feature(conservative_impl_trait)]
-#![feature(try_from)]
-#![feature(btree_range)]
-#![feature(const_fn)]
-#![feature(inclusive_range)]
-#![feature(inclusive_range_fields)]
-
-
-
-#![feature(proc_macro, specialization)]
-//#[cfg(feature = "python")]
+#![cfg_attr(feature = "python", feature(proc_macro, specialization))]
+/* The crate keeps the Rust 2015 idioms it was written in: bare trait objects, `&(ref a, ref b)`
+   patterns and explicit lifetimes. */
+#![allow(bare_trait_objects, ellipsis_inclusive_range_patterns, mismatched_lifetime_syntaxes)]
+#![allow(clippy::needless_borrowed_reference, clippy::match_ref_pats, clippy::redundant_static_lifetimes,
+         clippy::redundant_field_names, clippy::needless_lifetimes)]
+
+#[cfg(feature = "python")]
 #[macro_use]
 extern crate pyo3;
 
@@ -17,15 +13,38 @@ extern crate uuid;
 
 pub mod identifiers;
 pub mod uri;
+pub mod iri;
+pub mod uri_split;
+pub mod namespace;
+pub mod vocab;
+pub mod inference;
+pub mod rdfs;
+pub mod owl_rl;
+pub mod lexer;
+pub mod datalog;
+pub mod equality;
+pub mod regex;
+pub mod property_path;
+pub mod shacl;
+pub mod shex;
+pub mod sha256;
+pub mod json;
+pub mod xml;
+pub mod nquads;
+pub mod turtle;
+pub mod canonicalize;
+pub mod isomorphism;
+pub mod patch;
+pub mod limits;
+pub mod sparql;
+pub mod http;
+pub mod server;
 pub mod literal;
 pub mod blank;
 pub mod indexed_hash_map;
 pub mod indexed_quad_set;
 pub mod store;
+pub mod fulltext;
 
-//#[cfg(feature = "python")]
+#[cfg(feature = "python")]
 pub mod python;
-
-
-
-
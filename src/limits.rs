@@ -0,0 +1,234 @@
+use std::cell::Cell;
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/* The clock and cancellation flag are read once every this many units of work. */
+const CHECK_INTERVAL: usize = 1024;
+/* Estimated bytes of one intermediate row, and of each value bound in it. */
+const ROW_BYTES: usize = 48;
+const BINDING_BYTES: usize = 96;
+
+/// A flag shared between an operation and whoever may cancel it, possibly from another thread.
+/// Clones share the flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Bounds on a single query, update or bulk operation. Operations check them cooperatively as they
+/// work, so a limit is noticed shortly after it is passed rather than exactly at it.
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub timeout: Option<Duration>,
+    /* Rows held by one intermediate result, or quads staged by a bulk operation. */
+    pub max_rows: Option<usize>,
+    /* Estimated bytes held by one intermediate result or staged by a bulk operation. */
+    pub max_memory: Option<usize>,
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl Limits {
+    pub fn new() -> Limits {
+        Limits::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Limits {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_max_rows(mut self, max_rows: usize) -> Limits {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
+    pub fn with_max_memory(mut self, max_memory: usize) -> Limits {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Limits {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.timeout.is_none() && self.max_rows.is_none() && self.max_memory.is_none() && self.cancellation.is_none()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitKind {
+    Cancelled,
+    Timeout,
+    Rows,
+    Memory,
+}
+
+/// Why an operation was stopped by its limits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitExceeded {
+    pub kind: LimitKind,
+    pub message: String,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The error of an operation run with limits: stopped by a limit, or failed for any other reason.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OperationError {
+    Limit(LimitExceeded),
+    Failed(String),
+}
+
+impl OperationError {
+    pub fn is_limit(&self) -> bool {
+        matches!(self, &OperationError::Limit(_))
+    }
+}
+
+impl fmt::Display for OperationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OperationError::Limit(ref limit) => limit.fmt(f),
+            OperationError::Failed(ref message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for OperationError {
+    fn from(message: String) -> OperationError {
+        OperationError::Failed(message)
+    }
+}
+
+/// Tracks one operation against its limits. Checks fail with the limit's message as a plain
+/// error, so they fit code returning `Result<_, String>`; `exceeded` then tells that error apart.
+pub struct Guard {
+    limits: Limits,
+    started: Instant,
+    work: Cell<usize>,
+    exceeded: Cell<Option<LimitKind>>,
+}
+
+impl Guard {
+    pub fn new(limits: Limits) -> Guard {
+        Guard { limits, started: Instant::now(), work: Cell::new(0), exceeded: Cell::new(None) }
+    }
+
+    pub fn unlimited() -> Guard {
+        Guard::new(Limits::default())
+    }
+
+    pub fn borrow_limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// The limit that stopped the operation, once a check has failed.
+    pub fn exceeded(&self) -> Option<LimitExceeded> {
+        self.exceeded.get().map(|kind| LimitExceeded { kind, message: self.message(kind) })
+    }
+
+    /// Turns an error from the guarded operation into an `OperationError`.
+    pub fn error(&self, message: String) -> OperationError {
+        match self.exceeded() {
+            Some(limit) => OperationError::Limit(limit),
+            None => OperationError::Failed(message),
+        }
+    }
+
+    fn message(&self, kind: LimitKind) -> String {
+        match kind {
+            LimitKind::Cancelled => "The operation was cancelled.".to_owned(),
+            LimitKind::Timeout => format!("The operation exceeded its time limit of {:?}.", self.limits.timeout.unwrap_or_default()),
+            LimitKind::Rows => format!("The operation exceeded its limit of {} intermediate rows.", self.limits.max_rows.unwrap_or(0)),
+            LimitKind::Memory => format!("The operation exceeded its memory limit of {} bytes.", self.limits.max_memory.unwrap_or(0)),
+        }
+    }
+
+    fn fail(&self, kind: LimitKind) -> Result<(), String> {
+        self.exceeded.set(Some(kind));
+        Err(self.message(kind))
+    }
+
+    /// Checks cancellation and the time limit now.
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(kind) = self.exceeded.get() {
+            return Err(self.message(kind));
+        }
+        if self.limits.cancellation.as_ref().map(|t| t.is_cancelled()).unwrap_or(false) {
+            return self.fail(LimitKind::Cancelled);
+        }
+        if self.limits.timeout.map(|t| self.started.elapsed() > t).unwrap_or(false) {
+            return self.fail(LimitKind::Timeout);
+        }
+        Ok(())
+    }
+
+    /// The time left before the time limit, if there is one.
+    pub fn remaining_time(&self) -> Option<Duration> {
+        self.limits.timeout.map(|t| t.checked_sub(self.started.elapsed()).unwrap_or_default())
+    }
+
+    /// Counts a unit of work, such as a quad visited, checking the clock every so often.
+    pub fn work(&self) -> Result<(), String> {
+        if let Some(kind) = self.exceeded.get() {
+            return Err(self.message(kind));
+        }
+        if self.limits.timeout.is_none() && self.limits.cancellation.is_none() {
+            return Ok(());
+        }
+        let work = self.work.get() + 1;
+        self.work.set(work);
+        if work.is_multiple_of(CHECK_INTERVAL) { self.check() } else { Ok(()) }
+    }
+
+    /// Checks an intermediate result holding `rows` rows of about `width` values each.
+    pub fn rows(&self, rows: usize, width: usize) -> Result<(), String> {
+        self.row_count(rows)?;
+        self.bytes(rows * (ROW_BYTES + width * BINDING_BYTES))?;
+        self.work()
+    }
+
+    /// Checks the number of rows or quads an operation holds, without estimating their size.
+    pub fn row_count(&self, rows: usize) -> Result<(), String> {
+        if self.limits.max_rows.map(|max| rows > max).unwrap_or(false) {
+            return self.fail(LimitKind::Rows);
+        }
+        Ok(())
+    }
+
+    /// Checks an estimate of the bytes an operation holds.
+    pub fn bytes(&self, bytes: usize) -> Result<(), String> {
+        if self.limits.max_memory.map(|max| bytes > max).unwrap_or(false) {
+            return self.fail(LimitKind::Memory);
+        }
+        Ok(())
+    }
+}
+
+/// The estimated heap size of a string, for memory limits.
+pub fn string_bytes(text: &str) -> usize {
+    mem::size_of::<String>() + text.len()
+}
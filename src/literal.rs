@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 use store::StorageEngine;
 use uri::RDFUri;
@@ -5,6 +6,200 @@ use uri::RDFUri;
 pub static STRING_URI: &'static str = "http://www.w3.org/2001/XMLSchema#string";
 pub static LANG_STRING_URI: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
 static LITERAL_HASH_PREFIX: &'static str = "L:";
+static XSD: &'static str = "http://www.w3.org/2001/XMLSchema#";
+/* The integer types derived from xsd:integer, with their bounds. */
+static INTEGER_TYPES: &'static [(&'static str, i128, i128)] = &[
+    ("integer", i128::MIN, i128::MAX), ("long", i64::MIN as i128, i64::MAX as i128),
+    ("int", i32::MIN as i128, i32::MAX as i128), ("short", i16::MIN as i128, i16::MAX as i128),
+    ("byte", i8::MIN as i128, i8::MAX as i128), ("nonNegativeInteger", 0, i128::MAX),
+    ("positiveInteger", 1, i128::MAX), ("nonPositiveInteger", i128::MIN, 0), ("negativeInteger", i128::MIN, -1),
+    ("unsignedLong", 0, u64::MAX as i128), ("unsignedInt", 0, u32::MAX as i128),
+    ("unsignedShort", 0, u16::MAX as i128), ("unsignedByte", 0, u8::MAX as i128),
+];
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+/* The xsd:decimal grammar: an optional sign, then digits with at most one point among them. */
+fn is_decimal(lexical: &str) -> bool {
+    let unsigned = lexical.strip_prefix(['+', '-']).unwrap_or(lexical);
+    let mut parts = unsigned.splitn(2, '.');
+    let whole = parts.next().unwrap_or("");
+    match parts.next() {
+        Some(fraction) => (whole.is_empty() || is_digits(whole)) && (fraction.is_empty() || is_digits(fraction)) && !(whole.is_empty() && fraction.is_empty()),
+        None => is_digits(whole),
+    }
+}
+
+/* The xsd:double grammar: a decimal with an optional exponent, or one of the special values. */
+fn is_double(lexical: &str) -> bool {
+    if ["INF", "+INF", "-INF", "NaN"].contains(&lexical) {
+        return true;
+    }
+    let mut parts = lexical.splitn(2, ['e', 'E']);
+    let mantissa = parts.next().unwrap_or("");
+    is_decimal(mantissa) && parts.next().map(|e| is_digits(e.strip_prefix(['+', '-']).unwrap_or(e))).unwrap_or(true)
+}
+
+/* Strips a timezone suffix, "Z" or "+hh:mm" / "-hh:mm", if there is one. */
+fn strip_timezone(lexical: &str) -> &str {
+    if let Some(rest) = lexical.strip_suffix('Z') {
+        return rest;
+    }
+    let split = lexical.len().saturating_sub(6);
+    match (lexical.get(..split), lexical.get(split..).map(str::as_bytes)) {
+        (Some(rest), Some(&[sign, h1, h2, b':', m1, m2])) if !rest.is_empty() && (sign == b'+' || sign == b'-')
+            && [h1, h2, m1, m2].iter().all(u8::is_ascii_digit) => rest,
+        _ => lexical,
+    }
+}
+
+fn is_date(lexical: &str) -> bool {
+    let parts: Vec<&str> = lexical.strip_prefix('-').unwrap_or(lexical).split('-').collect();
+    if parts.len() != 3 || parts[0].len() < 4 || !parts.iter().all(|p| is_digits(p)) || parts[1].len() != 2 || parts[2].len() != 2 {
+        return false;
+    }
+    let (month, day): (u32, u32) = (parts[1].parse().unwrap(), parts[2].parse().unwrap());
+    (1..13).contains(&month) && (1..32).contains(&day)
+}
+
+fn is_time(lexical: &str) -> bool {
+    let (clock, fraction) = match lexical.find('.') {
+        Some(point) => (&lexical[..point], Some(&lexical[point + 1..])),
+        None => (lexical, None),
+    };
+    let parts: Vec<&str> = clock.split(':').collect();
+    parts.len() == 3 && parts.iter().all(|p| p.len() == 2 && is_digits(p)) && fraction.map(is_digits).unwrap_or(true)
+        && parts[0] < "24" && parts[1] < "60" && parts[2] < "60"
+}
+
+/// The value of a literal with a numeric XSD datatype. `None` if the datatype is not numeric or the
+/// lexical form is not in its lexical space.
+pub fn numeric_value(lexical_form: &str, datatype: &str) -> Option<f64> {
+    if !datatype.starts_with(XSD) {
+        return None;
+    }
+    let local = &datatype[XSD.len()..];
+    let lexical = lexical_form.trim();
+    if let Some(&(_, min, max)) = INTEGER_TYPES.iter().find(|t| t.0 == local) {
+        if !is_digits(lexical.strip_prefix(['+', '-']).unwrap_or(lexical)) {
+            return None;
+        }
+        let value: i128 = lexical.strip_prefix('+').unwrap_or(lexical).parse().ok()?;
+        return if value >= min && value <= max { Some(value as f64) } else { None };
+    }
+    match local {
+        "decimal" if is_decimal(lexical) => lexical.parse().ok(),
+        "double" | "float" if is_double(lexical) => match lexical {
+            "INF" | "+INF" => Some(f64::INFINITY),
+            "-INF" => Some(f64::NEG_INFINITY),
+            "NaN" => Some(f64::NAN),
+            _ => lexical.parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+/// Whether `lexical_form` is in the lexical space of `datatype`. Only the common XSD datatypes are
+/// checked; literals of any other datatype are taken to be well-typed.
+pub fn is_well_typed(lexical_form: &str, datatype: &str) -> bool {
+    if !datatype.starts_with(XSD) {
+        return true;
+    }
+    let local = &datatype[XSD.len()..];
+    let lexical = lexical_form.trim();
+    if INTEGER_TYPES.iter().any(|t| t.0 == local) || ["decimal", "double", "float"].contains(&local) {
+        return numeric_value(lexical, datatype).is_some();
+    }
+    match local {
+        "boolean" => ["true", "false", "1", "0"].contains(&lexical),
+        "date" => is_date(strip_timezone(lexical)),
+        "time" => is_time(strip_timezone(lexical)),
+        "dateTime" => {
+            let rest = strip_timezone(lexical);
+            rest.find('T').map(|t| is_date(&rest[..t]) && is_time(&rest[t + 1..])).unwrap_or(false)
+        }
+        _ => true,
+    }
+}
+
+/// The value of an xsd:dateTime, xsd:date or xsd:time literal: the seconds since 1970-01-01
+/// (a date being its first instant, a time falling on that day), shifted to UTC when a timezone
+/// is given, and the digits of any fraction of a second, without trailing zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TemporalValue {
+    pub seconds: i64,
+    pub fraction: String,
+    pub has_timezone: bool,
+}
+
+impl TemporalValue {
+    /// Compares two values, or `None` when only one of them has a timezone.
+    pub fn compare(&self, other: &TemporalValue) -> Option<Ordering> {
+        if self.has_timezone != other.has_timezone {
+            return None;
+        }
+        /* Fraction digits without trailing zeros compare as strings. */
+        Some(self.seconds.cmp(&other.seconds).then_with(|| self.fraction.cmp(&other.fraction)))
+    }
+}
+
+/* Days from 1970-01-01 to a date of the proleptic Gregorian calendar. */
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    era * 146097 + year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year - 719468
+}
+
+/// The value of a literal with datatype xsd:dateTime, xsd:date or xsd:time. `None` for other
+/// datatypes, lexical forms not in the lexical space, and years beyond nine digits.
+pub fn temporal_value(lexical_form: &str, datatype: &str) -> Option<TemporalValue> {
+    let lexical = lexical_form.trim();
+    let rest = strip_timezone(lexical);
+    let offset_minutes = match &lexical[rest.len()..] {
+        "" | "Z" => 0,
+        zone => {
+            let minutes = zone[1..3].parse::<i64>().ok()? * 60 + zone[4..].parse::<i64>().ok()?;
+            if zone.starts_with('-') { -minutes } else { minutes }
+        }
+    };
+    let (date, time) = match datatype.strip_prefix(XSD)? {
+        "dateTime" => rest.find('T').map(|t| (Some(&rest[..t]), Some(&rest[t + 1..])))?,
+        "date" => (Some(rest), None),
+        "time" => (None, Some(rest)),
+        _ => return None,
+    };
+    let mut seconds = -offset_minutes * 60;
+    let mut fraction = String::new();
+    if let Some(date) = date {
+        if !is_date(date) {
+            return None;
+        }
+        let (sign, unsigned) = match date.strip_prefix('-') {
+            Some(unsigned) => (-1, unsigned),
+            None => (1, date),
+        };
+        let parts: Vec<&str> = unsigned.split('-').collect();
+        if parts[0].len() > 9 {
+            return None;
+        }
+        let year = sign * parts[0].parse::<i64>().ok()?;
+        seconds += days_from_civil(year, parts[1].parse().ok()?, parts[2].parse().ok()?) * 86400;
+    }
+    if let Some(time) = time {
+        if !is_time(time) {
+            return None;
+        }
+        let (clock, digits) = time.split_at(time.find('.').unwrap_or(time.len()));
+        let parts: Vec<i64> = clock.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        seconds += parts[0] * 3600 + parts[1] * 60 + parts[2];
+        fraction = digits.trim_start_matches('.').trim_end_matches('0').to_owned();
+    }
+    Some(TemporalValue { seconds: seconds, fraction: fraction, has_timezone: lexical.len() != rest.len() })
+}
 
 #[derive(PartialEq, PartialOrd, Clone, Debug)]
 pub struct Literal {
@@ -28,47 +223,47 @@ impl Literal {
     pub fn new(store: &mut StorageEngine, lexical_form: &str, data_type: Option<&str>, lang: Option<&str>) -> Literal {
         let (determined_data_type, determined_lang): (&str, Option<String>) =
         match (data_type, lang) {
-            (None, None) => (&STRING_URI, None),
+            (None, None) => (STRING_URI, None),
             (Some(dt), None) => (dt, None),
-            (None, Some(l)) => (&LANG_STRING_URI, Some(l.to_owned())),
+            (None, Some(l)) => (LANG_STRING_URI, Some(l.to_owned())),
             (Some(dt), Some(l)) => {
-                if !(dt == LANG_STRING_URI) {
-                    panic!(format!("data_type must be None or \"{}\" when using lang arg.", LANG_STRING_URI))
+                if dt != LANG_STRING_URI {
+                    panic!("data_type must be None or \"{}\" when using lang arg.", LANG_STRING_URI)
                 }
-                (&LANG_STRING_URI, Some(l.to_owned()))
+                (LANG_STRING_URI, Some(l.to_owned()))
             }
         };
         let data_type_uri = RDFUri::from_string(store, determined_data_type);
-        let l = Literal { lexical_form: lexical_form.to_owned(), data_type: data_type_uri, lang: determined_lang };
-        println!("new literal: {:?}",l);
-        l
+        Literal { lexical_form: lexical_form.to_owned(), data_type: data_type_uri, lang: determined_lang }
     }
 
+    /* Err(()) means the literal was never interned; callers need nothing more. */
+    #[allow(clippy::result_unit_err)]
     pub fn construct_if_exist(store: &StorageEngine, lexical_form: &str, data_type: Option<&str>, lang: Option<&str>) -> Result<Literal, ()> {
         let (determined_data_type, determined_lang): (&str, Option<String>) =
             match (data_type, lang) {
-                (None, None) => (&STRING_URI, None),
+                (None, None) => (STRING_URI, None),
                 (Some(dt), None) => (dt, None),
-                (None, Some(l)) => (&LANG_STRING_URI, Some(l.to_owned())),
+                (None, Some(l)) => (LANG_STRING_URI, Some(l.to_owned())),
                 (Some(dt), Some(l)) => {
-                    if !(dt == LANG_STRING_URI) {
-                        panic!(format!("data_type must be None or {} when using lang arg.", LANG_STRING_URI))
+                    if dt != LANG_STRING_URI {
+                        panic!("data_type must be None or {} when using lang arg.", LANG_STRING_URI)
                     }
-                    (&LANG_STRING_URI, Some(l.to_owned()))
+                    (LANG_STRING_URI, Some(l.to_owned()))
                 }
             };
         let data_type_uri = if let Ok(u) = RDFUri::from_string_if_exist(store, determined_data_type) { u } else {
             return Result::Err(());
         };
-        let l = Literal { lexical_form: lexical_form.to_owned(), data_type: data_type_uri, lang: determined_lang };
-        println!("constructed literal: {:?}",l);
-        Ok(l)
+        Ok(Literal { lexical_form: lexical_form.to_owned(), data_type: data_type_uri, lang: determined_lang })
     }
 
     pub fn from_string(store: &mut StorageEngine, lexical_form: &str) -> Literal {
         Self::new(store, lexical_form, None, None)
     }
 
+    /* Err(()) as in `construct_if_exist`. */
+    #[allow(clippy::result_unit_err)]
     pub fn from_string_if_exist(store: &StorageEngine, lexical_form: &str) -> Result<Literal, ()> {
         Self::construct_if_exist(store, lexical_form, None, None)
     }
@@ -78,11 +273,13 @@ impl Literal {
         Self::new(store, lexical_form, None, Some(lang))
     }
 
+    /* Err(()) as in `construct_if_exist`. */
+    #[allow(clippy::result_unit_err)]
     pub fn with_lang_if_exist(store: &StorageEngine, lexical_form: &str, lang: &str) -> Result<Literal, ()> {
         Self::construct_if_exist(store, lexical_form, None, Some(lang))
     }
 
-    pub fn parse_raw_literal(store: &mut StorageEngine, raw_literal: &str) -> Literal {
+    pub fn parse_raw_literal(_store: &mut StorageEngine, _raw_literal: &str) -> Literal {
         unimplemented!("raw literal parsing is not yet implemented!")
     }
 
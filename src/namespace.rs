@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Iter as BTreeMapIter;
+use iri::IriRef;
+
+fn is_valid_prefix(prefix: &str) -> bool {
+    let mut chars = prefix.chars();
+    match chars.next() {
+        None => true,
+        Some(first) => first.is_alphabetic() &&
+            chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.') &&
+            !prefix.ends_with('.')
+    }
+}
+
+fn is_valid_local_name(local: &str) -> bool {
+    !local.ends_with('.') && !local.starts_with('-') && !local.starts_with('.') &&
+        local.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Prefix <-> namespace bindings kept alongside the store, used for CURIE expansion and compaction.
+/// Each prefix maps to one namespace and each namespace to the prefix it is written with.
+#[derive(Default, Clone, Debug)]
+pub struct NamespaceManager {
+    namespaces_by_prefix: BTreeMap<String, String>,
+    prefixes_by_namespace: BTreeMap<String, String>,
+}
+
+impl NamespaceManager {
+    /// Binds `prefix` to `namespace`. An existing binding for the prefix is only replaced when
+    /// `replace` is true; otherwise it is kept and binding the prefix to a different namespace is
+    /// an error. A namespace may be bound under several prefixes: `prefix` answers the most
+    /// recently bound one, unless `replace` is false and it already has one.
+    pub fn bind(&mut self, prefix: &str, namespace: &str, replace: bool) -> Result<(), String> {
+        if !is_valid_prefix(prefix) {
+            return Err(format!("{:?} is not a valid namespace prefix.", prefix));
+        }
+        IriRef::parse_absolute(namespace)?;
+        if let Some(existing) = self.namespaces_by_prefix.get(prefix).cloned() {
+            if existing == namespace {
+                if replace {
+                    self.prefixes_by_namespace.insert(namespace.to_owned(), prefix.to_owned());
+                }
+                return Ok(());
+            }
+            if !replace {
+                return Err(format!("Prefix {:?} is already bound to <{}>.", prefix, existing));
+            }
+            self.unbind(prefix)?;
+        }
+        if replace || !self.prefixes_by_namespace.contains_key(namespace) {
+            self.prefixes_by_namespace.insert(namespace.to_owned(), prefix.to_owned());
+        }
+        self.namespaces_by_prefix.insert(prefix.to_owned(), namespace.to_owned());
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, prefix: &str) -> Result<String, String> {
+        let namespace = if let Some(n) = self.namespaces_by_prefix.remove(prefix) { n }
+            else { return Err(format!("Prefix {:?} is not bound.", prefix)); };
+        let bound_here = self.prefixes_by_namespace.get(&namespace).map(|p| p == prefix).unwrap_or(false);
+        if bound_here {
+            /* Fall back to another prefix still bound to the namespace. */
+            let other = self.namespaces_by_prefix.iter().find(|&(_, n)| *n == namespace).map(|(p, _)| p.clone());
+            match other {
+                Some(other) => self.prefixes_by_namespace.insert(namespace.clone(), other),
+                None => self.prefixes_by_namespace.remove(&namespace),
+            };
+        }
+        Ok(namespace)
+    }
+
+    pub fn namespace<'a>(&'a self, prefix: &str) -> Option<&'a str> {
+        self.namespaces_by_prefix.get(prefix).map(|n| n.as_str())
+    }
+
+    pub fn prefix<'a>(&'a self, namespace: &str) -> Option<&'a str> {
+        self.prefixes_by_namespace.get(namespace).map(|p| p.as_str())
+    }
+
+    /// Iterates `(prefix, namespace)` pairs in prefix order.
+    pub fn namespaces<'a>(&'a self) -> BTreeMapIter<'a, String, String> {
+        self.namespaces_by_prefix.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.namespaces_by_prefix.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.namespaces_by_prefix.is_empty()
+    }
+
+    pub fn expand_curie(&self, curie: &str) -> Result<String, String> {
+        let colon = if let Some(i) = curie.find(':') { i }
+            else { return Err(format!("{:?} is not a CURIE, it has no colon.", curie)); };
+        let (prefix, local) = (&curie[..colon], &curie[colon + 1..]);
+        if let Some(namespace) = self.namespace(prefix) {
+            Ok(format!("{}{}", namespace, local))
+        } else {
+            Err(format!("Prefix {:?} is not bound.", prefix))
+        }
+    }
+
+    /// Compacts `iri` against the longest bound namespace it starts with, if the remainder
+    /// is a valid local name.
+    pub fn compact_iri(&self, iri: &str) -> Option<String> {
+        let mut best: Option<(&str, &str)> = None;
+        for (namespace, prefix) in self.prefixes_by_namespace.iter() {
+            if iri.starts_with(namespace.as_str()) && is_valid_local_name(&iri[namespace.len()..]) {
+                let longer = best.map(|(n, _)| namespace.len() > n.len()).unwrap_or(true);
+                if longer {
+                    best = Some((namespace, prefix));
+                }
+            }
+        }
+        best.map(|(namespace, prefix)| format!("{}:{}", prefix, &iri[namespace.len()..]))
+    }
+}
@@ -0,0 +1,269 @@
+use std::fs;
+use std::path::Path;
+use identifiers::InternalID;
+use iri::IriRef;
+use blank::{BlankNode, BlankNodeScope};
+use lexer::{Token, TokenStream};
+use literal::{self, Literal};
+use uri::RDFUri;
+use store::{StorageEngine, StoreNode, InternalQuad, GraphID};
+
+/// Escapes a lexical form for a canonical N-Triples string literal.
+pub fn escape_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            c if c < '\u{20}' || c == '\u{7f}' => escaped.push_str(&format!("\\u{:04X}", c as u32)),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+/// An RDF term as written in N-Triples, independent of any store. Blank node labels are the
+/// store-wide labels, so a term read back resolves to the node it was written from.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Term {
+    Iri(String),
+    Blank(String),
+    /* Lexical form, then datatype IRI unless it is xsd:string or the literal has a language. */
+    Literal(String, Option<String>, Option<String>),
+}
+
+impl Term {
+    pub fn from_node(store: &StorageEngine, id: &InternalID) -> Result<Term, String> {
+        match store.lookup_node_by_iid(id) {
+            Ok(&StoreNode::URIRef(ref u)) => Ok(Term::Iri(u.to_string(store))),
+            Ok(&StoreNode::Blank(ref b)) => Ok(Term::Blank(b.borrow_label().to_owned())),
+            Ok(&StoreNode::Literal(ref l)) => {
+                let datatype = l.borrow_datatype_uri().to_string(store);
+                let lang = l.borrow_lang().map(|l| l.to_owned());
+                let datatype = if lang.is_some() || datatype == literal::STRING_URI { None } else { Some(datatype) };
+                Ok(Term::Literal(l.borrow_lexical_form().to_owned(), datatype, lang))
+            }
+            Err(_) => Err(format!("No node for internal id {:?}.", id)),
+        }
+    }
+
+    /// Reads one IRI, blank node or literal term.
+    pub fn read(tokens: &mut TokenStream) -> Result<Term, String> {
+        let offset = tokens.offset();
+        match tokens.next() {
+            Some(Token::IriRef(iri)) => Ok(Term::Iri(iri)),
+            Some(Token::Blank(label)) => Ok(Term::Blank(label)),
+            Some(Token::Str(value)) => {
+                if let Some(Token::LangTag(lang)) = tokens.peek().cloned() {
+                    tokens.next();
+                    return Ok(Term::Literal(value, None, Some(lang)));
+                }
+                if tokens.eat_punct("^^") {
+                    return match tokens.next() {
+                        Some(Token::IriRef(ref datatype)) if datatype == literal::STRING_URI => Ok(Term::Literal(value, None, None)),
+                        Some(Token::IriRef(datatype)) => Ok(Term::Literal(value, Some(datatype), None)),
+                        _ => tokens.error("Expected a datatype IRI"),
+                    };
+                }
+                Ok(Term::Literal(value, None, None))
+            }
+            Some(other) => Err(format!("Expected an RDF term at offset {}, found {:?}.", offset, other)),
+            None => Err(format!("Expected an RDF term at offset {}, found end of input.", offset)),
+        }
+    }
+
+    pub fn to_ntriples(&self) -> String {
+        match self {
+            &Term::Iri(ref iri) => format!("<{}>", iri),
+            &Term::Blank(ref label) => format!("_:{}", label),
+            &Term::Literal(ref value, None, None) => format!("\"{}\"", escape_string(value)),
+            &Term::Literal(ref value, _, Some(ref lang)) => format!("\"{}\"@{}", escape_string(value), lang),
+            &Term::Literal(ref value, Some(ref datatype), None) => format!("\"{}\"^^<{}>", escape_string(value), datatype),
+        }
+    }
+
+    /// The internal ID of this term if the store already has it.
+    pub fn find(&self, store: &StorageEngine) -> Option<InternalID> {
+        let node = match self {
+            &Term::Iri(ref iri) => StoreNode::URIRef(RDFUri::from_string_if_exist(store, iri).ok()?),
+            &Term::Blank(ref label) => StoreNode::Blank(BlankNode::new(Some(label))),
+            &Term::Literal(ref value, ref datatype, ref lang) =>
+                StoreNode::Literal(Literal::construct_if_exist(store, value, datatype.as_ref().map(|d| d.as_str()), lang.as_ref().map(|l| l.as_str())).ok()?),
+        };
+        store.find_internal_id(&node).ok()
+    }
+
+    /// This term, with a blank node relabelled to the scope's store-wide blank node.
+    pub fn scoped(self, scope: &mut BlankNodeScope) -> Term {
+        match self {
+            Term::Blank(label) => Term::Blank(scope.blank_node(&label).borrow_label().to_owned()),
+            other => other,
+        }
+    }
+
+    pub fn intern(&self, store: &mut StorageEngine) -> Result<InternalID, String> {
+        let node = match self {
+            &Term::Iri(ref iri) => StoreNode::URIRef(RDFUri::try_from_string(store, iri)?),
+            &Term::Blank(ref label) => StoreNode::Blank(BlankNode::new(Some(label))),
+            &Term::Literal(ref value, ref datatype, ref lang) => {
+                if let Some(ref datatype) = *datatype {
+                    IriRef::parse_absolute(datatype)?;
+                }
+                StoreNode::Literal(Literal::new(store, value, datatype.as_ref().map(|d| d.as_str()), lang.as_ref().map(|l| l.as_str())))
+            }
+        };
+        store.find_or_add_internal_id(node)
+    }
+}
+
+/// Reads `subject predicate object graph? .`, returning `None` for the graph of a triple.
+pub fn read_quad(tokens: &mut TokenStream) -> Result<(Term, Term, Term, Option<Term>), String> {
+    let subject = Term::read(tokens)?;
+    let predicate = Term::read(tokens)?;
+    let object = Term::read(tokens)?;
+    let graph = if tokens.is_punct(".") { None } else { Some(Term::read(tokens)?) };
+    tokens.expect_punct(".")?;
+    Ok((subject, predicate, object, graph))
+}
+
+/// A subject, predicate, object and graph, which is `None` for the default graph.
+pub type Quad = (Term, Term, Term, Option<Term>);
+
+/// Parses N-Quads or N-Triples without loading them, checking that every IRI is absolute.
+pub fn parse(text: &str) -> Result<Vec<Quad>, String> {
+    let mut tokens = TokenStream::new(text)?;
+    let mut quads = Vec::new();
+    while !tokens.is_at_end() {
+        let quad = read_quad(&mut tokens)?;
+        for term in [Some(&quad.0), Some(&quad.1), Some(&quad.2), quad.3.as_ref()].iter().filter_map(|t| *t) {
+            let iri = match term {
+                &Term::Iri(ref iri) => iri,
+                &Term::Literal(_, Some(ref datatype), _) => datatype,
+                _ => continue,
+            };
+            IriRef::parse_absolute(iri).map_err(|e| format!("Statement {}: {}", quads.len() + 1, e))?;
+        }
+        quads.push(quad);
+    }
+    Ok(quads)
+}
+
+/// Reads N-Quads or N-Triples into `store`, returning the number of statements read. With `graph`,
+/// every triple goes into that graph and statements naming a graph are rejected. Blank node labels
+/// are scoped to `text`, so they never join blank nodes already in the store.
+pub fn load(store: &mut StorageEngine, text: &str, graph: Option<&GraphID>) -> Result<usize, String> {
+    read_into(store, text, graph, Some(&mut BlankNodeScope::new()))
+}
+
+/* Reads statements into `store`, keeping blank node labels as they are written unless `scope` is given. */
+fn read_into(store: &mut StorageEngine, text: &str, graph: Option<&GraphID>, mut scope: Option<&mut BlankNodeScope>) -> Result<usize, String> {
+    let mut tokens = TokenStream::new(text)?;
+    let mut count = 0;
+    while !tokens.is_at_end() {
+        let (mut subject, predicate, mut object, mut graph_name) = read_quad(&mut tokens)?;
+        if let Some(ref mut scope) = scope {
+            subject = subject.scoped(scope);
+            object = object.scoped(scope);
+            graph_name = graph_name.map(|g| g.scoped(scope));
+        }
+        let g = match (graph, graph_name) {
+            (Some(g), None) => g.clone(),
+            (Some(_), Some(_)) => return Err(format!("Statement {} names a graph, but only triples are allowed here.", count + 1)),
+            (None, Some(name)) => name.intern(store)?,
+            (None, None) => InternalID(0.into()),
+        };
+        let (s, p, o) = (subject.intern(store)?, predicate.intern(store)?, object.intern(store)?);
+        store.add_internal_quad(g, s, p, o);
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn is_default_graph(graph: &GraphID) -> bool {
+    *graph == InternalID(0.into())
+}
+
+/// Writes `quad` as one N-Quads line, including the trailing newline. Blank nodes are written as
+/// `_:` followed by whatever `blank_label` returns for them. The graph term is left out for the
+/// default graph, or always when `include_graph` is false.
+pub fn quad_to_line<F>(store: &StorageEngine, quad: &InternalQuad, include_graph: bool, blank_label: F) -> Result<String, String>
+    where F: Fn(&InternalID, &BlankNode) -> String {
+    let term = |id: &InternalID| -> Result<String, String> {
+        match store.lookup_node_by_iid(id) {
+            Ok(&StoreNode::Blank(ref b)) => Ok(format!("_:{}", blank_label(id, b))),
+            Ok(node) => Ok(node.to_ntriples(store)),
+            Err(_) => Err(format!("No node for internal id {:?}.", id)),
+        }
+    };
+    let (ref s, ref p, ref o, ref g) = *quad;
+    if include_graph && !is_default_graph(g) {
+        Ok(format!("{} {} {} {} .\n", term(s)?, term(p)?, term(o)?, term(g)?))
+    } else {
+        Ok(format!("{} {} {} .\n", term(s)?, term(p)?, term(o)?))
+    }
+}
+
+/// The asserted quads of `graph`, or of the whole dataset, as N-Quads with lines in code point order.
+/// A single graph is written as triples.
+pub fn serialize(store: &StorageEngine, graph: Option<&GraphID>) -> Result<String, String> {
+    let mut lines = Vec::new();
+    for (g, s, p, o) in store.search_engine_internal(graph.cloned(), None, None, None) {
+        lines.push(quad_to_line(store, &(s, p, o, g), graph.is_none(), |_, b| b.borrow_label().to_owned())?);
+    }
+    lines.sort();
+    lines.dedup();
+    Ok(lines.concat())
+}
+
+/* Prefix bindings are saved as comment lines ahead of the quads, so the file stays N-Quads. */
+static PREFIX_LINE: &'static str = "# @prefix ";
+
+/// Opens a store saved with `save_file`, or an empty store when `path` does not exist yet.
+pub fn open_file(path: &Path) -> Result<StorageEngine, String> {
+    let mut store = StorageEngine::default();
+    if path.exists() {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        read_prefix_lines(&mut store, &text).map_err(|e| format!("Could not load {}: {}", path.display(), e))?;
+        /* The file is the store itself, so its blank nodes keep their labels. */
+        read_into(&mut store, &text, None, None).map_err(|e| format!("Could not load {}: {}", path.display(), e))?;
+    }
+    Ok(store)
+}
+
+fn read_prefix_lines(store: &mut StorageEngine, text: &str) -> Result<(), String> {
+    for line in text.lines().take_while(|l| l.starts_with('#')) {
+        let binding = match line.strip_prefix(PREFIX_LINE) {
+            Some(binding) => binding.trim_end().trim_end_matches('.').trim_end(),
+            None => continue,
+        };
+        let (prefix, namespace) = match binding.find(':') {
+            Some(i) => (&binding[..i], binding[i + 1..].trim()),
+            None => return Err(format!("Invalid prefix line {:?}.", line)),
+        };
+        if !namespace.starts_with('<') || !namespace.ends_with('>') || namespace.len() < 2 {
+            return Err(format!("Invalid prefix line {:?}.", line));
+        }
+        store.borrow_namespace_manager_mut().bind(prefix, &namespace[1..namespace.len() - 1], true)?;
+    }
+    Ok(())
+}
+
+/// Saves the asserted quads of `store` to `path` as N-Quads, preceded by its prefix bindings as
+/// comments. The data is written to a temporary file beside `path` and renamed over it, so a
+/// failed save leaves the previous file intact.
+pub fn save_file(store: &StorageEngine, path: &Path) -> Result<(), String> {
+    let mut text = String::new();
+    for (prefix, namespace) in store.borrow_namespace_manager().namespaces() {
+        text.push_str(&format!("{}{}: <{}> .\n", PREFIX_LINE, prefix, namespace));
+    }
+    text.push_str(&serialize(store, None)?);
+    let mut temp = path.to_path_buf().into_os_string();
+    temp.push(".tmp");
+    fs::write(&temp, text.as_bytes()).map_err(|e| format!("Could not write {:?}: {}", temp, e))?;
+    fs::rename(&temp, path).map_err(|e| format!("Could not replace {}: {}", path.display(), e))
+}
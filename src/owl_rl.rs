@@ -0,0 +1,432 @@
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use inference::{InferenceRule, Derivation, PatternRule, RulePattern, RuleTerm, RuleConclusion};
+use rdfs::{RdfsVocabulary, rdfs_core_rules};
+use nquads::Term;
+use store::{StorageEngine, InternalQuad, GraphID};
+use vocab;
+
+#[derive(Clone, Debug)]
+pub struct OwlVocabulary {
+    pub rdf_first: InternalID,
+    pub rdf_rest: InternalID,
+    pub rdf_nil: InternalID,
+    pub same_as: InternalID,
+    pub different_from: InternalID,
+    pub inverse_of: InternalID,
+    pub transitive_property: InternalID,
+    pub symmetric_property: InternalID,
+    pub asymmetric_property: InternalID,
+    pub irreflexive_property: InternalID,
+    pub functional_property: InternalID,
+    pub inverse_functional_property: InternalID,
+    pub equivalent_class: InternalID,
+    pub equivalent_property: InternalID,
+    pub disjoint_with: InternalID,
+    pub property_disjoint_with: InternalID,
+    pub property_chain_axiom: InternalID,
+    pub thing: InternalID,
+    pub nothing: InternalID,
+    pub has_value: InternalID,
+    pub on_property: InternalID,
+    pub some_values_from: InternalID,
+    pub all_values_from: InternalID,
+    pub intersection_of: InternalID,
+    pub union_of: InternalID,
+}
+
+impl OwlVocabulary {
+    pub fn intern(store: &mut StorageEngine) -> Result<OwlVocabulary, String> {
+        OwlVocabulary::from_lookup(|iri| store.uri_str_to_internal_id(iri))
+    }
+
+    /// The vocabulary as the store already holds it; see `RdfsVocabulary::lookup`.
+    pub fn lookup(store: &StorageEngine) -> OwlVocabulary {
+        OwlVocabulary::from_lookup(|iri| Ok(Term::Iri(iri.to_owned()).find(store).unwrap_or(InternalID::MAX))).unwrap()
+    }
+
+    fn from_lookup<F: FnMut(&str) -> Result<InternalID, String>>(mut id: F) -> Result<OwlVocabulary, String> {
+        Ok(OwlVocabulary {
+            rdf_first: id(vocab::RDF_FIRST)?,
+            rdf_rest: id(vocab::RDF_REST)?,
+            rdf_nil: id(vocab::RDF_NIL)?,
+            same_as: id(vocab::OWL_SAME_AS)?,
+            different_from: id(vocab::OWL_DIFFERENT_FROM)?,
+            inverse_of: id(vocab::OWL_INVERSE_OF)?,
+            transitive_property: id(vocab::OWL_TRANSITIVE_PROPERTY)?,
+            symmetric_property: id(vocab::OWL_SYMMETRIC_PROPERTY)?,
+            asymmetric_property: id(vocab::OWL_ASYMMETRIC_PROPERTY)?,
+            irreflexive_property: id(vocab::OWL_IRREFLEXIVE_PROPERTY)?,
+            functional_property: id(vocab::OWL_FUNCTIONAL_PROPERTY)?,
+            inverse_functional_property: id(vocab::OWL_INVERSE_FUNCTIONAL_PROPERTY)?,
+            equivalent_class: id(vocab::OWL_EQUIVALENT_CLASS)?,
+            equivalent_property: id(vocab::OWL_EQUIVALENT_PROPERTY)?,
+            disjoint_with: id(vocab::OWL_DISJOINT_WITH)?,
+            property_disjoint_with: id(vocab::OWL_PROPERTY_DISJOINT_WITH)?,
+            property_chain_axiom: id(vocab::OWL_PROPERTY_CHAIN_AXIOM)?,
+            thing: id(vocab::OWL_THING)?,
+            nothing: id(vocab::OWL_NOTHING)?,
+            has_value: id(vocab::OWL_HAS_VALUE)?,
+            on_property: id(vocab::OWL_ON_PROPERTY)?,
+            some_values_from: id(vocab::OWL_SOME_VALUES_FROM)?,
+            all_values_from: id(vocab::OWL_ALL_VALUES_FROM)?,
+            intersection_of: id(vocab::OWL_INTERSECTION_OF)?,
+            union_of: id(vocab::OWL_UNION_OF)?,
+        })
+    }
+}
+
+/// Reads the RDF collection starting at `head` in `graph`, or `None` if it is malformed or cyclic.
+pub fn read_list(store: &StorageEngine, graph: &GraphID, head: &InternalID, v: &OwlVocabulary) -> Option<Vec<InternalID>> {
+    let mut members = Vec::new();
+    let mut seen = BTreeSet::new();
+    let mut node = head.clone();
+    while node != v.rdf_nil {
+        if !seen.insert(node.clone()) {
+            return None;
+        }
+        let first = store.search_engine_entailed(Some(graph.clone()), Some(node.clone()), Some(v.rdf_first.clone()), None).next()?.3;
+        let rest = store.search_engine_entailed(Some(graph.clone()), Some(node.clone()), Some(v.rdf_rest.clone()), None).next()?.3;
+        members.push(first);
+        node = rest;
+    }
+    Some(members)
+}
+
+/* The axioms `c predicate (m1 ... mn)` whose list `delta` may have just completed: `delta` itself
+   when it is such an axiom, or those whose list holds the node an rdf:first or rdf:rest quad is
+   about. Other quads affect no axiom. */
+fn affected_axioms(store: &StorageEngine, v: &OwlVocabulary, delta: &InternalQuad, predicate: &InternalID) -> Vec<(InternalID, Vec<InternalID>, InternalQuad)> {
+    let &(ref s, ref p, ref o, ref g) = delta;
+    if p == predicate {
+        return read_list(store, g, o, v).map(|members| vec![(s.clone(), members, delta.clone())]).unwrap_or_default();
+    }
+    if *p != v.rdf_first && *p != v.rdf_rest {
+        return Vec::new();
+    }
+    let mut nodes = BTreeSet::new();
+    let mut stack = vec![s.clone()];
+    while let Some(node) = stack.pop() {
+        if nodes.insert(node.clone()) {
+            stack.extend(store.search_engine_entailed(Some(g.clone()), None, Some(v.rdf_rest.clone()), Some(node)).map(|q| q.1));
+        }
+    }
+    let mut axioms = Vec::new();
+    for node in nodes {
+        for (_, c, _, list) in store.search_engine_entailed(Some(g.clone()), None, Some(predicate.clone()), Some(node)) {
+            if let Some(members) = read_list(store, g, &list, v) {
+                axioms.push((c.clone(), members, (c, predicate.clone(), list, g.clone())));
+            }
+        }
+    }
+    axioms
+}
+
+/// prp-spo2: `p owl:propertyChainAxiom (p1 ... pn) . x0 p1 x1 ... xn-1 pn xn => x0 p xn`
+pub struct PropertyChainRule {
+    v: OwlVocabulary,
+}
+
+type ChainPaths = BTreeMap<InternalID, Vec<InternalQuad>>;
+
+impl PropertyChainRule {
+    /* Walks the chain from `start` through `links`, either forwards (subject to object) or backwards,
+       keeping the first path of quads that reaches each node, in chain order. */
+    fn walk(&self, store: &StorageEngine, g: &GraphID, start: &InternalID, links: &[InternalID], forwards: bool) -> ChainPaths {
+        let mut frontier = ChainPaths::new();
+        frontier.insert(start.clone(), Vec::new());
+        let ordered: Vec<&InternalID> = if forwards { links.iter().collect() } else { links.iter().rev().collect() };
+        for link in ordered {
+            let mut next = ChainPaths::new();
+            for (node, path) in frontier.iter() {
+                let (s, o) = if forwards { (Some(node.clone()), None) } else { (None, Some(node.clone())) };
+                for (_, qs, qp, qo) in store.search_engine_entailed(Some(g.clone()), s, Some(link.clone()), o) {
+                    let reached = if forwards { qo.clone() } else { qs.clone() };
+                    if next.contains_key(&reached) {
+                        continue;
+                    }
+                    let mut extended = path.clone();
+                    if forwards { extended.push((qs, qp, qo, g.clone())); } else { extended.insert(0, (qs, qp, qo, g.clone())); }
+                    next.insert(reached, extended);
+                }
+            }
+            frontier = next;
+            if frontier.is_empty() {
+                break;
+            }
+        }
+        frontier
+    }
+
+    fn evaluate_chain(&self, store: &StorageEngine, g: &GraphID, property: &InternalID, chain: &[InternalID], axiom: &InternalQuad, derived: &mut Vec<Derivation>) {
+        if chain.is_empty() {
+            return;
+        }
+        let starts: BTreeSet<InternalID> = store.search_engine_entailed(Some(g.clone()), None, Some(chain[0].clone()), None).map(|q| q.1).collect();
+        for start in starts {
+            for (end, path) in self.walk(store, g, &start, chain, true) {
+                let mut premises = vec![axiom.clone()];
+                premises.extend(path);
+                derived.push(Derivation::new((start.clone(), property.clone(), end, g.clone()), premises));
+            }
+        }
+    }
+
+    fn chains(&self, store: &StorageEngine, g: &GraphID) -> Vec<(InternalID, Vec<InternalID>, InternalQuad)> {
+        store.search_engine_entailed(Some(g.clone()), None, Some(self.v.property_chain_axiom.clone()), None)
+            .filter_map(|(g, p, a, list)| read_list(store, &g, &list, &self.v).map(|chain| (p.clone(), chain, (p, a, list, g))))
+            .collect()
+    }
+}
+
+impl InferenceRule for PropertyChainRule {
+    fn name(&self) -> &'static str { "prp-spo2" }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        for (property, chain, axiom) in affected_axioms(store, &self.v, delta, &self.v.property_chain_axiom) {
+            self.evaluate_chain(store, g, &property, &chain, &axiom, derived);
+        }
+        if *p == self.v.property_chain_axiom {
+            return;
+        }
+        for (property, chain, axiom) in self.chains(store, g) {
+            for (i, link) in chain.iter().enumerate() {
+                if link != p {
+                    continue;
+                }
+                let starts = self.walk(store, g, s, &chain[..i], false);
+                let ends = self.walk(store, g, o, &chain[i + 1..], true);
+                for (start, before) in starts.iter() {
+                    for (end, after) in ends.iter() {
+                        let mut premises = vec![axiom.clone()];
+                        premises.extend(before.iter().cloned());
+                        premises.push(delta.clone());
+                        premises.extend(after.iter().cloned());
+                        derived.push(Derivation::new((start.clone(), property.clone(), end.clone(), g.clone()), premises));
+                    }
+                }
+            }
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref x, ref p, ref z, ref g) = quad;
+        for (property, chain, axiom) in self.chains(store, g) {
+            if property != *p || chain.is_empty() {
+                continue;
+            }
+            if let Some(path) = self.walk(store, g, x, &chain, true).remove(z) {
+                let mut premises = vec![axiom];
+                premises.extend(path);
+                return Some(Derivation::new(quad.clone(), premises));
+            }
+        }
+        None
+    }
+}
+
+/// cls-int1, cls-int2 and cls-uni, which need the class lists of owl:intersectionOf and owl:unionOf.
+pub struct ClassListRule {
+    rdf_type: InternalID,
+    v: OwlVocabulary,
+}
+
+impl ClassListRule {
+    fn type_of(&self, store: &StorageEngine, g: &GraphID, x: &InternalID, class: &InternalID) -> bool {
+        store.search_engine_entailed(Some(g.clone()), Some(x.clone()), Some(self.rdf_type.clone()), Some(class.clone())).next().is_some()
+    }
+
+    fn instances(&self, store: &StorageEngine, g: &GraphID, class: &InternalID) -> Vec<InternalID> {
+        store.search_engine_entailed(Some(g.clone()), None, Some(self.rdf_type.clone()), Some(class.clone())).map(|q| q.1).collect()
+    }
+
+    fn typing(&self, g: &GraphID, x: &InternalID, class: &InternalID) -> InternalQuad {
+        (x.clone(), self.rdf_type.clone(), class.clone(), g.clone())
+    }
+
+    fn axioms(&self, store: &StorageEngine, g: &GraphID, predicate: &InternalID) -> Vec<(InternalID, Vec<InternalID>, InternalQuad)> {
+        store.search_engine_entailed(Some(g.clone()), None, Some(predicate.clone()), None)
+            .filter_map(|(g, c, p, list)| read_list(store, &g, &list, &self.v).map(|members| (c.clone(), members, (c, p, list, g))))
+            .collect()
+    }
+
+    /* Everything entailed for individual `x` now known to be of type `class`. */
+    fn classify(&self, store: &StorageEngine, g: &GraphID, x: &InternalID, class: &InternalID, derived: &mut Vec<Derivation>) {
+        for (c, members, axiom) in self.axioms(store, g, &self.v.intersection_of) {
+            if c == *class {
+                for m in members.iter() {
+                    derived.push(Derivation::new(self.typing(g, x, m), vec![axiom.clone(), self.typing(g, x, class)]));
+                }
+            } else if members.contains(class) && members.iter().all(|m| m == class || self.type_of(store, g, x, m)) {
+                let mut premises = vec![axiom];
+                premises.extend(members.iter().map(|m| self.typing(g, x, m)));
+                derived.push(Derivation::new(self.typing(g, x, &c), premises));
+            }
+        }
+        for (c, members, axiom) in self.axioms(store, g, &self.v.union_of) {
+            if members.contains(class) {
+                derived.push(Derivation::new(self.typing(g, x, &c), vec![axiom, self.typing(g, x, class)]));
+            }
+        }
+    }
+}
+
+impl InferenceRule for ClassListRule {
+    fn name(&self) -> &'static str { "cls-int-uni" }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        if *p == self.rdf_type {
+            self.classify(store, g, s, o, derived);
+            return;
+        }
+        /* A new or completed class list only affects individuals of the classes it names. */
+        for (c, members, axiom) in affected_axioms(store, &self.v, delta, &self.v.intersection_of) {
+            for x in self.instances(store, g, &c) {
+                for m in members.iter() {
+                    derived.push(Derivation::new(self.typing(g, &x, m), vec![axiom.clone(), self.typing(g, &x, &c)]));
+                }
+            }
+            for x in members.first().map(|m| self.instances(store, g, m)).unwrap_or_default() {
+                if members.iter().all(|m| self.type_of(store, g, &x, m)) {
+                    let mut premises = vec![axiom.clone()];
+                    premises.extend(members.iter().map(|m| self.typing(g, &x, m)));
+                    derived.push(Derivation::new(self.typing(g, &x, &c), premises));
+                }
+            }
+        }
+        for (c, members, axiom) in affected_axioms(store, &self.v, delta, &self.v.union_of) {
+            for m in members.iter() {
+                for x in self.instances(store, g, m) {
+                    derived.push(Derivation::new(self.typing(g, &x, &c), vec![axiom.clone(), self.typing(g, &x, m)]));
+                }
+            }
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref x, ref t, ref class, ref g) = quad;
+        if *t != self.rdf_type {
+            return None;
+        }
+        for (c, members, axiom) in self.axioms(store, g, &self.v.intersection_of) {
+            /* cls-int1 */
+            if c == *class && members.iter().all(|m| self.type_of(store, g, x, m)) {
+                let mut premises = vec![axiom.clone()];
+                premises.extend(members.iter().map(|m| self.typing(g, x, m)));
+                return Some(Derivation::new(quad.clone(), premises));
+            }
+            /* cls-int2 */
+            if members.contains(class) && self.type_of(store, g, x, &c) {
+                return Some(Derivation::new(quad.clone(), vec![axiom, self.typing(g, x, &c)]));
+            }
+        }
+        for (c, members, axiom) in self.axioms(store, g, &self.v.union_of) {
+            if c == *class {
+                if let Some(m) = members.iter().find(|m| self.type_of(store, g, x, m)) {
+                    return Some(Derivation::new(quad.clone(), vec![axiom, self.typing(g, x, m)]));
+                }
+            }
+        }
+        None
+    }
+}
+
+fn var(n: usize) -> RuleTerm {
+    RuleTerm::Var(n)
+}
+
+fn con(id: &InternalID) -> RuleTerm {
+    RuleTerm::Const(id.clone())
+}
+
+fn pat(s: RuleTerm, p: RuleTerm, o: RuleTerm) -> RulePattern {
+    RulePattern(s, p, o)
+}
+
+fn rule(name: &'static str, body: Vec<RulePattern>, head: Vec<RulePattern>) -> PatternRule {
+    PatternRule::new(name, body, RuleConclusion::Quads(head))
+}
+
+fn inconsistency(name: &'static str, body: Vec<RulePattern>) -> PatternRule {
+    PatternRule::new(name, body, RuleConclusion::Inconsistent)
+}
+
+/// The OWL 2 RL/RDF rules for equality, property axioms, class axioms and the RL class expressions
+/// (hasValue, someValuesFrom, allValuesFrom, intersectionOf, unionOf), plus the RDFS schema rules
+/// they build on. Rules whose conclusion is `false` report `Inconsistency`s instead of deriving quads.
+/// eq-ref and the datatype rules are left out, as they would annotate every term in the store.
+pub fn owl_rl_rules(r: &RdfsVocabulary, v: &OwlVocabulary) -> Vec<Box<InferenceRule>> {
+    let mut rules: Vec<Box<InferenceRule>> = rdfs_core_rules(r);
+    for pattern_rule in owl_rl_pattern_rules(r, v) {
+        rules.push(Box::new(pattern_rule));
+    }
+    rules.push(Box::new(PropertyChainRule { v: v.clone() }));
+    rules.push(Box::new(ClassListRule { rdf_type: r.rdf_type.clone(), v: v.clone() }));
+    rules
+}
+
+/// The OWL 2 RL rules of `owl_rl_rules` that are plain Horn rules over triple patterns: all but the
+/// RDFS schema rules, prp-spo2 and the class list rules.
+pub fn owl_rl_pattern_rules(r: &RdfsVocabulary, v: &OwlVocabulary) -> Vec<PatternRule> {
+    let t = con(&r.rdf_type);
+    let same_as = con(&v.same_as);
+    let (x, y, z, p, q, o) = (var(0), var(1), var(2), var(3), var(4), var(5));
+    vec![
+        /* equality */
+        rule("eq-sym", vec![pat(x.clone(), same_as.clone(), y.clone())], vec![pat(y.clone(), same_as.clone(), x.clone())]),
+        rule("eq-trans", vec![pat(x.clone(), same_as.clone(), y.clone()), pat(y.clone(), same_as.clone(), z.clone())],
+             vec![pat(x.clone(), same_as.clone(), z.clone())]).with_distinct(0, 2),
+        rule("eq-rep-s", vec![pat(x.clone(), same_as.clone(), y.clone()), pat(x.clone(), p.clone(), o.clone())],
+             vec![pat(y.clone(), p.clone(), o.clone())]),
+        rule("eq-rep-p", vec![pat(p.clone(), same_as.clone(), q.clone()), pat(x.clone(), p.clone(), o.clone())],
+             vec![pat(x.clone(), q.clone(), o.clone())]),
+        rule("eq-rep-o", vec![pat(o.clone(), same_as.clone(), y.clone()), pat(x.clone(), p.clone(), o.clone())],
+             vec![pat(x.clone(), p.clone(), y.clone())]),
+        inconsistency("eq-diff1", vec![pat(x.clone(), same_as.clone(), y.clone()), pat(x.clone(), con(&v.different_from), y.clone())]),
+        /* property axioms */
+        rule("prp-fp", vec![pat(p.clone(), t.clone(), con(&v.functional_property)), pat(x.clone(), p.clone(), y.clone()), pat(x.clone(), p.clone(), z.clone())],
+             vec![pat(y.clone(), same_as.clone(), z.clone())]).with_distinct(1, 2),
+        rule("prp-ifp", vec![pat(p.clone(), t.clone(), con(&v.inverse_functional_property)), pat(x.clone(), p.clone(), z.clone()), pat(y.clone(), p.clone(), z.clone())],
+             vec![pat(x.clone(), same_as.clone(), y.clone())]).with_distinct(0, 1),
+        inconsistency("prp-irp", vec![pat(p.clone(), t.clone(), con(&v.irreflexive_property)), pat(x.clone(), p.clone(), x.clone())]),
+        rule("prp-symp", vec![pat(p.clone(), t.clone(), con(&v.symmetric_property)), pat(x.clone(), p.clone(), y.clone())],
+             vec![pat(y.clone(), p.clone(), x.clone())]),
+        inconsistency("prp-asyp", vec![pat(p.clone(), t.clone(), con(&v.asymmetric_property)), pat(x.clone(), p.clone(), y.clone()), pat(y.clone(), p.clone(), x.clone())]),
+        rule("prp-trp", vec![pat(p.clone(), t.clone(), con(&v.transitive_property)), pat(x.clone(), p.clone(), y.clone()), pat(y.clone(), p.clone(), z.clone())],
+             vec![pat(x.clone(), p.clone(), z.clone())]),
+        rule("prp-eqp1", vec![pat(p.clone(), con(&v.equivalent_property), q.clone()), pat(x.clone(), p.clone(), y.clone())],
+             vec![pat(x.clone(), q.clone(), y.clone())]),
+        rule("prp-eqp2", vec![pat(p.clone(), con(&v.equivalent_property), q.clone()), pat(x.clone(), q.clone(), y.clone())],
+             vec![pat(x.clone(), p.clone(), y.clone())]),
+        inconsistency("prp-pdw", vec![pat(p.clone(), con(&v.property_disjoint_with), q.clone()), pat(x.clone(), p.clone(), y.clone()), pat(x.clone(), q.clone(), y.clone())]),
+        rule("prp-inv1", vec![pat(p.clone(), con(&v.inverse_of), q.clone()), pat(x.clone(), p.clone(), y.clone())],
+             vec![pat(y.clone(), q.clone(), x.clone())]),
+        rule("prp-inv2", vec![pat(p.clone(), con(&v.inverse_of), q.clone()), pat(x.clone(), q.clone(), y.clone())],
+             vec![pat(y.clone(), p.clone(), x.clone())]),
+        /* class expressions, with z as the restriction class */
+        inconsistency("cls-nothing2", vec![pat(x.clone(), t.clone(), con(&v.nothing))]),
+        rule("cls-hv1", vec![pat(z.clone(), con(&v.has_value), y.clone()), pat(z.clone(), con(&v.on_property), p.clone()), pat(x.clone(), t.clone(), z.clone())],
+             vec![pat(x.clone(), p.clone(), y.clone())]),
+        rule("cls-hv2", vec![pat(z.clone(), con(&v.has_value), y.clone()), pat(z.clone(), con(&v.on_property), p.clone()), pat(x.clone(), p.clone(), y.clone())],
+             vec![pat(x.clone(), t.clone(), z.clone())]),
+        rule("cls-svf1", vec![pat(z.clone(), con(&v.some_values_from), q.clone()), pat(z.clone(), con(&v.on_property), p.clone()), pat(x.clone(), p.clone(), y.clone()), pat(y.clone(), t.clone(), q.clone())],
+             vec![pat(x.clone(), t.clone(), z.clone())]),
+        rule("cls-svf2", vec![pat(z.clone(), con(&v.some_values_from), con(&v.thing)), pat(z.clone(), con(&v.on_property), p.clone()), pat(x.clone(), p.clone(), y.clone())],
+             vec![pat(x.clone(), t.clone(), z.clone())]),
+        rule("cls-avf", vec![pat(z.clone(), con(&v.all_values_from), q.clone()), pat(z.clone(), con(&v.on_property), p.clone()), pat(x.clone(), t.clone(), z.clone()), pat(x.clone(), p.clone(), y.clone())],
+             vec![pat(y.clone(), t.clone(), q.clone())]),
+        /* class axioms, with p and q as the two classes */
+        rule("cax-eqc1", vec![pat(p.clone(), con(&v.equivalent_class), q.clone()), pat(x.clone(), t.clone(), p.clone())],
+             vec![pat(x.clone(), t.clone(), q.clone())]),
+        rule("cax-eqc2", vec![pat(p.clone(), con(&v.equivalent_class), q.clone()), pat(x.clone(), t.clone(), q.clone())],
+             vec![pat(x.clone(), t.clone(), p.clone())]),
+        inconsistency("cax-dw", vec![pat(p.clone(), con(&v.disjoint_with), q.clone()), pat(x.clone(), t.clone(), p.clone()), pat(x.clone(), t.clone(), q.clone())]),
+        /* schema */
+        rule("scm-eqc1", vec![pat(p.clone(), con(&v.equivalent_class), q.clone())],
+             vec![pat(p.clone(), con(&r.sub_class_of), q.clone()), pat(q.clone(), con(&r.sub_class_of), p.clone())]),
+        rule("scm-eqp1", vec![pat(p.clone(), con(&v.equivalent_property), q.clone())],
+             vec![pat(p.clone(), con(&r.sub_property_of), q.clone()), pat(q.clone(), con(&r.sub_property_of), p.clone())]),
+    ]
+}
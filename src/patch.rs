@@ -0,0 +1,335 @@
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use iri::IriRef;
+use lexer::{Token, TokenStream};
+use limits::{self, Guard, Limits, OperationError};
+use nquads::{Term, read_quad};
+use store::{StorageEngine, GraphID};
+
+/// One quad added or deleted by a patch. `graph` is `None` for the default graph.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PatchQuad {
+    pub subject: Term,
+    pub predicate: Term,
+    pub object: Term,
+    pub graph: Option<Term>,
+}
+
+impl PatchQuad {
+    fn to_row(&self, code: &str) -> String {
+        let mut row = format!("{} {} {} {}", code, self.subject.to_ntriples(), self.predicate.to_ntriples(), self.object.to_ntriples());
+        if let Some(ref g) = self.graph {
+            row.push(' ');
+            row.push_str(&g.to_ntriples());
+        }
+        row.push_str(" .\n");
+        row
+    }
+
+    /* The quad's IDs in `store`, as (graph, subject, predicate, object), if all its terms exist there. */
+    fn find(&self, store: &StorageEngine) -> Option<(GraphID, InternalID, InternalID, InternalID)> {
+        let graph = match self.graph {
+            Some(ref g) => g.find(store)?,
+            None => InternalID(0.into()),
+        };
+        Some((graph, self.subject.find(store)?, self.predicate.find(store)?, self.object.find(store)?))
+    }
+
+    /* The estimated bytes the quad takes while staged, for memory limits. */
+    fn size(&self) -> usize {
+        let term_bytes = |term: &Term| match *term {
+            Term::Iri(ref text) | Term::Blank(ref text) => limits::string_bytes(text),
+            Term::Literal(ref lexical, ref datatype, ref lang) => limits::string_bytes(lexical)
+                + datatype.as_ref().map(|d| limits::string_bytes(d)).unwrap_or(0) + lang.as_ref().map(|l| limits::string_bytes(l)).unwrap_or(0),
+        };
+        term_bytes(&self.subject) + term_bytes(&self.predicate) + term_bytes(&self.object) + self.graph.as_ref().map(term_bytes).unwrap_or(0)
+    }
+
+    /* Checks that the quad can be added: IRIs and datatypes are absolute, the subject and graph are
+       not literals and the predicate is an IRI. Run before anything is applied, so a bad row
+       cannot leave a patch half done. */
+    fn validate(&self) -> Result<(), String> {
+        for term in [&self.subject, &self.predicate, &self.object].iter().cloned().chain(self.graph.as_ref()) {
+            match *term {
+                Term::Iri(ref iri) => { IriRef::parse_absolute(iri)?; }
+                Term::Literal(_, Some(ref datatype), ref lang) => {
+                    if lang.is_some() {
+                        return Err("A literal cannot have both a language tag and a datatype.".to_owned());
+                    }
+                    IriRef::parse_absolute(datatype)?;
+                }
+                _ => {}
+            }
+        }
+        if let Term::Literal(..) = self.subject {
+            return Err("A literal cannot be a subject.".to_owned());
+        }
+        if let Some(Term::Literal(..)) = self.graph {
+            return Err("A literal cannot name a graph.".to_owned());
+        }
+        match self.predicate {
+            Term::Iri(_) => Ok(()),
+            _ => Err("A predicate must be an IRI.".to_owned()),
+        }
+    }
+
+    fn exists_in(&self, store: &StorageEngine) -> bool {
+        self.find(store).map(|(g, s, p, o)| store.search_engine_internal(Some(g), Some(s), Some(p), Some(o)).next().is_some()).unwrap_or(false)
+    }
+}
+
+/// A row of an RDF Patch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PatchRow {
+    Header(String, Term),
+    Begin,
+    Commit,
+    Abort,
+    AddPrefix(String, String),
+    DeletePrefix(String),
+    Add(PatchQuad),
+    Delete(PatchQuad),
+}
+
+/// A changeset in the RDF Patch format: `A`/`D` rows add and delete quads, `PA`/`PD` rows bind and
+/// unbind prefixes, and `TX`/`TC`/`TA` begin, commit and abort transactions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patch {
+    rows: Vec<PatchRow>,
+}
+
+fn read_prefix(tokens: &mut TokenStream) -> Result<String, String> {
+    match tokens.next() {
+        Some(Token::PrefixedName(ref prefix, ref local)) if local.is_empty() => Ok(prefix.clone()),
+        Some(Token::Str(prefix)) => Ok(prefix),
+        _ => tokens.error("Expected a prefix"),
+    }
+}
+
+impl Patch {
+    pub fn new() -> Patch {
+        Patch::default()
+    }
+
+    pub fn push(&mut self, row: PatchRow) {
+        self.rows.push(row);
+    }
+
+    pub fn borrow_rows<'a>(&'a self) -> &'a [PatchRow] {
+        &self.rows
+    }
+
+    pub fn added(&self) -> Vec<&PatchQuad> {
+        self.rows.iter().filter_map(|r| if let &PatchRow::Add(ref q) = r { Some(q) } else { None }).collect()
+    }
+
+    pub fn removed(&self) -> Vec<&PatchQuad> {
+        self.rows.iter().filter_map(|r| if let &PatchRow::Delete(ref q) = r { Some(q) } else { None }).collect()
+    }
+
+    pub fn parse(text: &str) -> Result<Patch, String> {
+        let mut tokens = TokenStream::new(text)?;
+        let mut patch = Patch::new();
+        while !tokens.is_at_end() {
+            let code = match tokens.next() {
+                Some(Token::Word(code)) => code,
+                _ => return tokens.error("Expected a patch row code"),
+            };
+            let row = match code.as_str() {
+                "H" => {
+                    let key = match tokens.next() {
+                        Some(Token::Word(key)) => key,
+                        _ => return tokens.error("Expected a header name"),
+                    };
+                    PatchRow::Header(key, Term::read(&mut tokens)?)
+                }
+                "TX" => PatchRow::Begin,
+                "TC" => PatchRow::Commit,
+                "TA" => PatchRow::Abort,
+                "PA" => {
+                    let prefix = read_prefix(&mut tokens)?;
+                    let namespace = match tokens.next() {
+                        Some(Token::IriRef(ns)) | Some(Token::Str(ns)) => ns,
+                        _ => return tokens.error("Expected a namespace IRI"),
+                    };
+                    PatchRow::AddPrefix(prefix, namespace)
+                }
+                "PD" => {
+                    let prefix = read_prefix(&mut tokens)?;
+                    if !tokens.is_punct(".") {
+                        tokens.next();
+                    }
+                    PatchRow::DeletePrefix(prefix)
+                }
+                "A" | "D" => {
+                    let (subject, predicate, object, graph) = read_quad(&mut tokens)?;
+                    let quad = PatchQuad { subject, predicate, object, graph };
+                    patch.push(if code == "A" { PatchRow::Add(quad) } else { PatchRow::Delete(quad) });
+                    continue;
+                }
+                other => return Err(format!("Unknown patch row code {:?} at offset {}.", other, tokens.offset())),
+            };
+            tokens.expect_punct(".")?;
+            patch.push(row);
+        }
+        Ok(patch)
+    }
+
+    pub fn serialize(&self) -> String {
+        self.rows.iter().map(|row| match row {
+            &PatchRow::Header(ref key, ref value) => format!("H {} {} .\n", key, value.to_ntriples()),
+            &PatchRow::Begin => "TX .\n".to_owned(),
+            &PatchRow::Commit => "TC .\n".to_owned(),
+            &PatchRow::Abort => "TA .\n".to_owned(),
+            &PatchRow::AddPrefix(ref prefix, ref namespace) => format!("PA {}: <{}> .\n", prefix, namespace),
+            &PatchRow::DeletePrefix(ref prefix) => format!("PD {}: .\n", prefix),
+            &PatchRow::Add(ref quad) => quad.to_row("A"),
+            &PatchRow::Delete(ref quad) => quad.to_row("D"),
+        }).collect()
+    }
+
+    /// Applies the patch to `store` as a whole: every row is checked before anything changes, and
+    /// rows of aborted transactions are skipped. In strict mode deleting a quad or prefix that is
+    /// not there is an error; otherwise such rows are ignored. Returns the number of quads added
+    /// and deleted.
+    pub fn apply(&self, store: &mut StorageEngine, strict: bool) -> Result<usize, String> {
+        self.apply_guarded(store, strict, &Guard::unlimited())
+    }
+
+    /// Applies the patch like `apply`, but stops with `OperationError::Limit` if staging its rows
+    /// exceeds `limits`. Limits are checked before anything changes, so the store is then untouched.
+    pub fn apply_with_limits(&self, store: &mut StorageEngine, strict: bool, limits: &Limits) -> Result<usize, OperationError> {
+        let guard = Guard::new(limits.clone());
+        self.apply_guarded(store, strict, &guard).map_err(|e| guard.error(e))
+    }
+
+    /// Applies the patch like `apply`, within the limits of `guard`. The quad rows staged count
+    /// towards its row and memory limits.
+    pub fn apply_guarded(&self, store: &mut StorageEngine, strict: bool, guard: &Guard) -> Result<usize, String> {
+        let mut namespaces = store.borrow_namespace_manager().clone();
+        let mut overlay: BTreeMap<&PatchQuad, bool> = BTreeMap::new();
+        let mut accepted: Vec<&PatchRow> = Vec::new();
+        let mut transaction = None;
+        let mut staged_bytes = 0;
+        for (i, row) in self.rows.iter().enumerate() {
+            guard.work()?;
+            if let &PatchRow::Add(ref quad) | &PatchRow::Delete(ref quad) = row {
+                staged_bytes += quad.size();
+                guard.row_count(accepted.len() + 1)?;
+                guard.bytes(staged_bytes)?;
+            }
+            match row {
+                &PatchRow::Header(..) => {}
+                &PatchRow::Begin => {
+                    if transaction.is_some() {
+                        return Err(format!("Row {}: transactions cannot be nested.", i + 1));
+                    }
+                    transaction = Some((overlay.clone(), namespaces.clone(), accepted.len()));
+                }
+                &PatchRow::Commit => {
+                    if transaction.take().is_none() {
+                        return Err(format!("Row {}: commit outside a transaction.", i + 1));
+                    }
+                }
+                &PatchRow::Abort => {
+                    let (saved_overlay, saved_namespaces, saved_len) = transaction.take()
+                        .ok_or_else(|| format!("Row {}: abort outside a transaction.", i + 1))?;
+                    overlay = saved_overlay;
+                    namespaces = saved_namespaces;
+                    accepted.truncate(saved_len);
+                }
+                &PatchRow::AddPrefix(ref prefix, ref namespace) => {
+                    namespaces.bind(prefix, namespace, true).map_err(|e| format!("Row {}: {}", i + 1, e))?;
+                    accepted.push(row);
+                }
+                &PatchRow::DeletePrefix(ref prefix) => {
+                    if namespaces.unbind(prefix).is_ok() {
+                        accepted.push(row);
+                    } else if strict {
+                        return Err(format!("Row {}: prefix {:?} is not bound.", i + 1, prefix));
+                    }
+                }
+                &PatchRow::Add(ref quad) => {
+                    quad.validate().map_err(|e| format!("Row {}: {}", i + 1, e))?;
+                    overlay.insert(quad, true);
+                    accepted.push(row);
+                }
+                &PatchRow::Delete(ref quad) => {
+                    let present = overlay.get(quad).cloned().unwrap_or_else(|| quad.exists_in(store));
+                    if present {
+                        overlay.insert(quad, false);
+                        accepted.push(row);
+                    } else if strict {
+                        return Err(format!("Row {}: cannot delete absent quad {}", i + 1, quad.to_row("D").trim_end()));
+                    }
+                }
+            }
+        }
+        if transaction.is_some() {
+            return Err("The patch ends inside a transaction.".to_owned());
+        }
+        guard.check()?;
+        let mut changes = 0;
+        for row in accepted {
+            match row {
+                &PatchRow::AddPrefix(ref prefix, ref namespace) => store.borrow_namespace_manager_mut().bind(prefix, namespace, true)?,
+                &PatchRow::DeletePrefix(ref prefix) => { store.borrow_namespace_manager_mut().unbind(prefix)?; }
+                &PatchRow::Add(ref quad) => {
+                    let graph = match quad.graph {
+                        Some(ref g) => g.intern(store)?,
+                        None => InternalID(0.into()),
+                    };
+                    let (s, p, o) = (quad.subject.intern(store)?, quad.predicate.intern(store)?, quad.object.intern(store)?);
+                    store.add_internal_quad(graph, s, p, o);
+                    changes += 1;
+                }
+                &PatchRow::Delete(ref quad) => {
+                    if let Some((g, s, p, o)) = quad.find(store) {
+                        store.remove_internal_quad(g, s, p, o);
+                        changes += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(changes)
+    }
+}
+
+fn collect_quads(store: &StorageEngine, graph: Option<&GraphID>) -> Result<BTreeSet<PatchQuad>, String> {
+    let default_graph = InternalID(0.into());
+    let mut quads = BTreeSet::new();
+    for (g, s, p, o) in store.search_engine_internal(graph.cloned(), None, None, None) {
+        /* A single graph is compared without its name, so graphs with different names can be diffed. */
+        let graph_term = if graph.is_some() || g == default_graph { None } else { Some(Term::from_node(store, &g)?) };
+        quads.insert(PatchQuad { subject: Term::from_node(store, &s)?, predicate: Term::from_node(store, &p)?, object: Term::from_node(store, &o)?, graph: graph_term });
+    }
+    Ok(quads)
+}
+
+/// The changes that turn `old_graph` of `old` into `new_graph` of `new`, or one whole dataset into
+/// the other, as a patch with one transaction. Terms are compared by value and blank nodes by label,
+/// so the stores may differ. When graphs are compared, the rows name the old graph so the patch
+/// applies to `old`.
+pub fn diff(old: &StorageEngine, old_graph: Option<&GraphID>, new: &StorageEngine, new_graph: Option<&GraphID>) -> Result<Patch, String> {
+    if old_graph.is_some() != new_graph.is_some() {
+        return Err("Diff a graph against a graph, or a dataset against a dataset.".to_owned());
+    }
+    let target = match old_graph {
+        Some(g) if *g != InternalID(0.into()) => Some(Term::from_node(old, g)?),
+        _ => None,
+    };
+    let before = collect_quads(old, old_graph)?;
+    let after = collect_quads(new, new_graph)?;
+    let with_target = |q: &PatchQuad| if old_graph.is_some() { PatchQuad { graph: target.clone(), ..q.clone() } } else { q.clone() };
+    let mut patch = Patch::new();
+    patch.push(PatchRow::Begin);
+    for quad in before.difference(&after) {
+        patch.push(PatchRow::Delete(with_target(quad)));
+    }
+    for quad in after.difference(&before) {
+        patch.push(PatchRow::Add(with_target(quad)));
+    }
+    patch.push(PatchRow::Commit);
+    Ok(patch)
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeSet;
+use identifiers::InternalID;
+use indexed_quad_set::IndexKind;
+use limits::Guard;
+use nquads::Term;
+use store::{StorageEngine, GraphID};
+use sparql::algebra::PathExpression;
+use sparql::parser;
+
+/// A property path expression over internal IDs, as used by SHACL `sh:path` and SPARQL property
+/// paths. Paths are followed breadth first over the SPOG, POSG and OSPG indexes, and repetition
+/// visits each node once, so cycles terminate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyPath {
+    Predicate(InternalID),
+    Inverse(Box<PropertyPath>),
+    Sequence(Vec<PropertyPath>),
+    Alternative(Vec<PropertyPath>),
+    ZeroOrMore(Box<PropertyPath>),
+    OneOrMore(Box<PropertyPath>),
+    ZeroOrOne(Box<PropertyPath>),
+    /* Any predicate but these. */
+    NegatedSet(Vec<InternalID>),
+}
+
+impl PropertyPath {
+    /// Parses a path in SPARQL syntax, such as `skos:broader*` or `^rdfs:subClassOf+`, with
+    /// prefixes from the store's namespace manager.
+    pub fn parse(text: &str, store: &StorageEngine) -> Result<PropertyPath, String> {
+        Ok(PropertyPath::from_expression(&parser::parse_path(text, store.borrow_namespace_manager())?, store))
+    }
+
+    /// The path over the store's IDs. IRIs the store does not hold match no quads.
+    pub fn from_expression(expression: &PathExpression, store: &StorageEngine) -> PropertyPath {
+        let find = |iri: &String| Term::Iri(iri.clone()).find(store);
+        let each = |paths: &[PathExpression]| paths.iter().map(|p| PropertyPath::from_expression(p, store)).collect();
+        let boxed = |path: &PathExpression| Box::new(PropertyPath::from_expression(path, store));
+        match *expression {
+            PathExpression::Iri(ref iri) => match find(iri) {
+                Some(id) => PropertyPath::Predicate(id),
+                None => PropertyPath::Alternative(Vec::new()),
+            },
+            PathExpression::Inverse(ref inner) => PropertyPath::Inverse(boxed(inner)),
+            PathExpression::Sequence(ref steps) => PropertyPath::Sequence(each(steps)),
+            PathExpression::Alternative(ref options) => PropertyPath::Alternative(each(options)),
+            PathExpression::ZeroOrMore(ref inner) => PropertyPath::ZeroOrMore(boxed(inner)),
+            PathExpression::OneOrMore(ref inner) => PropertyPath::OneOrMore(boxed(inner)),
+            PathExpression::ZeroOrOne(ref inner) => PropertyPath::ZeroOrOne(boxed(inner)),
+            /* `!(a|^b)` is any predicate but `a` forwards, or any but `b` backwards. */
+            PathExpression::NegatedSet(ref forward, ref inverse) => {
+                let forward_set = PropertyPath::NegatedSet(forward.iter().filter_map(&find).collect());
+                let inverse_set = PropertyPath::Inverse(Box::new(PropertyPath::NegatedSet(inverse.iter().filter_map(find).collect())));
+                match (forward.is_empty(), inverse.is_empty()) {
+                    (false, false) => PropertyPath::Alternative(vec![forward_set, inverse_set]),
+                    (true, false) => inverse_set,
+                    _ => forward_set,
+                }
+            }
+        }
+    }
+
+    /// The single predicate this path is, if it is a plain predicate path.
+    pub fn as_predicate(&self) -> Option<&InternalID> {
+        if let &PropertyPath::Predicate(ref p) = self { Some(p) } else { None }
+    }
+
+    /// Whether the path matches zero-length paths, connecting every node to itself.
+    pub fn matches_empty(&self) -> bool {
+        match self {
+            &PropertyPath::Predicate(_) | &PropertyPath::NegatedSet(_) => false,
+            &PropertyPath::Inverse(ref inner) | &PropertyPath::OneOrMore(ref inner) => inner.matches_empty(),
+            &PropertyPath::Sequence(ref steps) => steps.iter().all(|p| p.matches_empty()),
+            &PropertyPath::Alternative(ref options) => options.iter().any(|p| p.matches_empty()),
+            &PropertyPath::ZeroOrMore(_) | &PropertyPath::ZeroOrOne(_) => true,
+        }
+    }
+
+    /// Every node reachable from `start` along the path, matching asserted and inferred quads
+    /// in `graph`, or in any graph.
+    pub fn evaluate(&self, store: &StorageEngine, graph: Option<&GraphID>, start: &InternalID) -> BTreeSet<InternalID> {
+        self.evaluate_guarded(store, graph, start, &Guard::unlimited()).unwrap_or_default()
+    }
+
+    /// Like `evaluate`, counting each node visited as work against `guard`.
+    pub fn evaluate_guarded(&self, store: &StorageEngine, graph: Option<&GraphID>, start: &InternalID, guard: &Guard) -> Result<BTreeSet<InternalID>, String> {
+        let mut starts = BTreeSet::new();
+        starts.insert(start.clone());
+        self.step(store, graph, &starts, false, guard)
+    }
+
+    /// Every node from which `end` is reachable along the path.
+    pub fn evaluate_inverse(&self, store: &StorageEngine, graph: Option<&GraphID>, end: &InternalID) -> BTreeSet<InternalID> {
+        self.evaluate_inverse_guarded(store, graph, end, &Guard::unlimited()).unwrap_or_default()
+    }
+
+    /// Like `evaluate_inverse`, counting each node visited as work against `guard`.
+    pub fn evaluate_inverse_guarded(&self, store: &StorageEngine, graph: Option<&GraphID>, end: &InternalID, guard: &Guard) -> Result<BTreeSet<InternalID>, String> {
+        let mut ends = BTreeSet::new();
+        ends.insert(end.clone());
+        self.step(store, graph, &ends, true, guard)
+    }
+
+    /// Every pair of nodes the path connects, as (start, end). Zero-length paths connect each
+    /// subject and object of `graph` to itself. The pairs are found from whichever end of the
+    /// path has fewer candidate nodes, and count towards the row and memory limits of `guard`.
+    pub fn pairs(&self, store: &StorageEngine, graph: Option<&GraphID>, guard: &Guard) -> Result<BTreeSet<(InternalID, InternalID)>, String> {
+        let starts = self.start_nodes(store, graph, false);
+        let ends = self.start_nodes(store, graph, true);
+        let backwards = ends.len() < starts.len();
+        let mut pairs = BTreeSet::new();
+        for node in if backwards { ends } else { starts } {
+            guard.work()?;
+            let mut from = BTreeSet::new();
+            from.insert(node.clone());
+            for other in self.step(store, graph, &from, backwards, guard)? {
+                pairs.insert(if backwards { (other, node.clone()) } else { (node.clone(), other) });
+            }
+            guard.rows(pairs.len(), 2)?;
+        }
+        Ok(pairs)
+    }
+
+    /* The nodes a match can start from, or end at when `backwards`; a superset found from the first step. */
+    fn start_nodes(&self, store: &StorageEngine, graph: Option<&GraphID>, backwards: bool) -> BTreeSet<InternalID> {
+        match self {
+            &PropertyPath::Predicate(ref p) => store.search_engine_entailed_by_index(IndexKind::POSG, graph.cloned(), None, Some(p.clone()), None)
+                .map(|(_, s, _, o)| if backwards { o } else { s }).collect(),
+            &PropertyPath::NegatedSet(_) => store.search_engine_entailed_by_index(IndexKind::GSPO, graph.cloned(), None, None, None)
+                .map(|(_, s, _, o)| if backwards { o } else { s }).collect(),
+            &PropertyPath::Inverse(ref inner) => inner.start_nodes(store, graph, !backwards),
+            &PropertyPath::Sequence(ref steps) => match if backwards { steps.last() } else { steps.first() } {
+                Some(first) => first.start_nodes(store, graph, backwards),
+                None => graph_nodes(store, graph),
+            },
+            &PropertyPath::Alternative(ref options) => {
+                let mut nodes = BTreeSet::new();
+                for path in options.iter() {
+                    nodes.extend(path.start_nodes(store, graph, backwards));
+                }
+                nodes
+            }
+            &PropertyPath::OneOrMore(ref inner) => inner.start_nodes(store, graph, backwards),
+            &PropertyPath::ZeroOrMore(_) | &PropertyPath::ZeroOrOne(_) => graph_nodes(store, graph),
+        }
+    }
+
+    /* Follows the path from every node in `from`, or against it when `backwards`. */
+    fn step(&self, store: &StorageEngine, graph: Option<&GraphID>, from: &BTreeSet<InternalID>, backwards: bool, guard: &Guard)
+            -> Result<BTreeSet<InternalID>, String> {
+        match self {
+            &PropertyPath::Predicate(ref p) => {
+                let mut reached = BTreeSet::new();
+                for node in from.iter() {
+                    guard.work()?;
+                    reached.extend(edges(store, graph, node, Some(p), backwards).map(|(_, n)| n));
+                }
+                Ok(reached)
+            }
+            &PropertyPath::NegatedSet(ref excluded) => {
+                let mut reached = BTreeSet::new();
+                for node in from.iter() {
+                    guard.work()?;
+                    reached.extend(edges(store, graph, node, None, backwards).filter(|&(ref p, _)| !excluded.contains(p)).map(|(_, n)| n));
+                }
+                Ok(reached)
+            }
+            &PropertyPath::Inverse(ref inner) => inner.step(store, graph, from, !backwards, guard),
+            &PropertyPath::Sequence(ref steps) => {
+                let ordered: Vec<&PropertyPath> = if backwards { steps.iter().rev().collect() } else { steps.iter().collect() };
+                let mut nodes = from.clone();
+                for path in ordered {
+                    nodes = path.step(store, graph, &nodes, backwards, guard)?;
+                }
+                Ok(nodes)
+            }
+            &PropertyPath::Alternative(ref options) => {
+                let mut reached = BTreeSet::new();
+                for path in options.iter() {
+                    reached.extend(path.step(store, graph, from, backwards, guard)?);
+                }
+                Ok(reached)
+            }
+            &PropertyPath::ZeroOrOne(ref inner) => {
+                let mut reached = from.clone();
+                reached.extend(inner.step(store, graph, from, backwards, guard)?);
+                Ok(reached)
+            }
+            &PropertyPath::ZeroOrMore(ref inner) => inner.closure(store, graph, from.clone(), backwards, guard),
+            &PropertyPath::OneOrMore(ref inner) => {
+                let first = inner.step(store, graph, from, backwards, guard)?;
+                inner.closure(store, graph, first, backwards, guard)
+            }
+        }
+    }
+
+    /* `seen` together with everything reachable from it by repeating this path. Each node
+       reached counts as work, and the nodes seen count towards the row and memory limits. */
+    fn closure(&self, store: &StorageEngine, graph: Option<&GraphID>, seen: BTreeSet<InternalID>, backwards: bool, guard: &Guard)
+               -> Result<BTreeSet<InternalID>, String> {
+        let mut seen = seen;
+        let mut frontier = seen.clone();
+        while !frontier.is_empty() {
+            let mut next = BTreeSet::new();
+            for node in self.step(store, graph, &frontier, backwards, guard)? {
+                guard.work()?;
+                if !seen.contains(&node) {
+                    next.insert(node);
+                }
+            }
+            seen.extend(next.iter().cloned());
+            guard.rows(seen.len(), 1)?;
+            frontier = next;
+        }
+        Ok(seen)
+    }
+}
+
+/* The predicates and nodes one quad away from `node`: its objects through the SPOG index, or when
+   `backwards` its subjects through the POSG index, or OSPG for any predicate. */
+fn edges<'a>(store: &'a StorageEngine, graph: Option<&GraphID>, node: &InternalID, predicate: Option<&InternalID>, backwards: bool)
+             -> Box<Iterator<Item=(InternalID, InternalID)> + 'a> {
+    let (graph, node, predicate) = (graph.cloned(), Some(node.clone()), predicate.cloned());
+    if backwards {
+        let index = if predicate.is_some() { IndexKind::POSG } else { IndexKind::OSPG };
+        Box::new(store.search_engine_entailed_by_index(index, graph, None, predicate, node).map(|(_, s, p, _)| (p, s)))
+    } else {
+        Box::new(store.search_engine_entailed_by_index(IndexKind::SPOG, graph, node, predicate, None).map(|(_, _, p, o)| (p, o)))
+    }
+}
+
+/* Every subject and object in `graph`, or in any graph. */
+fn graph_nodes(store: &StorageEngine, graph: Option<&GraphID>) -> BTreeSet<InternalID> {
+    let mut nodes = BTreeSet::new();
+    for (_, s, _, o) in store.search_engine_entailed_by_index(IndexKind::GSPO, graph.cloned(), None, None, None) {
+        nodes.insert(s);
+        nodes.insert(o);
+    }
+    nodes
+}
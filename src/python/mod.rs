@@ -5,9 +5,13 @@ use pyo3::Python;
 use pyo3::ObjectProtocol;
 use pyo3::prelude::*;
 use pyo3::PyObject;
+use pyo3::exc;
 //use std::convert::TryFrom;
 use store::{StorageEngine, StoreNode};
 use identifiers::InternalID;
+use inference::{Proof, ProofStep};
+use shex::ShapeSelector;
+use patch::Patch;
 use uri::RDFUri;
 use literal::Literal;
 use blank::BlankNode;
@@ -58,7 +62,7 @@ impl PyQStoreNode {
             }
             &PyQStoreNodeType::Blank(ref obj) => {
                 let s: &str = obj.extract(py).unwrap();
-                StoreNode::Blank(BlankNode::new(store, Some(s)))
+                StoreNode::Blank(BlankNode::new(Some(s)))
             }
         }
     }
@@ -112,7 +116,7 @@ impl PyQStoreNode {
                 PyQStoreNode { inner: PyQStoreNodeType::Literal(lit_string.into(), maybe_datatype, maybe_lang) }
             },
             &StoreNode::Blank(ref bl) => {
-                let bl_string: Py<PyString> = PyString::new(py,bl.lookup_identifier(store));
+                let bl_string: Py<PyString> = PyString::new(py,bl.borrow_label());
                 PyQStoreNode { inner: PyQStoreNodeType::Blank(bl_string.into()) }
             }
         }
@@ -351,6 +355,8 @@ impl PyIterProtocol for PyQStoreIterableResult {
 struct PyQStore {
     _engine: StorageEngine,
     default_graph_combined: bool,
+    include_inferred: bool,
+    expand_aliases: bool,
     debug: bool,
     token: PyToken,
 }
@@ -377,7 +383,7 @@ impl PyQStore {
             if let Ok(g_n) = g.to_native_store_node_if_exist(py, &self._engine) { Some(g_n) }
                 else { return Ok(PyQStoreIterableResult::py_node_empty_iter(py)); }
         } else { None };
-        let result = self._engine.search_nodes(g_native_node, s_native_node, p_native_node, o_native_node);
+        let result = self._engine.search_nodes_with_options(g_native_node, s_native_node, p_native_node, o_native_node, self.include_inferred, self.expand_aliases);
         if let Ok(res) = result {
             return Ok(PyQStoreIterableResult::create_with_iter(py, Box::new(res.map(|r|{
                 let (g_n, s_n, p_n, o_n) = r;
@@ -392,6 +398,23 @@ impl PyQStore {
         }
 
     }
+
+    fn proof_to_py(&self, py: Python, proof: &Proof) -> PyObject {
+        let (ref s, ref p, ref o, ref g) = proof.quad;
+        let node = |id: &InternalID| {
+            let native = self._engine.lookup_node_by_iid(id).unwrap();
+            PyQStoreNode::create_from_native_store_node_ref(native, py, &self._engine).into_object(py)
+        };
+        let triple_tup = PyTuple::new(py, &vec![node(s), node(p), node(o)]);
+        let quad_tup = PyTuple::new(py, &vec![triple_tup.into_object(py), node(g)]);
+        let (step, premises): (&str, Vec<PyObject>) = match proof.step {
+            ProofStep::Asserted => ("asserted", Vec::new()),
+            ProofStep::Circular => ("circular", Vec::new()),
+            ProofStep::Inferred { rule, ref premises } => (rule, premises.iter().map(|p| self.proof_to_py(py, p)).collect()),
+        };
+        let premises_tup = PyTuple::new(py, &premises);
+        PyTuple::new(py, &vec![quad_tup.into_object(py), PyString::new(py, step).into_object(py), premises_tup.into_object(py)]).into_object(py)
+    }
 }
 
 #[py::methods]
@@ -404,6 +427,8 @@ impl PyQStore {
             PyQStore {
                 _engine: StorageEngine::default(),
                 default_graph_combined: is_default_graph_combined,
+                include_inferred: false,
+                expand_aliases: false,
                 debug: is_debug,
                 token: token
             }
@@ -441,6 +466,201 @@ impl PyQStore {
         self._triples(py, triple, context)
     }
 
+    pub fn enable_rdfs_reasoning(&mut self) -> PyResult<()> {
+        self._engine.enable_rdfs_reasoning()
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn enable_owl_rl_reasoning(&mut self) -> PyResult<()> {
+        self._engine.enable_owl_rl_reasoning()
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn check_consistency(&self) -> PyResult<()> {
+        match self._engine.check_consistency() {
+            Ok(()) => Ok(()),
+            Err(found) => {
+                let rules: Vec<&str> = found.iter().map(|i| i.rule).collect();
+                Err(PyErr::new::<exc::ValueError, String>(format!("inconsistent store, violated: {}", rules.join(", "))))
+            }
+        }
+    }
+
+    pub fn disable_reasoning(&mut self) -> PyResult<()> {
+        self._engine.disable_reasoning();
+        Ok(())
+    }
+
+    /// Whether `triples` also returns quads materialized by the reasoner.
+    pub fn set_include_inferred(&mut self, include_inferred: bool) -> PyResult<()> {
+        self.include_inferred = include_inferred;
+        Ok(())
+    }
+
+    /// Returns `(((s, p, o), g), rule, premises)`, where rule is "asserted" for leaves and each
+    /// premise has the same shape, or None if the quad does not hold.
+    pub fn explain(&self, py: Python, triple: (&PyQStoreNode, &PyQStoreNode, &PyQStoreNode), context: Option<&PyQStoreNode>) -> PyResult<Option<PyObject>> {
+        let (s_py_node, p_py_node, o_py_node) = triple;
+        let mut ids = Vec::new();
+        for py_node in [s_py_node, p_py_node, o_py_node].iter() {
+            let found = py_node.to_native_store_node_if_exist(py, &self._engine).ok()
+                .and_then(|n| self._engine.find_internal_id(&n).ok());
+            if let Some(i) = found { ids.push(i); } else { return Ok(None); }
+        }
+        let gid = if let Some(g) = context {
+            let found = g.to_native_store_node_if_exist(py, &self._engine).ok()
+                .and_then(|g_n| self._engine.find_internal_id(&g_n).ok());
+            if let Some(i) = found { i } else { return Ok(None); }
+        } else { InternalID(0.into()) };
+        let proof = self._engine.explain(gid, ids[0].clone(), ids[1].clone(), ids[2].clone());
+        Ok(proof.map(|p| self.proof_to_py(py, &p)))
+    }
+
+    pub fn enable_equality_mode(&mut self) -> PyResult<()> {
+        self._engine.enable_equality_mode()
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn disable_equality_mode(&mut self) -> PyResult<()> {
+        self._engine.disable_equality_mode();
+        Ok(())
+    }
+
+    pub fn set_expand_aliases(&mut self, expand_aliases: bool) -> PyResult<()> {
+        self.expand_aliases = expand_aliases;
+        Ok(())
+    }
+
+    pub fn apply_rules(&mut self, py: Python, rules: &str, context: Option<&PyQStoreNode>) -> PyResult<usize> {
+        let gid = if let Some(g) = context {
+            let g_n = g.to_native_store_node(py, &mut self._engine);
+            Some(self._engine.find_or_add_internal_id(g_n)
+                .map_err(|e| PyErr::new::<exc::ValueError, String>(e))?)
+        } else { None };
+        self._engine.apply_rules(rules, gid)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    /// Returns whether the data graph conforms to the shapes graph. Pass a report graph to get
+    /// the individual results as `sh:ValidationReport` triples.
+    pub fn validate_shacl(&mut self, py: Python, shapes_graph: &PyQStoreNode, data_graph: &PyQStoreNode, report_graph: Option<&PyQStoreNode>) -> PyResult<bool> {
+        let mut graphs = Vec::new();
+        for g in Some(shapes_graph).into_iter().chain(Some(data_graph)).chain(report_graph) {
+            let g_n = g.to_native_store_node(py, &mut self._engine);
+            graphs.push(self._engine.find_or_add_internal_id(g_n)
+                .map_err(|e| PyErr::new::<exc::ValueError, String>(e))?);
+        }
+        let report_gid = graphs.get(2).cloned();
+        self._engine.validate_shacl(graphs[0].clone(), graphs[1].clone(), report_gid)
+            .map(|report| report.conforms)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    /// Returns `(node, shape, conformant)` for every node the query shape map selects, where shape
+    /// is the shape's IRI or "START".
+    pub fn validate_shex(&mut self, py: Python, schema: &str, shape_map: &str, context: Option<&PyQStoreNode>) -> PyResult<Vec<PyObject>> {
+        let gid = if let Some(g) = context {
+            let g_n = g.to_native_store_node(py, &mut self._engine);
+            Some(self._engine.find_or_add_internal_id(g_n)
+                .map_err(|e| PyErr::new::<exc::ValueError, String>(e))?)
+        } else { None };
+        let entries = self._engine.validate_shex(schema, shape_map, gid)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))?;
+        Ok(entries.iter().map(|e| {
+            let native = self._engine.lookup_node_by_iid(&e.node).unwrap();
+            let node = PyQStoreNode::create_from_native_store_node_ref(native, py, &self._engine).into_object(py);
+            let shape = match e.shape {
+                ShapeSelector::Start => "START",
+                ShapeSelector::Label(ref l) => l.as_str(),
+            };
+            PyTuple::new(py, &vec![node, PyString::new(py, shape).into_object(py), e.conformant.to_object(py)]).into_object(py)
+        }).collect())
+    }
+
+    /// Returns `(canonical N-Quads, SHA-256 hex)` for the context graph, or for the whole store.
+    pub fn canonicalize(&self, py: Python, context: Option<&PyQStoreNode>, work_limit: Option<usize>) -> PyResult<(String, String)> {
+        let gid = if let Some(g) = context {
+            let found = g.to_native_store_node_if_exist(py, &self._engine).ok()
+                .and_then(|g_n| self._engine.find_internal_id(&g_n).ok());
+            if let Some(i) = found { Some(i) } else { return Err(PyErr::new::<exc::ValueError, String>("Unknown graph.".to_owned())); }
+        } else { None };
+        let canonical = self._engine.canonicalize(gid, work_limit)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))?;
+        Ok((canonical.borrow_nquads().to_owned(), canonical.borrow_hash().to_owned()))
+    }
+
+    /// Applies an RDF Patch document; returns the number of quads added and deleted.
+    pub fn apply_patch(&mut self, text: &str, strict: Option<bool>) -> PyResult<usize> {
+        let patch = Patch::parse(text).map_err(|e| PyErr::new::<exc::ValueError, String>(e))?;
+        self._engine.apply_patch(&patch, strict.unwrap_or(true))
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    /// Runs a SPARQL query and returns the plan it was evaluated with, as text or as JSON when
+    /// `format` is "json".
+    pub fn explain_query(&self, query: &str, format: Option<&str>) -> PyResult<String> {
+        let plan = self._engine.explain_query(query)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))?;
+        match format.unwrap_or("text") {
+            "text" => Ok(plan.to_text()),
+            "json" => Ok(plan.to_json()),
+            other => Err(PyErr::new::<exc::ValueError, String>(format!("Unknown plan format '{}'.", other))),
+        }
+    }
+
+    pub fn skolemize(&mut self, py: Python, authority: &str, context: Option<&PyQStoreNode>) -> PyResult<usize> {
+        let gid = if let Some(g) = context {
+            let found = g.to_native_store_node_if_exist(py, &self._engine).ok()
+                .and_then(|g_n| self._engine.find_internal_id(&g_n).ok());
+            if let Some(i) = found { Some(i) } else { return Ok(0); }
+        } else { None };
+        self._engine.skolemize(gid, authority)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn deskolemize(&mut self, py: Python, context: Option<&PyQStoreNode>) -> PyResult<usize> {
+        let gid = if let Some(g) = context {
+            let found = g.to_native_store_node_if_exist(py, &self._engine).ok()
+                .and_then(|g_n| self._engine.find_internal_id(&g_n).ok());
+            if let Some(i) = found { Some(i) } else { return Ok(0); }
+        } else { None };
+        self._engine.deskolemize(gid)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn bind(&mut self, prefix: &str, namespace: &str, replace: Option<bool>) -> PyResult<()> {
+        let do_replace = replace.unwrap_or(true);
+        self._engine.borrow_namespace_manager_mut().bind(prefix, namespace, do_replace)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn unbind(&mut self, prefix: &str) -> PyResult<String> {
+        self._engine.borrow_namespace_manager_mut().unbind(prefix)
+            .map_err(|e| PyErr::new::<exc::KeyError, String>(e))
+    }
+
+    pub fn namespace(&self, prefix: &str) -> PyResult<Option<String>> {
+        Ok(self._engine.borrow_namespace_manager().namespace(prefix).map(|n| n.to_owned()))
+    }
+
+    pub fn prefix(&self, namespace: &str) -> PyResult<Option<String>> {
+        Ok(self._engine.borrow_namespace_manager().prefix(namespace).map(|p| p.to_owned()))
+    }
+
+    pub fn namespaces(&self) -> PyResult<Vec<(String, String)>> {
+        Ok(self._engine.borrow_namespace_manager().namespaces()
+            .map(|(p, n)| (p.clone(), n.clone())).collect())
+    }
+
+    pub fn expand_curie(&self, curie: &str) -> PyResult<String> {
+        self._engine.borrow_namespace_manager().expand_curie(curie)
+            .map_err(|e| PyErr::new::<exc::ValueError, String>(e))
+    }
+
+    pub fn compact_iri(&self, iri: &str) -> PyResult<Option<String>> {
+        Ok(self._engine.borrow_namespace_manager().compact_iri(iri))
+    }
+
 
     #[staticmethod]
     pub fn empty_iter(py: Python) -> PyResult<Py<PyQStoreIterableResult>> {
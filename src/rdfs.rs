@@ -0,0 +1,301 @@
+use identifiers::InternalID;
+use inference::{InferenceRule, Derivation, PatternRule, RulePattern, RuleTerm, RuleConclusion};
+use nquads::Term;
+use store::{StorageEngine, StoreNode, InternalQuad};
+use vocab;
+
+#[derive(Clone, Debug)]
+pub struct RdfsVocabulary {
+    pub rdf_type: InternalID,
+    pub rdf_property: InternalID,
+    pub sub_class_of: InternalID,
+    pub sub_property_of: InternalID,
+    pub domain: InternalID,
+    pub range: InternalID,
+    pub class: InternalID,
+    pub resource: InternalID,
+    pub literal: InternalID,
+    pub datatype: InternalID,
+    pub member: InternalID,
+    pub container_membership_property: InternalID,
+}
+
+impl RdfsVocabulary {
+    pub fn intern(store: &mut StorageEngine) -> Result<RdfsVocabulary, String> {
+        RdfsVocabulary::from_lookup(|iri| store.uri_str_to_internal_id(iri))
+    }
+
+    /// The vocabulary as the store already holds it, for when it cannot be changed. Terms the
+    /// store does not hold get `InternalID::MAX`, which matches no quads.
+    pub fn lookup(store: &StorageEngine) -> RdfsVocabulary {
+        RdfsVocabulary::from_lookup(|iri| Ok(Term::Iri(iri.to_owned()).find(store).unwrap_or(InternalID::MAX))).unwrap()
+    }
+
+    fn from_lookup<F: FnMut(&str) -> Result<InternalID, String>>(mut id: F) -> Result<RdfsVocabulary, String> {
+        Ok(RdfsVocabulary {
+            rdf_type: id(vocab::RDF_TYPE)?,
+            rdf_property: id(vocab::RDF_PROPERTY)?,
+            sub_class_of: id(vocab::RDFS_SUB_CLASS_OF)?,
+            sub_property_of: id(vocab::RDFS_SUB_PROPERTY_OF)?,
+            domain: id(vocab::RDFS_DOMAIN)?,
+            range: id(vocab::RDFS_RANGE)?,
+            class: id(vocab::RDFS_CLASS)?,
+            resource: id(vocab::RDFS_RESOURCE)?,
+            literal: id(vocab::RDFS_LITERAL)?,
+            datatype: id(vocab::RDFS_DATATYPE)?,
+            member: id(vocab::RDFS_MEMBER)?,
+            container_membership_property: id(vocab::RDFS_CONTAINER_MEMBERSHIP_PROPERTY)?,
+        })
+    }
+}
+
+pub fn is_literal(store: &StorageEngine, id: &InternalID) -> bool {
+    matches!(store.lookup_node_by_iid(id), Ok(&StoreNode::Literal(_)))
+}
+
+/// rdfs5 and rdfs11: `a P b . b P c => a P c` for a transitive schema predicate P.
+pub struct TransitivityRule {
+    name: &'static str,
+    predicate: InternalID,
+}
+
+impl InferenceRule for TransitivityRule {
+    fn name(&self) -> &'static str { self.name }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref a, ref p, ref b, ref g) = delta;
+        if *p != self.predicate {
+            return;
+        }
+        for (_, _, _, c) in store.search_engine_entailed(Some(g.clone()), Some(b.clone()), Some(p.clone()), None) {
+            let premises = vec![delta.clone(), (b.clone(), p.clone(), c.clone(), g.clone())];
+            derived.push(Derivation::new((a.clone(), p.clone(), c, g.clone()), premises));
+        }
+        for (_, z, _, _) in store.search_engine_entailed(Some(g.clone()), None, Some(p.clone()), Some(a.clone())) {
+            let premises = vec![(z.clone(), p.clone(), a.clone(), g.clone()), delta.clone()];
+            derived.push(Derivation::new((z, p.clone(), b.clone(), g.clone()), premises));
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref a, ref p, ref c, ref g) = quad;
+        if *p != self.predicate {
+            return None;
+        }
+        store.search_engine_entailed(Some(g.clone()), Some(a.clone()), Some(p.clone()), None)
+            .find(|&(_, _, _, ref b)| store.search_engine_entailed(Some(g.clone()), Some(b.clone()), Some(p.clone()), Some(c.clone())).next().is_some())
+            .map(|(_, _, _, b)| Derivation::new(quad.clone(), vec![(a.clone(), p.clone(), b.clone(), g.clone()), (b, p.clone(), c.clone(), g.clone())]))
+    }
+}
+
+/// rdfs2 and rdfs3: `p rdfs:domain c . s p o => s rdf:type c`, and likewise `o rdf:type c` for ranges.
+pub struct DomainRangeRule {
+    name: &'static str,
+    schema_predicate: InternalID,
+    rdf_type: InternalID,
+    types_object: bool,
+}
+
+impl DomainRangeRule {
+    fn typed_node(&self, subject: &InternalID, object: &InternalID, store: &StorageEngine) -> Option<InternalID> {
+        if !self.types_object {
+            Some(subject.clone())
+        } else if is_literal(store, object) {
+            None
+        } else {
+            Some(object.clone())
+        }
+    }
+}
+
+impl InferenceRule for DomainRangeRule {
+    fn name(&self) -> &'static str { self.name }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        if *p == self.schema_predicate {
+            for (_, s2, _, o2) in store.search_engine_entailed(Some(g.clone()), None, Some(s.clone()), None) {
+                if let Some(x) = self.typed_node(&s2, &o2, store) {
+                    let premises = vec![delta.clone(), (s2, s.clone(), o2, g.clone())];
+                    derived.push(Derivation::new((x, self.rdf_type.clone(), o.clone(), g.clone()), premises));
+                }
+            }
+        }
+        if let Some(x) = self.typed_node(s, o, store) {
+            for (_, _, _, c) in store.search_engine_entailed(Some(g.clone()), Some(p.clone()), Some(self.schema_predicate.clone()), None) {
+                let premises = vec![(p.clone(), self.schema_predicate.clone(), c.clone(), g.clone()), delta.clone()];
+                derived.push(Derivation::new((x.clone(), self.rdf_type.clone(), c, g.clone()), premises));
+            }
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref x, ref t, ref c, ref g) = quad;
+        if *t != self.rdf_type || (self.types_object && is_literal(store, x)) {
+            return None;
+        }
+        for (_, p, _, _) in store.search_engine_entailed(Some(g.clone()), None, Some(self.schema_predicate.clone()), Some(c.clone())) {
+            let (s, o) = if self.types_object { (None, Some(x.clone())) } else { (Some(x.clone()), None) };
+            if let Some((_, qs, qp, qo)) = store.search_engine_entailed(Some(g.clone()), s, Some(p.clone()), o).next() {
+                let premises = vec![(p, self.schema_predicate.clone(), c.clone(), g.clone()), (qs, qp, qo, g.clone())];
+                return Some(Derivation::new(quad.clone(), premises));
+            }
+        }
+        None
+    }
+}
+
+/// rdfs7: `p rdfs:subPropertyOf q . s p o => s q o`
+pub struct SubPropertyRule {
+    sub_property_of: InternalID,
+}
+
+impl InferenceRule for SubPropertyRule {
+    fn name(&self) -> &'static str { "rdfs7" }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        if *p == self.sub_property_of {
+            for (_, s2, _, o2) in store.search_engine_entailed(Some(g.clone()), None, Some(s.clone()), None) {
+                let premises = vec![delta.clone(), (s2.clone(), s.clone(), o2.clone(), g.clone())];
+                derived.push(Derivation::new((s2, o.clone(), o2, g.clone()), premises));
+            }
+        }
+        for (_, _, _, q) in store.search_engine_entailed(Some(g.clone()), Some(p.clone()), Some(self.sub_property_of.clone()), None) {
+            let premises = vec![(p.clone(), self.sub_property_of.clone(), q.clone(), g.clone()), delta.clone()];
+            derived.push(Derivation::new((s.clone(), q, o.clone(), g.clone()), premises));
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref s, ref q, ref o, ref g) = quad;
+        store.search_engine_entailed(Some(g.clone()), None, Some(self.sub_property_of.clone()), Some(q.clone()))
+            .find(|&(_, ref p, _, _)| store.search_engine_entailed(Some(g.clone()), Some(s.clone()), Some(p.clone()), Some(o.clone())).next().is_some())
+            .map(|(_, p, _, _)| Derivation::new(quad.clone(), vec![(p.clone(), self.sub_property_of.clone(), q.clone(), g.clone()), (s.clone(), p, o.clone(), g.clone())]))
+    }
+}
+
+/// rdfs9: `c rdfs:subClassOf d . x rdf:type c => x rdf:type d`
+pub struct SubClassRule {
+    sub_class_of: InternalID,
+    rdf_type: InternalID,
+}
+
+impl InferenceRule for SubClassRule {
+    fn name(&self) -> &'static str { "rdfs9" }
+
+    fn apply(&self, delta: &InternalQuad, store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        if *p == self.sub_class_of {
+            for (_, x, _, _) in store.search_engine_entailed(Some(g.clone()), None, Some(self.rdf_type.clone()), Some(s.clone())) {
+                let premises = vec![delta.clone(), (x.clone(), self.rdf_type.clone(), s.clone(), g.clone())];
+                derived.push(Derivation::new((x, self.rdf_type.clone(), o.clone(), g.clone()), premises));
+            }
+        }
+        if *p == self.rdf_type {
+            for (_, _, _, d) in store.search_engine_entailed(Some(g.clone()), Some(o.clone()), Some(self.sub_class_of.clone()), None) {
+                let premises = vec![(o.clone(), self.sub_class_of.clone(), d.clone(), g.clone()), delta.clone()];
+                derived.push(Derivation::new((s.clone(), self.rdf_type.clone(), d, g.clone()), premises));
+            }
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref x, ref t, ref d, ref g) = quad;
+        if *t != self.rdf_type {
+            return None;
+        }
+        store.search_engine_entailed(Some(g.clone()), None, Some(self.sub_class_of.clone()), Some(d.clone()))
+            .find(|&(_, ref c, _, _)| store.search_engine_entailed(Some(g.clone()), Some(x.clone()), Some(t.clone()), Some(c.clone())).next().is_some())
+            .map(|(_, c, _, _)| Derivation::new(quad.clone(), vec![(c.clone(), self.sub_class_of.clone(), d.clone(), g.clone()), (x.clone(), t.clone(), c, g.clone())]))
+    }
+}
+
+/// rdfs6, rdfs8, rdfs10, rdfs12 and rdfs13: `x rdf:type C => x P y`, where `y` is either a fixed
+/// term or `x` itself.
+pub struct TypeEntailmentRule {
+    name: &'static str,
+    rdf_type: InternalID,
+    class: InternalID,
+    predicate: InternalID,
+    object: Option<InternalID>,
+}
+
+impl InferenceRule for TypeEntailmentRule {
+    fn name(&self) -> &'static str { self.name }
+
+    fn apply(&self, delta: &InternalQuad, _store: &StorageEngine, derived: &mut Vec<Derivation>) {
+        let &(ref s, ref p, ref o, ref g) = delta;
+        if *p == self.rdf_type && *o == self.class {
+            let object = self.object.clone().unwrap_or_else(|| s.clone());
+            derived.push(Derivation::new((s.clone(), self.predicate.clone(), object, g.clone()), vec![delta.clone()]));
+        }
+    }
+
+    fn rederive(&self, quad: &InternalQuad, store: &StorageEngine) -> Option<Derivation> {
+        let &(ref s, ref p, ref o, ref g) = quad;
+        if *p != self.predicate || *o != self.object.clone().unwrap_or_else(|| s.clone()) {
+            return None;
+        }
+        let premise = (s.clone(), self.rdf_type.clone(), self.class.clone(), g.clone());
+        if store.search_engine_entailed(Some(g.clone()), Some(s.clone()), Some(self.rdf_type.clone()), Some(self.class.clone())).next().is_some() {
+            Some(Derivation::new(quad.clone(), vec![premise]))
+        } else {
+            None
+        }
+    }
+}
+
+/// The schema rules shared by RDFS and OWL 2 RL: rdfs2, 3, 5, 7, 9 and 11 (prp-dom, prp-rng,
+/// scm-spo, prp-spo1, cax-sco and scm-sco).
+pub fn rdfs_core_rules(v: &RdfsVocabulary) -> Vec<Box<InferenceRule>> {
+    vec![
+        Box::new(DomainRangeRule { name: "rdfs2", schema_predicate: v.domain.clone(), rdf_type: v.rdf_type.clone(), types_object: false }),
+        Box::new(DomainRangeRule { name: "rdfs3", schema_predicate: v.range.clone(), rdf_type: v.rdf_type.clone(), types_object: true }),
+        Box::new(TransitivityRule { name: "rdfs5", predicate: v.sub_property_of.clone() }),
+        Box::new(SubPropertyRule { sub_property_of: v.sub_property_of.clone() }),
+        Box::new(SubClassRule { sub_class_of: v.sub_class_of.clone(), rdf_type: v.rdf_type.clone() }),
+        Box::new(TransitivityRule { name: "rdfs11", predicate: v.sub_class_of.clone() }),
+    ]
+}
+
+/// The RDFS entailment rules rdfs2, 3, 5, 6, 7, 8, 9, 10, 11, 12 and 13. The datatype (rdfs1) and
+/// resource typing (rdfs4a/b) rules are left out, as they type every term in the store.
+/// Rules only join quads within the same graph.
+pub fn rdfs_rules(v: &RdfsVocabulary) -> Vec<Box<InferenceRule>> {
+    let mut rules = rdfs_core_rules(v);
+    rules.push(Box::new(TypeEntailmentRule { name: "rdfs6", rdf_type: v.rdf_type.clone(), class: v.rdf_property.clone(), predicate: v.sub_property_of.clone(), object: None }));
+    rules.push(Box::new(TypeEntailmentRule { name: "rdfs8", rdf_type: v.rdf_type.clone(), class: v.class.clone(), predicate: v.sub_class_of.clone(), object: Some(v.resource.clone()) }));
+    rules.push(Box::new(TypeEntailmentRule { name: "rdfs10", rdf_type: v.rdf_type.clone(), class: v.class.clone(), predicate: v.sub_class_of.clone(), object: None }));
+    rules.push(Box::new(TypeEntailmentRule { name: "rdfs12", rdf_type: v.rdf_type.clone(), class: v.container_membership_property.clone(), predicate: v.sub_property_of.clone(), object: Some(v.member.clone()) }));
+    rules.push(Box::new(TypeEntailmentRule { name: "rdfs13", rdf_type: v.rdf_type.clone(), class: v.datatype.clone(), predicate: v.sub_class_of.clone(), object: Some(v.literal.clone()) }));
+    rules
+}
+
+/// The RDFS rules of `rdfs_rules` as Horn rules over triple patterns, for backward chaining.
+/// rdfs3 types literal objects here too; callers drop conclusions about literals.
+pub fn rdfs_pattern_rules(v: &RdfsVocabulary, core_only: bool) -> Vec<PatternRule> {
+    let con = |id: &InternalID| RuleTerm::Const(id.clone());
+    let (x, y, p, c, d) = (RuleTerm::Var(0), RuleTerm::Var(1), RuleTerm::Var(2), RuleTerm::Var(3), RuleTerm::Var(4));
+    let t = con(&v.rdf_type);
+    let rule = |name: &'static str, body: Vec<RulePattern>, head: RulePattern| PatternRule::new(name, body, RuleConclusion::Quads(vec![head]));
+    let mut rules = vec![
+        rule("rdfs2", vec![RulePattern(p.clone(), con(&v.domain), c.clone()), RulePattern(x.clone(), p.clone(), y.clone())], RulePattern(x.clone(), t.clone(), c.clone())),
+        rule("rdfs3", vec![RulePattern(p.clone(), con(&v.range), c.clone()), RulePattern(x.clone(), p.clone(), y.clone())], RulePattern(y.clone(), t.clone(), c.clone())),
+        rule("rdfs5", vec![RulePattern(p.clone(), con(&v.sub_property_of), c.clone()), RulePattern(c.clone(), con(&v.sub_property_of), d.clone())],
+             RulePattern(p.clone(), con(&v.sub_property_of), d.clone())),
+        rule("rdfs7", vec![RulePattern(p.clone(), con(&v.sub_property_of), c.clone()), RulePattern(x.clone(), p.clone(), y.clone())], RulePattern(x.clone(), c.clone(), y.clone())),
+        rule("rdfs9", vec![RulePattern(c.clone(), con(&v.sub_class_of), d.clone()), RulePattern(x.clone(), t.clone(), c.clone())], RulePattern(x.clone(), t.clone(), d.clone())),
+        rule("rdfs11", vec![RulePattern(c.clone(), con(&v.sub_class_of), d.clone()), RulePattern(d.clone(), con(&v.sub_class_of), p.clone())],
+             RulePattern(c.clone(), con(&v.sub_class_of), p.clone())),
+    ];
+    if !core_only {
+        let typed = |class: &InternalID| vec![RulePattern(x.clone(), t.clone(), con(class))];
+        rules.push(rule("rdfs6", typed(&v.rdf_property), RulePattern(x.clone(), con(&v.sub_property_of), x.clone())));
+        rules.push(rule("rdfs8", typed(&v.class), RulePattern(x.clone(), con(&v.sub_class_of), con(&v.resource))));
+        rules.push(rule("rdfs10", typed(&v.class), RulePattern(x.clone(), con(&v.sub_class_of), x.clone())));
+        rules.push(rule("rdfs12", typed(&v.container_membership_property), RulePattern(x.clone(), con(&v.sub_property_of), con(&v.member))));
+        rules.push(rule("rdfs13", typed(&v.datatype), RulePattern(x.clone(), con(&v.sub_class_of), con(&v.literal))));
+    }
+    rules
+}
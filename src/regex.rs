@@ -0,0 +1,381 @@
+/// A small regular expression matcher covering the XPath/XSD regex features used by SHACL
+/// `sh:pattern` and SPARQL `REGEX`: literals, `.`, classes and ranges, the `\d \w \s` escapes and
+/// their negations, anchors, groups, alternation and counted quantifiers. Patterns compile to an
+/// NFA that is simulated state-set at a time, so matching is linear in the text and never backtracks.
+/// Flags: `i` (case-insensitive), `s` (dot matches newlines), `m` (multi-line anchors),
+/// `x` (whitespace in the pattern is ignored) and `q` (the pattern is matched literally).
+#[derive(Clone, Debug)]
+pub struct Regex {
+    states: Vec<State>,
+    start: usize,
+    case_insensitive: bool,
+    dot_all: bool,
+    multi_line: bool,
+}
+
+/* Counted quantifiers are compiled by unrolling, so counts and the size of the result are bounded. */
+const MAX_REPEAT: usize = 1000;
+const MAX_STATES: usize = 100000;
+
+#[derive(Clone, Debug)]
+enum State {
+    Consume(Node, usize),
+    Split(usize, usize),
+    Assert(Node, usize),
+    Matched,
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Escape(char),
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Escape(char),
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+fn escape_matches(escape: char, c: char) -> bool {
+    match escape {
+        'd' => c.is_ascii_digit(),
+        'D' => !c.is_ascii_digit(),
+        'w' => c.is_alphanumeric() || c == '_',
+        'W' => !(c.is_alphanumeric() || c == '_'),
+        's' => c == ' ' || c == '\t' || c == '\n' || c == '\r',
+        'S' => !(c == ' ' || c == '\t' || c == '\n' || c == '\r'),
+        'n' => c == '\n',
+        'r' => c == '\r',
+        't' => c == '\t',
+        other => c == other,
+    }
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("Invalid regular expression: {} at position {}.", message, self.position))
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.position += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return None;
+        }
+        /* Counts too long to parse are out of range anyway. */
+        Some(self.chars[start..self.position].iter().collect::<String>().parse().unwrap_or(usize::MAX))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => { self.position += 1; (0, None) }
+            Some('+') => { self.position += 1; (1, None) }
+            Some('?') => { self.position += 1; (0, Some(1)) }
+            Some('{') => {
+                self.position += 1;
+                let min = if let Some(n) = self.number() { n } else { return self.error("expected a repetition count"); };
+                let max = if self.peek() == Some(',') {
+                    self.position += 1;
+                    self.number()
+                } else {
+                    Some(min)
+                };
+                if self.peek() != Some('}') {
+                    return self.error("expected \"}\"");
+                }
+                self.position += 1;
+                if max.map(|m| m < min).unwrap_or(false) {
+                    return self.error("repetition counts are out of order");
+                }
+                if min > MAX_REPEAT || max.map(|m| m > MAX_REPEAT).unwrap_or(false) {
+                    return self.error("repetition count too large");
+                }
+                (min, max)
+            }
+            _ => return Ok(atom),
+        };
+        /* Lazy quantifiers match the same strings, which is all `is_match` needs. */
+        if self.peek() == Some('?') {
+            self.position += 1;
+        }
+        Ok(Node::Repeat(Box::new(atom), min, max))
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.peek().unwrap();
+        self.position += 1;
+        match c {
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => {
+                if self.peek() == Some('?') && self.chars.get(self.position + 1) == Some(&':') {
+                    self.position += 2;
+                }
+                let alternatives = self.alternatives()?;
+                if self.peek() != Some(')') {
+                    return self.error("unclosed group");
+                }
+                self.position += 1;
+                Ok(Node::Group(alternatives))
+            }
+            '[' => self.class(),
+            '\\' => {
+                let escaped = if let Some(e) = self.peek() { e } else { return self.error("trailing backslash"); };
+                self.position += 1;
+                Ok(Node::Escape(escaped))
+            }
+            '*' | '+' | '?' | '{' => self.error("nothing to repeat"),
+            other => Ok(Node::Char(other)),
+        }
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = if let Some(c) = self.peek() { c } else { return self.error("unclosed character class"); };
+            self.position += 1;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+            let item = if c == '\\' {
+                let escaped = if let Some(e) = self.peek() { e } else { return self.error("trailing backslash"); };
+                self.position += 1;
+                ClassItem::Escape(escaped)
+            } else {
+                ClassItem::Char(c)
+            };
+            let ranged = self.peek() == Some('-') && self.chars.get(self.position + 1).map(|&n| n != ']').unwrap_or(false);
+            match item {
+                ClassItem::Char(low) if ranged => {
+                    self.position += 1;
+                    let mut high = self.peek().unwrap();
+                    self.position += 1;
+                    if high == '\\' {
+                        high = if let Some(e) = self.peek() { e } else { return self.error("trailing backslash"); };
+                        self.position += 1;
+                    }
+                    items.push(ClassItem::Range(low, high));
+                }
+                other => items.push(other),
+            }
+        }
+        Ok(Node::Class(items, negated))
+    }
+}
+
+/* The number of states `node` compiles to, saturating. */
+fn compiled_size(node: &Node) -> usize {
+    match node {
+        &Node::Group(ref alternatives) => alternatives.iter()
+            .map(|alt| alt.iter().fold(0usize, |size, n| size.saturating_add(compiled_size(n))))
+            .fold(alternatives.len().saturating_sub(1), |size, alt| size.saturating_add(alt)),
+        &Node::Repeat(ref inner, min, max) => {
+            let inner = compiled_size(inner);
+            let optional = match max {
+                None => inner.saturating_add(1),
+                Some(m) => (m - min).saturating_mul(inner.saturating_add(1)),
+            };
+            optional.saturating_add(min.saturating_mul(inner))
+        }
+        _ => 1,
+    }
+}
+
+fn compile_sequence(states: &mut Vec<State>, nodes: &[Node], next: usize) -> usize {
+    nodes.iter().rev().fold(next, |following, node| compile_node(states, node, following))
+}
+
+fn compile_alternatives(states: &mut Vec<State>, alternatives: &[Vec<Node>], next: usize) -> usize {
+    let mut starts: Vec<usize> = alternatives.iter().map(|alt| compile_sequence(states, alt, next)).collect();
+    let mut entry = starts.pop().unwrap_or(next);
+    while let Some(other) = starts.pop() {
+        states.push(State::Split(other, entry));
+        entry = states.len() - 1;
+    }
+    entry
+}
+
+/* Compiles `node` so that it continues to `next`, returning its entry state. */
+fn compile_node(states: &mut Vec<State>, node: &Node, next: usize) -> usize {
+    match node {
+        &Node::Group(ref alternatives) => compile_alternatives(states, alternatives, next),
+        &Node::Start | &Node::End => {
+            states.push(State::Assert(node.clone(), next));
+            states.len() - 1
+        }
+        &Node::Repeat(ref inner, min, max) => {
+            let mut entry = match max {
+                None => {
+                    states.push(State::Split(0, next));
+                    let looped = states.len() - 1;
+                    let body = compile_node(states, inner, looped);
+                    states[looped] = State::Split(body, next);
+                    looped
+                }
+                Some(m) => {
+                    let mut optional = next;
+                    for _ in min..m {
+                        let body = compile_node(states, inner, optional);
+                        states.push(State::Split(body, next));
+                        optional = states.len() - 1;
+                    }
+                    optional
+                }
+            };
+            for _ in 0..min {
+                entry = compile_node(states, inner, entry);
+            }
+            entry
+        }
+        single => {
+            states.push(State::Consume(single.clone(), next));
+            states.len() - 1
+        }
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str, flags: &str) -> Result<Regex, String> {
+        if let Some(f) = flags.chars().find(|f| !"ismxq".contains(*f)) {
+            return Err(format!("Unsupported regular expression flag {:?}.", f));
+        }
+        let mut source: String = pattern.to_owned();
+        if flags.contains('x') {
+            source = source.chars().filter(|c| !c.is_whitespace()).collect();
+        }
+        let alternatives = if flags.contains('q') {
+            vec![source.chars().map(Node::Char).collect()]
+        } else {
+            let mut parser = Parser { chars: source.chars().collect(), position: 0 };
+            let parsed = parser.alternatives()?;
+            if parser.position < parser.chars.len() {
+                return parser.error("unbalanced \")\"");
+            }
+            parsed
+        };
+        if compiled_size(&Node::Group(alternatives.clone())) > MAX_STATES {
+            return Err("Regular expression is too large.".to_owned());
+        }
+        let mut states = vec![State::Matched];
+        let start = compile_alternatives(&mut states, &alternatives, 0);
+        Ok(Regex { states, start, case_insensitive: flags.contains('i'), dot_all: flags.contains('s'), multi_line: flags.contains('m') })
+    }
+
+    /// Whether the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let mut current: Vec<usize> = Vec::new();
+        for pos in 0..chars.len() + 1 {
+            let mut seen = vec![false; self.states.len()];
+            for &state in current.iter() {
+                seen[state] = true;
+            }
+            let mut reached = Vec::new();
+            /* A match may begin at any position. */
+            let mut pending: Vec<usize> = current.iter().cloned().chain(Some(self.start)).collect();
+            while let Some(state) = pending.pop() {
+                reached.push(state);
+                let follow = match self.states[state] {
+                    State::Matched => return true,
+                    State::Split(a, b) => vec![a, b],
+                    State::Assert(ref node, next) => if self.assertion_holds(node, &chars, pos) { vec![next] } else { vec![] },
+                    State::Consume(..) => vec![],
+                };
+                for next in follow {
+                    if !seen[next] {
+                        seen[next] = true;
+                        pending.push(next);
+                    }
+                }
+            }
+            if pos == chars.len() {
+                break;
+            }
+            current = reached.iter().filter_map(|&state| match self.states[state] {
+                State::Consume(ref node, next) if self.char_matches(node, chars[pos]) => Some(next),
+                _ => None,
+            }).collect();
+            current.sort();
+            current.dedup();
+        }
+        false
+    }
+
+    fn assertion_holds(&self, node: &Node, text: &[char], pos: usize) -> bool {
+        match node {
+            &Node::Start => pos == 0 || (self.multi_line && text[pos - 1] == '\n'),
+            &Node::End => pos == text.len() || (self.multi_line && text[pos] == '\n'),
+            _ => false,
+        }
+    }
+
+    fn char_matches(&self, node: &Node, c: char) -> bool {
+        match node {
+            &Node::Char(expected) => if self.case_insensitive { fold(expected) == fold(c) } else { expected == c },
+            &Node::Any => self.dot_all || (c != '\n' && c != '\r'),
+            &Node::Escape(e) => escape_matches(e, c),
+            &Node::Class(ref items, negated) => {
+                let folded = fold(c);
+                let found = items.iter().any(|item| match item {
+                    &ClassItem::Char(x) => x == c || (self.case_insensitive && fold(x) == folded),
+                    &ClassItem::Range(low, high) => (low <= c && c <= high) ||
+                        (self.case_insensitive && (low <= folded && folded <= high || c.to_uppercase().any(|u| low <= u && u <= high))),
+                    &ClassItem::Escape(e) => escape_matches(e, c),
+                });
+                found != negated
+            }
+            _ => false,
+        }
+    }
+}
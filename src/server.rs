@@ -0,0 +1,435 @@
+use std::io::{BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use blank::BlankNodeScope;
+use http::{self, HttpClient, Request, Response};
+use identifiers::InternalID;
+use iri::IriRef;
+use limits::{Limits, OperationError};
+use nquads::{self, Term};
+use patch::{Patch, PatchQuad, PatchRow};
+use sparql::{self, parser, QueryResult};
+use sparql::entailment::EntailmentRegime;
+use sparql::results::{self, ResultFormat, GRAPH_FORMATS};
+use store::{GraphID, StorageEngine};
+use turtle;
+
+/// Where the SPARQL 1.1 Protocol is served.
+pub const SPARQL_PATH: &'static str = "/sparql";
+/// Where the SPARQL 1.1 Graph Store HTTP Protocol is served, with `?default` or `?graph=IRI`.
+pub const GRAPH_STORE_PATH: &'static str = "/data";
+
+/// Largest request body accepted by default.
+pub const DEFAULT_MAX_REQUEST_SIZE: usize = 1 << 20;
+/// How long a client has to send a whole request by default.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A SPARQL 1.1 Protocol endpoint over a store, answering queries and updates at `/sparql` and
+/// reading and writing whole graphs at `/data`. When backed by a data file, the dataset is written back to it as N-Quads after each update that changes it.
+pub struct Endpoint {
+    store: StorageEngine,
+    read_only: bool,
+    data_file: Option<PathBuf>,
+    limits: Limits,
+    entailment: EntailmentRegime,
+    request_timeout: Duration,
+}
+
+enum Operation {
+    /* A query, with the entailment regime the request asked for. */
+    Query(String, Option<EntailmentRegime>),
+    Update(String),
+}
+
+/* The response to an operation that failed, or was stopped by the endpoint's limits. */
+fn failure(error: OperationError) -> Response {
+    match error {
+        OperationError::Limit(limit) => Response::text(503, &limit.message),
+        OperationError::Failed(message) => Response::text(500, &message),
+    }
+}
+
+impl Endpoint {
+    pub fn new(mut store: StorageEngine) -> Endpoint {
+        store.set_allowed_services(Some(Vec::new()));
+        Endpoint { store: store, read_only: false, data_file: None, limits: Limits::new(), entailment: EntailmentRegime::Simple,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT }
+    }
+
+    /// An endpoint persisted in the N-Quads file at `path`, which is loaded if it exists.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Endpoint, String> {
+        let path = path.as_ref().to_path_buf();
+        let mut endpoint = Endpoint::new(nquads::open_file(&path)?);
+        endpoint.data_file = Some(path);
+        Ok(endpoint)
+    }
+
+    /// Refuses updates with `403 Forbidden` when `read_only` is set.
+    pub fn with_read_only(mut self, read_only: bool) -> Endpoint {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Bounds each query, update and graph upload. One stopped by a limit gets `503 Service
+    /// Unavailable`, and an update so stopped leaves its remaining operations unapplied.
+    pub fn with_limits(mut self, limits: Limits) -> Endpoint {
+        self.limits = limits;
+        self
+    }
+
+    /// Lets `SERVICE` patterns query the endpoints in `endpoints`. An endpoint allows none by
+    /// default, so that requests cannot make it contact other hosts.
+    pub fn with_allowed_services(mut self, endpoints: Vec<String>) -> Endpoint {
+        self.store.set_allowed_services(Some(endpoints));
+        self
+    }
+
+    /// Answers queries under `regime` unless a request names another with the `entailment`
+    /// parameter, such as `entailment=rdfs`.
+    pub fn with_entailment(mut self, regime: EntailmentRegime) -> Endpoint {
+        self.entailment = regime;
+        self
+    }
+
+    /// Gives each client `timeout` to send its whole request, and as long to read the response,
+    /// before the connection is dropped so that the next can be served.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Endpoint {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn borrow_store(&self) -> &StorageEngine {
+        &self.store
+    }
+
+    pub fn borrow_store_mut(&mut self) -> &mut StorageEngine {
+        &mut self.store
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        match self.data_file {
+            Some(ref path) => nquads::save_file(&self.store, path),
+            None => Ok(()),
+        }
+    }
+
+    fn operation(&self, request: &Request) -> Result<Operation, Response> {
+        let utf8 = |body: &[u8]| String::from_utf8(body.to_vec()).map_err(|_| Response::text(400, "Request body is not valid UTF-8."));
+        let (params, mut operation) = match request.method.as_str() {
+            "GET" | "HEAD" => {
+                if request.query_param("update").is_some() {
+                    return Err(Response::text(400, "Updates must be sent with POST."));
+                }
+                (request.query.clone(), None)
+            }
+            "POST" => match request.content_type().as_deref() {
+                Some("application/x-www-form-urlencoded") => (http::parse_form(&utf8(&request.body)?), None),
+                Some("application/sparql-query") => (request.query.clone(), Some(Operation::Query(utf8(&request.body)?, None))),
+                Some("application/sparql-update") => (request.query.clone(), Some(Operation::Update(utf8(&request.body)?))),
+                Some(other) => return Err(Response::text(415, &format!("Unsupported content type '{}'.", other))),
+                None => return Err(Response::text(415, "Missing Content-Type.")),
+            },
+            _ => return Err(Response::text(405, "Method not allowed.").with_header("Allow", "GET, HEAD, POST")),
+        };
+        let mut entailment = None;
+        for &(ref name, ref value) in params.iter() {
+            match name.as_str() {
+                "query" | "update" if operation.is_some() => return Err(Response::text(400, "Only one query or update may be given.")),
+                "query" => operation = Some(Operation::Query(value.clone(), None)),
+                "update" => operation = Some(Operation::Update(value.clone())),
+                "entailment" => match EntailmentRegime::from_name(value) {
+                    Some(regime) => entailment = Some(regime),
+                    None => return Err(Response::text(400, &format!("Unknown entailment regime '{}'; use simple, rdfs or owl-rl.", value))),
+                },
+                "default-graph-uri" | "named-graph-uri" | "using-graph-uri" | "using-named-graph-uri" =>
+                    return Err(Response::text(400, &format!("The '{}' parameter is not supported.", name))),
+                _ => {}
+            }
+        }
+        match operation {
+            Some(Operation::Query(text, _)) => Ok(Operation::Query(text, entailment)),
+            Some(Operation::Update(_)) if entailment.is_some() => Err(Response::text(400, "The 'entailment' parameter only applies to queries.")),
+            Some(operation) => Ok(operation),
+            None => Err(Response::text(400, "Missing 'query' or 'update' parameter.")),
+        }
+    }
+
+    fn run_query(&self, request: &Request, text: &str, entailment: EntailmentRegime) -> Response {
+        let query = match parser::parse_query(text, self.store.borrow_namespace_manager()) {
+            Ok(query) => query,
+            Err(e) => return Response::text(400, &e),
+        };
+        let evaluator = sparql::eval::Evaluator::new(&self.store).with_limits(self.limits.clone()).with_entailment(entailment);
+        let result = match evaluator.execute(&query) {
+            Ok(result) => result,
+            Err(e) => return failure(evaluator.borrow_guard().error(e)),
+        };
+        let format = match ResultFormat::negotiate(request.header("accept"), &result) {
+            Some(format) => format,
+            None => {
+                let offered: Vec<&str> = ResultFormat::available(&result).iter().map(|f| f.media_type()).collect();
+                return Response::text(406, &format!("Results are available as {}.", offered.join(", ")));
+            }
+        };
+        let mut body = Vec::new();
+        if let Err(e) = results::write(&result, format, &mut body) {
+            return Response::text(500, &e.to_string());
+        }
+        Response::new(200).with_header("Content-Type", &format!("{}; charset=utf-8", format.media_type()))
+            .with_header("Vary", "Accept").with_body(body)
+    }
+
+    fn run_update(&mut self, text: &str) -> Response {
+        if self.read_only {
+            return Response::text(403, "This endpoint is read-only.");
+        }
+        let operations = match parser::parse_update(text, self.store.borrow_namespace_manager()) {
+            Ok(operations) => operations,
+            Err(e) => return Response::text(400, &e),
+        };
+        let revision = self.store.revision();
+        let result = sparql::execute_update_with_limits(&mut self.store, &operations, &self.limits);
+        match self.settle(revision, result) {
+            Ok(_) => Response::new(204),
+            Err(e) => failure(e),
+        }
+    }
+
+    /* Saves the store if an operation changed it since `revision`. One that failed may have
+    applied some of its changes before stopping, so the store is saved then too, lest the file
+    lag behind memory; one that changed nothing leaves the file alone. */
+    fn settle(&self, revision: u64, result: Result<usize, OperationError>) -> Result<usize, OperationError> {
+        if self.store.revision() != revision {
+            self.persist()?;
+        }
+        result
+    }
+
+    /* The graph named by `?default` or `?graph=IRI`, `None` being the default graph. */
+    fn graph_parameter(request: &Request) -> Result<Option<String>, Response> {
+        let default = request.query.iter().any(|&(ref name, _)| name == "default");
+        match (default, request.query_param("graph")) {
+            (true, None) => Ok(None),
+            (false, Some(iri)) => match IriRef::parse_absolute(iri) {
+                Ok(_) => Ok(Some(iri.to_owned())),
+                Err(e) => Err(Response::text(400, &format!("Invalid graph IRI: {}", e))),
+            },
+            _ => Err(Response::text(400, "Give exactly one of ?default or ?graph=IRI.")),
+        }
+    }
+
+    /* The graph's id, or `None` if it is a named graph with no quads. */
+    fn existing_graph(&self, graph: &Option<String>) -> Option<GraphID> {
+        match *graph {
+            None => Some(InternalID(0.into())),
+            Some(ref iri) => Term::Iri(iri.clone()).find(&self.store)
+                .filter(|g| self.store.search_engine_internal(Some(g.clone()), None, None, None).next().is_some()),
+        }
+    }
+
+    /* A patch deleting every asserted quad of `graph`. */
+    fn clear_patch(&self, graph: &Option<String>) -> Result<Patch, String> {
+        let mut patch = Patch::new();
+        if let Some(id) = self.existing_graph(graph) {
+            let name = graph.as_ref().map(|iri| Term::Iri(iri.clone()));
+            for (subject, predicate, object) in turtle::graph_triples(&self.store, &id)? {
+                patch.push(PatchRow::Delete(PatchQuad { subject: subject, predicate: predicate, object: object, graph: name.clone() }));
+            }
+        }
+        Ok(patch)
+    }
+
+    fn get_graph(&self, request: &Request, graph: &Option<String>) -> Response {
+        let id = match self.existing_graph(graph) {
+            Some(id) => id,
+            None => return Response::text(404, "No such graph."),
+        };
+        let format = match ResultFormat::choose(request.header("accept"), GRAPH_FORMATS) {
+            Some(format) => format,
+            None => return Response::text(406, "Graphs are available as text/turtle and application/n-triples."),
+        };
+        let triples = match turtle::graph_triples(&self.store, &id) {
+            Ok(triples) => triples,
+            Err(e) => return Response::text(500, &e),
+        };
+        let body = match format {
+            ResultFormat::Turtle => turtle::serialize(&triples, self.store.borrow_namespace_manager()).into_bytes(),
+            _ => {
+                let mut body = Vec::new();
+                if let Err(e) = results::write(&QueryResult::Graph(triples), format, &mut body) {
+                    return Response::text(500, &e.to_string());
+                }
+                body
+            }
+        };
+        Response::new(200).with_header("Content-Type", &format!("{}; charset=utf-8", format.media_type()))
+            .with_header("Vary", "Accept").with_body(body)
+    }
+
+    /* Replaces (for PUT) or adds to (for POST) the graph with the triples in the request body. */
+    fn put_graph(&mut self, request: &Request, graph: &Option<String>, replace: bool) -> Response {
+        match request.content_type().as_deref() {
+            Some("text/turtle") | Some("application/x-turtle") | Some("application/n-triples") | Some("text/plain") => {}
+            Some(other) => return Response::text(415, &format!("Unsupported content type '{}'.", other)),
+            None => return Response::text(415, "Missing Content-Type."),
+        }
+        let text = match String::from_utf8(request.body.clone()) {
+            Ok(text) => text,
+            Err(_) => return Response::text(400, "Request body is not valid UTF-8."),
+        };
+        let triples = match turtle::parse(&text, graph.as_ref().map(|g| g.as_str())) {
+            Ok(triples) => triples,
+            Err(e) => return Response::text(400, &e),
+        };
+        let existed = self.existing_graph(graph).is_some();
+        let mut patch = if replace {
+            match self.clear_patch(graph) {
+                Ok(patch) => patch,
+                Err(e) => return Response::text(500, &e),
+            }
+        } else {
+            Patch::new()
+        };
+        /* Blank node labels are scoped to the uploaded document. */
+        let mut scope = BlankNodeScope::new();
+        let name = graph.as_ref().map(|iri| Term::Iri(iri.clone()));
+        for (subject, predicate, object) in triples {
+            patch.push(PatchRow::Add(PatchQuad { subject: subject.scoped(&mut scope), predicate: predicate,
+                object: object.scoped(&mut scope), graph: name.clone() }));
+        }
+        if let Err(e) = self.apply(&patch) {
+            return failure(e);
+        }
+        Response::new(if !existed && self.existing_graph(graph).is_some() { 201 } else { 204 })
+    }
+
+    fn delete_graph(&mut self, graph: &Option<String>) -> Response {
+        if self.existing_graph(graph).is_none() {
+            return Response::text(404, "No such graph.");
+        }
+        match self.clear_patch(graph).map_err(OperationError::from).and_then(|patch| self.apply(&patch)) {
+            Ok(_) => Response::new(204),
+            Err(e) => failure(e),
+        }
+    }
+
+    fn apply(&mut self, patch: &Patch) -> Result<usize, OperationError> {
+        let revision = self.store.revision();
+        let result = patch.apply_with_limits(&mut self.store, false, &self.limits);
+        self.settle(revision, result)
+    }
+
+    fn graph_store(&mut self, request: &Request) -> Response {
+        let graph = match Endpoint::graph_parameter(request) {
+            Ok(graph) => graph,
+            Err(response) => return response,
+        };
+        let writes = match request.method.as_str() {
+            "GET" | "HEAD" => return self.get_graph(request, &graph),
+            "PUT" | "POST" | "DELETE" => true,
+            _ => false,
+        };
+        if !writes {
+            return Response::text(405, "Method not allowed.").with_header("Allow", "GET, HEAD, PUT, POST, DELETE");
+        }
+        if self.read_only {
+            return Response::text(403, "This endpoint is read-only.");
+        }
+        match request.method.as_str() {
+            "PUT" => self.put_graph(request, &graph, true),
+            "POST" => self.put_graph(request, &graph, false),
+            _ => self.delete_graph(&graph),
+        }
+    }
+
+    /// Answers one request.
+    pub fn handle(&mut self, request: &Request) -> Response {
+        if request.path == GRAPH_STORE_PATH {
+            return self.graph_store(request);
+        }
+        if request.path != SPARQL_PATH {
+            return Response::text(404, &format!("No resource at {}.", request.path));
+        }
+        match self.operation(request) {
+            Ok(Operation::Query(text, entailment)) => self.run_query(request, &text, entailment.unwrap_or(self.entailment)),
+            Ok(Operation::Update(_)) if request.method != "POST" => Response::text(400, "Updates must be sent with POST."),
+            Ok(Operation::Update(text)) => self.run_update(&text),
+            Err(response) => response,
+        }
+    }
+
+    fn serve_connection(&mut self, stream: TcpStream, max_request_size: usize) {
+        let mut writer = match stream.try_clone() {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+        let _ = writer.set_write_timeout(Some(self.request_timeout));
+        let mut reader = BufReader::new(http::DeadlineStream::new(stream, self.request_timeout));
+        let (response, head_only) = match http::read_request(&mut reader, max_request_size) {
+            Ok(Some(request)) => (self.handle(&request), request.method == "HEAD"),
+            Ok(None) => return,
+            Err((status, message)) => (Response::text(status, &message), false),
+        };
+        let _ = response.write_to(&mut writer, head_only);
+        let _ = writer.flush();
+    }
+
+    /// Serves requests from `listener` one at a time until it fails, so updates never interleave.
+    /// A client too slow to send its request within the request timeout gets `408 Request Timeout`.
+    /// Bodies over `max_request_size` bytes are refused with `413 Payload Too Large`.
+    pub fn serve(&mut self, listener: &TcpListener, max_request_size: usize) -> Result<(), String> {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => self.serve_connection(stream, max_request_size),
+                Err(e) => return Err(format!("Could not accept connection: {}", e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An HTTP client answering requests with endpoints in this process rather than over the
+/// network, such as stand-in SPARQL endpoints for `SERVICE` patterns in tests. Each endpoint
+/// serves the URLs under its base, as `http://example.org` serves `http://example.org/sparql`.
+pub struct LocalClient {
+    endpoints: Vec<(String, RefCell<Endpoint>)>,
+}
+
+impl Default for LocalClient {
+    fn default() -> LocalClient {
+        LocalClient::new()
+    }
+}
+
+impl LocalClient {
+    pub fn new() -> LocalClient {
+        LocalClient { endpoints: Vec::new() }
+    }
+
+    pub fn with_endpoint(mut self, base_url: &str, endpoint: Endpoint) -> LocalClient {
+        self.endpoints.push((base_url.to_owned(), RefCell::new(endpoint)));
+        self
+    }
+}
+
+impl HttpClient for LocalClient {
+    fn send(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8]) -> Result<Response, String> {
+        let (scheme, authority, target) = http::split_url(url)?;
+        let endpoint = self.endpoints.iter()
+            .find(|&&(ref base, _)| http::split_url(base).map(|(s, a, _)| s == scheme && a.eq_ignore_ascii_case(&authority)).unwrap_or(false))
+            .map(|&(_, ref endpoint)| endpoint)
+            .ok_or_else(|| format!("Could not reach {}: no local endpoint serves it.", url))?;
+        let mut parts = target.splitn(2, '?');
+        let request = Request {
+            method: method.to_uppercase(),
+            path: parts.next().unwrap_or("/").to_owned(),
+            query: http::parse_form(parts.next().unwrap_or("")),
+            headers: headers.iter().map(|&(ref n, ref v)| (n.to_lowercase(), v.clone())).collect(),
+            body: body.to_vec(),
+        };
+        let mut endpoint = endpoint.try_borrow_mut().map_err(|_| format!("{} is already answering a request.", url))?;
+        Ok(endpoint.handle(&request))
+    }
+}
@@ -0,0 +1,610 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use literal::{self, Literal};
+use blank::BlankNode;
+use property_path::PropertyPath;
+use regex::Regex;
+use store::{StorageEngine, StoreNode, GraphID};
+use vocab;
+
+/* The SHACL terms the validator reads or writes, by local name. */
+static SHACL_TERMS: &'static [&'static str] = &["NodeShape", "PropertyShape", "targetClass", "targetNode",
+    "targetSubjectsOf", "targetObjectsOf", "property", "path", "inversePath", "alternativePath",
+    "zeroOrMorePath", "oneOrMorePath", "zeroOrOnePath", "class", "datatype", "nodeKind", "minCount",
+    "maxCount", "minExclusive", "minInclusive", "maxExclusive", "maxInclusive", "minLength", "maxLength",
+    "pattern", "flags", "languageIn", "uniqueLang", "equals", "disjoint", "lessThan", "lessThanOrEquals",
+    "not", "and", "or", "xone", "node", "hasValue", "in", "closed", "ignoredProperties", "deactivated",
+    "severity", "message", "Violation", "IRI", "BlankNode", "Literal", "BlankNodeOrIRI",
+    "BlankNodeOrLiteral", "IRIOrLiteral", "ValidationReport", "ValidationResult", "conforms", "result",
+    "focusNode", "resultPath", "value", "sourceShape", "sourceConstraintComponent", "resultSeverity",
+    "resultMessage"];
+
+#[derive(Clone, Debug)]
+pub struct ShaclVocabulary {
+    terms: BTreeMap<&'static str, InternalID>,
+    rdf_type: InternalID,
+    rdf_first: InternalID,
+    rdf_rest: InternalID,
+    rdf_nil: InternalID,
+    rdfs_class: InternalID,
+    sub_class_of: InternalID,
+}
+
+impl ShaclVocabulary {
+    pub fn intern(store: &mut StorageEngine) -> Result<ShaclVocabulary, String> {
+        let mut terms = BTreeMap::new();
+        for name in SHACL_TERMS.iter() {
+            terms.insert(*name, store.uri_str_to_internal_id(&format!("{}{}", vocab::SHACL_NAMESPACE, name))?);
+        }
+        Ok(ShaclVocabulary {
+            terms,
+            rdf_type: store.uri_str_to_internal_id(vocab::RDF_TYPE)?,
+            rdf_first: store.uri_str_to_internal_id(vocab::RDF_FIRST)?,
+            rdf_rest: store.uri_str_to_internal_id(vocab::RDF_REST)?,
+            rdf_nil: store.uri_str_to_internal_id(vocab::RDF_NIL)?,
+            rdfs_class: store.uri_str_to_internal_id(vocab::RDFS_CLASS)?,
+            sub_class_of: store.uri_str_to_internal_id(vocab::RDFS_SUB_CLASS_OF)?,
+        })
+    }
+
+    pub fn sh(&self, name: &str) -> &InternalID {
+        &self.terms[name]
+    }
+}
+
+/// One `sh:ValidationResult`. The constraint component is the local name of its SHACL IRI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationResult {
+    pub focus_node: InternalID,
+    pub result_path: Option<InternalID>,
+    pub value: Option<InternalID>,
+    pub source_shape: InternalID,
+    pub source_constraint_component: &'static str,
+    pub severity: InternalID,
+    pub message: Option<InternalID>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport {
+    pub conforms: bool,
+    pub results: Vec<ValidationResult>,
+}
+
+/// Validates the data graph against the shapes in the shapes graph. Data is read with
+/// `search_engine_entailed`, so enabled inference rules are taken into account.
+/// `sh:qualifiedValueShape` and SPARQL-based constraints are not supported.
+pub struct Validator<'a> {
+    store: &'a StorageEngine,
+    shapes_graph: GraphID,
+    data_graph: GraphID,
+    v: ShaclVocabulary,
+}
+
+type ShapeStack = Vec<(InternalID, InternalID)>;
+/* A constraint parameter, the component reporting its violations, and the test a value must pass. */
+type Comparison = (&'static str, &'static str, fn(Ordering) -> bool);
+type LengthBound = (&'static str, &'static str, fn(usize, usize) -> bool);
+
+impl<'a> Validator<'a> {
+    pub fn new(store: &'a StorageEngine, vocabulary: ShaclVocabulary, shapes_graph: GraphID, data_graph: GraphID) -> Validator<'a> {
+        Validator { store, shapes_graph, data_graph, v: vocabulary }
+    }
+
+    fn shape_values(&self, shape: &InternalID, term: &str) -> Vec<InternalID> {
+        self.store.search_engine_entailed(Some(self.shapes_graph.clone()), Some(shape.clone()), Some(self.v.sh(term).clone()), None)
+            .map(|q| q.3).collect()
+    }
+
+    fn shape_value(&self, shape: &InternalID, term: &str) -> Option<InternalID> {
+        self.shape_values(shape, term).into_iter().next()
+    }
+
+    fn shapes_object(&self, subject: &InternalID, predicate: &InternalID) -> Option<InternalID> {
+        self.store.search_engine_entailed(Some(self.shapes_graph.clone()), Some(subject.clone()), Some(predicate.clone()), None)
+            .next().map(|q| q.3)
+    }
+
+    /* Members of the RDF list at `head` in the shapes graph. */
+    fn list(&self, head: &InternalID) -> Result<Vec<InternalID>, String> {
+        let mut members = Vec::new();
+        let mut seen = BTreeSet::new();
+        let mut node = head.clone();
+        while node != self.v.rdf_nil {
+            if !seen.insert(node.clone()) {
+                return Err("Cyclic RDF list in the shapes graph.".to_owned());
+            }
+            let first = self.shapes_object(&node, &self.v.rdf_first);
+            let rest = self.shapes_object(&node, &self.v.rdf_rest);
+            match (first, rest) {
+                (Some(f), Some(r)) => { members.push(f); node = r; }
+                _ => return Err("Malformed RDF list in the shapes graph.".to_owned()),
+            }
+        }
+        Ok(members)
+    }
+
+    fn data_objects(&self, subject: &InternalID, predicate: &InternalID) -> Vec<InternalID> {
+        self.store.search_engine_entailed(Some(self.data_graph.clone()), Some(subject.clone()), Some(predicate.clone()), None)
+            .map(|q| q.3).collect()
+    }
+
+    fn data_subjects(&self, predicate: &InternalID, object: &InternalID) -> Vec<InternalID> {
+        self.store.search_engine_entailed(Some(self.data_graph.clone()), None, Some(predicate.clone()), Some(object.clone()))
+            .map(|q| q.1).collect()
+    }
+
+    fn node(&self, id: &InternalID) -> Option<&'a StoreNode> {
+        self.store.lookup_node_by_iid(id).ok()
+    }
+
+    fn literal(&self, id: &InternalID) -> Option<&'a Literal> {
+        if let Some(&StoreNode::Literal(ref l)) = self.node(id) { Some(l) } else { None }
+    }
+
+    /* The string form SHACL uses for length and pattern checks; blank nodes have none. */
+    fn string_value(&self, id: &InternalID) -> Option<String> {
+        match self.node(id) {
+            Some(&StoreNode::URIRef(ref u)) => Some(u.to_string(self.store)),
+            Some(&StoreNode::Literal(ref l)) => Some(l.borrow_lexical_form().to_owned()),
+            _ => None,
+        }
+    }
+
+    fn integer(&self, id: &InternalID) -> Option<usize> {
+        self.literal(id).and_then(|l| l.borrow_lexical_form().trim().parse().ok())
+    }
+
+    fn is_true(&self, id: &Option<InternalID>) -> bool {
+        id.as_ref().and_then(|i| self.literal(i)).map(|l| l.borrow_lexical_form() == "true" || l.borrow_lexical_form() == "1").unwrap_or(false)
+    }
+
+    fn parse_path(&self, node: &InternalID) -> Result<PropertyPath, String> {
+        if let Some(&StoreNode::URIRef(_)) = self.node(node) {
+            if *node != self.v.rdf_nil {
+                return Ok(PropertyPath::Predicate(node.clone()));
+            }
+        }
+        if self.shapes_object(node, &self.v.rdf_first).is_some() {
+            let steps = self.list(node)?.iter().map(|n| self.parse_path(n)).collect::<Result<Vec<_>, String>>()?;
+            return Ok(PropertyPath::Sequence(steps));
+        }
+        if let Some(inner) = self.shape_value(node, "inversePath") {
+            return Ok(PropertyPath::Inverse(Box::new(self.parse_path(&inner)?)));
+        }
+        if let Some(list) = self.shape_value(node, "alternativePath") {
+            let options = self.list(&list)?.iter().map(|n| self.parse_path(n)).collect::<Result<Vec<_>, String>>()?;
+            return Ok(PropertyPath::Alternative(options));
+        }
+        if let Some(inner) = self.shape_value(node, "zeroOrMorePath") {
+            return Ok(PropertyPath::ZeroOrMore(Box::new(self.parse_path(&inner)?)));
+        }
+        if let Some(inner) = self.shape_value(node, "oneOrMorePath") {
+            return Ok(PropertyPath::OneOrMore(Box::new(self.parse_path(&inner)?)));
+        }
+        if let Some(inner) = self.shape_value(node, "zeroOrOnePath") {
+            return Ok(PropertyPath::ZeroOrOne(Box::new(self.parse_path(&inner)?)));
+        }
+        Err("Unrecognised property path in the shapes graph.".to_owned())
+    }
+
+    /* `class` and everything declared a subclass of it in the data graph, transitively. */
+    fn subclasses(&self, class: &InternalID) -> BTreeSet<InternalID> {
+        let mut found = BTreeSet::new();
+        let mut pending = vec![class.clone()];
+        while let Some(c) = pending.pop() {
+            if found.insert(c.clone()) {
+                pending.extend(self.data_subjects(&self.v.sub_class_of, &c));
+            }
+        }
+        found
+    }
+
+    fn instances(&self, class: &InternalID) -> BTreeSet<InternalID> {
+        let mut found = BTreeSet::new();
+        for c in self.subclasses(class) {
+            found.extend(self.data_subjects(&self.v.rdf_type, &c));
+        }
+        found
+    }
+
+    fn is_instance_of(&self, node: &InternalID, class: &InternalID) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut pending = self.data_objects(node, &self.v.rdf_type);
+        while let Some(c) = pending.pop() {
+            if c == *class {
+                return true;
+            }
+            if seen.insert(c.clone()) {
+                pending.extend(self.data_objects(&c, &self.v.sub_class_of));
+            }
+        }
+        false
+    }
+
+    pub fn focus_nodes(&self, shape: &InternalID) -> BTreeSet<InternalID> {
+        let mut focus = BTreeSet::new();
+        focus.extend(self.shape_values(shape, "targetNode"));
+        for class in self.shape_values(shape, "targetClass") {
+            focus.extend(self.instances(&class));
+        }
+        let is_class = self.store.search_engine_entailed(Some(self.shapes_graph.clone()), Some(shape.clone()), Some(self.v.rdf_type.clone()), Some(self.v.rdfs_class.clone())).next().is_some();
+        if is_class {
+            focus.extend(self.instances(shape));
+        }
+        for predicate in self.shape_values(shape, "targetSubjectsOf") {
+            focus.extend(self.store.search_engine_entailed(Some(self.data_graph.clone()), None, Some(predicate), None).map(|q| q.1));
+        }
+        for predicate in self.shape_values(shape, "targetObjectsOf") {
+            focus.extend(self.store.search_engine_entailed(Some(self.data_graph.clone()), None, Some(predicate), None).map(|q| q.3));
+        }
+        focus
+    }
+
+    /* Shapes with at least one target, including implicit class targets. */
+    fn targeted_shapes(&self) -> BTreeSet<InternalID> {
+        let mut shapes = BTreeSet::new();
+        for term in ["targetClass", "targetNode", "targetSubjectsOf", "targetObjectsOf"].iter() {
+            shapes.extend(self.store.search_engine_entailed(Some(self.shapes_graph.clone()), None, Some(self.v.sh(term).clone()), None).map(|q| q.1));
+        }
+        for kind in ["NodeShape", "PropertyShape"].iter() {
+            for (_, shape, _, _) in self.store.search_engine_entailed(Some(self.shapes_graph.clone()), None, Some(self.v.rdf_type.clone()), Some(self.v.sh(kind).clone())) {
+                let is_class = self.store.search_engine_entailed(Some(self.shapes_graph.clone()), Some(shape.clone()), Some(self.v.rdf_type.clone()), Some(self.v.rdfs_class.clone())).next().is_some();
+                if is_class {
+                    shapes.insert(shape);
+                }
+            }
+        }
+        shapes
+    }
+
+    pub fn validate(&self) -> Result<ValidationReport, String> {
+        let mut results = Vec::new();
+        let mut stack = ShapeStack::new();
+        for shape in self.targeted_shapes() {
+            let focus: Vec<InternalID> = self.focus_nodes(&shape).into_iter().collect();
+            results.extend(self.validate_shape(&shape, &focus, &mut stack)?);
+        }
+        Ok(ValidationReport { conforms: results.is_empty(), results })
+    }
+
+    /// Whether `node` conforms to `shape`. Recursive shape references are assumed to conform.
+    pub fn conforms(&self, node: &InternalID, shape: &InternalID, stack: &mut ShapeStack) -> Result<bool, String> {
+        let key = (shape.clone(), node.clone());
+        if stack.contains(&key) {
+            return Ok(true);
+        }
+        stack.push(key);
+        let results = self.validate_shape(shape, ::std::slice::from_ref(node), stack);
+        stack.pop();
+        Ok(results?.is_empty())
+    }
+
+    pub fn validate_shape(&self, shape: &InternalID, focus_nodes: &[InternalID], stack: &mut ShapeStack) -> Result<Vec<ValidationResult>, String> {
+        let mut results = Vec::new();
+        if self.is_true(&self.shape_value(shape, "deactivated")) {
+            return Ok(results);
+        }
+        let path_node = self.shape_value(shape, "path");
+        let path = if let Some(ref p) = path_node { Some(self.parse_path(p)?) } else { None };
+        for focus in focus_nodes.iter() {
+            let values: Vec<InternalID> = match path {
+                Some(ref p) => p.evaluate(self.store, Some(&self.data_graph), focus).into_iter().collect(),
+                None => vec![focus.clone()],
+            };
+            let context = ResultContext { shape, focus, path: path_node.as_ref(), severity: self.shape_value(shape, "severity").unwrap_or(self.v.sh("Violation").clone()), message: self.shape_value(shape, "message") };
+            self.check_value_types(&context, &values, &mut results)?;
+            self.check_cardinality_and_values(&context, path.is_some(), &values, &mut results)?;
+            self.check_pairs(&context, &values, &mut results);
+            self.check_logical(&context, &values, stack, &mut results)?;
+            self.check_closed(&context, &values, &mut results)?;
+        }
+        Ok(results)
+    }
+
+    fn check_value_types(&self, c: &ResultContext, values: &[InternalID], results: &mut Vec<ValidationResult>) -> Result<(), String> {
+        for class in self.shape_values(c.shape, "class") {
+            for v in values.iter().filter(|v| !self.is_instance_of(v, &class)) {
+                results.push(c.result(Some(v), "ClassConstraintComponent"));
+            }
+        }
+        for datatype in self.shape_values(c.shape, "datatype") {
+            let expected = self.node(&datatype);
+            for v in values.iter() {
+                let matches = match (self.literal(v), expected) {
+                    (Some(l), Some(&StoreNode::URIRef(ref d))) => l.borrow_datatype_uri() == d && literal::is_well_typed(l.borrow_lexical_form(), &d.to_string(self.store)),
+                    _ => false,
+                };
+                if !matches {
+                    results.push(c.result(Some(v), "DatatypeConstraintComponent"));
+                }
+            }
+        }
+        for kind in self.shape_values(c.shape, "nodeKind") {
+            let allowed: &[&str] = if kind == *self.v.sh("IRI") { &["IRI"] }
+                else if kind == *self.v.sh("BlankNode") { &["BlankNode"] }
+                else if kind == *self.v.sh("Literal") { &["Literal"] }
+                else if kind == *self.v.sh("BlankNodeOrIRI") { &["BlankNode", "IRI"] }
+                else if kind == *self.v.sh("BlankNodeOrLiteral") { &["BlankNode", "Literal"] }
+                else if kind == *self.v.sh("IRIOrLiteral") { &["IRI", "Literal"] }
+                else { return Err("Unknown sh:nodeKind in the shapes graph.".to_owned()); };
+            for v in values.iter() {
+                let actual = match self.node(v) {
+                    Some(&StoreNode::URIRef(_)) => "IRI",
+                    Some(&StoreNode::Blank(_)) => "BlankNode",
+                    _ => "Literal",
+                };
+                if !allowed.contains(&actual) {
+                    results.push(c.result(Some(v), "NodeKindConstraintComponent"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /* Orders two literals the way SPARQL's `<` would: numbers by value, and strings, booleans and
+       dates of the same datatype by value too. Ill-typed literals and other pairs are incomparable. */
+    fn compare(&self, a: &InternalID, b: &InternalID) -> Option<Ordering> {
+        let (la, lb) = (self.literal(a)?, self.literal(b)?);
+        let (da, db) = (la.borrow_datatype_uri().to_string(self.store), lb.borrow_datatype_uri().to_string(self.store));
+        let (xa, xb) = (la.borrow_lexical_form(), lb.borrow_lexical_form());
+        if let (Some(x), Some(y)) = (literal::numeric_value(xa, &da), literal::numeric_value(xb, &db)) {
+            return x.partial_cmp(&y);
+        }
+        if da != db || !literal::is_well_typed(xa, &da) || !literal::is_well_typed(xb, &db) {
+            return None;
+        }
+        if da == vocab::XSD_BOOLEAN {
+            let truth = |x: &str| x.trim() == "true" || x.trim() == "1";
+            Some(truth(xa).cmp(&truth(xb)))
+        } else if da == vocab::XSD_STRING || da == vocab::XSD_DATE || da == vocab::XSD_DATE_TIME || da == vocab::XSD_TIME {
+            Some(xa.cmp(xb))
+        } else {
+            None
+        }
+    }
+
+    fn check_cardinality_and_values(&self, c: &ResultContext, is_property: bool, values: &[InternalID], results: &mut Vec<ValidationResult>) -> Result<(), String> {
+        if is_property {
+            if let Some(min) = self.shape_value(c.shape, "minCount").and_then(|m| self.integer(&m)) {
+                if values.len() < min {
+                    results.push(c.result(None, "MinCountConstraintComponent"));
+                }
+            }
+            if let Some(max) = self.shape_value(c.shape, "maxCount").and_then(|m| self.integer(&m)) {
+                if values.len() > max {
+                    results.push(c.result(None, "MaxCountConstraintComponent"));
+                }
+            }
+        }
+        let ranges: [Comparison; 4] = [
+            ("minExclusive", "MinExclusiveConstraintComponent", |o| o == Ordering::Greater),
+            ("minInclusive", "MinInclusiveConstraintComponent", |o| o != Ordering::Less),
+            ("maxExclusive", "MaxExclusiveConstraintComponent", |o| o == Ordering::Less),
+            ("maxInclusive", "MaxInclusiveConstraintComponent", |o| o != Ordering::Greater),
+        ];
+        for &(term, component, accepts) in ranges.iter() {
+            for bound in self.shape_values(c.shape, term) {
+                for v in values.iter() {
+                    if !self.compare(v, &bound).map(accepts).unwrap_or(false) {
+                        results.push(c.result(Some(v), component));
+                    }
+                }
+            }
+        }
+        let lengths: [LengthBound; 2] = [
+            ("minLength", "MinLengthConstraintComponent", |len, bound| len >= bound),
+            ("maxLength", "MaxLengthConstraintComponent", |len, bound| len <= bound),
+        ];
+        for &(term, component, accepts) in lengths.iter() {
+            if let Some(bound) = self.shape_value(c.shape, term).and_then(|b| self.integer(&b)) {
+                for v in values.iter() {
+                    let ok = self.string_value(v).map(|s| accepts(s.chars().count(), bound)).unwrap_or(false);
+                    if !ok {
+                        results.push(c.result(Some(v), component));
+                    }
+                }
+            }
+        }
+        for pattern in self.shape_values(c.shape, "pattern") {
+            let source = self.string_value(&pattern).unwrap_or_default();
+            let flags = self.shape_value(c.shape, "flags").and_then(|f| self.string_value(&f)).unwrap_or_default();
+            let regex = Regex::new(&source, &flags)?;
+            for v in values.iter() {
+                if !self.string_value(v).map(|s| regex.is_match(&s)).unwrap_or(false) {
+                    results.push(c.result(Some(v), "PatternConstraintComponent"));
+                }
+            }
+        }
+        for list in self.shape_values(c.shape, "languageIn") {
+            let ranges: Vec<String> = self.list(&list)?.iter().filter_map(|r| self.string_value(r)).map(|r| r.to_lowercase()).collect();
+            for v in values.iter() {
+                let lang = self.literal(v).and_then(|l| l.borrow_lang()).map(|l| l.to_lowercase());
+                let ok = lang.map(|l| ranges.iter().any(|r| r == "*" || l == *r || l.starts_with(&format!("{}-", r)))).unwrap_or(false);
+                if !ok {
+                    results.push(c.result(Some(v), "LanguageInConstraintComponent"));
+                }
+            }
+        }
+        if is_property && self.is_true(&self.shape_value(c.shape, "uniqueLang")) {
+            let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+            for lang in values.iter().filter_map(|v| self.literal(v)).filter_map(|l| l.borrow_lang()) {
+                *counts.entry(lang.to_lowercase()).or_insert(0) += 1;
+            }
+            for _ in counts.values().filter(|&&n| n > 1) {
+                results.push(c.result(None, "UniqueLangConstraintComponent"));
+            }
+        }
+        for required in self.shape_values(c.shape, "hasValue") {
+            if !values.contains(&required) {
+                results.push(c.result(None, "HasValueConstraintComponent"));
+            }
+        }
+        for list in self.shape_values(c.shape, "in") {
+            let allowed = self.list(&list)?;
+            for v in values.iter().filter(|v| !allowed.contains(v)) {
+                results.push(c.result(Some(v), "InConstraintComponent"));
+            }
+        }
+        Ok(())
+    }
+
+    /* sh:equals, sh:disjoint, sh:lessThan and sh:lessThanOrEquals compare against another property of the focus node. */
+    fn check_pairs(&self, c: &ResultContext, values: &[InternalID], results: &mut Vec<ValidationResult>) {
+        for other in self.shape_values(c.shape, "equals") {
+            let others = self.data_objects(c.focus, &other);
+            for v in values.iter().filter(|v| !others.contains(v)) {
+                results.push(c.result(Some(v), "EqualsConstraintComponent"));
+            }
+            for o in others.iter().filter(|o| !values.contains(o)) {
+                results.push(c.result(Some(o), "EqualsConstraintComponent"));
+            }
+        }
+        for other in self.shape_values(c.shape, "disjoint") {
+            let others = self.data_objects(c.focus, &other);
+            for v in values.iter().filter(|v| others.contains(v)) {
+                results.push(c.result(Some(v), "DisjointConstraintComponent"));
+            }
+        }
+        let orders: [Comparison; 2] = [
+            ("lessThan", "LessThanConstraintComponent", |o| o == Ordering::Less),
+            ("lessThanOrEquals", "LessThanOrEqualsConstraintComponent", |o| o != Ordering::Greater),
+        ];
+        for &(term, component, accepts) in orders.iter() {
+            for other in self.shape_values(c.shape, term) {
+                let others = self.data_objects(c.focus, &other);
+                for v in values.iter() {
+                    if others.iter().any(|o| !self.compare(v, o).map(accepts).unwrap_or(false)) {
+                        results.push(c.result(Some(v), component));
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_logical(&self, c: &ResultContext, values: &[InternalID], stack: &mut ShapeStack, results: &mut Vec<ValidationResult>) -> Result<(), String> {
+        for negated in self.shape_values(c.shape, "not") {
+            for v in values.iter() {
+                if self.conforms(v, &negated, stack)? {
+                    results.push(c.result(Some(v), "NotConstraintComponent"));
+                }
+            }
+        }
+        let combinations: [(&str, &str); 3] = [("and", "AndConstraintComponent"), ("or", "OrConstraintComponent"), ("xone", "XoneConstraintComponent")];
+        for &(term, component) in combinations.iter() {
+            for list in self.shape_values(c.shape, term) {
+                let members = self.list(&list)?;
+                for v in values.iter() {
+                    let mut conforming = 0;
+                    for member in members.iter() {
+                        if self.conforms(v, member, stack)? {
+                            conforming += 1;
+                        }
+                    }
+                    let ok = match term {
+                        "and" => conforming == members.len(),
+                        "or" => conforming > 0,
+                        _ => conforming == 1,
+                    };
+                    if !ok {
+                        results.push(c.result(Some(v), component));
+                    }
+                }
+            }
+        }
+        for node_shape in self.shape_values(c.shape, "node") {
+            for v in values.iter() {
+                if !self.conforms(v, &node_shape, stack)? {
+                    results.push(c.result(Some(v), "NodeConstraintComponent"));
+                }
+            }
+        }
+        for property_shape in self.shape_values(c.shape, "property") {
+            results.extend(self.validate_shape(&property_shape, values, stack)?);
+        }
+        Ok(())
+    }
+
+    fn check_closed(&self, c: &ResultContext, values: &[InternalID], results: &mut Vec<ValidationResult>) -> Result<(), String> {
+        if !self.is_true(&self.shape_value(c.shape, "closed")) {
+            return Ok(());
+        }
+        let mut allowed: BTreeSet<InternalID> = self.shape_values(c.shape, "property").iter()
+            .filter_map(|p| self.shape_value(p, "path"))
+            .filter(|p| matches!(self.node(p), Some(&StoreNode::URIRef(_))))
+            .collect();
+        for list in self.shape_values(c.shape, "ignoredProperties") {
+            allowed.extend(self.list(&list)?);
+        }
+        for v in values.iter() {
+            for (_, _, p, o) in self.store.search_engine_entailed(Some(self.data_graph.clone()), Some(v.clone()), None, None) {
+                if !allowed.contains(&p) {
+                    let mut result = c.result(Some(&o), "ClosedConstraintComponent");
+                    result.focus_node = v.clone();
+                    result.result_path = Some(p);
+                    results.push(result);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct ResultContext<'c> {
+    shape: &'c InternalID,
+    focus: &'c InternalID,
+    path: Option<&'c InternalID>,
+    severity: InternalID,
+    message: Option<InternalID>,
+}
+
+impl<'c> ResultContext<'c> {
+    fn result(&self, value: Option<&InternalID>, component: &'static str) -> ValidationResult {
+        ValidationResult {
+            focus_node: self.focus.clone(),
+            result_path: self.path.cloned(),
+            value: value.cloned(),
+            source_shape: self.shape.clone(),
+            source_constraint_component: component,
+            severity: self.severity.clone(),
+            message: self.message.clone(),
+        }
+    }
+}
+
+pub fn validate(store: &mut StorageEngine, shapes_graph: &GraphID, data_graph: &GraphID) -> Result<ValidationReport, String> {
+    let vocabulary = ShaclVocabulary::intern(store)?;
+    let validator = Validator::new(store, vocabulary, shapes_graph.clone(), data_graph.clone());
+    validator.validate()
+}
+
+/// Writes `report` into `graph` as a `sh:ValidationReport` with one `sh:ValidationResult` per result,
+/// and returns the report's blank node.
+pub fn write_report(store: &mut StorageEngine, graph: &GraphID, report: &ValidationReport) -> Result<InternalID, String> {
+    let v = ShaclVocabulary::intern(store)?;
+    let report_node = store.find_or_add_internal_id(StoreNode::Blank(BlankNode::new(None)))?;
+    let conforms = Literal::new(store, if report.conforms { "true" } else { "false" }, Some(vocab::XSD_BOOLEAN), None);
+    let conforms_id = store.find_or_add_internal_id(StoreNode::Literal(conforms))?;
+    store.add_internal_quad(graph.clone(), report_node.clone(), v.rdf_type.clone(), v.sh("ValidationReport").clone());
+    store.add_internal_quad(graph.clone(), report_node.clone(), v.sh("conforms").clone(), conforms_id);
+    for result in report.results.iter() {
+        let node = store.find_or_add_internal_id(StoreNode::Blank(BlankNode::new(None)))?;
+        let component = store.uri_str_to_internal_id(&format!("{}{}", vocab::SHACL_NAMESPACE, result.source_constraint_component))?;
+        store.add_internal_quad(graph.clone(), report_node.clone(), v.sh("result").clone(), node.clone());
+        store.add_internal_quad(graph.clone(), node.clone(), v.rdf_type.clone(), v.sh("ValidationResult").clone());
+        store.add_internal_quad(graph.clone(), node.clone(), v.sh("focusNode").clone(), result.focus_node.clone());
+        store.add_internal_quad(graph.clone(), node.clone(), v.sh("sourceShape").clone(), result.source_shape.clone());
+        store.add_internal_quad(graph.clone(), node.clone(), v.sh("sourceConstraintComponent").clone(), component);
+        store.add_internal_quad(graph.clone(), node.clone(), v.sh("resultSeverity").clone(), result.severity.clone());
+        if let Some(ref path) = result.result_path {
+            store.add_internal_quad(graph.clone(), node.clone(), v.sh("resultPath").clone(), path.clone());
+        }
+        if let Some(ref value) = result.value {
+            store.add_internal_quad(graph.clone(), node.clone(), v.sh("value").clone(), value.clone());
+        }
+        if let Some(ref message) = result.message {
+            store.add_internal_quad(graph.clone(), node.clone(), v.sh("resultMessage").clone(), message.clone());
+        }
+    }
+    Ok(report_node)
+}
@@ -0,0 +1,1050 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use iri::IriRef;
+use lexer::{Token, TokenStream};
+use literal::{self, Literal};
+use blank::BlankNode;
+use regex::Regex;
+use store::{StorageEngine, StoreNode, GraphID};
+use vocab;
+
+/// Bound on the steps one `Schema::validate` call spends matching neighbourhoods to triple
+/// expressions, where the search for an assignment can otherwise grow exponentially.
+pub static DEFAULT_WORK_LIMIT: usize = 1000000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeKind {
+    Iri,
+    BNode,
+    Literal,
+    NonLiteral,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueSetValue {
+    Value(InternalID),
+    IriStem(String),
+    Language(String),
+    LanguageStem(String),
+}
+
+#[derive(Clone, Debug)]
+pub enum Facet {
+    Length(usize),
+    MinLength(usize),
+    MaxLength(usize),
+    Pattern(Regex),
+    MinInclusive(f64),
+    MinExclusive(f64),
+    MaxInclusive(f64),
+    MaxExclusive(f64),
+    TotalDigits(usize),
+    FractionDigits(usize),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct NodeConstraint {
+    pub kind: Option<NodeKind>,
+    pub datatype: Option<InternalID>,
+    pub values: Option<Vec<ValueSetValue>>,
+    pub facets: Vec<Facet>,
+}
+
+/// A triple constraint. `id` is unique within the schema and is used to count matched triples.
+#[derive(Clone, Debug)]
+pub struct TripleConstraint {
+    pub id: usize,
+    pub predicate: InternalID,
+    pub inverse: bool,
+    pub value: Option<Box<ShapeExpr>>,
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TripleExpr {
+    EachOf(Vec<TripleExpr>, usize, Option<usize>),
+    OneOf(Vec<TripleExpr>, usize, Option<usize>),
+    Constraint(TripleConstraint),
+    /* `&label`, only present until the schema is fully parsed. */
+    Include(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Shape {
+    pub closed: bool,
+    pub extra: Vec<InternalID>,
+    pub expression: Option<TripleExpr>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ShapeExpr {
+    And(Vec<ShapeExpr>),
+    Or(Vec<ShapeExpr>),
+    Not(Box<ShapeExpr>),
+    Ref(String),
+    NodeConstraint(NodeConstraint),
+    Shape(Shape),
+    Anything,
+}
+
+/// The shape a node is checked against: the schema's `start` or a labelled shape. Labels are
+/// full IRIs, or `_:name` for blank node labels.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ShapeSelector {
+    Start,
+    Label(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeSelector {
+    Node(InternalID),
+    /* `{FOCUS p o}`, or `{FOCUS p _}` when the object is `None`. */
+    SubjectsOf(InternalID, Option<InternalID>),
+    /* `{s p FOCUS}`, or `{_ p FOCUS}` when the subject is `None`. */
+    ObjectsOf(Option<InternalID>, InternalID),
+}
+
+/// A query shape map: which nodes to check against which shapes.
+#[derive(Clone, Debug, Default)]
+pub struct ShapeMap {
+    associations: Vec<(NodeSelector, ShapeSelector)>,
+}
+
+impl ShapeMap {
+    pub fn new() -> ShapeMap {
+        ShapeMap::default()
+    }
+
+    pub fn add(&mut self, node: NodeSelector, shape: ShapeSelector) {
+        self.associations.push((node, shape));
+    }
+
+    pub fn borrow_associations<'a>(&'a self) -> &'a [(NodeSelector, ShapeSelector)] {
+        &self.associations
+    }
+}
+
+/// One entry of a result shape map.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeMapEntry {
+    pub node: InternalID,
+    pub shape: ShapeSelector,
+    pub conformant: bool,
+}
+
+/// A parsed ShExC schema. IRIs and literals it mentions are interned into the store it was parsed with.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    shapes: BTreeMap<String, ShapeExpr>,
+    start: Option<ShapeExpr>,
+    prefixes: BTreeMap<String, String>,
+}
+
+struct ShexParser<'s> {
+    tokens: TokenStream,
+    store: &'s mut StorageEngine,
+    prefixes: BTreeMap<String, String>,
+    base: Option<String>,
+    next_constraint: usize,
+    triple_labels: BTreeMap<String, TripleExpr>,
+}
+
+impl<'s> ShexParser<'s> {
+    fn resolve_prefixed(&self, prefix: &str, local: &str) -> Result<String, String> {
+        if let Some(namespace) = self.prefixes.get(prefix) {
+            return Ok(format!("{}{}", namespace, local));
+        }
+        self.store.borrow_namespace_manager().expand_curie(&format!("{}:{}", prefix, local))
+    }
+
+    fn resolve_relative(&self, iri: String) -> String {
+        match self.base {
+            Some(ref base) if !iri.contains(':') => format!("{}{}", base, iri),
+            _ => iri,
+        }
+    }
+
+    fn is_iri(&self) -> bool {
+        matches!(self.tokens.peek(), Some(&Token::IriRef(_)) | Some(&Token::PrefixedName(..)))
+    }
+
+    fn iri(&mut self) -> Result<String, String> {
+        match self.tokens.next() {
+            Some(Token::IriRef(iri)) => Ok(self.resolve_relative(iri)),
+            Some(Token::PrefixedName(prefix, local)) => self.resolve_prefixed(&prefix, &local),
+            _ => self.tokens.error("Expected an IRI"),
+        }
+    }
+
+    fn label(&mut self) -> Result<String, String> {
+        if let Some(Token::Blank(name)) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            return Ok(format!("_:{}", name));
+        }
+        self.iri()
+    }
+
+    fn predicate(&mut self) -> Result<InternalID, String> {
+        if self.tokens.eat_word("a") {
+            return self.store.uri_str_to_internal_id(vocab::RDF_TYPE);
+        }
+        let iri = self.iri()?;
+        self.store.uri_str_to_internal_id(&iri)
+    }
+
+    fn literal(&mut self, lexical_form: &str, datatype: Option<&str>, lang: Option<&str>) -> Result<InternalID, String> {
+        if let Some(datatype) = datatype {
+            if lang.is_some() {
+                return self.tokens.error("A literal cannot have both a language tag and a datatype");
+            }
+            IriRef::parse_absolute(datatype)?;
+        }
+        let literal = Literal::new(self.store, lexical_form, datatype, lang);
+        self.store.find_or_add_internal_id(StoreNode::Literal(literal))
+    }
+
+    /* A literal value, or `None` if the next token does not start one. */
+    fn literal_value(&mut self) -> Result<Option<InternalID>, String> {
+        let token = if let Some(t) = self.tokens.peek().cloned() { t } else { return Ok(None); };
+        let id = match token {
+            Token::Integer(n) => { self.tokens.next(); self.literal(&n, Some(vocab::XSD_INTEGER), None)? }
+            Token::Decimal(n) => { self.tokens.next(); self.literal(&n, Some(vocab::XSD_DECIMAL), None)? }
+            Token::Double(n) => { self.tokens.next(); self.literal(&n, Some(vocab::XSD_DOUBLE), None)? }
+            Token::Word(ref w) if w == "true" || w == "false" => { self.tokens.next(); self.literal(w, Some(vocab::XSD_BOOLEAN), None)? }
+            Token::Str(value) => {
+                self.tokens.next();
+                if let Some(Token::LangTag(lang)) = self.tokens.peek().cloned() {
+                    self.tokens.next();
+                    self.literal(&value, None, Some(&lang))?
+                } else if self.tokens.eat_punct("^^") {
+                    let datatype = self.iri()?;
+                    self.literal(&value, Some(&datatype), None)?
+                } else {
+                    self.literal(&value, None, None)?
+                }
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(id))
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        match self.tokens.next() {
+            Some(Token::Integer(n)) | Some(Token::Decimal(n)) | Some(Token::Double(n)) => n.parse().map_err(|_| format!("Invalid number {}.", n)),
+            _ => self.tokens.error("Expected a number"),
+        }
+    }
+
+    fn integer(&mut self) -> Result<usize, String> {
+        match self.tokens.next() {
+            Some(Token::Integer(n)) => n.parse().map_err(|_| format!("Invalid integer {}.", n)),
+            _ => self.tokens.error("Expected an integer"),
+        }
+    }
+
+    fn schema(&mut self) -> Result<(BTreeMap<String, ShapeExpr>, Option<ShapeExpr>), String> {
+        let mut shapes = BTreeMap::new();
+        let mut start = None;
+        while !self.tokens.is_at_end() {
+            if self.tokens.eat_word("prefix") {
+                let prefix = match self.tokens.next() {
+                    Some(Token::PrefixedName(ref prefix, ref local)) if local.is_empty() => prefix.clone(),
+                    _ => return self.tokens.error("Expected a prefix name"),
+                };
+                let namespace = match self.tokens.next() {
+                    Some(Token::IriRef(iri)) => iri,
+                    _ => return self.tokens.error("Expected a namespace IRI"),
+                };
+                self.prefixes.insert(prefix, namespace);
+            } else if self.tokens.eat_word("base") {
+                self.base = match self.tokens.next() {
+                    Some(Token::IriRef(iri)) => Some(iri),
+                    _ => return self.tokens.error("Expected a base IRI"),
+                };
+            } else if self.tokens.is_word("import") {
+                return self.tokens.error("Imports and semantic actions are not supported");
+            } else if self.tokens.eat_word("start") {
+                self.tokens.expect_punct("=")?;
+                start = Some(self.shape_expression()?);
+            } else {
+                let label = self.label()?;
+                if self.tokens.is_word("external") {
+                    return self.tokens.error("External shapes are not supported");
+                }
+                let expression = self.shape_expression()?;
+                if shapes.insert(label.clone(), expression).is_some() {
+                    return Err(format!("Shape {} is declared twice.", label));
+                }
+            }
+        }
+        Ok((shapes, start))
+    }
+
+    fn shape_expression(&mut self) -> Result<ShapeExpr, String> {
+        let mut options = vec![self.shape_and()?];
+        while self.tokens.eat_word("or") {
+            options.push(self.shape_and()?);
+        }
+        Ok(if options.len() == 1 { options.pop().unwrap() } else { ShapeExpr::Or(options) })
+    }
+
+    fn shape_and(&mut self) -> Result<ShapeExpr, String> {
+        let mut parts = vec![self.shape_not()?];
+        while self.tokens.eat_word("and") {
+            parts.push(self.shape_not()?);
+        }
+        Ok(if parts.len() == 1 { parts.pop().unwrap() } else { ShapeExpr::And(parts) })
+    }
+
+    fn shape_not(&mut self) -> Result<ShapeExpr, String> {
+        if self.tokens.eat_word("not") {
+            return Ok(ShapeExpr::Not(Box::new(self.shape_atom()?)));
+        }
+        self.shape_atom()
+    }
+
+    fn starts_shape(&self) -> bool {
+        self.tokens.is_word("closed") || self.tokens.is_word("extra") || self.tokens.is_punct("@") ||
+            (self.tokens.is_punct("{") && !self.starts_cardinality())
+    }
+
+    /* `{m,n}` after a value expression is a cardinality, `{ ... }` a shape. */
+    fn starts_cardinality(&self) -> bool {
+        if let Some(&Token::Integer(_)) = self.tokens.peek_at(1) { self.tokens.is_punct("{") } else { false }
+    }
+
+    fn shape_or_ref(&mut self) -> Result<ShapeExpr, String> {
+        if self.tokens.eat_punct("@") {
+            return Ok(ShapeExpr::Ref(self.label()?));
+        }
+        Ok(ShapeExpr::Shape(self.shape_definition()?))
+    }
+
+    fn shape_atom(&mut self) -> Result<ShapeExpr, String> {
+        if self.tokens.eat_punct("(") {
+            let inner = self.shape_expression()?;
+            self.tokens.expect_punct(")")?;
+            return Ok(inner);
+        }
+        if self.tokens.eat_punct(".") {
+            return Ok(ShapeExpr::Anything);
+        }
+        if self.starts_shape() {
+            return self.shape_or_ref();
+        }
+        let constraint = self.node_constraint()?;
+        let non_literal = matches!(constraint.kind, Some(NodeKind::Iri) | Some(NodeKind::BNode) | Some(NodeKind::NonLiteral));
+        if non_literal && self.starts_shape() {
+            return Ok(ShapeExpr::And(vec![ShapeExpr::NodeConstraint(constraint), self.shape_or_ref()?]));
+        }
+        Ok(ShapeExpr::NodeConstraint(constraint))
+    }
+
+    fn node_constraint(&mut self) -> Result<NodeConstraint, String> {
+        let mut constraint = NodeConstraint::default();
+        let kinds = [("literal", NodeKind::Literal), ("iri", NodeKind::Iri), ("bnode", NodeKind::BNode), ("nonliteral", NodeKind::NonLiteral)];
+        if let Some(&(_, kind)) = kinds.iter().find(|&&(word, _)| self.tokens.is_word(word)) {
+            self.tokens.next();
+            constraint.kind = Some(kind);
+        } else if self.is_iri() {
+            let datatype = self.iri()?;
+            constraint.datatype = Some(self.store.uri_str_to_internal_id(&datatype)?);
+        } else if self.tokens.eat_punct("[") {
+            constraint.values = Some(self.value_set()?);
+        }
+        self.facets(&mut constraint.facets)?;
+        if constraint.kind.is_none() && constraint.datatype.is_none() && constraint.values.is_none() && constraint.facets.is_empty() {
+            return self.tokens.error("Expected a shape expression");
+        }
+        Ok(constraint)
+    }
+
+    fn value_set(&mut self) -> Result<Vec<ValueSetValue>, String> {
+        let mut values = Vec::new();
+        while !self.tokens.eat_punct("]") {
+            if self.tokens.is_punct("-") || self.tokens.is_punct(".") {
+                return self.tokens.error("Value set exclusions are not supported");
+            }
+            if self.is_iri() {
+                let iri = self.iri()?;
+                if self.tokens.eat_punct("~") {
+                    values.push(ValueSetValue::IriStem(iri));
+                } else {
+                    values.push(ValueSetValue::Value(self.store.uri_str_to_internal_id(&iri)?));
+                }
+            } else if let Some(Token::LangTag(tag)) = self.tokens.peek().cloned() {
+                self.tokens.next();
+                if self.tokens.eat_punct("~") {
+                    values.push(ValueSetValue::LanguageStem(tag));
+                } else {
+                    values.push(ValueSetValue::Language(tag));
+                }
+            } else if let Some(id) = self.literal_value()? {
+                values.push(ValueSetValue::Value(id));
+            } else {
+                return self.tokens.error("Expected a value set value");
+            }
+        }
+        Ok(values)
+    }
+
+    fn facets(&mut self, facets: &mut Vec<Facet>) -> Result<(), String> {
+        loop {
+            if let Some(Token::Regex(pattern, flags)) = self.tokens.peek().cloned() {
+                self.tokens.next();
+                facets.push(Facet::Pattern(Regex::new(&pattern, &flags)?));
+            } else if self.tokens.eat_word("pattern") {
+                let pattern = match self.tokens.next() {
+                    Some(Token::Str(p)) => p,
+                    _ => return self.tokens.error("Expected a pattern string"),
+                };
+                facets.push(Facet::Pattern(Regex::new(&pattern, "")?));
+            } else if self.tokens.eat_word("length") {
+                facets.push(Facet::Length(self.integer()?));
+            } else if self.tokens.eat_word("minlength") {
+                facets.push(Facet::MinLength(self.integer()?));
+            } else if self.tokens.eat_word("maxlength") {
+                facets.push(Facet::MaxLength(self.integer()?));
+            } else if self.tokens.eat_word("mininclusive") {
+                facets.push(Facet::MinInclusive(self.number()?));
+            } else if self.tokens.eat_word("minexclusive") {
+                facets.push(Facet::MinExclusive(self.number()?));
+            } else if self.tokens.eat_word("maxinclusive") {
+                facets.push(Facet::MaxInclusive(self.number()?));
+            } else if self.tokens.eat_word("maxexclusive") {
+                facets.push(Facet::MaxExclusive(self.number()?));
+            } else if self.tokens.eat_word("totaldigits") {
+                facets.push(Facet::TotalDigits(self.integer()?));
+            } else if self.tokens.eat_word("fractiondigits") {
+                facets.push(Facet::FractionDigits(self.integer()?));
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn shape_definition(&mut self) -> Result<Shape, String> {
+        let mut shape = Shape { closed: false, extra: Vec::new(), expression: None };
+        loop {
+            if self.tokens.eat_word("closed") {
+                shape.closed = true;
+            } else if self.tokens.eat_word("extra") {
+                while self.is_iri() || self.tokens.is_word("a") {
+                    shape.extra.push(self.predicate()?);
+                }
+            } else {
+                break;
+            }
+        }
+        self.tokens.expect_punct("{")?;
+        if !self.tokens.is_punct("}") {
+            shape.expression = Some(self.triple_expression()?);
+        }
+        self.tokens.expect_punct("}")?;
+        Ok(shape)
+    }
+
+    fn triple_expression(&mut self) -> Result<TripleExpr, String> {
+        let mut options = vec![self.each_of()?];
+        while self.tokens.eat_punct("|") {
+            options.push(self.each_of()?);
+        }
+        Ok(if options.len() == 1 { options.pop().unwrap() } else { TripleExpr::OneOf(options, 1, Some(1)) })
+    }
+
+    fn each_of(&mut self) -> Result<TripleExpr, String> {
+        let mut parts = vec![self.unary_triple_expression()?];
+        while self.tokens.eat_punct(";") {
+            if self.tokens.is_punct("}") || self.tokens.is_punct(")") || self.tokens.is_punct("|") {
+                break;
+            }
+            parts.push(self.unary_triple_expression()?);
+        }
+        Ok(if parts.len() == 1 { parts.pop().unwrap() } else { TripleExpr::EachOf(parts, 1, Some(1)) })
+    }
+
+    fn cardinality(&mut self) -> Result<(usize, Option<usize>), String> {
+        if self.tokens.eat_punct("*") {
+            return Ok((0, None));
+        }
+        if self.tokens.eat_punct("+") {
+            return Ok((1, None));
+        }
+        if self.tokens.eat_punct("?") {
+            return Ok((0, Some(1)));
+        }
+        if self.starts_cardinality() {
+            self.tokens.next();
+            let min = self.integer()?;
+            let max = if self.tokens.eat_punct(",") {
+                if self.tokens.eat_punct("*") || self.tokens.is_punct("}") { None } else { Some(self.integer()?) }
+            } else {
+                Some(min)
+            };
+            self.tokens.expect_punct("}")?;
+            return Ok((min, max));
+        }
+        Ok((1, Some(1)))
+    }
+
+    fn unary_triple_expression(&mut self) -> Result<TripleExpr, String> {
+        let label = if self.tokens.eat_punct("$") { Some(self.label()?) } else { None };
+        if label.is_none() && self.tokens.eat_punct("&") {
+            return Ok(TripleExpr::Include(self.label()?));
+        }
+        let expression = if self.tokens.eat_punct("(") {
+            let inner = self.triple_expression()?;
+            self.tokens.expect_punct(")")?;
+            let (min, max) = self.cardinality()?;
+            match inner {
+                TripleExpr::EachOf(parts, 1, Some(1)) => TripleExpr::EachOf(parts, min, max),
+                TripleExpr::OneOf(options, 1, Some(1)) => TripleExpr::OneOf(options, min, max),
+                other => if (min, max) == (1, Some(1)) { other } else { TripleExpr::EachOf(vec![other], min, max) },
+            }
+        } else {
+            let inverse = self.tokens.eat_punct("^");
+            let predicate = self.predicate()?;
+            let value = match self.shape_expression()? {
+                ShapeExpr::Anything => None,
+                other => Some(Box::new(other)),
+            };
+            let (min, max) = self.cardinality()?;
+            self.next_constraint += 1;
+            TripleExpr::Constraint(TripleConstraint { id: self.next_constraint, predicate, inverse, value, min, max })
+        };
+        if let Some(l) = label {
+            self.triple_labels.insert(l, expression.clone());
+        }
+        Ok(expression)
+    }
+
+    /* Inlines `&label` includes, renumbering constraints so every occurrence is counted separately. */
+    fn resolve_includes(&mut self, expression: &TripleExpr, depth: usize) -> Result<TripleExpr, String> {
+        if depth > 64 {
+            return Err("Triple expression includes are nested too deeply or cyclic.".to_owned());
+        }
+        Ok(match expression {
+            &TripleExpr::EachOf(ref parts, min, max) => TripleExpr::EachOf(
+                parts.iter().map(|p| self.resolve_includes(p, depth)).collect::<Result<Vec<_>, String>>()?, min, max),
+            &TripleExpr::OneOf(ref options, min, max) => TripleExpr::OneOf(
+                options.iter().map(|o| self.resolve_includes(o, depth)).collect::<Result<Vec<_>, String>>()?, min, max),
+            &TripleExpr::Constraint(ref c) => {
+                self.next_constraint += 1;
+                TripleExpr::Constraint(TripleConstraint { id: self.next_constraint, ..c.clone() })
+            }
+            &TripleExpr::Include(ref label) => {
+                let included = self.triple_labels.get(label).cloned()
+                    .ok_or_else(|| format!("Unknown triple expression label {}.", label))?;
+                self.resolve_includes(&included, depth + 1)?
+            }
+        })
+    }
+
+    fn resolve_shape_includes(&mut self, expression: &mut ShapeExpr) -> Result<(), String> {
+        match expression {
+            &mut ShapeExpr::And(ref mut parts) | &mut ShapeExpr::Or(ref mut parts) => {
+                for part in parts.iter_mut() {
+                    self.resolve_shape_includes(part)?;
+                }
+            }
+            &mut ShapeExpr::Not(ref mut inner) => self.resolve_shape_includes(inner)?,
+            &mut ShapeExpr::Shape(ref mut shape) => {
+                if let Some(e) = shape.expression.take() {
+                    let mut resolved = self.resolve_includes(&e, 0)?;
+                    self.resolve_value_includes(&mut resolved)?;
+                    shape.expression = Some(resolved);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn resolve_value_includes(&mut self, expression: &mut TripleExpr) -> Result<(), String> {
+        match expression {
+            &mut TripleExpr::EachOf(ref mut parts, _, _) | &mut TripleExpr::OneOf(ref mut parts, _, _) => {
+                for part in parts.iter_mut() {
+                    self.resolve_value_includes(part)?;
+                }
+            }
+            &mut TripleExpr::Constraint(ref mut c) => {
+                if let Some(ref mut value) = c.value {
+                    self.resolve_shape_includes(value)?;
+                }
+            }
+            &mut TripleExpr::Include(_) => {}
+        }
+        Ok(())
+    }
+
+    fn node_selector(&mut self) -> Result<NodeSelector, String> {
+        if self.tokens.eat_punct("{") {
+            let selector = if self.tokens.eat_word("focus") {
+                let predicate = self.predicate()?;
+                let object = if self.tokens.eat_word("_") { None } else { Some(self.term()?) };
+                NodeSelector::SubjectsOf(predicate, object)
+            } else {
+                let subject = if self.tokens.eat_word("_") { None } else { Some(self.term()?) };
+                let predicate = self.predicate()?;
+                self.tokens.expect_word("focus")?;
+                NodeSelector::ObjectsOf(subject, predicate)
+            };
+            self.tokens.expect_punct("}")?;
+            return Ok(selector);
+        }
+        Ok(NodeSelector::Node(self.term()?))
+    }
+
+    fn term(&mut self) -> Result<InternalID, String> {
+        if self.is_iri() {
+            let iri = self.iri()?;
+            return self.store.uri_str_to_internal_id(&iri);
+        }
+        if let Some(Token::Blank(name)) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            return self.store.find_internal_id(&StoreNode::Blank(BlankNode::new(Some(&name))))
+                .map_err(|_| format!("Unknown blank node _:{}.", name));
+        }
+        if let Some(id) = self.literal_value()? {
+            return Ok(id);
+        }
+        self.tokens.error("Expected a node")
+    }
+
+    fn shape_map(&mut self) -> Result<ShapeMap, String> {
+        let mut map = ShapeMap::new();
+        while !self.tokens.is_at_end() {
+            let node = self.node_selector()?;
+            let shape = match self.tokens.peek().cloned() {
+                Some(Token::LangTag(ref tag)) if tag.eq_ignore_ascii_case("start") => { self.tokens.next(); ShapeSelector::Start }
+                _ => {
+                    self.tokens.expect_punct("@")?;
+                    ShapeSelector::Label(self.label()?)
+                }
+            };
+            map.add(node, shape);
+            if !self.tokens.eat_punct(",") {
+                break;
+            }
+        }
+        if !self.tokens.is_at_end() {
+            return self.tokens.error("Expected \",\" between shape map entries");
+        }
+        Ok(map)
+    }
+}
+
+impl Schema {
+    /// Parses a ShExC schema. Prefixes come from `PREFIX` declarations, falling back to the store's
+    /// namespace bindings. Imports, external shapes, semantic actions and value set exclusions are
+    /// not supported.
+    pub fn parse(store: &mut StorageEngine, text: &str) -> Result<Schema, String> {
+        let mut parser = ShexParser { tokens: TokenStream::with_regex_literals(text)?, store, prefixes: BTreeMap::new(),
+            base: None, next_constraint: 0, triple_labels: BTreeMap::new() };
+        let (mut shapes, mut start) = parser.schema()?;
+        for expression in shapes.values_mut() {
+            parser.resolve_shape_includes(expression)?;
+        }
+        if let Some(ref mut expression) = start {
+            parser.resolve_shape_includes(expression)?;
+        }
+        let schema = Schema { shapes, start, prefixes: parser.prefixes };
+        schema.check_references()?;
+        schema.check_negation()?;
+        Ok(schema)
+    }
+
+    pub fn borrow_shapes<'a>(&'a self) -> &'a BTreeMap<String, ShapeExpr> {
+        &self.shapes
+    }
+
+    pub fn borrow_start<'a>(&'a self) -> Option<&'a ShapeExpr> {
+        self.start.as_ref()
+    }
+
+    fn check_references(&self) -> Result<(), String> {
+        fn visit(schema: &Schema, expression: &ShapeExpr) -> Result<(), String> {
+            match expression {
+                &ShapeExpr::And(ref parts) | &ShapeExpr::Or(ref parts) => parts.iter().try_for_each(|p| visit(schema, p)),
+                &ShapeExpr::Not(ref inner) => visit(schema, inner),
+                &ShapeExpr::Ref(ref label) if !schema.shapes.contains_key(label) => Err(format!("Reference to undeclared shape {}.", label)),
+                &ShapeExpr::Shape(ref shape) => shape.expression.as_ref().map(|e| visit_triples(schema, e)).unwrap_or(Ok(())),
+                _ => Ok(()),
+            }
+        }
+        fn visit_triples(schema: &Schema, expression: &TripleExpr) -> Result<(), String> {
+            match expression {
+                &TripleExpr::EachOf(ref parts, _, _) | &TripleExpr::OneOf(ref parts, _, _) => parts.iter().try_for_each(|p| visit_triples(schema, p)),
+                &TripleExpr::Constraint(ref c) => c.value.as_ref().map(|v| visit(schema, v)).unwrap_or(Ok(())),
+                &TripleExpr::Include(_) => Ok(()),
+            }
+        }
+        for expression in self.shapes.values().chain(self.start.as_ref()) {
+            visit(self, expression)?;
+        }
+        Ok(())
+    }
+
+    /* Rejects schemas where a shape depends on itself through a negation, which has no well-defined
+       meaning: each reference is followed to see whether it leads back to where a NOT was crossed. */
+    fn check_negation(&self) -> Result<(), String> {
+        fn visit(expression: &ShapeExpr, negated: bool, edges: &mut Vec<(String, bool)>) {
+            match expression {
+                &ShapeExpr::And(ref parts) | &ShapeExpr::Or(ref parts) => parts.iter().for_each(|p| visit(p, negated, edges)),
+                &ShapeExpr::Not(ref inner) => visit(inner, true, edges),
+                &ShapeExpr::Ref(ref label) => edges.push((label.clone(), negated)),
+                &ShapeExpr::Shape(ref shape) => shape.expression.iter().for_each(|e| visit_triples(e, negated, edges)),
+                _ => {}
+            }
+        }
+        fn visit_triples(expression: &TripleExpr, negated: bool, edges: &mut Vec<(String, bool)>) {
+            match expression {
+                &TripleExpr::EachOf(ref parts, _, _) | &TripleExpr::OneOf(ref parts, _, _) => parts.iter().for_each(|p| visit_triples(p, negated, edges)),
+                &TripleExpr::Constraint(ref c) => c.value.iter().for_each(|v| visit(v, negated, edges)),
+                &TripleExpr::Include(_) => {}
+            }
+        }
+        let mut dependencies: BTreeMap<&str, Vec<(String, bool)>> = BTreeMap::new();
+        for (label, expression) in self.shapes.iter() {
+            let mut edges = Vec::new();
+            visit(expression, false, &mut edges);
+            dependencies.insert(label, edges);
+        }
+        let reaches = |from: &str, to: &str| {
+            let mut seen: BTreeSet<&str> = BTreeSet::new();
+            let mut agenda = vec![from];
+            while let Some(label) = agenda.pop() {
+                if label == to {
+                    return true;
+                }
+                if seen.insert(label) {
+                    agenda.extend(dependencies.get(label).into_iter().flat_map(|edges| edges.iter().map(|e| e.0.as_str())));
+                }
+            }
+            false
+        };
+        for (label, edges) in dependencies.iter() {
+            if let Some(&(ref target, _)) = edges.iter().find(|&&(ref target, negated)| negated && reaches(target, label)) {
+                return Err(format!("Shape {} depends on itself through a negation, via {}.", label, target));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a query shape map such as `<n1>@<S>, {FOCUS a ex:C}@START`, using the schema's prefixes.
+    pub fn parse_shape_map(&self, store: &mut StorageEngine, text: &str) -> Result<ShapeMap, String> {
+        let mut parser = ShexParser { tokens: TokenStream::new(text)?, store, prefixes: self.prefixes.clone(),
+            base: None, next_constraint: 0, triple_labels: BTreeMap::new() };
+        parser.shape_map()
+    }
+
+    /// Checks every node the shape map selects in `graph` and returns the result shape map. Fails
+    /// if matching takes more than `DEFAULT_WORK_LIMIT` steps.
+    pub fn validate(&self, store: &StorageEngine, graph: &GraphID, map: &ShapeMap) -> Result<Vec<ShapeMapEntry>, String> {
+        let validator = ShexValidator { store, graph: graph.clone(), schema: self, work: Cell::new(0) };
+        let mut entries = Vec::new();
+        for &(ref selector, ref shape) in map.borrow_associations().iter() {
+            let nodes: BTreeSet<InternalID> = match selector {
+                &NodeSelector::Node(ref n) => Some(n.clone()).into_iter().collect(),
+                &NodeSelector::SubjectsOf(ref p, ref o) => store.search_engine_entailed(Some(graph.clone()), None, Some(p.clone()), o.clone()).map(|q| q.1).collect(),
+                &NodeSelector::ObjectsOf(ref s, ref p) => store.search_engine_entailed(Some(graph.clone()), s.clone(), Some(p.clone()), None).map(|q| q.3).collect(),
+            };
+            for node in nodes {
+                let conformant = validator.check(&node, shape)?;
+                entries.push(ShapeMapEntry { node, shape: shape.clone(), conformant });
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Writes a result shape map in the compact syntax, one `node@shape` or `node@!shape` per line.
+pub fn format_shape_map(store: &StorageEngine, entries: &[ShapeMapEntry]) -> String {
+    let lines: Vec<String> = entries.iter().map(|e| {
+        let node = store.lookup_node_by_iid(&e.node).map(|n| n.to_ntriples(store)).unwrap_or_default();
+        let shape = match e.shape {
+            ShapeSelector::Start => "START".to_owned(),
+            ShapeSelector::Label(ref l) if l.starts_with("_:") => l.clone(),
+            ShapeSelector::Label(ref l) => format!("<{}>", l),
+        };
+        format!("{}@{}{}", node, if e.conformant { "" } else { "!" }, shape)
+    }).collect();
+    lines.join(",\n")
+}
+
+type Typing = Vec<(InternalID, String)>;
+
+/* A neighbourhood triple of the focus node: direction, predicate and the node at the other end. */
+type Arc = (bool, InternalID, InternalID);
+
+struct ShexValidator<'a> {
+    store: &'a StorageEngine,
+    graph: GraphID,
+    schema: &'a Schema,
+    work: Cell<usize>,
+}
+
+fn collect_constraints<'e>(expression: &'e TripleExpr, found: &mut Vec<&'e TripleConstraint>) {
+    match expression {
+        &TripleExpr::EachOf(ref parts, _, _) | &TripleExpr::OneOf(ref parts, _, _) => {
+            for part in parts.iter() {
+                collect_constraints(part, found);
+            }
+        }
+        &TripleExpr::Constraint(ref c) => found.push(c),
+        &TripleExpr::Include(_) => {}
+    }
+}
+
+/* Counts a step of the search for a typing, failing once the validation has taken `DEFAULT_WORK_LIMIT`. */
+fn charge(work: &Cell<usize>) -> Result<(), String> {
+    work.set(work.get() + 1);
+    if work.get() > DEFAULT_WORK_LIMIT {
+        return Err(format!("ShEx validation exceeded the work limit of {} steps.", DEFAULT_WORK_LIMIT));
+    }
+    Ok(())
+}
+
+/* Whether `times` repetitions of `expression` can account for exactly the matched triple counts. */
+fn feasible(expression: &TripleExpr, counts: &BTreeMap<usize, usize>, times: usize, limit: usize, work: &Cell<usize>) -> Result<bool, String> {
+    charge(work)?;
+    let range = |min: usize, max: Option<usize>| (min * times, max.map(|m| m * times).unwrap_or_else(|| limit.max(min * times)));
+    match expression {
+        &TripleExpr::Constraint(ref c) => {
+            let n = counts.get(&c.id).cloned().unwrap_or(0);
+            Ok(n >= c.min * times && c.max.map(|m| n <= m * times).unwrap_or(times > 0 || n == 0))
+        }
+        &TripleExpr::EachOf(ref parts, min, max) => {
+            let (low, high) = range(min, max);
+            for k in low..high + 1 {
+                let mut all = true;
+                for part in parts.iter() {
+                    if !feasible(part, counts, k, limit, work)? {
+                        all = false;
+                        break;
+                    }
+                }
+                if all {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        &TripleExpr::OneOf(ref options, min, max) => {
+            let (low, high) = range(min, max);
+            for k in low..high + 1 {
+                if split_feasible(options, counts, k, limit, work)? {
+                    return Ok(true);
+                }
+            }
+            Ok(false)
+        }
+        &TripleExpr::Include(_) => Ok(false),
+    }
+}
+
+/* Whether `k` repetitions of a one-of can be shared among its options. */
+fn split_feasible(options: &[TripleExpr], counts: &BTreeMap<usize, usize>, k: usize, limit: usize, work: &Cell<usize>) -> Result<bool, String> {
+    let (first, rest) = if let Some(split) = options.split_first() { split } else { return Ok(k == 0); };
+    for taken in 0..k + 1 {
+        if feasible(first, counts, taken, limit, work)? && split_feasible(rest, counts, k - taken, limit, work)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+impl<'a> ShexValidator<'a> {
+    fn check(&self, node: &InternalID, shape: &ShapeSelector) -> Result<bool, String> {
+        let mut typing = Typing::new();
+        match shape {
+            &ShapeSelector::Start => {
+                let start = self.schema.start.as_ref().ok_or_else(|| "The schema has no start shape.".to_owned())?;
+                self.satisfies(node, start, &mut typing)
+            }
+            &ShapeSelector::Label(ref label) => self.satisfies_label(node, label, &mut typing),
+        }
+    }
+
+    /* Recursive references are assumed to hold while they are being checked. */
+    fn satisfies_label(&self, node: &InternalID, label: &str, typing: &mut Typing) -> Result<bool, String> {
+        let expression = self.schema.shapes.get(label).ok_or_else(|| format!("Unknown shape {}.", label))?;
+        charge(&self.work)?;
+        let key = (node.clone(), label.to_owned());
+        if typing.contains(&key) {
+            return Ok(true);
+        }
+        typing.push(key);
+        let result = self.satisfies(node, expression, typing);
+        typing.pop();
+        result
+    }
+
+    fn satisfies(&self, node: &InternalID, expression: &ShapeExpr, typing: &mut Typing) -> Result<bool, String> {
+        Ok(match expression {
+            &ShapeExpr::And(ref parts) => {
+                for part in parts.iter() {
+                    if !self.satisfies(node, part, typing)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            &ShapeExpr::Or(ref options) => {
+                for option in options.iter() {
+                    if self.satisfies(node, option, typing)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            &ShapeExpr::Not(ref inner) => !self.satisfies(node, inner, typing)?,
+            &ShapeExpr::Ref(ref label) => self.satisfies_label(node, label, typing)?,
+            &ShapeExpr::NodeConstraint(ref constraint) => self.node_satisfies(node, constraint),
+            &ShapeExpr::Shape(ref shape) => self.shape_matches(node, shape, typing)?,
+            &ShapeExpr::Anything => true,
+        })
+    }
+
+    fn string_value(&self, node: &StoreNode) -> Option<String> {
+        match node {
+            &StoreNode::URIRef(ref u) => Some(u.to_string(self.store)),
+            &StoreNode::Literal(ref l) => Some(l.borrow_lexical_form().to_owned()),
+            &StoreNode::Blank(_) => None,
+        }
+    }
+
+    fn node_satisfies(&self, id: &InternalID, constraint: &NodeConstraint) -> bool {
+        let node = if let Ok(n) = self.store.lookup_node_by_iid(id) { n } else { return false; };
+        let literal = if let &StoreNode::Literal(ref l) = node { Some(l) } else { None };
+        if let Some(kind) = constraint.kind {
+            let ok = match (kind, node) {
+                (NodeKind::Iri, &StoreNode::URIRef(_)) | (NodeKind::BNode, &StoreNode::Blank(_)) | (NodeKind::Literal, &StoreNode::Literal(_)) => true,
+                (NodeKind::NonLiteral, _) => literal.is_none(),
+                _ => false,
+            };
+            if !ok {
+                return false;
+            }
+        }
+        if let Some(ref datatype) = constraint.datatype {
+            let ok = match (literal, self.store.lookup_node_by_iid(datatype)) {
+                (Some(l), Ok(&StoreNode::URIRef(ref d))) => l.borrow_datatype_uri() == d && literal::is_well_typed(l.borrow_lexical_form(), &d.to_string(self.store)),
+                _ => false,
+            };
+            if !ok {
+                return false;
+            }
+        }
+        if let Some(ref values) = constraint.values {
+            let lang = literal.and_then(|l| l.borrow_lang()).map(|l| l.to_lowercase());
+            let iri = if let &StoreNode::URIRef(ref u) = node { Some(u.to_string(self.store)) } else { None };
+            let ok = values.iter().any(|v| match v {
+                &ValueSetValue::Value(ref value) => value == id,
+                &ValueSetValue::IriStem(ref stem) => iri.as_ref().map(|i| i.starts_with(stem.as_str())).unwrap_or(false),
+                &ValueSetValue::Language(ref tag) => lang.as_ref().map(|l| *l == tag.to_lowercase()).unwrap_or(false),
+                &ValueSetValue::LanguageStem(ref stem) => {
+                    let stem = stem.to_lowercase();
+                    lang.as_ref().map(|l| *l == stem || l.starts_with(&format!("{}-", stem))).unwrap_or(false)
+                }
+            });
+            if !ok {
+                return false;
+            }
+        }
+        let text = self.string_value(node);
+        let datatype = literal.map(|l| l.borrow_datatype_uri().to_string(self.store));
+        let number = literal.and_then(|l| literal::numeric_value(l.borrow_lexical_form(), datatype.as_ref().unwrap()));
+        /* Digit facets only apply to xsd:decimal and the types derived from it. */
+        let decimal = number.is_some() && datatype.as_ref().map(|d| d != vocab::XSD_DOUBLE && !d.ends_with("#float")).unwrap_or(false);
+        let digits = literal.map(|l| {
+            let lexical = l.borrow_lexical_form().trim().trim_start_matches(['+', '-']);
+            let mut parts = lexical.splitn(2, '.');
+            let whole = parts.next().unwrap_or("").trim_start_matches('0');
+            let fraction = parts.next().unwrap_or("").trim_end_matches('0');
+            (whole.len() + fraction.len(), fraction.len())
+        });
+        constraint.facets.iter().all(|facet| match facet {
+            &Facet::Length(n) => text.as_ref().map(|t| t.chars().count() == n).unwrap_or(false),
+            &Facet::MinLength(n) => text.as_ref().map(|t| t.chars().count() >= n).unwrap_or(false),
+            &Facet::MaxLength(n) => text.as_ref().map(|t| t.chars().count() <= n).unwrap_or(false),
+            &Facet::Pattern(ref regex) => text.as_ref().map(|t| regex.is_match(t)).unwrap_or(false),
+            &Facet::MinInclusive(bound) => number.map(|n| n >= bound).unwrap_or(false),
+            &Facet::MinExclusive(bound) => number.map(|n| n > bound).unwrap_or(false),
+            &Facet::MaxInclusive(bound) => number.map(|n| n <= bound).unwrap_or(false),
+            &Facet::MaxExclusive(bound) => number.map(|n| n < bound).unwrap_or(false),
+            &Facet::TotalDigits(n) => decimal && digits.map(|(total, _)| total <= n).unwrap_or(false),
+            &Facet::FractionDigits(n) => decimal && digits.map(|(_, fraction)| fraction <= n).unwrap_or(false),
+        })
+    }
+
+    fn neighbourhood(&self, node: &InternalID, inverse_predicates: &BTreeSet<InternalID>) -> Vec<Arc> {
+        let mut arcs: Vec<Arc> = self.store.search_engine_entailed(Some(self.graph.clone()), Some(node.clone()), None, None)
+            .map(|q| (false, q.2, q.3)).collect();
+        for p in inverse_predicates.iter() {
+            arcs.extend(self.store.search_engine_entailed(Some(self.graph.clone()), None, Some(p.clone()), Some(node.clone())).map(|q| (true, q.2, q.1)));
+        }
+        arcs.sort();
+        arcs.dedup();
+        arcs
+    }
+
+    /* Each arc is given to one triple constraint it satisfies (or left unmatched when its predicate is
+       EXTRA), and the shape matches if some assignment meets every cardinality. */
+    fn shape_matches(&self, node: &InternalID, shape: &Shape, typing: &mut Typing) -> Result<bool, String> {
+        let mut constraints = Vec::new();
+        if let Some(ref e) = shape.expression {
+            collect_constraints(e, &mut constraints);
+        }
+        let mentioned: BTreeSet<(bool, InternalID)> = constraints.iter().map(|c| (c.inverse, c.predicate.clone())).collect();
+        let inverse_predicates: BTreeSet<InternalID> = constraints.iter().filter(|c| c.inverse).map(|c| c.predicate.clone()).collect();
+        let mut choices: Vec<Vec<Option<usize>>> = Vec::new();
+        for (inverse, predicate, other) in self.neighbourhood(node, &inverse_predicates) {
+            let mut candidates = Vec::new();
+            for c in constraints.iter().filter(|c| c.inverse == inverse && c.predicate == predicate) {
+                let ok = match c.value {
+                    Some(ref value) => self.satisfies(&other, value, typing)?,
+                    None => true,
+                };
+                if ok {
+                    candidates.push(Some(c.id));
+                }
+            }
+            let extra = shape.extra.contains(&predicate);
+            if extra {
+                candidates.push(None);
+            }
+            if candidates.is_empty() {
+                if mentioned.contains(&(inverse, predicate.clone())) || (shape.closed && !inverse) {
+                    return Ok(false);
+                }
+                continue;
+            }
+            choices.push(candidates);
+        }
+        let expression = if let Some(ref e) = shape.expression { e } else { return Ok(true); };
+        let limit = choices.len() + 1;
+        let mut counts = BTreeMap::new();
+        assign(&choices, &mut counts, expression, limit, &self.work)
+    }
+}
+
+fn assign(choices: &[Vec<Option<usize>>], counts: &mut BTreeMap<usize, usize>, expression: &TripleExpr, limit: usize, work: &Cell<usize>) -> Result<bool, String> {
+    let (first, rest) = if let Some(split) = choices.split_first() { split } else { return feasible(expression, counts, 1, limit, work); };
+    for choice in first.iter() {
+        charge(work)?;
+        if let &Some(id) = choice {
+            *counts.entry(id).or_insert(0) += 1;
+        }
+        let found = assign(rest, counts, expression, limit, work);
+        if let &Some(id) = choice {
+            *counts.get_mut(&id).unwrap() -= 1;
+        }
+        if found? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
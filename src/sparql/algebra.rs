@@ -0,0 +1,352 @@
+use std::collections::BTreeMap;
+use nquads::Term;
+use vocab;
+
+/// A variable or a fixed term in a pattern. Blank nodes in query patterns are parsed as
+/// variables whose names start with `_:`, which cannot be projected.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TermPattern {
+    Variable(String),
+    Term(Term),
+}
+
+impl TermPattern {
+    pub fn as_variable(&self) -> Option<&str> {
+        if let &TermPattern::Variable(ref v) = self { Some(v) } else { None }
+    }
+
+    /* Blank node variables belong to their own pattern, so they are never replaced. */
+    fn substitute(&self, bindings: &BTreeMap<String, Term>) -> TermPattern {
+        match self.as_variable().filter(|v| !v.starts_with("_:")).and_then(|v| bindings.get(v)) {
+            Some(term) => TermPattern::Term(term.clone()),
+            None => self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TriplePattern {
+    pub subject: TermPattern,
+    pub predicate: TermPattern,
+    pub object: TermPattern,
+}
+
+/// A triple pattern of an update template, in the default graph when `graph` is `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuadPattern {
+    pub triple: TriplePattern,
+    pub graph: Option<TermPattern>,
+}
+
+/// A property path. A negated property set holds the IRIs it excludes going forwards, then those
+/// it excludes going backwards.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathExpression {
+    Iri(String),
+    Inverse(Box<PathExpression>),
+    Sequence(Vec<PathExpression>),
+    Alternative(Vec<PathExpression>),
+    ZeroOrMore(Box<PathExpression>),
+    OneOrMore(Box<PathExpression>),
+    ZeroOrOne(Box<PathExpression>),
+    NegatedSet(Vec<String>, Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComparisonOperator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    Sample,
+    GroupConcat,
+}
+
+impl AggregateFunction {
+    /// The keyword naming the function in queries.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Min => "MIN",
+            AggregateFunction::Max => "MAX",
+            AggregateFunction::Avg => "AVG",
+            AggregateFunction::Sample => "SAMPLE",
+            AggregateFunction::GroupConcat => "GROUP_CONCAT",
+        }
+    }
+}
+
+/// An aggregate call; `expression` is `None` for `COUNT(*)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregate {
+    pub function: AggregateFunction,
+    pub distinct: bool,
+    pub expression: Option<Box<Expression>>,
+    pub separator: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Variable(String),
+    Constant(Term),
+    Or(Box<Expression>, Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Compare(ComparisonOperator, Box<Expression>, Box<Expression>),
+    Arithmetic(ArithmeticOperator, Box<Expression>, Box<Expression>),
+    Negate(Box<Expression>),
+    In(Box<Expression>, Vec<Expression>, bool),
+    Bound(String),
+    /* Built-in functions by upper-case name, or casts and extension functions by IRI. */
+    Call(String, Vec<Expression>),
+    Exists(Box<GraphPattern>, bool),
+    Aggregate(Aggregate),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphPattern {
+    Bgp(Vec<TriplePattern>),
+    Join(Box<GraphPattern>, Box<GraphPattern>),
+    LeftJoin(Box<GraphPattern>, Box<GraphPattern>, Option<Expression>),
+    Union(Box<GraphPattern>, Box<GraphPattern>),
+    Minus(Box<GraphPattern>, Box<GraphPattern>),
+    Filter(Expression, Box<GraphPattern>),
+    Graph(TermPattern, Box<GraphPattern>),
+    Extend(Box<GraphPattern>, String, Expression),
+    Values(Vec<String>, Vec<Vec<Option<Term>>>),
+    /* A subject and object connected by a path that is not a plain predicate, inverse or sequence. */
+    Path(TermPattern, PathExpression, TermPattern),
+    /* A pattern answered by a remote endpoint, with errors ignored when `SILENT`. */
+    Service(TermPattern, Box<GraphPattern>, bool),
+}
+
+impl GraphPattern {
+    pub fn empty() -> GraphPattern {
+        GraphPattern::Bgp(Vec::new())
+    }
+
+    pub fn is_path(&self) -> bool {
+        matches!(self, &GraphPattern::Path(..))
+    }
+
+    pub fn is_service(&self) -> bool {
+        matches!(self, &GraphPattern::Service(..))
+    }
+
+    /* Joins `other` onto this pattern, dropping the empty group on either side. */
+    pub fn join(self, other: GraphPattern) -> GraphPattern {
+        match (self, other) {
+            (GraphPattern::Bgp(ref a), other) if a.is_empty() => other,
+            (this, GraphPattern::Bgp(ref b)) if b.is_empty() => this,
+            (GraphPattern::Bgp(mut a), GraphPattern::Bgp(b)) => {
+                a.extend(b);
+                GraphPattern::Bgp(a)
+            }
+            (this, other) => GraphPattern::Join(Box::new(this), Box::new(other)),
+        }
+    }
+
+    /// The pattern with each variable bound in `bindings` replaced by its value, which is how
+    /// EXISTS evaluates its pattern against the solution being filtered.
+    pub fn substitute(&self, bindings: &BTreeMap<String, Term>) -> GraphPattern {
+        let boxed = |p: &GraphPattern| Box::new(p.substitute(bindings));
+        match self {
+            &GraphPattern::Bgp(ref triples) => GraphPattern::Bgp(triples.iter().map(|t| TriplePattern {
+                subject: t.subject.substitute(bindings),
+                predicate: t.predicate.substitute(bindings),
+                object: t.object.substitute(bindings),
+            }).collect()),
+            &GraphPattern::Join(ref a, ref b) => GraphPattern::Join(boxed(a), boxed(b)),
+            &GraphPattern::LeftJoin(ref a, ref b, ref condition) => GraphPattern::LeftJoin(boxed(a), boxed(b), condition.as_ref().map(|c| c.substitute(bindings))),
+            &GraphPattern::Union(ref a, ref b) => GraphPattern::Union(boxed(a), boxed(b)),
+            &GraphPattern::Minus(ref a, ref b) => GraphPattern::Minus(boxed(a), boxed(b)),
+            &GraphPattern::Filter(ref condition, ref inner) => GraphPattern::Filter(condition.substitute(bindings), boxed(inner)),
+            &GraphPattern::Graph(ref name, ref inner) => GraphPattern::Graph(name.substitute(bindings), boxed(inner)),
+            &GraphPattern::Extend(ref inner, ref v, ref expression) => GraphPattern::Extend(boxed(inner), v.clone(), expression.substitute(bindings)),
+            &GraphPattern::Values(..) => self.clone(),
+            &GraphPattern::Path(ref subject, ref path, ref object) => GraphPattern::Path(subject.substitute(bindings), path.clone(), object.substitute(bindings)),
+            &GraphPattern::Service(ref name, ref inner, silent) => GraphPattern::Service(name.substitute(bindings), boxed(inner), silent),
+        }
+    }
+
+    /// The variables this pattern can bind, in order of first appearance.
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<String>) {
+        fn add(v: &str, variables: &mut Vec<String>) {
+            if !variables.iter().any(|x| x == v) {
+                variables.push(v.to_owned());
+            }
+        }
+        match self {
+            &GraphPattern::Bgp(ref triples) => {
+                for t in triples.iter() {
+                    for p in [&t.subject, &t.predicate, &t.object].iter() {
+                        if let Some(v) = p.as_variable() {
+                            add(v, variables);
+                        }
+                    }
+                }
+            }
+            &GraphPattern::Join(ref a, ref b) | &GraphPattern::LeftJoin(ref a, ref b, _) | &GraphPattern::Union(ref a, ref b) => {
+                a.collect_variables(variables);
+                b.collect_variables(variables);
+            }
+            &GraphPattern::Minus(ref a, _) | &GraphPattern::Filter(_, ref a) => a.collect_variables(variables),
+            &GraphPattern::Graph(ref g, ref inner) | &GraphPattern::Service(ref g, ref inner, _) => {
+                if let Some(v) = g.as_variable() {
+                    add(v, variables);
+                }
+                inner.collect_variables(variables);
+            }
+            &GraphPattern::Extend(ref inner, ref v, _) => {
+                inner.collect_variables(variables);
+                add(v, variables);
+            }
+            &GraphPattern::Values(ref vars, _) => {
+                for v in vars.iter() {
+                    add(v, variables);
+                }
+            }
+            &GraphPattern::Path(ref subject, _, ref object) => {
+                for p in [subject, object].iter() {
+                    if let Some(v) = p.as_variable() {
+                        add(v, variables);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Expression {
+    /// The expression with each variable bound in `bindings` replaced by its value.
+    pub fn substitute(&self, bindings: &BTreeMap<String, Term>) -> Expression {
+        let boxed = |e: &Expression| Box::new(e.substitute(bindings));
+        match self {
+            &Expression::Variable(ref v) => bindings.get(v).map(|t| Expression::Constant(t.clone())).unwrap_or_else(|| self.clone()),
+            &Expression::Constant(_) => self.clone(),
+            &Expression::Or(ref a, ref b) => Expression::Or(boxed(a), boxed(b)),
+            &Expression::And(ref a, ref b) => Expression::And(boxed(a), boxed(b)),
+            &Expression::Not(ref a) => Expression::Not(boxed(a)),
+            &Expression::Compare(operator, ref a, ref b) => Expression::Compare(operator, boxed(a), boxed(b)),
+            &Expression::Arithmetic(operator, ref a, ref b) => Expression::Arithmetic(operator, boxed(a), boxed(b)),
+            &Expression::Negate(ref a) => Expression::Negate(boxed(a)),
+            &Expression::In(ref a, ref list, negated) => Expression::In(boxed(a), list.iter().map(|e| e.substitute(bindings)).collect(), negated),
+            &Expression::Bound(ref v) if bindings.contains_key(v) => Expression::Constant(Term::Literal("true".to_owned(), Some(vocab::XSD_BOOLEAN.to_owned()), None)),
+            &Expression::Bound(_) => self.clone(),
+            &Expression::Call(ref name, ref args) => Expression::Call(name.clone(), args.iter().map(|e| e.substitute(bindings)).collect()),
+            &Expression::Exists(ref pattern, negated) => Expression::Exists(Box::new(pattern.substitute(bindings)), negated),
+            &Expression::Aggregate(ref aggregate) => Expression::Aggregate(Aggregate {
+                expression: aggregate.expression.as_ref().map(|e| boxed(e)),
+                ..aggregate.clone()
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderCondition {
+    pub expression: Expression,
+    pub descending: bool,
+}
+
+/// A projected variable, computed by `expression` for `(expr AS ?v)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projection {
+    pub variable: String,
+    pub expression: Option<Expression>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryForm {
+    /* `projection` is `None` for `SELECT *`. */
+    Select { distinct: bool, reduced: bool, projection: Option<Vec<Projection>> },
+    Construct(Vec<TriplePattern>),
+    Describe(Vec<TermPattern>),
+    Ask,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    pub form: QueryForm,
+    pub pattern: GraphPattern,
+    pub group_by: Vec<(Expression, Option<String>)>,
+    pub having: Vec<Expression>,
+    pub order_by: Vec<OrderCondition>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Query {
+    /// True when the query groups its solutions, explicitly or by aggregating in the projection.
+    pub fn is_grouped(&self) -> bool {
+        let projected = match self.form {
+            QueryForm::Select { projection: Some(ref items), .. } => items.iter().any(|p| p.expression.as_ref().map(contains_aggregate).unwrap_or(false)),
+            _ => false,
+        };
+        !self.group_by.is_empty() || !self.having.is_empty() || projected ||
+            self.order_by.iter().any(|o| contains_aggregate(&o.expression))
+    }
+}
+
+pub fn contains_aggregate(expression: &Expression) -> bool {
+    match expression {
+        &Expression::Aggregate(_) => true,
+        &Expression::Or(ref a, ref b) | &Expression::And(ref a, ref b) |
+        &Expression::Compare(_, ref a, ref b) | &Expression::Arithmetic(_, ref a, ref b) => contains_aggregate(a) || contains_aggregate(b),
+        &Expression::Not(ref a) | &Expression::Negate(ref a) => contains_aggregate(a),
+        &Expression::In(ref a, ref list, _) => contains_aggregate(a) || list.iter().any(contains_aggregate),
+        &Expression::Call(_, ref args) => args.iter().any(contains_aggregate),
+        _ => false,
+    }
+}
+
+/// The graphs a `CLEAR` or `DROP` applies to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphTarget {
+    Default,
+    Named(String),
+    AllNamed,
+    All,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UpdateOperation {
+    InsertData(Vec<QuadPattern>),
+    DeleteData(Vec<QuadPattern>),
+    DeleteWhere(Vec<QuadPattern>),
+    /* `DELETE { .. } INSERT { .. } WHERE { .. }`, with the `WITH` graph if one was given. */
+    Modify { with: Option<String>, delete: Vec<QuadPattern>, insert: Vec<QuadPattern>, pattern: Box<GraphPattern> },
+    Clear { target: GraphTarget, silent: bool },
+    Create { graph: String, silent: bool },
+    /* `ADD`, `COPY` and `MOVE`; `None` is the default graph. */
+    Add { from: Option<String>, to: Option<String>, silent: bool },
+    Copy { from: Option<String>, to: Option<String>, silent: bool },
+    Move { from: Option<String>, to: Option<String>, silent: bool },
+    Load { source: String, into: Option<String>, silent: bool },
+}
@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use identifiers::InternalID;
+use inference::{Bindings, PatternRule, RuleConclusion, RulePattern, RuleTerm};
+use limits::Guard;
+use owl_rl::{OwlVocabulary, owl_rl_pattern_rules, read_list};
+use rdfs::{self, RdfsVocabulary, rdfs_pattern_rules};
+use store::{StorageEngine, GraphID};
+
+/// Which entailed answers basic graph patterns see, on top of the quads in the store and any
+/// the store has materialized itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EntailmentRegime {
+    #[default]
+    Simple,
+    Rdfs,
+    OwlRl,
+}
+
+impl EntailmentRegime {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            EntailmentRegime::Simple => "simple",
+            EntailmentRegime::Rdfs => "rdfs",
+            EntailmentRegime::OwlRl => "owl-rl",
+        }
+    }
+
+    /// The regime named `simple`, `rdfs` or `owl-rl`, as given on a command line or to the protocol.
+    pub fn from_name(name: &str) -> Option<EntailmentRegime> {
+        match name.to_lowercase().as_str() {
+            "simple" | "none" => Some(EntailmentRegime::Simple),
+            "rdfs" => Some(EntailmentRegime::Rdfs),
+            "owl-rl" | "owl2-rl" | "owl" => Some(EntailmentRegime::OwlRl),
+            _ => None,
+        }
+    }
+}
+
+type Triple = (InternalID, InternalID, InternalID);
+type Goal = (GraphID, Option<InternalID>, Option<InternalID>, Option<InternalID>);
+
+/// Answers triple patterns under an entailment regime by backward chaining: the regime's rules are
+/// applied only to the patterns a query asks about and the subgoals those need, reading the store's
+/// indexes. Answers are tabled per goal, so recursive rules terminate, and live only as long as the
+/// `Entailment`; nothing is written to the store. As with the forward-chaining rules, rules join
+/// quads within one graph.
+pub struct Entailment<'s> {
+    store: &'s StorageEngine,
+    /* Each rule with the only graph it applies in, for rules compiled from one graph's schema. */
+    rules: Vec<(Option<GraphID>, PatternRule)>,
+    tables: RefCell<BTreeMap<Goal, BTreeSet<Triple>>>,
+    complete: RefCell<BTreeSet<Goal>>,
+}
+
+impl<'s> Entailment<'s> {
+    /// The rules of `regime` over the vocabulary the store holds. Rules about terms the store does
+    /// not hold are left out, as they could not match or conclude anything a query can name.
+    pub fn new(store: &'s StorageEngine, regime: EntailmentRegime) -> Entailment<'s> {
+        let r = RdfsVocabulary::lookup(store);
+        let mut rules: Vec<(Option<GraphID>, PatternRule)> = Vec::new();
+        match regime {
+            EntailmentRegime::Simple => {}
+            EntailmentRegime::Rdfs => rules.extend(rdfs_pattern_rules(&r, false).into_iter().map(|rule| (None, rule))),
+            EntailmentRegime::OwlRl => {
+                let v = OwlVocabulary::lookup(store);
+                rules.extend(rdfs_pattern_rules(&r, true).into_iter().map(|rule| (None, rule)));
+                rules.extend(owl_rl_pattern_rules(&r, &v).into_iter().map(|rule| (None, rule)));
+                rules.extend(schema_rules(store, &r, &v));
+            }
+        }
+        rules.retain(|&(_, ref rule)| !rule.borrow_head().is_empty() &&
+            rule.borrow_body().iter().chain(rule.borrow_head().iter()).all(|pattern| {
+                [&pattern.0, &pattern.1, &pattern.2].iter().all(|t| **t != RuleTerm::Const(InternalID::MAX))
+            }));
+        Entailment { store, rules, tables: RefCell::new(BTreeMap::new()), complete: RefCell::new(BTreeSet::new()) }
+    }
+
+    /// The triples in `graph` matching a pattern, where `None` matches anything: those in the store
+    /// and those the regime entails from them.
+    pub fn matches(&self, graph: &GraphID, subject: Option<InternalID>, predicate: Option<InternalID>, object: Option<InternalID>,
+                   guard: &Guard) -> Result<Vec<Triple>, String> {
+        let goal = (graph.clone(), subject, predicate, object);
+        if !self.complete.borrow().contains(&goal) {
+            loop {
+                let mut visited = BTreeSet::new();
+                let mut changed = false;
+                self.expand(&goal, &mut visited, &mut changed, guard)?;
+                /* A round that found nothing new leaves every goal it visited at its fixpoint. */
+                if !changed {
+                    self.complete.borrow_mut().extend(visited);
+                    break;
+                }
+            }
+        }
+        Ok(self.tables.borrow().get(&goal).map(|answers| answers.iter().cloned().collect()).unwrap_or_default())
+    }
+
+    /* One round of naive evaluation of `goal` and of the subgoals it needs, each visited once per
+       round. Sets `changed` when any goal gains an answer. */
+    fn expand(&self, goal: &Goal, visited: &mut BTreeSet<Goal>, changed: &mut bool, guard: &Guard) -> Result<(), String> {
+        if self.complete.borrow().contains(goal) || !visited.insert(goal.clone()) {
+            return Ok(());
+        }
+        if !self.tables.borrow().contains_key(goal) {
+            let (ref g, ref s, ref p, ref o) = *goal;
+            let stored: BTreeSet<Triple> = self.store.search_engine_entailed(Some(g.clone()), s.clone(), p.clone(), o.clone())
+                .map(|(_, s, p, o)| (s, p, o)).collect();
+            *changed |= !stored.is_empty();
+            self.tables.borrow_mut().insert(goal.clone(), stored);
+        }
+        let mut found = Vec::new();
+        for &(ref only, ref rule) in self.rules.iter() {
+            if only.as_ref().map(|g| *g != goal.0).unwrap_or(false) {
+                continue;
+            }
+            for head in rule.borrow_head() {
+                let bindings = match bind_goal(head, goal, rule.empty_bindings()) {
+                    Some(bindings) => bindings,
+                    None => continue,
+                };
+                let results = self.join(&goal.0, rule.borrow_body(), bindings, visited, changed, guard)?;
+                for bindings in results.into_iter().filter(|b| rule.allows(b)) {
+                    /* Literals cannot be subjects, as when rdfs3 types the object of a ranged property. */
+                    if let (Some(s), Some(p), Some(o)) = head.resolve(&bindings) {
+                        if !rdfs::is_literal(self.store, &s) {
+                            found.push((s, p, o));
+                        }
+                    }
+                }
+            }
+        }
+        let mut tables = self.tables.borrow_mut();
+        let answers = tables.get_mut(goal).unwrap();
+        for triple in found {
+            if answers.insert(triple) {
+                *changed = true;
+            }
+        }
+        Ok(())
+    }
+
+    /* Extends `bindings` through every remaining body pattern, most bound first, matching each
+       against the answers to it as a subgoal. */
+    fn join(&self, graph: &GraphID, remaining: &[RulePattern], bindings: Bindings, visited: &mut BTreeSet<Goal>, changed: &mut bool,
+            guard: &Guard) -> Result<Vec<Bindings>, String> {
+        if remaining.is_empty() {
+            return Ok(vec![bindings]);
+        }
+        guard.work()?;
+        let next = (0..remaining.len()).max_by_key(|&i| remaining[i].bound_count(&bindings)).unwrap();
+        let pattern = &remaining[next];
+        let rest: Vec<RulePattern> = remaining.iter().enumerate().filter(|&(i, _)| i != next).map(|(_, r)| r.clone()).collect();
+        let (s, p, o) = pattern.resolve(&bindings);
+        let subgoal = (graph.clone(), s, p, o);
+        self.expand(&subgoal, visited, changed, guard)?;
+        let answers: Vec<Triple> = self.tables.borrow().get(&subgoal).map(|answers| answers.iter().cloned().collect()).unwrap_or_default();
+        let mut results = Vec::new();
+        for (s, p, o) in answers {
+            if let Some(extended) = pattern.unify(&s, &p, &o, &bindings) {
+                results.extend(self.join(graph, &rest, extended, visited, changed, guard)?);
+            }
+        }
+        Ok(results)
+    }
+}
+
+/* Binds the variables of a rule head to the terms `goal` is bound to, if the two can match. */
+fn bind_goal(head: &RulePattern, goal: &Goal, mut bindings: Bindings) -> Option<Bindings> {
+    for &(term, id) in [(&head.0, &goal.1), (&head.1, &goal.2), (&head.2, &goal.3)].iter() {
+        let id = match *id {
+            Some(ref id) => id,
+            None => continue,
+        };
+        match *term {
+            RuleTerm::Const(ref c) => if c != id {
+                return None;
+            },
+            RuleTerm::Var(v) => {
+                if bindings[v].as_ref().map(|b| b != id).unwrap_or(false) {
+                    return None;
+                }
+                bindings[v] = Some(id.clone());
+            }
+        }
+    }
+    Some(bindings)
+}
+
+/* prp-spo2, cls-int1, cls-int2 and cls-uni, compiled from the property chains and class lists of
+   each graph into rules over the properties and classes they name. */
+fn schema_rules(store: &StorageEngine, r: &RdfsVocabulary, v: &OwlVocabulary) -> Vec<(Option<GraphID>, PatternRule)> {
+    let lists = |predicate: &InternalID| -> Vec<(GraphID, InternalID, Vec<InternalID>)> {
+        store.search_engine_entailed(None, None, Some(predicate.clone()), None)
+            .filter_map(|(g, subject, _, head)| read_list(store, &g, &head, v).map(|members| (g, subject, members)))
+            .filter(|&(_, _, ref members)| !members.is_empty())
+            .collect()
+    };
+    let rule = |name: &'static str, body: Vec<RulePattern>, head: Vec<RulePattern>| PatternRule::new(name, body, RuleConclusion::Quads(head));
+    let typed = |class: &InternalID| RulePattern(RuleTerm::Var(0), RuleTerm::Const(r.rdf_type.clone()), RuleTerm::Const(class.clone()));
+    let mut rules = Vec::new();
+    for (g, property, chain) in lists(&v.property_chain_axiom) {
+        let body = chain.iter().enumerate().map(|(i, link)| RulePattern(RuleTerm::Var(i), RuleTerm::Const(link.clone()), RuleTerm::Var(i + 1))).collect();
+        let head = RulePattern(RuleTerm::Var(0), RuleTerm::Const(property), RuleTerm::Var(chain.len()));
+        rules.push((Some(g), rule("prp-spo2", body, vec![head])));
+    }
+    for (g, class, members) in lists(&v.intersection_of) {
+        rules.push((Some(g.clone()), rule("cls-int1", members.iter().map(&typed).collect(), vec![typed(&class)])));
+        rules.push((Some(g), rule("cls-int2", vec![typed(&class)], members.iter().map(&typed).collect())));
+    }
+    for (g, class, members) in lists(&v.union_of) {
+        for member in members.iter() {
+            rules.push((Some(g.clone()), rule("cls-uni", vec![typed(member)], vec![typed(&class)])));
+        }
+    }
+    rules
+}
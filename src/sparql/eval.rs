@@ -0,0 +1,1519 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::rc::Rc;
+use std::time::Instant;
+use blank::BlankNode;
+use fulltext;
+use identifiers::InternalID;
+use indexed_quad_set::IndexKind;
+use limits::{Guard, LimitExceeded, Limits};
+use literal;
+use nquads::Term;
+use patch::{Patch, PatchRow, PatchQuad};
+use property_path::PropertyPath;
+use regex::Regex;
+use store::{StorageEngine, GraphID};
+use vocab;
+use sparql::QueryResult;
+use sparql::algebra::*;
+use sparql::entailment::{Entailment, EntailmentRegime};
+use sparql::explain::{self, PlanNode};
+use sparql::federation::{self, SERVICE_BATCH_SIZE};
+use sparql::planner::{self, BgpPlan, JoinAlgorithm, Planner};
+
+static XSD: &'static str = "http://www.w3.org/2001/XMLSchema#";
+static XSD_FLOAT: &'static str = "http://www.w3.org/2001/XMLSchema#float";
+static INTEGER_TYPES: &'static [&'static str] = &["integer", "int", "long", "short", "byte", "nonNegativeInteger",
+    "positiveInteger", "negativeInteger", "nonPositiveInteger", "unsignedLong", "unsignedInt", "unsignedShort", "unsignedByte"];
+
+/// A value bound to a variable: a node of the store, or a term computed by an expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Node(InternalID),
+    Computed(Term),
+}
+
+pub type Solution = BTreeMap<String, Value>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Number {
+    Integer(i64),
+    Decimal(f64),
+    Double(f64),
+}
+
+impl Number {
+    fn as_f64(&self) -> f64 {
+        match *self {
+            Number::Integer(i) => i as f64,
+            Number::Decimal(d) | Number::Double(d) => d,
+        }
+    }
+
+    fn to_term(self) -> Term {
+        match self {
+            Number::Integer(i) => Term::Literal(i.to_string(), Some(vocab::XSD_INTEGER.to_owned()), None),
+            Number::Decimal(d) => {
+                let mut lexical = format!("{}", d);
+                if !lexical.contains('.') {
+                    lexical.push_str(".0");
+                }
+                Term::Literal(lexical, Some(vocab::XSD_DECIMAL.to_owned()), None)
+            }
+            Number::Double(d) => {
+                let lexical = if d.is_nan() { "NaN".to_owned() } else if d.is_infinite() { if d > 0.0 { "INF".to_owned() } else { "-INF".to_owned() } } else { format!("{:E}", d) };
+                Term::Literal(lexical, Some(vocab::XSD_DOUBLE.to_owned()), None)
+            }
+        }
+    }
+}
+
+fn number(term: &Term) -> Option<Number> {
+    if let &Term::Literal(ref lexical, Some(ref datatype), None) = term {
+        if !datatype.starts_with(XSD) {
+            return None;
+        }
+        let local = &datatype[XSD.len()..];
+        let lexical = lexical.trim();
+        if INTEGER_TYPES.contains(&local) {
+            return lexical.trim_start_matches('+').parse().ok().map(Number::Integer);
+        }
+        if local == "decimal" {
+            return lexical.parse().ok().map(Number::Decimal);
+        }
+        if local == "double" || local == "float" {
+            return match lexical {
+                "INF" | "+INF" => Some(Number::Double(f64::INFINITY)),
+                "-INF" => Some(Number::Double(f64::NEG_INFINITY)),
+                "NaN" => Some(Number::Double(f64::NAN)),
+                _ => lexical.parse().ok().map(Number::Double),
+            };
+        }
+    }
+    None
+}
+
+fn boolean(value: bool) -> Term {
+    Term::Literal(if value { "true" } else { "false" }.to_owned(), Some(vocab::XSD_BOOLEAN.to_owned()), None)
+}
+
+fn simple_literal(value: String) -> Term {
+    Term::Literal(value, None, None)
+}
+
+/* The lexical form and language of a simple, xsd:string or language-tagged literal. */
+fn string_value(term: &Term) -> Option<(&str, Option<&str>)> {
+    if let &Term::Literal(ref lexical, None, ref lang) = term {
+        Some((lexical, lang.as_ref().map(|l| l.as_str())))
+    } else {
+        None
+    }
+}
+
+fn effective_boolean_value(term: &Term) -> Option<bool> {
+    if let Some(n) = number(term) {
+        let f = n.as_f64();
+        return Some(f != 0.0 && !f.is_nan());
+    }
+    match term {
+        &Term::Literal(ref lexical, Some(ref datatype), None) if datatype == vocab::XSD_BOOLEAN => Some(lexical == "true" || lexical == "1"),
+        &Term::Literal(ref lexical, None, None) => Some(!lexical.is_empty()),
+        _ => None,
+    }
+}
+
+/* The operator value comparison of two terms, or `None` where they cannot be compared. */
+fn compare_values(a: &Term, b: &Term) -> Option<Ordering> {
+    if let (Some(x), Some(y)) = (number(a), number(b)) {
+        return match (x, y) {
+            (Number::Integer(i), Number::Integer(j)) => Some(i.cmp(&j)),
+            _ => x.as_f64().partial_cmp(&y.as_f64()),
+        };
+    }
+    match (a, b) {
+        (&Term::Literal(ref x, None, None), &Term::Literal(ref y, None, None)) => Some(x.cmp(y)),
+        (&Term::Literal(ref x, None, Some(ref l)), &Term::Literal(ref y, None, Some(ref m))) if l.eq_ignore_ascii_case(m) => Some(x.cmp(y)),
+        (&Term::Literal(ref x, Some(ref d), None), &Term::Literal(ref y, Some(ref e), None)) if d == e && d != literal::LANG_STRING_URI => {
+            if d == vocab::XSD_BOOLEAN {
+                Some((x == "true" || x == "1").cmp(&(y == "true" || y == "1")))
+            } else {
+                /* Dates and times compare as instants, normalized to UTC. */
+                literal::temporal_value(x, d)?.compare(&literal::temporal_value(y, d)?)
+            }
+        }
+        _ => None,
+    }
+}
+
+fn equal_values(a: &Term, b: &Term) -> Option<bool> {
+    if let Some(ordering) = compare_values(a, b) {
+        return Some(ordering == Ordering::Equal);
+    }
+    if a == b {
+        return Some(true);
+    }
+    match (a, b) {
+        /* Literals of unknown datatypes might still denote the same value. */
+        (&Term::Literal(_, Some(_), None), &Term::Literal(_, Some(_), None)) => None,
+        _ => Some(false),
+    }
+}
+
+/// The ORDER BY ordering: unbound, then blank nodes, IRIs and literals. Literals go by kind:
+/// numbers, simple strings, language-tagged strings, booleans, then the rest. Numbers are ordered
+/// by value with NaN first, and booleans by value. Ties fall back to comparing the terms
+/// themselves, so the order is total and sorting is deterministic.
+pub fn order_terms(a: Option<&Term>, b: Option<&Term>) -> Ordering {
+    let rank = |t: Option<&Term>| match t {
+        None => 0,
+        Some(&Term::Blank(_)) => 1,
+        Some(&Term::Iri(_)) => 2,
+        Some(&Term::Literal(..)) => 3,
+    };
+    match (a, b) {
+        (Some(x), Some(y)) if rank(a) == 3 && rank(b) == 3 => order_literals(x, y),
+        (Some(x), Some(y)) if rank(a) == rank(b) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn order_literals(x: &Term, y: &Term) -> Ordering {
+    let kind = |t: &Term| match t {
+        _ if number(t).is_some() => 0,
+        &Term::Literal(_, None, None) => 1,
+        &Term::Literal(_, None, Some(_)) => 2,
+        &Term::Literal(_, Some(ref d), None) if d == vocab::XSD_BOOLEAN => 3,
+        _ => 4,
+    };
+    let truth = |t: &Term| if let &Term::Literal(ref lexical, _, _) = t { lexical == "true" || lexical == "1" } else { false };
+    let by_value = match (number(x), number(y)) {
+        (Some(m), Some(n)) => {
+            let (f, g) = (m.as_f64(), n.as_f64());
+            let by_float = match (f.is_nan(), g.is_nan()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => f.partial_cmp(&g).unwrap_or(Ordering::Equal),
+            };
+            /* Integers too close for a double to tell apart are told apart exactly. */
+            let exact = |n: Number| if let Number::Integer(i) = n { Some(i) } else { None };
+            by_float.then_with(|| exact(m).cmp(&exact(n)))
+        }
+        _ if kind(x) == 3 && kind(y) == 3 => truth(x).cmp(&truth(y)),
+        _ => Ordering::Equal,
+    };
+    kind(x).cmp(&kind(y)).then(by_value).then_with(|| x.cmp(y))
+}
+
+fn fresh_blank_label() -> String {
+    BlankNode::new(None).borrow_label().to_owned()
+}
+
+fn encode_for_uri(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/* `None` when the result does not fit, as for the absolute value of the smallest integer. */
+fn numeric_function(name: &str, n: Number) -> Option<Number> {
+    let apply = |f: f64| match name {
+        "ABS" => f.abs(),
+        "CEIL" => f.ceil(),
+        "FLOOR" => f.floor(),
+        _ => (f + 0.5).floor(),
+    };
+    match n {
+        Number::Integer(i) if name == "ABS" => i.checked_abs().map(Number::Integer),
+        Number::Integer(i) => Some(Number::Integer(i)),
+        Number::Decimal(d) => Some(Number::Decimal(apply(d))),
+        Number::Double(d) => Some(Number::Double(apply(d))),
+    }
+}
+
+fn arithmetic(operator: ArithmeticOperator, a: Number, b: Number) -> Option<Number> {
+    if let (Number::Integer(x), Number::Integer(y)) = (a, b) {
+        return match operator {
+            ArithmeticOperator::Add => x.checked_add(y).map(Number::Integer),
+            ArithmeticOperator::Subtract => x.checked_sub(y).map(Number::Integer),
+            ArithmeticOperator::Multiply => x.checked_mul(y).map(Number::Integer),
+            ArithmeticOperator::Divide => if y == 0 { None } else { Some(Number::Decimal(x as f64 / y as f64)) },
+        };
+    }
+    let (x, y) = (a.as_f64(), b.as_f64());
+    let result = match operator {
+        ArithmeticOperator::Add => x + y,
+        ArithmeticOperator::Subtract => x - y,
+        ArithmeticOperator::Multiply => x * y,
+        ArithmeticOperator::Divide => x / y,
+    };
+    match (a, b) {
+        (Number::Double(_), _) | (_, Number::Double(_)) => Some(Number::Double(result)),
+        _ if operator == ArithmeticOperator::Divide && y == 0.0 => None,
+        _ => Some(Number::Decimal(result)),
+    }
+}
+
+fn cast(datatype: &str, term: &Term) -> Option<Term> {
+    let lexical = match term {
+        &Term::Iri(ref iri) if datatype == vocab::XSD_STRING => return Some(simple_literal(iri.clone())),
+        &Term::Literal(ref lexical, _, _) => lexical.trim().to_owned(),
+        _ => return None,
+    };
+    let n = number(term);
+    if datatype == vocab::XSD_STRING {
+        Some(simple_literal(lexical))
+    } else if datatype == vocab::XSD_INTEGER {
+        match n {
+            Some(Number::Integer(i)) => Some(Number::Integer(i).to_term()),
+            Some(other) => { let f = other.as_f64().trunc(); if f.is_finite() { Some(Number::Integer(f as i64).to_term()) } else { None } }
+            None => lexical.parse().ok().map(|i| Number::Integer(i).to_term()),
+        }
+    } else if datatype == vocab::XSD_DECIMAL {
+        n.map(|n| n.as_f64()).or_else(|| lexical.parse().ok()).filter(|f: &f64| f.is_finite()).map(|f| Number::Decimal(f).to_term())
+    } else if datatype == vocab::XSD_DOUBLE || datatype == XSD_FLOAT {
+        n.map(|n| n.as_f64()).or_else(|| lexical.parse().ok()).map(|f| Number::Double(f).to_term())
+    } else if datatype == vocab::XSD_BOOLEAN {
+        match n {
+            Some(n) => Some(boolean(n.as_f64() != 0.0)),
+            None => match lexical.as_str() {
+                "true" | "1" => Some(boolean(true)),
+                "false" | "0" => Some(boolean(false)),
+                _ => None,
+            },
+        }
+    } else {
+        None
+    }
+}
+
+/// Evaluates query algebra against a store. The default graph of queries is the store's default
+/// graph, and GRAPH patterns range over every other graph holding quads. Asserted and inferred
+/// quads are both visible. Basic graph patterns are evaluated in the order chosen by the planner.
+pub struct Evaluator<'s> {
+    store: &'s StorageEngine,
+    planner: Planner<'s>,
+    profile: Option<RefCell<Profile>>,
+    guard: Rc<Guard>,
+    entailment: Option<Entailment<'s>>,
+    regexes: RefCell<RegexCache>,
+}
+
+/* The rows of the hashed side of a join by their values of the join variables, and those variables. */
+type JoinTable = (HashMap<Vec<JoinKey>, Vec<usize>>, Vec<String>);
+
+/* REGEX patterns given as constants, compiled once per pattern and flags; `None` if invalid. */
+type RegexCache = HashMap<(String, String), Option<Rc<Regex>>>;
+
+/* The operators being evaluated while profiling, innermost last, and the last finished query. */
+struct Profile {
+    open: Vec<PlanNode>,
+    finished: Option<PlanNode>,
+}
+
+/* A value as compared by joins: a store node, or a computed term that is not in the store. */
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum JoinKey {
+    Node(InternalID),
+    Term(Term),
+}
+
+/* An end of a path pattern: an unbound variable, a store node, or a term the store does not hold. */
+enum PathEnd {
+    Free(String),
+    Node(InternalID),
+    Absent(Term),
+}
+
+/* A solution being modified, with the members of its group when the query aggregates. */
+struct Row {
+    solution: Solution,
+    group: Option<Vec<Solution>>,
+}
+
+impl<'s> Evaluator<'s> {
+    pub fn new(store: &'s StorageEngine) -> Evaluator<'s> {
+        Evaluator { store, planner: Planner::new(store), profile: None, guard: Rc::new(Guard::unlimited()), entailment: None, regexes: RefCell::new(HashMap::new()) }
+    }
+
+    /// Matches basic graph patterns under `regime`, so they also see the answers it entails. Other
+    /// operators, property paths among them, see only the quads in the store.
+    pub fn with_entailment(mut self, regime: EntailmentRegime) -> Evaluator<'s> {
+        self.entailment = match regime {
+            EntailmentRegime::Simple => None,
+            regime => Some(Entailment::new(self.store, regime)),
+        };
+        self
+    }
+
+    /// Bounds evaluation by `limits`, counted from now. Evaluation stops with an error once one is
+    /// exceeded, and `limit_exceeded` then tells which.
+    pub fn with_limits(self, limits: Limits) -> Evaluator<'s> {
+        self.with_guard(Rc::new(Guard::new(limits)))
+    }
+
+    /// Bounds evaluation by a guard shared with other steps of the same operation.
+    pub fn with_guard(mut self, guard: Rc<Guard>) -> Evaluator<'s> {
+        self.guard = guard;
+        self
+    }
+
+    pub fn borrow_guard(&self) -> &Guard {
+        &self.guard
+    }
+
+    /// The limit that stopped evaluation, if one did.
+    pub fn limit_exceeded(&self) -> Option<LimitExceeded> {
+        self.guard.exceeded()
+    }
+
+    /* Adds a solution to an intermediate result, within the limits on its size. */
+    fn push(&self, out: &mut Vec<Solution>, solution: Solution) -> Result<(), String> {
+        self.guard.rows(out.len() + 1, solution.len())?;
+        out.push(solution);
+        Ok(())
+    }
+
+    /// Records the plan of each query executed, with row counts and timings, for `profiled_plan`.
+    pub fn with_profiling(mut self) -> Evaluator<'s> {
+        self.profile = Some(RefCell::new(Profile { open: Vec::new(), finished: None }));
+        self
+    }
+
+    /// The plan of the last query executed with profiling enabled.
+    pub fn profiled_plan(&self) -> Option<PlanNode> {
+        self.profile.as_ref().and_then(|profile| profile.borrow().finished.clone())
+    }
+
+    /* Opens a plan operator when profiling, returning its depth and start time. */
+    fn enter<F: FnOnce() -> (String, String)>(&self, describe: F) -> Option<(usize, Instant)> {
+        self.profile.as_ref().map(|profile| {
+            let (operator, detail) = describe();
+            let mut profile = profile.borrow_mut();
+            profile.open.push(PlanNode::new(&operator, detail));
+            (profile.open.len(), Instant::now())
+        })
+    }
+
+    fn set_estimate(&self, rows: f64) {
+        if let Some(ref profile) = self.profile {
+            if let Some(node) = profile.borrow_mut().open.last_mut() {
+                node.estimated_rows = Some(rows);
+            }
+        }
+    }
+
+    /* Closes the operator opened by `enter`, which produced `rows` rows. Operators inside it that
+       were left open by an error are dropped. */
+    fn leave(&self, entered: Option<(usize, Instant)>, rows: usize) {
+        if let (Some(profile), Some((depth, started))) = (self.profile.as_ref(), entered) {
+            let mut profile = profile.borrow_mut();
+            let profile = &mut *profile;
+            profile.open.truncate(depth);
+            if let Some(mut node) = profile.open.pop() {
+                node.time = started.elapsed();
+                node.actual_rows = rows;
+                node.loops = 1;
+                match profile.open.last_mut() {
+                    Some(parent) => parent.attach(node),
+                    None => profile.finished = Some(node),
+                }
+            }
+        }
+    }
+
+    pub fn term(&self, value: &Value) -> Option<Term> {
+        match value {
+            &Value::Node(ref id) => Term::from_node(self.store, id).ok(),
+            &Value::Computed(ref term) => Some(term.clone()),
+        }
+    }
+
+    fn node_id(&self, value: &Value) -> Option<InternalID> {
+        match value {
+            &Value::Node(ref id) => Some(id.clone()),
+            &Value::Computed(ref term) => term.find(self.store),
+        }
+    }
+
+    fn same_value(&self, a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (&Value::Node(ref x), &Value::Node(ref y)) => x == y,
+            _ => self.term(a) == self.term(b),
+        }
+    }
+
+    fn compatible(&self, a: &Solution, b: &Solution) -> bool {
+        a.iter().all(|(k, v)| b.get(k).map(|w| self.same_value(v, w)).unwrap_or(true))
+    }
+
+    fn merge(&self, a: &Solution, b: &Solution) -> Solution {
+        let mut merged = a.clone();
+        for (k, v) in b.iter() {
+            merged.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+        merged
+    }
+
+    fn join_key(&self, solution: &Solution, variables: &[String]) -> Option<Vec<JoinKey>> {
+        variables.iter().map(|v| solution.get(v).map(|value| match self.node_id(value) {
+            Some(id) => JoinKey::Node(id),
+            None => JoinKey::Term(self.term(value).unwrap_or_else(|| Term::Iri(String::new()))),
+        })).collect()
+    }
+
+    /* The positions of `solutions` by their values of `variables`; solutions leaving one unbound are left out. */
+    fn hash_table(&self, solutions: &[Solution], variables: &[String]) -> HashMap<Vec<JoinKey>, Vec<usize>> {
+        let mut table = HashMap::new();
+        for (i, solution) in solutions.iter().enumerate() {
+            if let Some(key) = self.join_key(solution, variables) {
+                table.entry(key).or_insert_with(Vec::new).push(i);
+            }
+        }
+        table
+    }
+
+    /* The solutions of `right` compatible with `solution`, found through `table` when joining on variables. */
+    fn join_candidates<'a>(&self, solution: &Solution, right: &'a [Solution], table: &Option<JoinTable>) -> Vec<&'a Solution> {
+        let candidates: Vec<&Solution> = match *table {
+            Some((ref table, ref variables)) => match self.join_key(solution, variables) {
+                Some(key) => table.get(&key).map(|positions| positions.iter().map(|&i| &right[i]).collect()).unwrap_or_else(Vec::new),
+                None => right.iter().collect(),
+            },
+            None => right.iter().collect(),
+        };
+        candidates.into_iter().filter(|b| self.compatible(solution, b)).collect()
+    }
+
+    /* Joins solutions that all bind `variable` to a store node, sorting both sides by it first. */
+    fn merge_join(&self, left: Vec<Solution>, right: Vec<Solution>, variable: &str) -> Result<Vec<Solution>, String> {
+        let keyed = |solutions: Vec<Solution>| {
+            let mut keyed: Vec<(Option<InternalID>, Solution)> = solutions.into_iter().map(|s| (s.get(variable).and_then(|v| self.node_id(v)), s)).collect();
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            keyed
+        };
+        let (left, right) = (keyed(left), keyed(right));
+        let mut joined = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() && j < right.len() {
+            match left[i].0.cmp(&right[j].0) {
+                Ordering::Less => i += 1,
+                Ordering::Greater => j += 1,
+                Ordering::Equal => {
+                    let end = right[j..].iter().position(|r| r.0 != right[j].0).map(|n| j + n).unwrap_or(right.len());
+                    while i < left.len() && left[i].0 == right[j].0 {
+                        self.guard.work()?;
+                        for r in right[j..end].iter().filter(|r| self.compatible(&left[i].1, &r.1)) {
+                            self.push(&mut joined, self.merge(&left[i].1, &r.1))?;
+                        }
+                        i += 1;
+                    }
+                    j = end;
+                }
+            }
+        }
+        Ok(joined)
+    }
+
+    /// Graphs other than the default graph that hold at least one quad.
+    pub fn named_graphs(&self) -> Vec<GraphID> {
+        let default_graph = InternalID(0.into());
+        self.store.graph_ids().into_iter().filter(|g| *g != default_graph).collect()
+    }
+
+    pub fn execute(&self, query: &Query) -> Result<QueryResult, String> {
+        let started = self.enter(|| {
+            let (operator, detail) = explain::query_operator(query);
+            (operator.to_owned(), detail)
+        });
+        /* A limit hit inside an expression, as by EXISTS, makes the expression fail rather than the query. */
+        let result = self.execute_query(query).and_then(|result| match self.guard.exceeded() {
+            Some(limit) => Err(limit.message),
+            None => Ok(result),
+        });
+        let rows = match result {
+            Ok(QueryResult::Solutions { ref rows, .. }) => rows.len(),
+            Ok(QueryResult::Boolean(_)) => 1,
+            Ok(QueryResult::Graph(ref triples)) => triples.len(),
+            Err(_) => 0,
+        };
+        self.leave(started, rows);
+        result
+    }
+
+    fn execute_query(&self, query: &Query) -> Result<QueryResult, String> {
+        let default_graph = InternalID(0.into());
+        let solutions = self.evaluate_pattern(&query.pattern, Some(&default_graph))?;
+        let mut rows = self.group(query, solutions, Some(&default_graph));
+        if let QueryForm::Select { projection: Some(ref items), .. } = query.form {
+            for item in items.iter() {
+                if let Some(ref expression) = item.expression {
+                    for row in rows.iter_mut() {
+                        if let Some(value) = self.evaluate(expression, &row.solution, row.group.as_deref(), Some(&default_graph)) {
+                            row.solution.insert(item.variable.clone(), Value::Computed(value));
+                        }
+                    }
+                }
+            }
+        }
+        if !query.order_by.is_empty() {
+            let mut keyed: Vec<(Vec<Option<Term>>, Row)> = rows.into_iter().map(|row| {
+                let key = query.order_by.iter()
+                    .map(|c| self.evaluate(&c.expression, &row.solution, row.group.as_deref(), Some(&default_graph)))
+                    .collect();
+                (key, row)
+            }).collect();
+            keyed.sort_by(|a, b| {
+                for (i, condition) in query.order_by.iter().enumerate() {
+                    let ordering = order_terms(a.0[i].as_ref(), b.0[i].as_ref());
+                    if ordering != Ordering::Equal {
+                        return if condition.descending { ordering.reverse() } else { ordering };
+                    }
+                }
+                Ordering::Equal
+            });
+            rows = keyed.into_iter().map(|(_, row)| row).collect();
+        }
+        let solutions: Vec<Solution> = rows.into_iter().map(|row| row.solution).collect();
+        match query.form {
+            QueryForm::Select { distinct, reduced, ref projection } => {
+                let variables: Vec<String> = match *projection {
+                    Some(ref items) => items.iter().map(|p| p.variable.clone()).collect(),
+                    None => query.pattern.variables().into_iter().filter(|v| !v.starts_with("_:")).collect(),
+                };
+                let mut seen = BTreeSet::new();
+                let mut table = Vec::new();
+                for solution in solutions.iter() {
+                    let row: Vec<Option<Term>> = variables.iter().map(|v| solution.get(v).and_then(|value| self.term(value))).collect();
+                    if (distinct || reduced) && !seen.insert(row.clone()) {
+                        continue;
+                    }
+                    table.push(row);
+                }
+                let rows = self.slice(table, query);
+                Ok(QueryResult::Solutions { variables, rows })
+            }
+            QueryForm::Ask => Ok(QueryResult::Boolean(!self.slice(solutions, query).is_empty())),
+            QueryForm::Construct(ref template) => {
+                let mut seen = BTreeSet::new();
+                let mut triples = Vec::new();
+                for solution in self.slice(solutions, query).iter() {
+                    let mut blanks = BTreeMap::new();
+                    for pattern in template.iter() {
+                        let quad = QuadPattern { triple: pattern.clone(), graph: None };
+                        if let Some(q) = self.instantiate(&quad, solution, &None, &mut blanks) {
+                            let triple = (q.subject, q.predicate, q.object);
+                            if seen.insert(triple.clone()) {
+                                triples.push(triple);
+                            }
+                        }
+                    }
+                }
+                Ok(QueryResult::Graph(triples))
+            }
+            QueryForm::Describe(ref resources) => {
+                let mut nodes = BTreeSet::new();
+                let solutions = self.slice(solutions, query);
+                for resource in resources.iter() {
+                    match resource {
+                        &TermPattern::Term(ref term) => nodes.extend(term.find(self.store)),
+                        &TermPattern::Variable(ref v) => nodes.extend(solutions.iter().filter_map(|s| s.get(v).and_then(|value| self.node_id(value)))),
+                    }
+                }
+                Ok(QueryResult::Graph(self.describe(nodes)?))
+            }
+        }
+    }
+
+    fn slice<T>(&self, items: Vec<T>, query: &Query) -> Vec<T> {
+        let limit = query.limit.unwrap_or(usize::MAX);
+        items.into_iter().skip(query.offset).take(limit).collect()
+    }
+
+    /* The concise bounded description of each node: its triples in any graph, following blank objects. */
+    fn describe(&self, nodes: BTreeSet<InternalID>) -> Result<Vec<(Term, Term, Term)>, String> {
+        let mut pending: Vec<InternalID> = nodes.into_iter().collect();
+        let mut visited = BTreeSet::new();
+        let mut seen = BTreeSet::new();
+        let mut triples = Vec::new();
+        while let Some(node) = pending.pop() {
+            if !visited.insert(node.clone()) {
+                continue;
+            }
+            for (_, s, p, o) in self.store.search_engine_entailed(None, Some(node.clone()), None, None) {
+                self.guard.work()?;
+                let object = Term::from_node(self.store, &o)?;
+                if let Term::Blank(_) = object {
+                    pending.push(o.clone());
+                }
+                let triple = (Term::from_node(self.store, &s)?, Term::from_node(self.store, &p)?, object);
+                if seen.insert(triple.clone()) {
+                    triples.push(triple);
+                }
+            }
+        }
+        Ok(triples)
+    }
+
+    fn group(&self, query: &Query, solutions: Vec<Solution>, graph: Option<&GraphID>) -> Vec<Row> {
+        if !query.is_grouped() {
+            return solutions.into_iter().map(|solution| Row { solution, group: None }).collect();
+        }
+        let mut groups: Vec<(Vec<Option<Term>>, Vec<Solution>)> = Vec::new();
+        let mut index: BTreeMap<Vec<Option<Term>>, usize> = BTreeMap::new();
+        for solution in solutions {
+            let key: Vec<Option<Term>> = query.group_by.iter().map(|&(ref e, _)| self.evaluate(e, &solution, None, graph)).collect();
+            let position = *index.entry(key.clone()).or_insert_with(|| { groups.push((key, Vec::new())); groups.len() - 1 });
+            groups[position].1.push(solution);
+        }
+        /* Aggregating without GROUP BY makes one group, even of no solutions. */
+        if groups.is_empty() && query.group_by.is_empty() {
+            groups.push((Vec::new(), Vec::new()));
+        }
+        let mut rows = Vec::new();
+        for (key, members) in groups {
+            let mut solution = Solution::new();
+            for (value, &(ref expression, ref alias)) in key.into_iter().zip(query.group_by.iter()) {
+                let name = match (alias, expression) {
+                    (&Some(ref alias), _) => alias.clone(),
+                    (&None, &Expression::Variable(ref v)) => v.clone(),
+                    _ => continue,
+                };
+                if let Some(value) = value {
+                    solution.insert(name, Value::Computed(value));
+                }
+            }
+            let keep = query.having.iter().all(|condition| {
+                self.evaluate(condition, &solution, Some(&members), graph).and_then(|t| effective_boolean_value(&t)).unwrap_or(false)
+            });
+            if keep {
+                rows.push(Row { solution, group: Some(members) });
+            }
+        }
+        rows
+    }
+
+    /// The solutions of `pattern` with `graph` as the active graph; `None` is a graph with no quads.
+    pub fn evaluate_pattern(&self, pattern: &GraphPattern, graph: Option<&GraphID>) -> Result<Vec<Solution>, String> {
+        let started = self.enter(|| {
+            let (operator, detail) = explain::pattern_operator(pattern);
+            (operator.to_owned(), detail)
+        });
+        let solutions = self.guard.check().and_then(|_| self.evaluate_operator(pattern, graph)).and_then(|solutions| {
+            self.guard.rows(solutions.len(), solutions.first().map(|s| s.len()).unwrap_or(0))?;
+            Ok(solutions)
+        });
+        self.leave(started, solutions.as_ref().map(|s| s.len()).unwrap_or(0));
+        solutions
+    }
+
+    fn evaluate_operator(&self, pattern: &GraphPattern, graph: Option<&GraphID>) -> Result<Vec<Solution>, String> {
+        match pattern {
+            &GraphPattern::Bgp(ref triples) => match graph {
+                Some(graph) => {
+                    let plan = self.planner.plan_bgp(triples, graph);
+                    self.set_estimate(plan.steps.last().map(|step| step.estimated_rows).unwrap_or(1.0));
+                    self.execute_bgp(&plan, graph)
+                }
+                None if triples.is_empty() => Ok(vec![Solution::new()]),
+                None => Ok(Vec::new()),
+            },
+            /* A path is followed from each solution on its left, rather than from every node. */
+            &GraphPattern::Join(ref left_pattern, ref right_pattern) if right_pattern.is_path() => {
+                let left = self.evaluate_pattern(left_pattern, graph)?;
+                let started = self.enter(|| {
+                    let (operator, detail) = explain::pattern_operator(right_pattern);
+                    (operator.to_owned(), detail)
+                });
+                let mut joined = Vec::new();
+                let result = match **right_pattern {
+                    GraphPattern::Path(ref subject, ref path, ref object) => {
+                        let path = PropertyPath::from_expression(path, self.store);
+                        left.iter().try_for_each(|a| self.match_path(subject, &path, object, a, graph, &mut joined))
+                    }
+                    _ => Ok(()),
+                };
+                self.leave(started, joined.len());
+                result.map(|_| joined)
+            }
+            /* A SERVICE is asked only for the solutions compatible with those on its left. */
+            &GraphPattern::Join(ref left_pattern, ref right_pattern) if right_pattern.is_service() => {
+                let left = self.evaluate_pattern(left_pattern, graph)?;
+                let started = self.enter(|| {
+                    let (operator, detail) = explain::pattern_operator(right_pattern);
+                    (operator.to_owned(), detail)
+                });
+                let joined = match **right_pattern {
+                    GraphPattern::Service(ref name, ref inner, silent) => self.join_service(name, inner, silent, left),
+                    _ => Ok(left),
+                };
+                self.leave(started, joined.as_ref().map(|s| s.len()).unwrap_or(0));
+                joined
+            }
+            &GraphPattern::Join(ref left_pattern, ref right_pattern) => {
+                let left = self.evaluate_pattern(left_pattern, graph)?;
+                let right = self.evaluate_pattern(right_pattern, graph)?;
+                let table = self.join_table(left_pattern, right_pattern, &right);
+                let mut joined = Vec::new();
+                for a in left.iter() {
+                    self.guard.work()?;
+                    for b in self.join_candidates(a, &right, &table) {
+                        self.push(&mut joined, self.merge(a, b))?;
+                    }
+                }
+                Ok(joined)
+            }
+            &GraphPattern::LeftJoin(ref left_pattern, ref right_pattern, ref condition) => {
+                let left = self.evaluate_pattern(left_pattern, graph)?;
+                let right = self.evaluate_pattern(right_pattern, graph)?;
+                let table = self.join_table(left_pattern, right_pattern, &right);
+                let mut joined = Vec::new();
+                for a in left.iter() {
+                    self.guard.work()?;
+                    let mut matched = false;
+                    for b in self.join_candidates(a, &right, &table) {
+                        let merged = self.merge(a, b);
+                        let holds = condition.as_ref().map(|c| self.holds(c, &merged, graph)).unwrap_or(true);
+                        if holds {
+                            self.push(&mut joined, merged)?;
+                            matched = true;
+                        }
+                    }
+                    if !matched {
+                        self.push(&mut joined, a.clone())?;
+                    }
+                }
+                Ok(joined)
+            }
+            &GraphPattern::Union(ref left, ref right) => {
+                let mut solutions = self.evaluate_pattern(left, graph)?;
+                solutions.extend(self.evaluate_pattern(right, graph)?);
+                Ok(solutions)
+            }
+            &GraphPattern::Minus(ref left, ref right) => {
+                let right = self.evaluate_pattern(right, graph)?;
+                let mut remaining = Vec::new();
+                for a in self.evaluate_pattern(left, graph)? {
+                    self.guard.work()?;
+                    if !right.iter().any(|b| self.compatible(&a, b) && a.keys().any(|k| b.contains_key(k))) {
+                        remaining.push(a);
+                    }
+                }
+                Ok(remaining)
+            }
+            &GraphPattern::Filter(ref condition, ref inner) => {
+                Ok(self.evaluate_pattern(inner, graph)?.into_iter().filter(|s| self.holds(condition, s, graph)).collect())
+            }
+            &GraphPattern::Graph(ref name, ref inner) => match name {
+                &TermPattern::Term(ref term) => {
+                    let id = term.find(self.store).filter(|id| *id != InternalID(0.into()));
+                    self.evaluate_pattern(inner, id.as_ref())
+                }
+                &TermPattern::Variable(ref v) => {
+                    let mut solutions = Vec::new();
+                    for g in self.named_graphs() {
+                        for mut solution in self.evaluate_pattern(inner, Some(&g))? {
+                            let bound = Value::Node(g.clone());
+                            if solution.get(v).map(|existing| self.same_value(existing, &bound)).unwrap_or(true) {
+                                solution.insert(v.clone(), bound);
+                                self.push(&mut solutions, solution)?;
+                            }
+                        }
+                    }
+                    Ok(solutions)
+                }
+            },
+            &GraphPattern::Extend(ref inner, ref variable, ref expression) => {
+                let mut solutions = self.evaluate_pattern(inner, graph)?;
+                for solution in solutions.iter_mut() {
+                    if let Some(value) = self.evaluate(expression, solution, None, graph) {
+                        solution.insert(variable.clone(), Value::Computed(value));
+                    }
+                }
+                Ok(solutions)
+            }
+            &GraphPattern::Path(ref subject, ref path, ref object) => {
+                let mut solutions = Vec::new();
+                self.match_path(subject, &PropertyPath::from_expression(path, self.store), object, &Solution::new(), graph, &mut solutions)?;
+                Ok(solutions)
+            }
+            &GraphPattern::Service(ref name, ref inner, silent) => self.join_service(name, inner, silent, vec![Solution::new()]),
+            &GraphPattern::Values(ref variables, ref rows) => {
+                Ok(rows.iter().map(|row| {
+                    variables.iter().zip(row.iter())
+                        .filter_map(|(v, t)| t.as_ref().map(|t| (v.clone(), t.find(self.store).map(Value::Node).unwrap_or_else(|| Value::Computed(t.clone())))))
+                        .collect()
+                }).collect())
+            }
+        }
+    }
+
+    /* Joins `left` with the solutions of `inner` at the endpoint `name`, sending the values bound on
+       the left in batches of VALUES rows. A SILENT service that fails leaves `left` as it is, and
+       one the store does not allow fails without being asked. */
+    fn join_service(&self, name: &TermPattern, inner: &GraphPattern, silent: bool, left: Vec<Solution>) -> Result<Vec<Solution>, String> {
+        let mut joined = Vec::new();
+        let mut endpoints: Vec<(String, Vec<Solution>)> = Vec::new();
+        for a in left {
+            let endpoint = match name {
+                &TermPattern::Term(Term::Iri(ref iri)) => Some(iri.clone()),
+                &TermPattern::Variable(ref v) => match a.get(v).and_then(|value| self.term(value)) {
+                    Some(Term::Iri(iri)) => Some(iri),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match endpoint {
+                Some(endpoint) => match endpoints.iter().position(|&(ref e, _)| *e == endpoint) {
+                    Some(i) => endpoints[i].1.push(a),
+                    None => endpoints.push((endpoint, vec![a])),
+                },
+                None if silent => self.push(&mut joined, a)?,
+                None => return Err(format!("SERVICE {} is not bound to an IRI.", explain::term_pattern_text(name))),
+            }
+        }
+        let variables: Vec<String> = inner.variables().into_iter().filter(|v| !v.starts_with("_:")).collect();
+        for (endpoint, solutions) in endpoints {
+            let allowed = self.store.borrow_allowed_services().map(|services| services.contains(&endpoint)).unwrap_or(true);
+            let bound: Vec<String> = variables.iter().filter(|v| solutions.iter().any(|s| s.contains_key(*v))).cloned().collect();
+            let batch_size = if bound.is_empty() { solutions.len() } else { SERVICE_BATCH_SIZE };
+            for batch in solutions.chunks(batch_size) {
+                self.guard.check()?;
+                let mut rows: Vec<Vec<Option<Term>>> = Vec::new();
+                for s in batch.iter() {
+                    let row: Vec<Option<Term>> = bound.iter().map(|v| s.get(v).and_then(|value| self.term(value))).collect();
+                    if !rows.contains(&row) {
+                        rows.push(row);
+                    }
+                }
+                let query = federation::service_query(inner, &bound, &rows);
+                let fetched = if allowed {
+                    federation::fetch(self.store.borrow_http_client(), &endpoint, &query, self.guard.remaining_time())
+                } else {
+                    Err(format!("SERVICE <{}> is not an allowed endpoint.", endpoint))
+                };
+                match fetched {
+                    Ok((names, found)) => {
+                        let found = self.remote_solutions(&names, found);
+                        for a in batch.iter() {
+                            self.guard.work()?;
+                            for b in self.join_candidates(a, &found, &None) {
+                                self.push(&mut joined, self.merge(a, b))?;
+                            }
+                        }
+                    }
+                    Err(_) if silent => for a in batch.iter() {
+                        self.push(&mut joined, a.clone())?;
+                    },
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(joined)
+    }
+
+    /* Solutions from a remote endpoint, with its terms as store nodes where the store holds them and
+       its blank nodes relabelled, since labels only mean something within one response. */
+    fn remote_solutions(&self, variables: &[String], rows: Vec<Vec<Option<Term>>>) -> Vec<Solution> {
+        let mut labels: HashMap<String, String> = HashMap::new();
+        rows.into_iter().map(|row| {
+            variables.iter().zip(row).filter_map(|(v, t)| t.map(|t| {
+                let value = match t {
+                    Term::Blank(label) => Value::Computed(Term::Blank(labels.entry(label).or_insert_with(fresh_blank_label).clone())),
+                    t => t.find(self.store).map(Value::Node).unwrap_or_else(|| Value::Computed(t)),
+                };
+                (v.clone(), value)
+            })).collect()
+        }).collect()
+    }
+
+    /* A hash table over `right` when the planner joins the two patterns by hashing. */
+    fn join_table(&self, left: &GraphPattern, right: &GraphPattern, solutions: &[Solution]) -> Option<JoinTable> {
+        match planner::choose_join(left, right) {
+            (JoinAlgorithm::Hash, variables) => Some((self.hash_table(solutions, &variables), variables)),
+            _ => None,
+        }
+    }
+
+    /// The solutions of a basic graph pattern plan in `graph`.
+    pub fn execute_bgp(&self, plan: &BgpPlan, graph: &GraphID) -> Result<Vec<Solution>, String> {
+        let mut solutions = vec![Solution::new()];
+        for (i, step) in plan.steps.iter().enumerate() {
+            let started = self.enter(|| explain::step_operator(step, i == 0));
+            self.set_estimate(step.estimated_rows);
+            solutions = match step.join {
+                JoinAlgorithm::NestedLoop => {
+                    let mut next = Vec::new();
+                    for solution in solutions.iter() {
+                        self.match_triple(&step.pattern, step.index, solution, graph, &mut next)?;
+                    }
+                    next
+                }
+                JoinAlgorithm::Hash => {
+                    let mut scanned = Vec::new();
+                    self.match_triple(&step.pattern, step.index, &Solution::new(), graph, &mut scanned)?;
+                    let table = Some((self.hash_table(&scanned, &step.join_variables), step.join_variables.clone()));
+                    let mut next = Vec::new();
+                    for solution in solutions.iter() {
+                        self.guard.work()?;
+                        for found in self.join_candidates(solution, &scanned, &table) {
+                            self.push(&mut next, self.merge(solution, found))?;
+                        }
+                    }
+                    next
+                }
+                JoinAlgorithm::Merge => {
+                    let mut scanned = Vec::new();
+                    self.match_triple(&step.pattern, step.index, &Solution::new(), graph, &mut scanned)?;
+                    self.merge_join(solutions, scanned, &step.join_variables[0])?
+                }
+            };
+            self.leave(started, solutions.len());
+            if solutions.is_empty() {
+                break;
+            }
+        }
+        Ok(solutions)
+    }
+
+    fn match_triple(&self, triple: &TriplePattern, index: Option<IndexKind>, solution: &Solution, graph: &GraphID, out: &mut Vec<Solution>) -> Result<(), String> {
+        if let TermPattern::Term(Term::Iri(ref predicate)) = triple.predicate {
+            if predicate == fulltext::FULLTEXT_MATCH_PREDICATE {
+                return self.match_fulltext(triple, solution, graph, out);
+            }
+        }
+        let positions = [&triple.subject, &triple.predicate, &triple.object];
+        let mut bound: Vec<Option<InternalID>> = Vec::with_capacity(3);
+        for position in positions.iter() {
+            let id = match **position {
+                TermPattern::Term(ref term) => match term.find(self.store) {
+                    Some(id) => Some(id),
+                    None => return Ok(()),
+                },
+                TermPattern::Variable(ref v) => match solution.get(v) {
+                    Some(value) => match self.node_id(value) {
+                        Some(id) => Some(id),
+                        None => return Ok(()),
+                    },
+                    None => None,
+                },
+            };
+            bound.push(id);
+        }
+        let quads: Box<Iterator<Item=(GraphID, InternalID, InternalID, InternalID)>> = match (self.entailment.as_ref(), index) {
+            (Some(entailment), _) => {
+                let triples = entailment.matches(graph, bound[0].clone(), bound[1].clone(), bound[2].clone(), &self.guard)?;
+                Box::new(triples.into_iter().map(|(s, p, o)| (graph.clone(), s, p, o)))
+            }
+            (None, Some(index)) => self.store.search_engine_entailed_by_index(index, Some(graph.clone()), bound[0].clone(), bound[1].clone(), bound[2].clone()),
+            (None, None) => self.store.search_engine_entailed(Some(graph.clone()), bound[0].clone(), bound[1].clone(), bound[2].clone()),
+        };
+        for (_, s, p, o) in quads {
+            self.guard.work()?;
+            let mut extended = solution.clone();
+            let mut consistent = true;
+            for (i, found) in [s, p, o].iter().enumerate() {
+                if let (&TermPattern::Variable(ref v), None) = (positions[i], &bound[i]) {
+                    match extended.get(v) {
+                        Some(&Value::Node(ref existing)) if existing != found => consistent = false,
+                        Some(_) => {}
+                        None => { extended.insert(v.clone(), Value::Node(found.clone())); }
+                    }
+                }
+            }
+            if consistent {
+                self.push(out, extended)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path_end(&self, pattern: &TermPattern, solution: &Solution) -> PathEnd {
+        let term = match *pattern {
+            TermPattern::Term(ref term) => term.clone(),
+            TermPattern::Variable(ref v) => match solution.get(v) {
+                Some(value) => match self.node_id(value) {
+                    Some(id) => return PathEnd::Node(id),
+                    None => match self.term(value) {
+                        Some(term) => term,
+                        None => return PathEnd::Free(v.clone()),
+                    },
+                },
+                None => return PathEnd::Free(v.clone()),
+            },
+        };
+        match term.find(self.store) {
+            Some(id) => PathEnd::Node(id),
+            None => PathEnd::Absent(term),
+        }
+    }
+
+    /* Extends `solution` with each way `subject path object` matches in `graph`, searching from
+       whichever end is bound. */
+    fn match_path(&self, subject: &TermPattern, path: &PropertyPath, object: &TermPattern, solution: &Solution, graph: Option<&GraphID>, out: &mut Vec<Solution>) -> Result<(), String> {
+        let graph = match graph {
+            Some(graph) => graph,
+            None => return Ok(()),
+        };
+        let bind = |variable: &str, value: Value, out: &mut Vec<Solution>| -> Result<(), String> {
+            let mut extended = solution.clone();
+            extended.insert(variable.to_owned(), value);
+            self.push(out, extended)
+        };
+        match (self.path_end(subject, solution), self.path_end(object, solution)) {
+            (PathEnd::Node(s), end) => {
+                let reached = path.evaluate_guarded(self.store, Some(graph), &s, &self.guard)?;
+                match end {
+                    PathEnd::Free(v) => for node in reached {
+                        bind(&v, Value::Node(node), out)?;
+                    },
+                    PathEnd::Node(o) => if reached.contains(&o) {
+                        self.push(out, solution.clone())?;
+                    },
+                    PathEnd::Absent(_) => {}
+                }
+            }
+            (PathEnd::Free(v), PathEnd::Node(o)) => for node in path.evaluate_inverse_guarded(self.store, Some(graph), &o, &self.guard)? {
+                bind(&v, Value::Node(node), out)?;
+            },
+            (PathEnd::Free(a), PathEnd::Free(b)) => for (s, o) in path.pairs(self.store, Some(graph), &self.guard)? {
+                self.guard.work()?;
+                if a == b {
+                    if s == o {
+                        bind(&a, Value::Node(s), out)?;
+                    }
+                } else {
+                    let mut extended = solution.clone();
+                    extended.insert(a.clone(), Value::Node(s));
+                    extended.insert(b.clone(), Value::Node(o));
+                    self.push(out, extended)?;
+                }
+            },
+            /* A term the store does not hold only matches itself, by a zero-length path. */
+            (PathEnd::Absent(t), PathEnd::Free(v)) | (PathEnd::Free(v), PathEnd::Absent(t)) => if path.matches_empty() {
+                bind(&v, Value::Computed(t), out)?;
+            },
+            (PathEnd::Absent(a), PathEnd::Absent(b)) => if a == b && path.matches_empty() {
+                self.push(out, solution.clone())?;
+            },
+            (PathEnd::Absent(_), PathEnd::Node(_)) => {}
+        }
+        Ok(())
+    }
+
+    /* `?s <fulltext#match> "query"` binds the subjects whose literals in `graph` match the query. */
+    fn match_fulltext(&self, triple: &TriplePattern, solution: &Solution, graph: &GraphID, out: &mut Vec<Solution>) -> Result<(), String> {
+        let query = match triple.object {
+            TermPattern::Term(ref term) => Some(term.clone()),
+            TermPattern::Variable(ref v) => solution.get(v).and_then(|value| self.term(value)),
+        };
+        let (text, lang) = match query {
+            Some(Term::Literal(text, _, lang)) => (text, lang),
+            _ => return Err("The fulltext match pattern needs a literal query as its object.".to_owned()),
+        };
+        let subject = match triple.subject {
+            TermPattern::Term(ref term) => Some(term.find(self.store)),
+            TermPattern::Variable(ref v) => solution.get(v).map(|value| self.node_id(value)),
+        };
+        if let Some(None) = subject {
+            return Ok(());
+        }
+        for (found, _) in self.store.fulltext_search_subjects(&text, lang.as_deref(), None, Some(graph.clone()))? {
+            match subject {
+                Some(Some(ref s)) if *s != found => {}
+                Some(_) => self.push(out, solution.clone())?,
+                None => {
+                    let mut extended = solution.clone();
+                    extended.insert(triple.subject.as_variable().unwrap_or_default().to_owned(), Value::Node(found));
+                    self.push(out, extended)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn holds(&self, condition: &Expression, solution: &Solution, graph: Option<&GraphID>) -> bool {
+        self.evaluate(condition, solution, None, graph).and_then(|t| effective_boolean_value(&t)).unwrap_or(false)
+    }
+
+    /// The value of `expression` for `solution`, or `None` for an error or unbound variable.
+    /// Aggregates are computed over `group`.
+    pub fn evaluate(&self, expression: &Expression, solution: &Solution, group: Option<&[Solution]>, graph: Option<&GraphID>) -> Option<Term> {
+        let eval = |e: &Expression| self.evaluate(e, solution, group, graph);
+        match expression {
+            &Expression::Variable(ref v) => solution.get(v).and_then(|value| self.term(value)),
+            &Expression::Constant(ref term) => Some(term.clone()),
+            &Expression::Or(ref a, ref b) => {
+                let (x, y) = (eval(a).and_then(|t| effective_boolean_value(&t)), eval(b).and_then(|t| effective_boolean_value(&t)));
+                match (x, y) {
+                    (Some(true), _) | (_, Some(true)) => Some(boolean(true)),
+                    (Some(false), Some(false)) => Some(boolean(false)),
+                    _ => None,
+                }
+            }
+            &Expression::And(ref a, ref b) => {
+                let (x, y) = (eval(a).and_then(|t| effective_boolean_value(&t)), eval(b).and_then(|t| effective_boolean_value(&t)));
+                match (x, y) {
+                    (Some(false), _) | (_, Some(false)) => Some(boolean(false)),
+                    (Some(true), Some(true)) => Some(boolean(true)),
+                    _ => None,
+                }
+            }
+            &Expression::Not(ref a) => eval(a).and_then(|t| effective_boolean_value(&t)).map(|b| boolean(!b)),
+            &Expression::Compare(operator, ref a, ref b) => {
+                let (x, y) = (eval(a)?, eval(b)?);
+                let result = match operator {
+                    ComparisonOperator::Equal => equal_values(&x, &y)?,
+                    ComparisonOperator::NotEqual => !equal_values(&x, &y)?,
+                    ComparisonOperator::Less => compare_values(&x, &y)? == Ordering::Less,
+                    ComparisonOperator::LessOrEqual => compare_values(&x, &y)? != Ordering::Greater,
+                    ComparisonOperator::Greater => compare_values(&x, &y)? == Ordering::Greater,
+                    ComparisonOperator::GreaterOrEqual => compare_values(&x, &y)? != Ordering::Less,
+                };
+                Some(boolean(result))
+            }
+            &Expression::Arithmetic(operator, ref a, ref b) => arithmetic(operator, number(&eval(a)?)?, number(&eval(b)?)?).map(|n| n.to_term()),
+            &Expression::Negate(ref a) => {
+                let negated = match number(&eval(a)?)? {
+                    Number::Integer(i) => Number::Integer(i.checked_neg()?),
+                    Number::Decimal(d) => Number::Decimal(-d),
+                    Number::Double(d) => Number::Double(-d),
+                };
+                Some(negated.to_term())
+            }
+            &Expression::In(ref a, ref list, negated) => {
+                let x = eval(a)?;
+                let mut error = false;
+                for item in list.iter() {
+                    match eval(item).and_then(|y| equal_values(&x, &y)) {
+                        Some(true) => return Some(boolean(!negated)),
+                        Some(false) => {}
+                        None => error = true,
+                    }
+                }
+                if error { None } else { Some(boolean(negated)) }
+            }
+            &Expression::Bound(ref v) => Some(boolean(solution.contains_key(v))),
+            &Expression::Exists(ref pattern, negated) => {
+                let bindings: BTreeMap<String, Term> = solution.iter()
+                    .filter_map(|(v, value)| self.term(value).map(|t| (v.clone(), t)))
+                    .collect();
+                let solutions = self.evaluate_pattern(&pattern.substitute(&bindings), graph).ok()?;
+                let exists = solutions.iter().any(|s| self.compatible(solution, s));
+                Some(boolean(exists != negated))
+            }
+            &Expression::Aggregate(ref aggregate) => self.aggregate(aggregate, group.unwrap_or(&[]), graph),
+            &Expression::Call(ref name, ref args) => self.call(name, args, solution, group, graph),
+        }
+    }
+
+    fn aggregate(&self, aggregate: &Aggregate, group: &[Solution], graph: Option<&GraphID>) -> Option<Term> {
+        let mut values: Vec<Term> = match aggregate.expression {
+            Some(ref e) => group.iter().filter_map(|s| self.evaluate(e, s, None, graph)).collect(),
+            None => {
+                let count = if aggregate.distinct {
+                    group.iter().map(|s| s.iter().map(|(k, v)| (k.clone(), self.term(v))).collect::<Vec<_>>()).collect::<BTreeSet<_>>().len()
+                } else {
+                    group.len()
+                };
+                return Some(Number::Integer(count as i64).to_term());
+            }
+        };
+        if aggregate.distinct {
+            let mut seen = BTreeSet::new();
+            values.retain(|v| seen.insert(v.clone()));
+        }
+        match aggregate.function {
+            AggregateFunction::Count => Some(Number::Integer(values.len() as i64).to_term()),
+            AggregateFunction::Sum | AggregateFunction::Avg => {
+                let mut total = Number::Integer(0);
+                for v in values.iter() {
+                    total = arithmetic(ArithmeticOperator::Add, total, number(v)?)?;
+                }
+                if aggregate.function == AggregateFunction::Sum {
+                    Some(total.to_term())
+                } else if values.is_empty() {
+                    Some(Number::Integer(0).to_term())
+                } else {
+                    let divided = arithmetic(ArithmeticOperator::Divide, total, Number::Integer(values.len() as i64))?;
+                    Some(divided.to_term())
+                }
+            }
+            AggregateFunction::Min => values.into_iter().min_by(|a, b| order_terms(Some(a), Some(b))),
+            AggregateFunction::Max => values.into_iter().max_by(|a, b| order_terms(Some(a), Some(b))),
+            AggregateFunction::Sample => values.into_iter().next(),
+            AggregateFunction::GroupConcat => {
+                let parts: Option<Vec<String>> = values.iter().map(|v| string_value(v).map(|(s, _)| s.to_owned())
+                    .or_else(|| if let &Term::Literal(ref s, _, _) = v { Some(s.clone()) } else { None })).collect();
+                Some(simple_literal(parts?.join(&aggregate.separator)))
+            }
+        }
+    }
+
+    fn call(&self, name: &str, args: &[Expression], solution: &Solution, group: Option<&[Solution]>, graph: Option<&GraphID>) -> Option<Term> {
+        let eval = |i: usize| args.get(i).and_then(|e| self.evaluate(e, solution, group, graph));
+        match name {
+            "IF" => {
+                let condition = eval(0).and_then(|t| effective_boolean_value(&t))?;
+                return if condition { eval(1) } else { eval(2) };
+            }
+            "COALESCE" => return (0..args.len()).filter_map(&eval).next(),
+            "BNODE" => return Some(Term::Blank(fresh_blank_label())),
+            _ => {}
+        }
+        let values: Vec<Term> = (0..args.len()).map(eval).collect::<Option<Vec<Term>>>()?;
+        let arg = |i: usize| values.get(i);
+        let string_arg = |i: usize| arg(i).and_then(string_value);
+        match name {
+            "STR" => match arg(0)? {
+                &Term::Iri(ref iri) => Some(simple_literal(iri.clone())),
+                &Term::Literal(ref lexical, _, _) => Some(simple_literal(lexical.clone())),
+                _ => None,
+            },
+            "LANG" => match arg(0)? {
+                &Term::Literal(_, _, ref lang) => Some(simple_literal(lang.clone().unwrap_or_default())),
+                _ => None,
+            },
+            "LANGMATCHES" => {
+                let (tag, range) = (string_arg(0)?.0.to_lowercase(), string_arg(1)?.0.to_lowercase());
+                let matches = if range == "*" { !tag.is_empty() } else { tag == range || tag.starts_with(&format!("{}-", range)) };
+                Some(boolean(matches))
+            }
+            "DATATYPE" => match arg(0)? {
+                &Term::Literal(_, Some(ref datatype), None) => Some(Term::Iri(datatype.clone())),
+                &Term::Literal(_, None, None) => Some(Term::Iri(vocab::XSD_STRING.to_owned())),
+                &Term::Literal(_, _, Some(_)) => Some(Term::Iri(literal::LANG_STRING_URI.to_owned())),
+                _ => None,
+            },
+            "IRI" | "URI" => match arg(0)? {
+                &Term::Iri(ref iri) => Some(Term::Iri(iri.clone())),
+                &Term::Literal(ref lexical, None, None) => Some(Term::Iri(lexical.clone())),
+                _ => None,
+            },
+            "ABS" | "CEIL" | "FLOOR" | "ROUND" => numeric_function(name, number(arg(0)?)?).map(|n| n.to_term()),
+            "CONCAT" => {
+                let parts: Vec<(&str, Option<&str>)> = (0..values.len()).map(&string_arg).collect::<Option<Vec<_>>>()?;
+                let lang = parts.first().and_then(|p| p.1).filter(|l| parts.iter().all(|p| p.1 == Some(*l)));
+                let joined: String = parts.iter().map(|p| p.0).collect();
+                Some(Term::Literal(joined, None, lang.map(|l| l.to_owned())))
+            }
+            "STRLEN" => Some(Number::Integer(string_arg(0)?.0.chars().count() as i64).to_term()),
+            "UCASE" | "LCASE" => {
+                let (value, lang) = string_arg(0)?;
+                let changed = if name == "UCASE" { value.to_uppercase() } else { value.to_lowercase() };
+                Some(Term::Literal(changed, None, lang.map(|l| l.to_owned())))
+            }
+            "ENCODE_FOR_URI" => Some(simple_literal(encode_for_uri(string_arg(0)?.0))),
+            "CONTAINS" => Some(boolean(string_arg(0)?.0.contains(string_arg(1)?.0))),
+            "STRSTARTS" => Some(boolean(string_arg(0)?.0.starts_with(string_arg(1)?.0))),
+            "STRENDS" => Some(boolean(string_arg(0)?.0.ends_with(string_arg(1)?.0))),
+            "STRBEFORE" | "STRAFTER" => {
+                let ((value, lang), (needle, _)) = (string_arg(0)?, string_arg(1)?);
+                let result = match value.find(needle) {
+                    Some(i) if name == "STRBEFORE" => Term::Literal(value[..i].to_owned(), None, lang.map(|l| l.to_owned())),
+                    Some(i) => Term::Literal(value[i + needle.len()..].to_owned(), None, lang.map(|l| l.to_owned())),
+                    None => simple_literal(String::new()),
+                };
+                Some(result)
+            }
+            "SUBSTR" => {
+                let (value, lang) = string_arg(0)?;
+                let start = number(arg(1)?)?.as_f64().round() as i64;
+                let chars: Vec<char> = value.chars().collect();
+                let end = match arg(2) {
+                    Some(length) => start.saturating_add(number(length)?.as_f64().round() as i64),
+                    None => chars.len() as i64 + 1,
+                };
+                let (from, to) = (::std::cmp::max(start, 1) as usize - 1, ::std::cmp::min(::std::cmp::max(end, 1), chars.len() as i64 + 1) as usize - 1);
+                let substring: String = if from < to { chars[from..to].iter().collect() } else { String::new() };
+                Some(Term::Literal(substring, None, lang.map(|l| l.to_owned())))
+            }
+            "REGEX" => {
+                let flags = match arg(2) { Some(f) => string_value(f)?.0, None => "" };
+                let pattern = string_arg(1)?.0;
+                let constant = args[1..].iter().all(|a| matches!(a, &Expression::Constant(_)));
+                let regex = if constant {
+                    let key = (pattern.to_owned(), flags.to_owned());
+                    let mut regexes = self.regexes.borrow_mut();
+                    regexes.entry(key).or_insert_with(|| Regex::new(pattern, flags).ok().map(Rc::new)).clone()?
+                } else {
+                    Rc::new(Regex::new(pattern, flags).ok()?)
+                };
+                Some(boolean(regex.is_match(string_arg(0)?.0)))
+            }
+            "ISIRI" | "ISURI" => Some(boolean(matches!(arg(0)?, &Term::Iri(_)))),
+            "ISBLANK" => Some(boolean(matches!(arg(0)?, &Term::Blank(_)))),
+            "ISLITERAL" => Some(boolean(matches!(arg(0)?, &Term::Literal(..)))),
+            "ISNUMERIC" => Some(boolean(number(arg(0)?).is_some())),
+            "SAMETERM" => Some(boolean(arg(0)? == arg(1)?)),
+            "STRDT" => match (string_arg(0)?, arg(1)?) {
+                ((value, None), &Term::Iri(ref datatype)) => {
+                    let datatype = if datatype == vocab::XSD_STRING { None } else { Some(datatype.clone()) };
+                    Some(Term::Literal(value.to_owned(), datatype, None))
+                }
+                _ => None,
+            },
+            "STRLANG" => match (string_arg(0)?, string_arg(1)?) {
+                ((value, None), (lang, None)) if !lang.is_empty() => Some(Term::Literal(value.to_owned(), None, Some(lang.to_owned()))),
+                _ => None,
+            },
+            datatype => if values.len() == 1 { cast(datatype, arg(0)?) } else { None },
+        }
+    }
+
+    /* ---- Updates ---- */
+
+    /* Fills a template quad from `solution`; `None` if a variable is unbound or the quad is not valid RDF. */
+    fn instantiate(&self, quad: &QuadPattern, solution: &Solution, default_graph: &Option<Term>, blanks: &mut BTreeMap<String, String>) -> Option<PatchQuad> {
+        let mut fill = |pattern: &TermPattern| -> Option<Term> {
+            match pattern {
+                &TermPattern::Term(ref term) => Some(term.clone()),
+                &TermPattern::Variable(ref v) if v.starts_with("_:") => {
+                    Some(Term::Blank(blanks.entry(v.clone()).or_insert_with(fresh_blank_label).clone()))
+                }
+                &TermPattern::Variable(ref v) => solution.get(v).and_then(|value| self.term(value)),
+            }
+        };
+        let subject = fill(&quad.triple.subject)?;
+        let predicate = fill(&quad.triple.predicate)?;
+        let object = fill(&quad.triple.object)?;
+        let graph = match quad.graph {
+            Some(ref g) => Some(fill(g)?),
+            None => default_graph.clone(),
+        };
+        match (&subject, &predicate, &graph) {
+            (&Term::Literal(..), _, _) | (_, &Term::Blank(_), _) | (_, &Term::Literal(..), _) | (_, _, &Some(Term::Literal(..))) => None,
+            _ => Some(PatchQuad { subject, predicate, object, graph }),
+        }
+    }
+
+    fn graph_quads(&self, graph: &GraphID) -> Result<Vec<PatchQuad>, String> {
+        let name = if *graph == InternalID(0.into()) { None } else { Some(Term::from_node(self.store, graph)?) };
+        let mut quads = Vec::new();
+        for (_, s, p, o) in self.store.search_engine_internal(Some(graph.clone()), None, None, None) {
+            self.guard.rows(quads.len() + 1, 4)?;
+            quads.push(PatchQuad { subject: Term::from_node(self.store, &s)?, predicate: Term::from_node(self.store, &p)?,
+                object: Term::from_node(self.store, &o)?, graph: name.clone() });
+        }
+        Ok(quads)
+    }
+
+    fn graph_id(&self, iri: &Option<String>) -> Option<GraphID> {
+        match *iri {
+            Some(ref iri) => Term::Iri(iri.clone()).find(self.store),
+            None => Some(InternalID(0.into())),
+        }
+    }
+
+    /// The changes an update operation makes, as a patch of deletions followed by insertions.
+    pub fn plan_update(&self, operation: &UpdateOperation) -> Result<Patch, String> {
+        let mut patch = Patch::new();
+        let no_solution = Solution::new();
+        match operation {
+            &UpdateOperation::InsertData(ref quads) => {
+                let mut blanks = BTreeMap::new();
+                for quad in quads.iter() {
+                    patch.push(PatchRow::Add(self.instantiate(quad, &no_solution, &None, &mut blanks).ok_or("Invalid quad in INSERT DATA.")?));
+                }
+            }
+            &UpdateOperation::DeleteData(ref quads) => {
+                let mut blanks = BTreeMap::new();
+                for quad in quads.iter() {
+                    patch.push(PatchRow::Delete(self.instantiate(quad, &no_solution, &None, &mut blanks).ok_or("Invalid quad in DELETE DATA.")?));
+                }
+            }
+            &UpdateOperation::DeleteWhere(ref quads) => {
+                let modify = UpdateOperation::Modify { with: None, delete: quads.clone(), insert: Vec::new(), pattern: Box::new(quads_to_pattern(quads)) };
+                return self.plan_update(&modify);
+            }
+            &UpdateOperation::Modify { ref with, ref delete, ref insert, ref pattern } => {
+                let target = with.as_ref().map(|w| Term::Iri(w.clone()));
+                let graph = match *with {
+                    Some(_) => self.graph_id(with),
+                    None => Some(InternalID(0.into())),
+                };
+                let solutions = self.evaluate_pattern(pattern, graph.as_ref())?;
+                let mut additions = Vec::new();
+                for solution in solutions.iter() {
+                    let mut blanks = BTreeMap::new();
+                    for quad in delete.iter() {
+                        if let Some(q) = self.instantiate(quad, solution, &target, &mut blanks) {
+                            patch.push(PatchRow::Delete(q));
+                        }
+                    }
+                    for quad in insert.iter() {
+                        if let Some(q) = self.instantiate(quad, solution, &target, &mut blanks) {
+                            additions.push(PatchRow::Add(q));
+                        }
+                    }
+                }
+                for row in additions {
+                    patch.push(row);
+                }
+            }
+            &UpdateOperation::Clear { ref target, .. } => {
+                let graphs = match *target {
+                    GraphTarget::Default => vec![InternalID(0.into())],
+                    GraphTarget::Named(ref iri) => self.graph_id(&Some(iri.clone())).into_iter().collect(),
+                    GraphTarget::AllNamed => self.named_graphs(),
+                    GraphTarget::All => self.store.graph_ids(),
+                };
+                for graph in graphs.iter() {
+                    for quad in self.graph_quads(graph)? {
+                        patch.push(PatchRow::Delete(quad));
+                    }
+                }
+            }
+            /* Graphs exist while they hold quads, so there is nothing to create. */
+            &UpdateOperation::Create { .. } => {}
+            &UpdateOperation::Add { ref from, ref to, .. } | &UpdateOperation::Copy { ref from, ref to, .. } | &UpdateOperation::Move { ref from, ref to, .. } => {
+                if from == to {
+                    return Ok(patch);
+                }
+                let replace = !matches!(operation, &UpdateOperation::Add { .. });
+                let target = to.as_ref().map(|t| Term::Iri(t.clone()));
+                if replace {
+                    if let Some(g) = self.graph_id(to) {
+                        for quad in self.graph_quads(&g)? {
+                            patch.push(PatchRow::Delete(quad));
+                        }
+                    }
+                }
+                let source = match self.graph_id(from) { Some(g) => self.graph_quads(&g)?, None => Vec::new() };
+                if let &UpdateOperation::Move { .. } = operation {
+                    for quad in source.iter() {
+                        patch.push(PatchRow::Delete(quad.clone()));
+                    }
+                }
+                for quad in source {
+                    patch.push(PatchRow::Add(PatchQuad { graph: target.clone(), ..quad }));
+                }
+            }
+            &UpdateOperation::Load { silent, ref source, .. } => {
+                if !silent {
+                    return Err(format!("LOAD <{}> is not supported.", source));
+                }
+            }
+        }
+        Ok(patch)
+    }
+}
+
+/* The WHERE pattern of `DELETE WHERE`, which is its own template. */
+fn quads_to_pattern(quads: &[QuadPattern]) -> GraphPattern {
+    let mut pattern = GraphPattern::empty();
+    for quad in quads.iter() {
+        let bgp = GraphPattern::Bgp(vec![quad.triple.clone()]);
+        pattern = match quad.graph {
+            Some(ref g) => pattern.join(GraphPattern::Graph(g.clone(), Box::new(bgp))),
+            None => pattern.join(bgp),
+        };
+    }
+    pattern
+}
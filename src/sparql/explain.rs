@@ -0,0 +1,243 @@
+use std::time::Duration;
+use json;
+use nquads;
+use sparql::algebra::*;
+use sparql::federation;
+use sparql::planner::{self, JoinAlgorithm, PlanStep};
+
+/// An operator of an evaluated query plan, with the planner's estimate where it made one and what
+/// evaluation found. An operator run more than once, like the inner pattern of `GRAPH ?g` once
+/// per graph, appears once with its rows and time totalled over its `loops`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanNode {
+    pub operator: String,
+    pub detail: String,
+    pub estimated_rows: Option<f64>,
+    pub actual_rows: usize,
+    pub loops: usize,
+    pub time: Duration,
+    pub children: Vec<PlanNode>,
+}
+
+fn milliseconds(time: &Duration) -> f64 {
+    time.as_secs() as f64 * 1000.0 + time.subsec_nanos() as f64 / 1_000_000.0
+}
+
+impl PlanNode {
+    pub fn new(operator: &str, detail: String) -> PlanNode {
+        PlanNode { operator: operator.to_owned(), detail, estimated_rows: None, actual_rows: 0, loops: 0, time: Duration::new(0, 0), children: Vec::new() }
+    }
+
+    /// Adds `node` as a child, or into an existing child for the same operator.
+    pub fn attach(&mut self, node: PlanNode) {
+        match self.children.iter().position(|c| c.operator == node.operator && c.detail == node.detail) {
+            Some(i) => {
+                let existing = &mut self.children[i];
+                existing.actual_rows += node.actual_rows;
+                existing.loops += node.loops;
+                existing.time += node.time;
+                existing.estimated_rows = existing.estimated_rows.or(node.estimated_rows);
+                for child in node.children {
+                    existing.attach(child);
+                }
+            }
+            None => self.children.push(node),
+        }
+    }
+
+    /// The plan as an indented tree, one operator per line.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        self.write_text(0, &mut text);
+        text
+    }
+
+    fn write_text(&self, depth: usize, text: &mut String) {
+        for _ in 0..depth {
+            text.push_str("  ");
+        }
+        text.push_str(&self.operator);
+        if !self.detail.is_empty() {
+            text.push(' ');
+            text.push_str(&self.detail);
+        }
+        text.push_str("  (");
+        if let Some(estimate) = self.estimated_rows {
+            text.push_str(&format!("estimated rows: {:.1}, ", estimate));
+        }
+        text.push_str(&format!("rows: {}", self.actual_rows));
+        if self.loops > 1 {
+            text.push_str(&format!(" in {} loops", self.loops));
+        }
+        text.push_str(&format!(", time: {:.3} ms)\n", milliseconds(&self.time)));
+        for child in self.children.iter() {
+            child.write_text(depth + 1, text);
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let estimate = match self.estimated_rows {
+            Some(e) if e.is_finite() => format!("{:.1}", e),
+            _ => "null".to_owned(),
+        };
+        let children: Vec<String> = self.children.iter().map(|c| c.to_json()).collect();
+        format!("{{\"operator\":{},\"detail\":{},\"estimated_rows\":{},\"actual_rows\":{},\"loops\":{},\"time_ms\":{:.3},\"children\":[{}]}}",
+                json::quote(&self.operator), json::quote(&self.detail), estimate, self.actual_rows, self.loops, milliseconds(&self.time), children.join(","))
+    }
+}
+
+pub fn term_pattern_text(pattern: &TermPattern) -> String {
+    match *pattern {
+        TermPattern::Variable(ref v) if v.starts_with("_:") => v.clone(),
+        TermPattern::Variable(ref v) => format!("?{}", v),
+        TermPattern::Term(ref term) => term.to_ntriples(),
+    }
+}
+
+pub fn triple_pattern_text(pattern: &TriplePattern) -> String {
+    format!("{} {} {}", term_pattern_text(&pattern.subject), term_pattern_text(&pattern.predicate), term_pattern_text(&pattern.object))
+}
+
+/// Property paths in SPARQL syntax, for plan details and SERVICE queries.
+pub fn path_text(path: &PathExpression) -> String {
+    let group = |path: &PathExpression| match *path {
+        PathExpression::Sequence(_) | PathExpression::Alternative(_) => format!("({})", path_text(path)),
+        _ => path_text(path),
+    };
+    let join = |paths: &[PathExpression], separator: &str| paths.iter().map(&group).collect::<Vec<String>>().join(separator);
+    match *path {
+        PathExpression::Iri(ref iri) => format!("<{}>", iri),
+        PathExpression::Inverse(ref inner) => format!("^{}", group(inner)),
+        PathExpression::Sequence(ref steps) => join(steps, "/"),
+        PathExpression::Alternative(ref options) => join(options, "|"),
+        PathExpression::ZeroOrMore(ref inner) => format!("{}*", group(inner)),
+        PathExpression::OneOrMore(ref inner) => format!("{}+", group(inner)),
+        PathExpression::ZeroOrOne(ref inner) => format!("{}?", group(inner)),
+        PathExpression::NegatedSet(ref forward, ref inverse) => {
+            let members: Vec<String> = forward.iter().map(|iri| format!("<{}>", iri)).chain(inverse.iter().map(|iri| format!("^<{}>", iri))).collect();
+            format!("!({})", members.join("|"))
+        }
+    }
+}
+
+fn variables_text(variables: &[String]) -> String {
+    variables.iter().map(|v| if v.starts_with("_:") { v.clone() } else { format!("?{}", v) }).collect::<Vec<String>>().join(", ")
+}
+
+/// Expressions in SPARQL syntax, for plan details and SERVICE queries.
+pub fn expression_text(expression: &Expression) -> String {
+    let binary = |operator: &str, a: &Expression, b: &Expression| format!("({} {} {})", expression_text(a), operator, expression_text(b));
+    match *expression {
+        Expression::Variable(ref v) => format!("?{}", v),
+        Expression::Constant(ref term) => term.to_ntriples(),
+        Expression::Or(ref a, ref b) => binary("||", a, b),
+        Expression::And(ref a, ref b) => binary("&&", a, b),
+        Expression::Not(ref a) => format!("!{}", expression_text(a)),
+        Expression::Compare(operator, ref a, ref b) => binary(match operator {
+            ComparisonOperator::Equal => "=",
+            ComparisonOperator::NotEqual => "!=",
+            ComparisonOperator::Less => "<",
+            ComparisonOperator::LessOrEqual => "<=",
+            ComparisonOperator::Greater => ">",
+            ComparisonOperator::GreaterOrEqual => ">=",
+        }, a, b),
+        Expression::Arithmetic(operator, ref a, ref b) => binary(match operator {
+            ArithmeticOperator::Add => "+",
+            ArithmeticOperator::Subtract => "-",
+            ArithmeticOperator::Multiply => "*",
+            ArithmeticOperator::Divide => "/",
+        }, a, b),
+        Expression::Negate(ref a) => format!("-{}", expression_text(a)),
+        Expression::In(ref a, ref list, negated) => format!("({} {}IN ({}))", expression_text(a), if negated { "NOT " } else { "" },
+                                                             list.iter().map(expression_text).collect::<Vec<String>>().join(", ")),
+        Expression::Bound(ref v) => format!("BOUND(?{})", v),
+        Expression::Call(ref name, ref args) => {
+            let name = if name.contains(':') { format!("<{}>", name) } else { name.clone() };
+            format!("{}({})", name, args.iter().map(expression_text).collect::<Vec<String>>().join(", "))
+        }
+        Expression::Exists(ref pattern, negated) => format!("{}EXISTS {{ {} }}", if negated { "NOT " } else { "" }, federation::pattern_text(pattern)),
+        Expression::Aggregate(ref aggregate) => {
+            let argument = aggregate.expression.as_ref().map(|e| expression_text(e)).unwrap_or_else(|| "*".to_owned());
+            let separator = if aggregate.function == AggregateFunction::GroupConcat && aggregate.separator != " " {
+                format!("; SEPARATOR=\"{}\"", nquads::escape_string(&aggregate.separator))
+            } else {
+                String::new()
+            };
+            format!("{}({}{}{})", aggregate.function.name(), if aggregate.distinct { "DISTINCT " } else { "" }, argument, separator)
+        }
+    }
+}
+
+/// The operator name and detail of a graph pattern, including the join algorithm chosen for it.
+pub fn pattern_operator(pattern: &GraphPattern) -> (&'static str, String) {
+    let join = |left: &GraphPattern, right: &GraphPattern| match planner::choose_join(left, right) {
+        (JoinAlgorithm::Hash, variables) => format!("hash on {}", variables_text(&variables)),
+        (algorithm, _) => algorithm.name().to_owned(),
+    };
+    match *pattern {
+        GraphPattern::Bgp(ref triples) => ("bgp", format!("{} pattern{}", triples.len(), if triples.len() == 1 { "" } else { "s" })),
+        /* The evaluator follows a path from each solution on its left. */
+        GraphPattern::Join(_, ref b) if b.is_path() => ("join", "nested loop into path".to_owned()),
+        GraphPattern::Join(_, ref b) if b.is_service() => ("join", "bind join into service".to_owned()),
+        GraphPattern::Join(ref a, ref b) => ("join", join(a, b)),
+        GraphPattern::LeftJoin(ref a, ref b, ref condition) => ("optional", match *condition {
+            Some(ref c) => format!("{} filter {}", join(a, b), expression_text(c)),
+            None => join(a, b),
+        }),
+        GraphPattern::Union(..) => ("union", String::new()),
+        GraphPattern::Minus(..) => ("minus", String::new()),
+        GraphPattern::Filter(ref condition, _) => ("filter", expression_text(condition)),
+        GraphPattern::Graph(ref name, _) => ("graph", term_pattern_text(name)),
+        GraphPattern::Extend(_, ref variable, ref expression) => ("bind", format!("?{} := {}", variable, expression_text(expression))),
+        GraphPattern::Values(ref variables, ref rows) => ("values", format!("{} ({} rows)", variables_text(variables), rows.len())),
+        GraphPattern::Path(ref subject, ref path, ref object) => ("path", format!("{} {} {}", term_pattern_text(subject), path_text(path), term_pattern_text(object))),
+        GraphPattern::Service(ref name, _, silent) => ("service", format!("{}{}", if silent { "silent " } else { "" }, term_pattern_text(name))),
+    }
+}
+
+/// The operator name and detail of a step of a basic graph pattern plan.
+pub fn step_operator(step: &PlanStep, first: bool) -> (String, String) {
+    let operator = if first { "scan".to_owned() } else { format!("{} join", step.join.name()) };
+    let mut detail = triple_pattern_text(&step.pattern);
+    match step.index {
+        Some(index) => detail.push_str(&format!(" using {}", index.name())),
+        None => detail.push_str(" using full-text index"),
+    }
+    if !first && !step.join_variables.is_empty() {
+        detail.push_str(&format!(" on {}", variables_text(&step.join_variables)));
+    }
+    (operator, detail)
+}
+
+/// The operator name and detail of a query form and its solution modifiers.
+pub fn query_operator(query: &Query) -> (&'static str, String) {
+    let mut modifiers = Vec::new();
+    let operator = match query.form {
+        QueryForm::Select { distinct, reduced, .. } => {
+            if distinct {
+                modifiers.push("distinct".to_owned());
+            } else if reduced {
+                modifiers.push("reduced".to_owned());
+            }
+            "select"
+        }
+        QueryForm::Construct(_) => "construct",
+        QueryForm::Describe(_) => "describe",
+        QueryForm::Ask => "ask",
+    };
+    if query.is_grouped() {
+        modifiers.push(format!("group by {}", query.group_by.iter().map(|&(ref e, _)| expression_text(e)).collect::<Vec<String>>().join(", ")).trim().to_owned());
+    }
+    if !query.order_by.is_empty() {
+        modifiers.push(format!("order by {}", query.order_by.iter()
+            .map(|c| if c.descending { format!("DESC({})", expression_text(&c.expression)) } else { expression_text(&c.expression) })
+            .collect::<Vec<String>>().join(", ")));
+    }
+    if query.offset > 0 {
+        modifiers.push(format!("offset {}", query.offset));
+    }
+    if let Some(limit) = query.limit {
+        modifiers.push(format!("limit {}", limit));
+    }
+    (operator, modifiers.join(", "))
+}
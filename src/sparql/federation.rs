@@ -0,0 +1,86 @@
+use std::time::Duration;
+use http::HttpClient;
+use nquads::Term;
+use sparql::QueryResult;
+use sparql::algebra::*;
+use sparql::explain::{expression_text, path_text, term_pattern_text};
+use sparql::results::{self, ResultFormat};
+
+/// Solutions sent to a remote endpoint per request when a SERVICE pattern is joined to them.
+pub const SERVICE_BATCH_SIZE: usize = 100;
+
+/* The result formats asked of remote endpoints. CSV is left out, since it loses the kinds and
+   datatypes of terms. */
+static ACCEPT: &'static str = "application/sparql-results+json, application/sparql-results+xml;q=0.9, text/tab-separated-values;q=0.8";
+
+/// The pattern as the body of a SPARQL group, with every IRI written in full.
+pub fn pattern_text(pattern: &GraphPattern) -> String {
+    let group = |pattern: &GraphPattern| format!("{{ {} }}", pattern_text(pattern));
+    match *pattern {
+        GraphPattern::Bgp(ref triples) => triples.iter()
+            .map(|t| format!("{} {} {} .", term_pattern_text(&t.subject), term_pattern_text(&t.predicate), term_pattern_text(&t.object)))
+            .collect::<Vec<String>>().join(" "),
+        GraphPattern::Join(ref a, ref b) => format!("{} {}", group(a), group(b)),
+        GraphPattern::LeftJoin(ref a, ref b, ref condition) => match *condition {
+            Some(ref c) => format!("{} OPTIONAL {{ {} FILTER({}) }}", group(a), pattern_text(b), expression_text(c)),
+            None => format!("{} OPTIONAL {}", group(a), group(b)),
+        },
+        GraphPattern::Union(ref a, ref b) => format!("{} UNION {}", group(a), group(b)),
+        GraphPattern::Minus(ref a, ref b) => format!("{} MINUS {}", group(a), group(b)),
+        GraphPattern::Filter(ref condition, ref inner) => format!("{} FILTER({})", group(inner), expression_text(condition)),
+        GraphPattern::Graph(ref name, ref inner) => format!("GRAPH {} {}", term_pattern_text(name), group(inner)),
+        GraphPattern::Extend(ref inner, ref variable, ref expression) => format!("{} BIND({} AS ?{})", group(inner), expression_text(expression), variable),
+        GraphPattern::Values(ref variables, ref rows) => values_text(variables, rows),
+        GraphPattern::Path(ref subject, ref path, ref object) =>
+            format!("{} {} {} .", term_pattern_text(subject), path_text(path), term_pattern_text(object)),
+        GraphPattern::Service(ref name, ref inner, silent) =>
+            format!("SERVICE {}{} {}", if silent { "SILENT " } else { "" }, term_pattern_text(name), group(inner)),
+    }
+}
+
+fn values_text(variables: &[String], rows: &[Vec<Option<Term>>]) -> String {
+    let rows: Vec<String> = rows.iter().map(|row| {
+        let values: Vec<String> = row.iter().map(|v| v.as_ref().map(|t| t.to_ntriples()).unwrap_or_else(|| "UNDEF".to_owned())).collect();
+        format!("({})", values.join(" "))
+    }).collect();
+    format!("VALUES ({}) {{ {} }}", variables.iter().map(|v| format!("?{}", v)).collect::<Vec<String>>().join(" "), rows.join(" "))
+}
+
+/// The query sent for a SERVICE pattern: every variable of `pattern`, restricted to `rows` of
+/// values for `variables` when there are any. Blank nodes are local to the store, so they are
+/// sent as UNDEF.
+pub fn service_query(pattern: &GraphPattern, variables: &[String], rows: &[Vec<Option<Term>>]) -> String {
+    if variables.is_empty() {
+        return format!("SELECT * WHERE {{ {} }}", pattern_text(pattern));
+    }
+    let rows: Vec<Vec<Option<Term>>> = rows.iter()
+        .map(|row| row.iter().map(|v| match *v {
+            Some(Term::Blank(_)) => None,
+            ref other => other.clone(),
+        }).collect())
+        .collect();
+    format!("SELECT * WHERE {{ {{ {} }} {} }}", pattern_text(pattern), values_text(variables, &rows))
+}
+
+/// The variables of a set of solutions, and its rows of values in the same order.
+pub type SolutionTable = (Vec<String>, Vec<Vec<Option<Term>>>);
+
+/// Sends `query` to the SPARQL endpoint at `endpoint` and reads the solutions it answers with,
+/// waiting no longer than `timeout` when one is given.
+pub fn fetch(client: &HttpClient, endpoint: &str, query: &str, timeout: Option<Duration>) -> Result<SolutionTable, String> {
+    let headers = [("Content-Type".to_owned(), "application/sparql-query".to_owned()), ("Accept".to_owned(), ACCEPT.to_owned())];
+    let response = match timeout {
+        Some(timeout) => client.send_with_timeout("POST", endpoint, &headers, query.as_bytes(), timeout)?,
+        None => client.send("POST", endpoint, &headers, query.as_bytes())?,
+    };
+    let body = String::from_utf8(response.body.clone()).map_err(|_| format!("SERVICE <{}> answered with a body that is not UTF-8.", endpoint))?;
+    if response.status != 200 {
+        return Err(format!("SERVICE <{}> answered {}: {}", endpoint, response.status, body.trim()));
+    }
+    let format = response.content_type().and_then(|t| ResultFormat::from_media_type(&t)).filter(|f| *f != ResultFormat::Csv)
+        .ok_or_else(|| format!("SERVICE <{}> answered in an unsupported format {:?}.", endpoint, response.content_type().unwrap_or_default()))?;
+    match results::read(&body, format)? {
+        QueryResult::Solutions { variables, rows } => Ok((variables, rows)),
+        _ => Err(format!("SERVICE <{}> did not answer with solutions.", endpoint)),
+    }
+}
@@ -0,0 +1,89 @@
+pub mod algebra;
+pub mod parser;
+pub mod entailment;
+pub mod eval;
+pub mod explain;
+pub mod federation;
+pub mod planner;
+pub mod results;
+
+use std::rc::Rc;
+use limits::{Guard, Limits, OperationError};
+use nquads::Term;
+use store::StorageEngine;
+use sparql::algebra::UpdateOperation;
+use sparql::entailment::EntailmentRegime;
+use sparql::eval::Evaluator;
+use sparql::explain::PlanNode;
+
+/// The outcome of a query, with its terms resolved out of the store.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryResult {
+    /* One row per solution, holding the value of each variable in order, or `None` if unbound. */
+    Solutions { variables: Vec<String>, rows: Vec<Vec<Option<Term>>> },
+    Boolean(bool),
+    Graph(Vec<(Term, Term, Term)>),
+}
+
+/// Parses and runs a query. Prefixes bound in the store's namespace manager may be used undeclared.
+pub fn query(store: &StorageEngine, text: &str) -> Result<QueryResult, String> {
+    let query = parser::parse_query(text, store.borrow_namespace_manager())?;
+    Evaluator::new(store).execute(&query)
+}
+
+/// Parses and runs a query whose basic graph patterns also match the answers `regime` entails,
+/// found at query time without materializing them.
+pub fn query_with_entailment(store: &StorageEngine, text: &str, regime: EntailmentRegime) -> Result<QueryResult, String> {
+    let query = parser::parse_query(text, store.borrow_namespace_manager())?;
+    Evaluator::new(store).with_entailment(regime).execute(&query)
+}
+
+/// Parses and runs a query within `limits`, failing with `OperationError::Limit` when one is hit.
+pub fn query_with_limits(store: &StorageEngine, text: &str, limits: &Limits) -> Result<QueryResult, OperationError> {
+    let query = parser::parse_query(text, store.borrow_namespace_manager())?;
+    let evaluator = Evaluator::new(store).with_limits(limits.clone());
+    evaluator.execute(&query).map_err(|e| evaluator.borrow_guard().error(e))
+}
+
+/// Parses and runs a query with profiling, returning the plan it was evaluated with: the
+/// operators, the index read for each triple pattern, and estimated and actual rows with timings.
+pub fn explain(store: &StorageEngine, text: &str) -> Result<PlanNode, String> {
+    let query = parser::parse_query(text, store.borrow_namespace_manager())?;
+    let evaluator = Evaluator::new(store).with_profiling();
+    evaluator.execute(&query)?;
+    evaluator.profiled_plan().ok_or_else(|| "The query produced no plan.".to_owned())
+}
+
+/// Parses and runs an update request, returning the number of quads added and deleted. The request
+/// is parsed in full before anything changes, and each operation is applied atomically.
+pub fn update(store: &mut StorageEngine, text: &str) -> Result<usize, String> {
+    let operations = parser::parse_update(text, store.borrow_namespace_manager())?;
+    execute_update(store, &operations)
+}
+
+/// Parses and runs an update request within `limits`, which bound the request as a whole. An
+/// operation stopped by a limit changes nothing, but operations before it stay applied.
+pub fn update_with_limits(store: &mut StorageEngine, text: &str, limits: &Limits) -> Result<usize, OperationError> {
+    let operations = parser::parse_update(text, store.borrow_namespace_manager())?;
+    execute_update_with_limits(store, &operations, limits)
+}
+
+/// Applies parsed update operations in order, returning the number of quads added and deleted.
+pub fn execute_update(store: &mut StorageEngine, operations: &[UpdateOperation]) -> Result<usize, String> {
+    execute_update_guarded(store, operations, &Rc::new(Guard::unlimited()))
+}
+
+/// Applies parsed update operations in order within `limits`. See `update_with_limits`.
+pub fn execute_update_with_limits(store: &mut StorageEngine, operations: &[UpdateOperation], limits: &Limits) -> Result<usize, OperationError> {
+    let guard = Rc::new(Guard::new(limits.clone()));
+    execute_update_guarded(store, operations, &guard).map_err(|e| guard.error(e))
+}
+
+fn execute_update_guarded(store: &mut StorageEngine, operations: &[UpdateOperation], guard: &Rc<Guard>) -> Result<usize, String> {
+    let mut changes = 0;
+    for operation in operations.iter() {
+        let patch = Evaluator::new(store).with_guard(guard.clone()).plan_update(operation)?;
+        changes += patch.apply_guarded(store, false, guard)?;
+    }
+    Ok(changes)
+}
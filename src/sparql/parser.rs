@@ -0,0 +1,958 @@
+use std::collections::BTreeMap;
+use std::mem;
+use iri;
+use lexer::{Token, TokenStream};
+use namespace::NamespaceManager;
+use nquads::Term;
+use turtle;
+use vocab;
+use sparql::algebra::*;
+
+static BUILT_IN_FUNCTIONS: &'static [&'static str] = &["STR", "LANG", "LANGMATCHES", "DATATYPE", "IRI", "URI", "BNODE",
+    "ABS", "CEIL", "FLOOR", "ROUND", "CONCAT", "STRLEN", "UCASE", "LCASE", "ENCODE_FOR_URI", "CONTAINS", "STRSTARTS",
+    "STRENDS", "STRBEFORE", "STRAFTER", "SUBSTR", "REGEX", "ISIRI", "ISURI", "ISBLANK", "ISLITERAL", "ISNUMERIC",
+    "SAMETERM", "IF", "COALESCE", "STRDT", "STRLANG"];
+
+static AGGREGATES: &'static [(&'static str, AggregateFunction)] = &[("COUNT", AggregateFunction::Count),
+    ("SUM", AggregateFunction::Sum), ("MIN", AggregateFunction::Min), ("MAX", AggregateFunction::Max),
+    ("AVG", AggregateFunction::Avg), ("SAMPLE", AggregateFunction::Sample), ("GROUP_CONCAT", AggregateFunction::GroupConcat)];
+
+struct SparqlParser<'n> {
+    tokens: TokenStream,
+    namespaces: &'n NamespaceManager,
+    prefixes: BTreeMap<String, String>,
+    base: Option<String>,
+    next_blank: usize,
+    /* Path patterns of the triples block being parsed, or `None` in templates, which allow no paths. */
+    paths: Option<Vec<GraphPattern>>,
+}
+
+/* A predicate position: a variable, or a path, which may be a single IRI. */
+enum Verb {
+    Variable(String),
+    Path(PathExpression),
+}
+
+/// Parses a SPARQL 1.1 query. Prefixes not declared in the query are looked up in `namespaces`.
+pub fn parse_query(text: &str, namespaces: &NamespaceManager) -> Result<Query, String> {
+    let mut parser = SparqlParser::new(text, namespaces)?;
+    parser.prologue()?;
+    let query = parser.query()?;
+    if !parser.tokens.is_at_end() {
+        return parser.tokens.error("Unexpected text after the query");
+    }
+    Ok(query)
+}
+
+/// Parses a SPARQL 1.1 property path, such as `skos:broader*` or `^rdfs:subClassOf+`.
+pub fn parse_path(text: &str, namespaces: &NamespaceManager) -> Result<PathExpression, String> {
+    let mut parser = SparqlParser::new(text, namespaces)?;
+    let path = parser.path()?;
+    if !parser.tokens.is_at_end() {
+        return parser.tokens.error("Unexpected text after the path");
+    }
+    Ok(path)
+}
+
+/// Parses a SPARQL 1.1 update request into its operations, in order.
+pub fn parse_update(text: &str, namespaces: &NamespaceManager) -> Result<Vec<UpdateOperation>, String> {
+    let mut parser = SparqlParser::new(text, namespaces)?;
+    let mut operations = Vec::new();
+    loop {
+        parser.prologue()?;
+        if parser.tokens.is_at_end() {
+            break;
+        }
+        operations.push(parser.update_operation()?);
+        if !parser.tokens.eat_punct(";") {
+            break;
+        }
+    }
+    if !parser.tokens.is_at_end() {
+        return parser.tokens.error("Expected \";\" between update operations");
+    }
+    Ok(operations)
+}
+
+impl<'n> SparqlParser<'n> {
+    fn new(text: &str, namespaces: &'n NamespaceManager) -> Result<SparqlParser<'n>, String> {
+        Ok(SparqlParser { tokens: TokenStream::new(text)?, namespaces, prefixes: BTreeMap::new(), base: None, next_blank: 0, paths: None })
+    }
+
+    fn prologue(&mut self) -> Result<(), String> {
+        loop {
+            if self.tokens.eat_word("prefix") {
+                let prefix = match self.tokens.next() {
+                    Some(Token::PrefixedName(ref prefix, ref local)) if local.is_empty() => prefix.clone(),
+                    _ => return self.tokens.error("Expected a prefix name"),
+                };
+                let namespace = match self.tokens.next() {
+                    Some(Token::IriRef(iri)) => self.resolve_relative(iri)?,
+                    _ => return self.tokens.error("Expected a namespace IRI"),
+                };
+                self.prefixes.insert(prefix, namespace);
+            } else if self.tokens.eat_word("base") {
+                let base = match self.tokens.next() {
+                    Some(Token::IriRef(iri)) => self.resolve_relative(iri)?,
+                    _ => return self.tokens.error("Expected a base IRI"),
+                };
+                self.base = Some(base);
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn resolve_relative(&self, iri: String) -> Result<String, String> {
+        match self.base {
+            Some(ref base) => iri::resolve_iri(base, &iri),
+            None => Ok(iri),
+        }
+    }
+
+    fn resolve_prefixed(&self, prefix: &str, local: &str) -> Result<String, String> {
+        if let Some(namespace) = self.prefixes.get(prefix) {
+            return Ok(format!("{}{}", namespace, local));
+        }
+        self.namespaces.expand_curie(&format!("{}:{}", prefix, local))
+    }
+
+    fn is_iri(&self) -> bool {
+        matches!(self.tokens.peek(), Some(&Token::IriRef(_)) | Some(&Token::PrefixedName(..)))
+    }
+
+    fn iri(&mut self) -> Result<String, String> {
+        match self.tokens.next() {
+            Some(Token::IriRef(iri)) => self.resolve_relative(iri),
+            Some(Token::PrefixedName(prefix, local)) => self.resolve_prefixed(&prefix, &local),
+            _ => self.tokens.error("Expected an IRI"),
+        }
+    }
+
+    fn variable(&mut self) -> Result<String, String> {
+        match self.tokens.next() {
+            Some(Token::Var(name)) => Ok(name),
+            _ => self.tokens.error("Expected a variable"),
+        }
+    }
+
+    fn is_variable(&self) -> bool {
+        matches!(self.tokens.peek(), Some(&Token::Var(_)))
+    }
+
+    fn fresh_blank(&mut self) -> TermPattern {
+        self.next_blank += 1;
+        TermPattern::Variable(format!("_:b{}", self.next_blank))
+    }
+
+    fn integer(&mut self) -> Result<usize, String> {
+        match self.tokens.next() {
+            Some(Token::Integer(n)) => n.parse().map_err(|_| format!("Invalid integer {}.", n)),
+            _ => self.tokens.error("Expected an integer"),
+        }
+    }
+
+    fn literal(&mut self) -> Result<Option<Term>, String> {
+        turtle::parse_literal(self, |p| &mut p.tokens, |p| p.iri())
+    }
+
+    fn var_or_iri(&mut self) -> Result<TermPattern, String> {
+        if self.is_variable() {
+            Ok(TermPattern::Variable(self.variable()?))
+        } else {
+            Ok(TermPattern::Term(Term::Iri(self.iri()?)))
+        }
+    }
+
+    /* ---- Queries ---- */
+
+    fn query(&mut self) -> Result<Query, String> {
+        let form = if self.tokens.eat_word("select") {
+            let distinct = self.tokens.eat_word("distinct");
+            let reduced = !distinct && self.tokens.eat_word("reduced");
+            let projection = if self.tokens.eat_punct("*") { None } else { Some(self.projection()?) };
+            QueryForm::Select { distinct, reduced, projection }
+        } else if self.tokens.eat_word("construct") {
+            if self.tokens.is_word("where") || self.tokens.is_word("from") {
+                /* The short form: the pattern is its own template. */
+                self.dataset_clauses()?;
+                self.tokens.expect_word("where")?;
+                self.tokens.expect_punct("{")?;
+                let mut triples = Vec::new();
+                self.triples_block(&mut triples)?;
+                self.tokens.expect_punct("}")?;
+                let mut query = Query { form: QueryForm::Construct(triples.clone()), pattern: GraphPattern::Bgp(triples),
+                    group_by: Vec::new(), having: Vec::new(), order_by: Vec::new(), limit: None, offset: 0 };
+                self.solution_modifiers(&mut query)?;
+                return Ok(query);
+            }
+            QueryForm::Construct(self.triples_template()?)
+        } else if self.tokens.eat_word("describe") {
+            let mut resources = Vec::new();
+            if !self.tokens.eat_punct("*") {
+                while self.is_variable() || self.is_iri() {
+                    resources.push(self.var_or_iri()?);
+                }
+                if resources.is_empty() {
+                    return self.tokens.error("Expected resources to describe");
+                }
+            }
+            QueryForm::Describe(resources)
+        } else if self.tokens.eat_word("ask") {
+            QueryForm::Ask
+        } else {
+            return self.tokens.error("Expected SELECT, CONSTRUCT, DESCRIBE or ASK");
+        };
+        self.dataset_clauses()?;
+        let optional_where = matches!(form, QueryForm::Describe(_));
+        let pattern = if self.tokens.eat_word("where") || !optional_where || self.tokens.is_punct("{") {
+            self.group_graph_pattern()?
+        } else {
+            GraphPattern::empty()
+        };
+        let mut query = Query { form, pattern, group_by: Vec::new(), having: Vec::new(), order_by: Vec::new(), limit: None, offset: 0 };
+        self.solution_modifiers(&mut query)?;
+        Ok(query)
+    }
+
+    fn dataset_clauses(&mut self) -> Result<(), String> {
+        if self.tokens.is_word("from") {
+            return self.tokens.error("FROM and FROM NAMED are not supported; use GRAPH in the pattern instead");
+        }
+        Ok(())
+    }
+
+    fn projection(&mut self) -> Result<Vec<Projection>, String> {
+        let mut items = Vec::new();
+        loop {
+            if self.is_variable() {
+                items.push(Projection { variable: self.variable()?, expression: None });
+            } else if self.tokens.eat_punct("(") {
+                let expression = self.expression()?;
+                self.tokens.expect_word("as")?;
+                let variable = self.variable()?;
+                self.tokens.expect_punct(")")?;
+                items.push(Projection { variable, expression: Some(expression) });
+            } else {
+                break;
+            }
+        }
+        if items.is_empty() {
+            return self.tokens.error("Expected variables to select");
+        }
+        Ok(items)
+    }
+
+    fn solution_modifiers(&mut self, query: &mut Query) -> Result<(), String> {
+        if self.tokens.eat_word("group") {
+            self.tokens.expect_word("by")?;
+            loop {
+                if self.is_variable() {
+                    let v = self.variable()?;
+                    query.group_by.push((Expression::Variable(v), None));
+                } else if self.tokens.eat_punct("(") {
+                    let expression = self.expression()?;
+                    let alias = if self.tokens.eat_word("as") { Some(self.variable()?) } else { None };
+                    self.tokens.expect_punct(")")?;
+                    query.group_by.push((expression, alias));
+                } else if let Some(call) = self.function_call()? {
+                    query.group_by.push((call, None));
+                } else {
+                    break;
+                }
+            }
+            if query.group_by.is_empty() {
+                return self.tokens.error("Expected a grouping condition");
+            }
+        }
+        if self.tokens.eat_word("having") {
+            while let Some(constraint) = self.constraint()? {
+                query.having.push(constraint);
+            }
+            if query.having.is_empty() {
+                return self.tokens.error("Expected a HAVING condition");
+            }
+        }
+        if self.tokens.eat_word("order") {
+            self.tokens.expect_word("by")?;
+            loop {
+                if self.tokens.is_word("asc") || self.tokens.is_word("desc") {
+                    let descending = self.tokens.eat_word("desc");
+                    if !descending {
+                        self.tokens.next();
+                    }
+                    self.tokens.expect_punct("(")?;
+                    let expression = self.expression()?;
+                    self.tokens.expect_punct(")")?;
+                    query.order_by.push(OrderCondition { expression, descending });
+                } else if self.is_variable() {
+                    let v = self.variable()?;
+                    query.order_by.push(OrderCondition { expression: Expression::Variable(v), descending: false });
+                } else if let Some(expression) = self.constraint()? {
+                    query.order_by.push(OrderCondition { expression, descending: false });
+                } else {
+                    break;
+                }
+            }
+            if query.order_by.is_empty() {
+                return self.tokens.error("Expected an ordering condition");
+            }
+        }
+        loop {
+            if self.tokens.eat_word("limit") {
+                query.limit = Some(self.integer()?);
+            } else if self.tokens.eat_word("offset") {
+                query.offset = self.integer()?;
+            } else {
+                break;
+            }
+        }
+        if self.tokens.eat_word("values") {
+            let values = self.data_block()?;
+            let pattern = ::std::mem::replace(&mut query.pattern, GraphPattern::empty());
+            query.pattern = GraphPattern::Join(Box::new(pattern), Box::new(values));
+        }
+        Ok(())
+    }
+
+    /* ---- Graph patterns ---- */
+
+    fn group_graph_pattern(&mut self) -> Result<GraphPattern, String> {
+        self.tokens.expect_punct("{")?;
+        if self.tokens.is_word("select") {
+            return self.tokens.error("Subqueries are not supported");
+        }
+        let mut group = GraphPattern::empty();
+        let mut filters = Vec::new();
+        loop {
+            let mut triples = Vec::new();
+            let outer = self.paths.replace(Vec::new());
+            let block = self.triples_block(&mut triples);
+            let paths = mem::replace(&mut self.paths, outer).unwrap_or_default();
+            block?;
+            group = paths.into_iter().fold(group.join(GraphPattern::Bgp(triples)), |group, path| group.join(path));
+            if self.tokens.eat_word("optional") {
+                let (optional, condition) = match self.group_graph_pattern()? {
+                    GraphPattern::Filter(condition, inner) => (*inner, Some(condition)),
+                    other => (other, None),
+                };
+                group = GraphPattern::LeftJoin(Box::new(group), Box::new(optional), condition);
+            } else if self.tokens.eat_word("minus") {
+                let minus = self.group_graph_pattern()?;
+                group = GraphPattern::Minus(Box::new(group), Box::new(minus));
+            } else if self.tokens.eat_word("graph") {
+                let graph = self.var_or_iri()?;
+                let inner = self.group_graph_pattern()?;
+                group = group.join(GraphPattern::Graph(graph, Box::new(inner)));
+            } else if self.tokens.eat_word("service") {
+                let silent = self.tokens.eat_word("silent");
+                let endpoint = self.var_or_iri()?;
+                let inner = self.group_graph_pattern()?;
+                group = group.join(GraphPattern::Service(endpoint, Box::new(inner), silent));
+            } else if self.tokens.eat_word("filter") {
+                match self.constraint()? {
+                    Some(constraint) => filters.push(constraint),
+                    None => return self.tokens.error("Expected a filter condition"),
+                }
+            } else if self.tokens.eat_word("bind") {
+                self.tokens.expect_punct("(")?;
+                let expression = self.expression()?;
+                self.tokens.expect_word("as")?;
+                let variable = self.variable()?;
+                self.tokens.expect_punct(")")?;
+                group = GraphPattern::Extend(Box::new(group), variable, expression);
+            } else if self.tokens.eat_word("values") {
+                let values = self.data_block()?;
+                group = group.join(values);
+            } else if self.tokens.is_punct("{") {
+                let mut union = self.group_graph_pattern()?;
+                while self.tokens.eat_word("union") {
+                    let other = self.group_graph_pattern()?;
+                    union = GraphPattern::Union(Box::new(union), Box::new(other));
+                }
+                group = match group {
+                    GraphPattern::Bgp(ref t) if t.is_empty() => union,
+                    group => GraphPattern::Join(Box::new(group), Box::new(union)),
+                };
+            } else {
+                break;
+            }
+            self.tokens.eat_punct(".");
+        }
+        self.tokens.expect_punct("}")?;
+        Ok(filters.into_iter().fold(group, |pattern, filter| GraphPattern::Filter(filter, Box::new(pattern))))
+    }
+
+    fn data_block(&mut self) -> Result<GraphPattern, String> {
+        let mut variables = Vec::new();
+        let mut rows = Vec::new();
+        if self.is_variable() {
+            variables.push(self.variable()?);
+            self.tokens.expect_punct("{")?;
+            while !self.tokens.eat_punct("}") {
+                rows.push(vec![self.data_value()?]);
+            }
+        } else {
+            self.tokens.expect_punct("(")?;
+            while !self.tokens.eat_punct(")") {
+                variables.push(self.variable()?);
+            }
+            self.tokens.expect_punct("{")?;
+            while !self.tokens.eat_punct("}") {
+                self.tokens.expect_punct("(")?;
+                let mut row = Vec::new();
+                while !self.tokens.eat_punct(")") {
+                    row.push(self.data_value()?);
+                }
+                if row.len() != variables.len() {
+                    return self.tokens.error("VALUES row has the wrong number of values");
+                }
+                rows.push(row);
+            }
+        }
+        Ok(GraphPattern::Values(variables, rows))
+    }
+
+    fn data_value(&mut self) -> Result<Option<Term>, String> {
+        if self.tokens.eat_word("undef") {
+            return Ok(None);
+        }
+        if self.is_iri() {
+            return Ok(Some(Term::Iri(self.iri()?)));
+        }
+        match self.literal()? {
+            Some(literal) => Ok(Some(literal)),
+            None => self.tokens.error("Expected a value"),
+        }
+    }
+
+    /* ---- Triples ---- */
+
+    fn starts_term(&self) -> bool {
+        match self.tokens.peek() {
+            Some(&Token::Var(_)) | Some(&Token::IriRef(_)) | Some(&Token::PrefixedName(..)) | Some(&Token::Blank(_)) |
+            Some(&Token::Str(_)) | Some(&Token::Integer(_)) | Some(&Token::Decimal(_)) | Some(&Token::Double(_)) |
+            Some(&Token::Punct("[")) | Some(&Token::Punct("(")) => true,
+            Some(&Token::Word(ref w)) => w == "true" || w == "false",
+            _ => false,
+        }
+    }
+
+    /* Subject-led triples separated by dots, appended to `triples`. */
+    fn triples_block(&mut self, triples: &mut Vec<TriplePattern>) -> Result<(), String> {
+        while self.starts_term() {
+            let bracketed = self.tokens.is_punct("[");
+            let subject = self.term(triples)?;
+            /* `[ :p :o ]` may stand alone as a subject without further predicates. */
+            if !(bracketed && (self.tokens.is_punct(".") || self.tokens.is_punct("}"))) {
+                self.property_list(&subject, triples)?;
+            }
+            if !self.tokens.eat_punct(".") {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn triples_template(&mut self) -> Result<Vec<TriplePattern>, String> {
+        self.tokens.expect_punct("{")?;
+        let mut triples = Vec::new();
+        self.triples_block(&mut triples)?;
+        self.tokens.expect_punct("}")?;
+        Ok(triples)
+    }
+
+    fn property_list(&mut self, subject: &TermPattern, triples: &mut Vec<TriplePattern>) -> Result<(), String> {
+        loop {
+            let verb = self.verb()?;
+            loop {
+                let object = self.term(triples)?;
+                match verb {
+                    Verb::Variable(ref v) => triples.push(TriplePattern { subject: subject.clone(), predicate: TermPattern::Variable(v.clone()), object }),
+                    Verb::Path(ref path) => self.path_patterns(subject.clone(), path, object, triples),
+                }
+                if !self.tokens.eat_punct(",") {
+                    break;
+                }
+            }
+            if !self.tokens.eat_punct(";") {
+                return Ok(());
+            }
+            while self.tokens.eat_punct(";") {}
+            if self.tokens.is_punct(".") || self.tokens.is_punct("]") || self.tokens.is_punct("}") {
+                return Ok(());
+            }
+        }
+    }
+
+    fn verb(&mut self) -> Result<Verb, String> {
+        if self.is_variable() {
+            return Ok(Verb::Variable(self.variable()?));
+        }
+        let starts_path = self.is_iri() || self.tokens.is_word("a") || self.tokens.is_punct("^") || self.tokens.is_punct("^^") ||
+            self.tokens.is_punct("!") || self.tokens.is_punct("(");
+        if !starts_path {
+            return self.tokens.error("Expected a predicate");
+        }
+        let path = self.path()?;
+        if self.paths.is_none() {
+            if let PathExpression::Iri(_) = path {} else {
+                return self.tokens.error("Property paths are only allowed in graph patterns");
+            }
+        }
+        Ok(Verb::Path(path))
+    }
+
+    /* Adds the pattern of `subject path object`, translated as SPARQL does: a predicate or inverse
+       predicate is a triple pattern, and a sequence is a chain of them through fresh variables.
+       Other paths are evaluated as path patterns. */
+    fn path_patterns(&mut self, subject: TermPattern, path: &PathExpression, object: TermPattern, triples: &mut Vec<TriplePattern>) {
+        match *path {
+            PathExpression::Iri(ref iri) => triples.push(TriplePattern { subject, predicate: TermPattern::Term(Term::Iri(iri.clone())), object }),
+            PathExpression::Inverse(ref inner) => self.path_patterns(object, inner, subject, triples),
+            PathExpression::Sequence(ref steps) if !steps.is_empty() => {
+                let mut from = subject;
+                for (i, step) in steps.iter().enumerate() {
+                    let to = if i + 1 == steps.len() { object.clone() } else { self.fresh_blank() };
+                    self.path_patterns(from, step, to.clone(), triples);
+                    from = to;
+                }
+            }
+            _ => {
+                if let Some(ref mut paths) = self.paths {
+                    paths.push(GraphPattern::Path(subject, path.clone(), object));
+                }
+            }
+        }
+    }
+
+    /* ---- Property paths ---- */
+
+    fn path(&mut self) -> Result<PathExpression, String> {
+        let mut options = vec![self.path_sequence()?];
+        while self.tokens.eat_punct("|") {
+            options.push(self.path_sequence()?);
+        }
+        Ok(if options.len() == 1 { options.pop().unwrap() } else { PathExpression::Alternative(options) })
+    }
+
+    fn path_sequence(&mut self) -> Result<PathExpression, String> {
+        let mut steps = vec![self.path_element()?];
+        while self.tokens.eat_punct("/") {
+            steps.push(self.path_element()?);
+        }
+        Ok(if steps.len() == 1 { steps.pop().unwrap() } else { PathExpression::Sequence(steps) })
+    }
+
+    fn path_element(&mut self) -> Result<PathExpression, String> {
+        /* `^^p` lexes as the datatype marker, but is a double inverse here. */
+        if self.tokens.eat_punct("^^") {
+            return Ok(PathExpression::Inverse(Box::new(PathExpression::Inverse(Box::new(self.path_element()?)))));
+        }
+        if self.tokens.eat_punct("^") {
+            return Ok(PathExpression::Inverse(Box::new(self.path_element()?)));
+        }
+        let primary = self.path_primary()?;
+        Ok(if self.tokens.eat_punct("*") {
+            PathExpression::ZeroOrMore(Box::new(primary))
+        } else if self.tokens.eat_punct("+") {
+            PathExpression::OneOrMore(Box::new(primary))
+        } else if self.tokens.eat_punct("?") {
+            PathExpression::ZeroOrOne(Box::new(primary))
+        } else {
+            primary
+        })
+    }
+
+    fn path_primary(&mut self) -> Result<PathExpression, String> {
+        if self.tokens.eat_punct("(") {
+            let path = self.path()?;
+            self.tokens.expect_punct(")")?;
+            return Ok(path);
+        }
+        if self.tokens.eat_punct("!") {
+            let (mut forward, mut inverse) = (Vec::new(), Vec::new());
+            if self.tokens.eat_punct("(") {
+                if !self.tokens.eat_punct(")") {
+                    loop {
+                        self.negated_member(&mut forward, &mut inverse)?;
+                        if !self.tokens.eat_punct("|") {
+                            break;
+                        }
+                    }
+                    self.tokens.expect_punct(")")?;
+                }
+            } else {
+                self.negated_member(&mut forward, &mut inverse)?;
+            }
+            return Ok(PathExpression::NegatedSet(forward, inverse));
+        }
+        Ok(PathExpression::Iri(self.path_iri()?))
+    }
+
+    fn negated_member(&mut self, forward: &mut Vec<String>, inverse: &mut Vec<String>) -> Result<(), String> {
+        if self.tokens.eat_punct("^") {
+            inverse.push(self.path_iri()?);
+        } else {
+            forward.push(self.path_iri()?);
+        }
+        Ok(())
+    }
+
+    fn path_iri(&mut self) -> Result<String, String> {
+        if self.tokens.eat_word("a") {
+            return Ok(vocab::RDF_TYPE.to_owned());
+        }
+        self.iri()
+    }
+
+    /* A term or variable in a triple pattern, pushing the patterns of any blank node property
+    list or collection it writes onto `triples`. */
+    fn term(&mut self, triples: &mut Vec<TriplePattern>) -> Result<TermPattern, String> {
+        if self.is_variable() || self.is_iri() {
+            return self.var_or_iri();
+        }
+        if let Some(Token::Blank(label)) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            return Ok(TermPattern::Variable(format!("_:{}", label)));
+        }
+        if self.tokens.eat_punct("[") {
+            let node = self.fresh_blank();
+            if !self.tokens.eat_punct("]") {
+                self.property_list(&node, triples)?;
+                self.tokens.expect_punct("]")?;
+            }
+            return Ok(node);
+        }
+        if self.tokens.eat_punct("(") {
+            let mut items = Vec::new();
+            while !self.tokens.eat_punct(")") {
+                items.push(self.term(triples)?);
+            }
+            let mut list = TermPattern::Term(Term::Iri(vocab::RDF_NIL.to_owned()));
+            for item in items.into_iter().rev() {
+                let node = self.fresh_blank();
+                triples.push(TriplePattern { subject: node.clone(), predicate: TermPattern::Term(Term::Iri(vocab::RDF_FIRST.to_owned())), object: item });
+                triples.push(TriplePattern { subject: node.clone(), predicate: TermPattern::Term(Term::Iri(vocab::RDF_REST.to_owned())), object: list });
+                list = node;
+            }
+            return Ok(list);
+        }
+        match self.literal()? {
+            Some(literal) => Ok(TermPattern::Term(literal)),
+            None => self.tokens.error("Expected an RDF term or variable"),
+        }
+    }
+
+    /* ---- Expressions ---- */
+
+    fn constraint(&mut self) -> Result<Option<Expression>, String> {
+        if self.tokens.eat_punct("(") {
+            let expression = self.expression()?;
+            self.tokens.expect_punct(")")?;
+            return Ok(Some(expression));
+        }
+        self.function_call()
+    }
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let mut left = self.and_expression()?;
+        while self.tokens.eat_punct("||") {
+            let right = self.and_expression()?;
+            left = Expression::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and_expression(&mut self) -> Result<Expression, String> {
+        let mut left = self.relational_expression()?;
+        while self.tokens.eat_punct("&&") {
+            let right = self.relational_expression()?;
+            left = Expression::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn relational_expression(&mut self) -> Result<Expression, String> {
+        let left = self.additive_expression()?;
+        let operators = [("=", ComparisonOperator::Equal), ("!=", ComparisonOperator::NotEqual), ("<=", ComparisonOperator::LessOrEqual),
+            (">=", ComparisonOperator::GreaterOrEqual), ("<", ComparisonOperator::Less), (">", ComparisonOperator::Greater)];
+        for &(punct, operator) in operators.iter() {
+            if self.tokens.eat_punct(punct) {
+                let right = self.additive_expression()?;
+                return Ok(Expression::Compare(operator, Box::new(left), Box::new(right)));
+            }
+        }
+        let negated = if self.tokens.is_word("not") && self.tokens.peek_at(1).map(|t| if let &Token::Word(ref w) = t { w.eq_ignore_ascii_case("in") } else { false }).unwrap_or(false) {
+            self.tokens.next();
+            true
+        } else {
+            false
+        };
+        if self.tokens.eat_word("in") {
+            let list = self.expression_list()?;
+            return Ok(Expression::In(Box::new(left), list, negated));
+        }
+        Ok(left)
+    }
+
+    fn expression_list(&mut self) -> Result<Vec<Expression>, String> {
+        self.tokens.expect_punct("(")?;
+        let mut list = Vec::new();
+        if self.tokens.eat_punct(")") {
+            return Ok(list);
+        }
+        loop {
+            list.push(self.expression()?);
+            if self.tokens.eat_punct(")") {
+                return Ok(list);
+            }
+            self.tokens.expect_punct(",")?;
+        }
+    }
+
+    fn additive_expression(&mut self) -> Result<Expression, String> {
+        let mut left = self.multiplicative_expression()?;
+        loop {
+            let operator = if self.tokens.eat_punct("+") { ArithmeticOperator::Add }
+                else if self.tokens.eat_punct("-") { ArithmeticOperator::Subtract }
+                else { return Ok(left); };
+            let right = self.multiplicative_expression()?;
+            left = Expression::Arithmetic(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn multiplicative_expression(&mut self) -> Result<Expression, String> {
+        let mut left = self.unary_expression()?;
+        loop {
+            let operator = if self.tokens.eat_punct("*") { ArithmeticOperator::Multiply }
+                else if self.tokens.eat_punct("/") { ArithmeticOperator::Divide }
+                else { return Ok(left); };
+            let right = self.unary_expression()?;
+            left = Expression::Arithmetic(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary_expression(&mut self) -> Result<Expression, String> {
+        if self.tokens.eat_punct("!") {
+            return Ok(Expression::Not(Box::new(self.primary_expression()?)));
+        }
+        if self.tokens.eat_punct("+") {
+            return self.primary_expression();
+        }
+        if self.tokens.eat_punct("-") {
+            return Ok(Expression::Negate(Box::new(self.primary_expression()?)));
+        }
+        self.primary_expression()
+    }
+
+    fn primary_expression(&mut self) -> Result<Expression, String> {
+        if self.tokens.eat_punct("(") {
+            let expression = self.expression()?;
+            self.tokens.expect_punct(")")?;
+            return Ok(expression);
+        }
+        if self.is_variable() {
+            return Ok(Expression::Variable(self.variable()?));
+        }
+        if let Some(call) = self.function_call()? {
+            return Ok(call);
+        }
+        if self.is_iri() {
+            return Ok(Expression::Constant(Term::Iri(self.iri()?)));
+        }
+        match self.literal()? {
+            Some(literal) => Ok(Expression::Constant(literal)),
+            None => self.tokens.error("Expected an expression"),
+        }
+    }
+
+    /* A built-in call, aggregate, EXISTS or IRI function call, or `None` if none starts here. */
+    fn function_call(&mut self) -> Result<Option<Expression>, String> {
+        if self.is_iri() {
+            if let Some(&Token::Punct("(")) = self.tokens.peek_at(1) {
+                let name = self.iri()?;
+                let args = self.expression_list()?;
+                return Ok(Some(Expression::Call(name, args)));
+            }
+            return Ok(None);
+        }
+        let name = match self.tokens.peek() {
+            Some(&Token::Word(ref w)) => w.to_uppercase(),
+            _ => return Ok(None),
+        };
+        if name == "EXISTS" || (name == "NOT" && self.tokens.peek_at(1).map(|t| if let &Token::Word(ref w) = t { w.eq_ignore_ascii_case("exists") } else { false }).unwrap_or(false)) {
+            let negated = name == "NOT";
+            self.tokens.next();
+            if negated {
+                self.tokens.next();
+            }
+            let pattern = self.group_graph_pattern()?;
+            return Ok(Some(Expression::Exists(Box::new(pattern), negated)));
+        }
+        if name == "BOUND" {
+            self.tokens.next();
+            self.tokens.expect_punct("(")?;
+            let variable = self.variable()?;
+            self.tokens.expect_punct(")")?;
+            return Ok(Some(Expression::Bound(variable)));
+        }
+        if let Some(&(_, function)) = AGGREGATES.iter().find(|&&(n, _)| n == name) {
+            self.tokens.next();
+            self.tokens.expect_punct("(")?;
+            let distinct = self.tokens.eat_word("distinct");
+            let expression = if function == AggregateFunction::Count && self.tokens.eat_punct("*") { None } else { Some(Box::new(self.expression()?)) };
+            let mut separator = " ".to_owned();
+            if function == AggregateFunction::GroupConcat && self.tokens.eat_punct(";") {
+                self.tokens.expect_word("separator")?;
+                self.tokens.expect_punct("=")?;
+                separator = match self.tokens.next() {
+                    Some(Token::Str(s)) => s,
+                    _ => return self.tokens.error("Expected a separator string"),
+                };
+            }
+            self.tokens.expect_punct(")")?;
+            return Ok(Some(Expression::Aggregate(Aggregate { function, distinct, expression, separator })));
+        }
+        if BUILT_IN_FUNCTIONS.contains(&name.as_str()) {
+            self.tokens.next();
+            let args = self.expression_list()?;
+            return Ok(Some(Expression::Call(name, args)));
+        }
+        Ok(None)
+    }
+
+    /* ---- Updates ---- */
+
+    fn update_operation(&mut self) -> Result<UpdateOperation, String> {
+        if self.tokens.eat_word("insert") {
+            if self.tokens.eat_word("data") {
+                return Ok(UpdateOperation::InsertData(self.quad_data(true)?));
+            }
+            let insert = self.quad_pattern()?;
+            return self.modify(None, Vec::new(), insert);
+        }
+        if self.tokens.eat_word("delete") {
+            if self.tokens.eat_word("data") {
+                return Ok(UpdateOperation::DeleteData(self.quad_data(false)?));
+            }
+            if self.tokens.eat_word("where") {
+                let quads = self.quad_pattern()?;
+                self.check_no_blanks(&quads)?;
+                return Ok(UpdateOperation::DeleteWhere(quads));
+            }
+            let delete = self.quad_pattern()?;
+            self.check_no_blanks(&delete)?;
+            let insert = if self.tokens.eat_word("insert") { self.quad_pattern()? } else { Vec::new() };
+            return self.modify(None, delete, insert);
+        }
+        if self.tokens.eat_word("with") {
+            let with = self.iri()?;
+            let delete = if self.tokens.eat_word("delete") { self.quad_pattern()? } else { Vec::new() };
+            self.check_no_blanks(&delete)?;
+            let insert = if self.tokens.eat_word("insert") { self.quad_pattern()? } else { Vec::new() };
+            if delete.is_empty() && insert.is_empty() && !self.tokens.is_word("where") {
+                return self.tokens.error("Expected DELETE or INSERT");
+            }
+            return self.modify(Some(with), delete, insert);
+        }
+        if self.tokens.eat_word("clear") || self.tokens.eat_word("drop") {
+            let silent = self.tokens.eat_word("silent");
+            let target = if self.tokens.eat_word("default") { GraphTarget::Default }
+                else if self.tokens.eat_word("named") { GraphTarget::AllNamed }
+                else if self.tokens.eat_word("all") { GraphTarget::All }
+                else { self.tokens.expect_word("graph")?; GraphTarget::Named(self.iri()?) };
+            return Ok(UpdateOperation::Clear { target, silent });
+        }
+        if self.tokens.eat_word("create") {
+            let silent = self.tokens.eat_word("silent");
+            self.tokens.expect_word("graph")?;
+            return Ok(UpdateOperation::Create { graph: self.iri()?, silent });
+        }
+        if self.tokens.eat_word("load") {
+            let silent = self.tokens.eat_word("silent");
+            let source = self.iri()?;
+            let into = if self.tokens.eat_word("into") { self.tokens.expect_word("graph")?; Some(self.iri()?) } else { None };
+            return Ok(UpdateOperation::Load { source, into, silent });
+        }
+        for keyword in ["add", "copy", "move"].iter() {
+            if self.tokens.eat_word(keyword) {
+                let silent = self.tokens.eat_word("silent");
+                let from = self.graph_or_default()?;
+                self.tokens.expect_word("to")?;
+                let to = self.graph_or_default()?;
+                return Ok(match *keyword {
+                    "add" => UpdateOperation::Add { from, to, silent },
+                    "copy" => UpdateOperation::Copy { from, to, silent },
+                    _ => UpdateOperation::Move { from, to, silent },
+                });
+            }
+        }
+        self.tokens.error("Expected an update operation")
+    }
+
+    fn modify(&mut self, with: Option<String>, delete: Vec<QuadPattern>, insert: Vec<QuadPattern>) -> Result<UpdateOperation, String> {
+        if self.tokens.is_word("using") {
+            return self.tokens.error("USING is not supported; use GRAPH in the pattern instead");
+        }
+        self.tokens.expect_word("where")?;
+        let pattern = self.group_graph_pattern()?;
+        Ok(UpdateOperation::Modify { with, delete, insert, pattern: Box::new(pattern) })
+    }
+
+    fn graph_or_default(&mut self) -> Result<Option<String>, String> {
+        if self.tokens.eat_word("default") {
+            return Ok(None);
+        }
+        self.tokens.eat_word("graph");
+        Ok(Some(self.iri()?))
+    }
+
+    fn check_no_blanks(&self, quads: &[QuadPattern]) -> Result<(), String> {
+        for quad in quads.iter() {
+            let t = &quad.triple;
+            if [&t.subject, &t.predicate, &t.object].iter().any(|p| p.as_variable().map(|v| v.starts_with("_:")).unwrap_or(false)) {
+                return Err("Blank nodes are not allowed in DELETE templates.".to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    /* Ground quads for INSERT DATA and DELETE DATA; only inserted data may hold blank nodes. */
+    fn quad_data(&mut self, allow_blanks: bool) -> Result<Vec<QuadPattern>, String> {
+        let quads = self.quad_pattern()?;
+        for quad in quads.iter() {
+            let t = &quad.triple;
+            for p in [&t.subject, &t.predicate, &t.object].iter().chain(quad.graph.iter().collect::<Vec<_>>().iter()) {
+                if let Some(v) = p.as_variable() {
+                    if !v.starts_with("_:") {
+                        return Err(format!("Variable ?{} is not allowed in data.", v));
+                    }
+                    if !allow_blanks {
+                        return Err("Blank nodes are not allowed in DELETE DATA.".to_owned());
+                    }
+                }
+            }
+        }
+        Ok(quads)
+    }
+
+    fn quad_pattern(&mut self) -> Result<Vec<QuadPattern>, String> {
+        self.tokens.expect_punct("{")?;
+        let mut quads = Vec::new();
+        loop {
+            let mut triples = Vec::new();
+            self.triples_block(&mut triples)?;
+            quads.extend(triples.into_iter().map(|triple| QuadPattern { triple, graph: None }));
+            if !self.tokens.eat_word("graph") {
+                break;
+            }
+            let graph = self.var_or_iri()?;
+            let triples = self.triples_template()?;
+            quads.extend(triples.into_iter().map(|triple| QuadPattern { triple, graph: Some(graph.clone()) }));
+            self.tokens.eat_punct(".");
+        }
+        self.tokens.expect_punct("}")?;
+        Ok(quads)
+    }
+}
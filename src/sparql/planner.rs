@@ -0,0 +1,343 @@
+use std::collections::BTreeSet;
+use fulltext;
+use identifiers::InternalID;
+use indexed_quad_set::{IndexKind, QuadStatistics, INDEX_KINDS};
+use nquads::Term;
+use store::{StorageEngine, GraphID};
+use sparql::algebra::{GraphPattern, TermPattern, TriplePattern};
+
+/* Relative costs per row: an index seek for each probe of a nested loop, and building or probing a
+   hash table. Scanning a row of an index range costs 1. */
+const SEEK_COST: f64 = 4.0;
+const HASH_COST: f64 = 2.0;
+/* Subjects assumed to match a full-text query, which has no statistics. */
+const FULLTEXT_ESTIMATE: f64 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JoinAlgorithm {
+    /* Probes the index once for each solution so far, with the shared variables bound. */
+    NestedLoop,
+    /* Scans the pattern on its own and matches it to the solutions so far through a hash table. */
+    Hash,
+    /* Scans the pattern on its own and merges it with the solutions so far, both ordered by the one shared variable. */
+    Merge,
+}
+
+impl JoinAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            JoinAlgorithm::NestedLoop => "nested loop",
+            JoinAlgorithm::Hash => "hash",
+            JoinAlgorithm::Merge => "merge",
+        }
+    }
+}
+
+/// A triple pattern of a basic graph pattern plan, joined onto the solutions of the steps before it.
+/// The first step joins onto the single empty solution, so it is a plain scan.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlanStep {
+    pub pattern: TriplePattern,
+    /* The index read for the pattern, or `None` for the full-text match pattern. */
+    pub index: Option<IndexKind>,
+    pub join: JoinAlgorithm,
+    /* Variables bound by earlier steps that the pattern shares. */
+    pub join_variables: Vec<String>,
+    /* Estimated solutions after this step. */
+    pub estimated_rows: f64,
+}
+
+/// The triple patterns of a basic graph pattern in evaluation order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BgpPlan {
+    pub steps: Vec<PlanStep>,
+}
+
+/// What the planner knows about one position of a triple pattern.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Binding {
+    Free,
+    /* A constant term, or `None` for a term that is not in the store. */
+    Constant(Option<InternalID>),
+    /* A variable bound by an earlier step, to a value not known until evaluation. */
+    Joined,
+}
+
+impl Binding {
+    fn is_bound(&self) -> bool {
+        *self != Binding::Free
+    }
+}
+
+/// Quad counts from the statistics of the asserted and inferred indexes, used to estimate how many
+/// quads a pattern matches. Positions are assumed independent, except that a predicate's own
+/// subject and object counts are used when it is known.
+pub struct Cardinalities<'a> {
+    sources: Vec<&'a QuadStatistics>,
+    quads: f64,
+}
+
+impl<'a> Cardinalities<'a> {
+    pub fn new(store: &'a StorageEngine) -> Cardinalities<'a> {
+        let quads = (store.quad_count() + store.inferred_quad_count()) as f64;
+        Cardinalities { sources: store.quad_statistics(), quads }
+    }
+
+    fn sum<F: Fn(&QuadStatistics) -> usize>(&self, count: F) -> f64 {
+        self.sources.iter().map(|s| count(s)).sum::<usize>() as f64
+    }
+
+    pub fn quads(&self) -> f64 {
+        self.quads
+    }
+
+    /// The estimated number of quads matching a pattern in `graph`, or in any graph for `None`.
+    pub fn estimate(&self, graph: Option<&GraphID>, subject: &Binding, predicate: &Binding, object: &Binding) -> f64 {
+        if self.quads == 0.0 {
+            return 0.0;
+        }
+        let mut estimate = self.quads;
+        let mut per_predicate = None;
+        match *predicate {
+            Binding::Constant(None) => return 0.0,
+            Binding::Constant(Some(ref p)) => {
+                let quads = self.sum(|s| s.predicate(p).map(|e| e.quads).unwrap_or(0));
+                let subjects = self.sum(|s| s.predicate(p).map(|e| e.subjects).unwrap_or(0));
+                let objects = self.sum(|s| s.predicate(p).map(|e| e.objects).unwrap_or(0));
+                estimate = quads;
+                per_predicate = Some((subjects.max(1.0), objects.max(1.0)));
+            }
+            Binding::Joined => estimate /= self.sum(|s| s.predicate_count()).max(1.0),
+            Binding::Free => {}
+        }
+        let quads = self.quads;
+        /* A constant never matches more quads than hold it in that position. */
+        let narrow = |estimate: f64, binding: &Binding, count: &Fn(&QuadStatistics, &InternalID) -> usize, distinct: f64, per_predicate: Option<f64>| match *binding {
+            Binding::Free => estimate,
+            Binding::Constant(None) => 0.0,
+            Binding::Constant(Some(ref id)) => {
+                let matching = self.sum(|s| count(s, id));
+                (estimate * per_predicate.map(|d| 1.0 / d).unwrap_or(matching / quads)).min(matching)
+            }
+            Binding::Joined => estimate / per_predicate.unwrap_or(distinct).max(1.0),
+        };
+        estimate = narrow(estimate, subject, &|s, id| s.subject_quads(id), self.sum(|s| s.subject_count()), per_predicate.map(|p| p.0));
+        estimate = narrow(estimate, object, &|s, id| s.object_quads(id), self.sum(|s| s.object_count()), per_predicate.map(|p| p.1));
+        if let Some(g) = graph {
+            estimate *= self.sum(|s| s.graph_quads(g)) / quads;
+        }
+        if subject.is_bound() && predicate.is_bound() && object.is_bound() {
+            estimate = estimate.min(1.0);
+        }
+        estimate
+    }
+}
+
+/// A way of reading a pattern from one index: the quads in the range of its bound key prefix, and
+/// the position (in graph, subject, predicate, object order) its results are ordered by, if any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexChoice {
+    pub index: IndexKind,
+    pub range: f64,
+    pub ordered_by: Option<usize>,
+}
+
+/// Orders the patterns of basic graph patterns and chooses their indexes and joins from the
+/// store's quad statistics.
+pub struct Planner<'a> {
+    store: &'a StorageEngine,
+    cardinalities: Cardinalities<'a>,
+}
+
+/* A pattern's position bindings and estimates, with or without the variables bound by earlier steps. */
+struct Candidate {
+    bindings: [Binding; 3],
+    rows: f64,
+    choices: Vec<IndexChoice>,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(store: &'a StorageEngine) -> Planner<'a> {
+        Planner { store, cardinalities: Cardinalities::new(store) }
+    }
+
+    pub fn borrow_cardinalities(&self) -> &Cardinalities<'a> {
+        &self.cardinalities
+    }
+
+    fn binding(&self, position: &TermPattern, bound: &BTreeSet<String>) -> Binding {
+        match *position {
+            TermPattern::Term(ref term) => Binding::Constant(term.find(self.store).map(|id| self.store.canonical_id(&id))),
+            TermPattern::Variable(ref v) if bound.contains(v) => Binding::Joined,
+            TermPattern::Variable(_) => Binding::Free,
+        }
+    }
+
+    /// Every index the pattern can be read from, cheapest range first.
+    pub fn index_choices(&self, graph: &GraphID, bindings: &[Binding; 3]) -> Vec<IndexChoice> {
+        let mut choices: Vec<IndexChoice> = INDEX_KINDS.iter().map(|&index| {
+            let order = index.key_order();
+            let is_bound = |position: usize| position == 0 || bindings[position - 1].is_bound();
+            let prefix = order.iter().take_while(|&&position| is_bound(position)).count();
+            let in_prefix = |position: usize| order[..prefix].contains(&position);
+            let prefix_binding = |position: usize| if in_prefix(position) { bindings[position - 1].clone() } else { Binding::Free };
+            let range = self.cardinalities.estimate(if in_prefix(0) { Some(graph) } else { None }, &prefix_binding(1), &prefix_binding(2), &prefix_binding(3));
+            let ordered_by = order.get(prefix).cloned().filter(|&position| !is_bound(position));
+            IndexChoice { index, range, ordered_by }
+        }).collect();
+        /* The sort is stable, so ties keep the GSPO, SPOG, POSG, OSPG order. */
+        choices.sort_by(|a, b| a.range.partial_cmp(&b.range).unwrap_or(::std::cmp::Ordering::Equal));
+        choices
+    }
+
+    fn candidate(&self, pattern: &TriplePattern, graph: &GraphID, bound: &BTreeSet<String>) -> Candidate {
+        let bindings = [self.binding(&pattern.subject, bound), self.binding(&pattern.predicate, bound), self.binding(&pattern.object, bound)];
+        let rows = self.cardinalities.estimate(Some(graph), &bindings[0], &bindings[1], &bindings[2]);
+        let choices = self.index_choices(graph, &bindings);
+        Candidate { bindings, rows, choices }
+    }
+
+    /// Orders `triples` greedily, each time taking the pattern that is cheapest to join onto the
+    /// solutions so far, preferring patterns that share a variable with them over cross products.
+    pub fn plan_bgp(&self, triples: &[TriplePattern], graph: &GraphID) -> BgpPlan {
+        let mut remaining: Vec<&TriplePattern> = triples.iter().collect();
+        let mut bound: BTreeSet<String> = BTreeSet::new();
+        let mut rows = 1.0;
+        let mut ordered_by: Option<String> = None;
+        let mut steps = Vec::new();
+        while !remaining.is_empty() {
+            /* A full-text pattern with a variable query waits until the variable is bound. */
+            let ready: Vec<usize> = (0..remaining.len()).filter(|&i| {
+                !is_fulltext(remaining[i]) || remaining[i].object.as_variable().map(|v| bound.contains(v)).unwrap_or(true)
+            }).collect();
+            let ready = if ready.is_empty() { (0..remaining.len()).collect() } else { ready };
+            let connected: Vec<usize> = ready.iter().cloned().filter(|&i| !shared_variables(remaining[i], &bound).is_empty()).collect();
+            let eligible = if connected.is_empty() { ready } else { connected };
+            let mut best: Option<(f64, usize, PlanStep, Option<String>)> = None;
+            for i in eligible {
+                let (step, cost, ordering) = self.plan_step(remaining[i], graph, &bound, rows, ordered_by.as_deref());
+                let score = cost + step.estimated_rows;
+                if best.as_ref().map(|b| score < b.0).unwrap_or(true) {
+                    best = Some((score, i, step, ordering));
+                }
+            }
+            let (_, i, step, ordering) = best.unwrap();
+            let pattern = remaining.remove(i);
+            ordered_by = ordering;
+            rows = step.estimated_rows;
+            for position in [&pattern.subject, &pattern.predicate, &pattern.object].iter() {
+                if let Some(v) = position.as_variable() {
+                    bound.insert(v.to_owned());
+                }
+            }
+            steps.push(step);
+        }
+        BgpPlan { steps }
+    }
+
+    /* The cheapest way of joining `pattern` onto `rows` solutions binding `bound` and ordered by
+       `ordered_by`, with its cost and the variable the joined solutions are ordered by. Nested
+       loop and hash joins keep the order of the solutions so far. */
+    fn plan_step(&self, pattern: &TriplePattern, graph: &GraphID, bound: &BTreeSet<String>, rows: f64, ordered_by: Option<&str>)
+                 -> (PlanStep, f64, Option<String>) {
+        let join_variables = shared_variables(pattern, bound);
+        let ordering = ordered_by.map(|v| v.to_owned());
+        if is_fulltext(pattern) {
+            let matches = if self.binding(&pattern.subject, bound).is_bound() { 1.0 } else { FULLTEXT_ESTIMATE };
+            let step = PlanStep { pattern: pattern.clone(), index: None, join: JoinAlgorithm::NestedLoop, join_variables, estimated_rows: rows * matches };
+            return (step, rows * (SEEK_COST + matches), ordering);
+        }
+        let probe = self.candidate(pattern, graph, bound);
+        let mut step = PlanStep { pattern: pattern.clone(), index: Some(probe.choices[0].index), join: JoinAlgorithm::NestedLoop,
+            join_variables: join_variables.clone(), estimated_rows: rows * probe.rows };
+        let mut cost = rows * (SEEK_COST + probe.choices[0].range);
+        if bound.is_empty() {
+            let ordering = probe.choices[0].ordered_by.and_then(|position| position_pattern(pattern, position).as_variable().map(|v| v.to_owned()));
+            return (step, cost, ordering);
+        }
+        if join_variables.is_empty() || probe.bindings.iter().all(|b| *b != Binding::Joined) {
+            return (step, cost, ordering);
+        }
+        let scan = self.candidate(pattern, graph, &BTreeSet::new());
+        let hash_cost = scan.choices[0].range + HASH_COST * (rows + scan.rows);
+        if hash_cost < cost {
+            step.index = Some(scan.choices[0].index);
+            step.join = JoinAlgorithm::Hash;
+            cost = hash_cost;
+        }
+        if join_variables.len() == 1 && ordered_by == Some(join_variables[0].as_str()) {
+            let position = [&pattern.subject, &pattern.predicate, &pattern.object].iter()
+                .position(|p| p.as_variable() == Some(join_variables[0].as_str())).map(|i| i + 1);
+            if let Some(choice) = scan.choices.iter().find(|c| c.ordered_by.is_some() && c.ordered_by == position) {
+                let merge_cost = choice.range + rows + scan.rows;
+                if merge_cost < cost {
+                    step.index = Some(choice.index);
+                    step.join = JoinAlgorithm::Merge;
+                    cost = merge_cost;
+                }
+            }
+        }
+        (step, cost, ordering)
+    }
+}
+
+fn is_fulltext(pattern: &TriplePattern) -> bool {
+    match pattern.predicate {
+        TermPattern::Term(Term::Iri(ref predicate)) => predicate == fulltext::FULLTEXT_MATCH_PREDICATE,
+        _ => false,
+    }
+}
+
+/* The pattern at a position in graph, subject, predicate, object order; the graph is never a variable here. */
+fn position_pattern(pattern: &TriplePattern, position: usize) -> &TermPattern {
+    match position {
+        1 => &pattern.subject,
+        2 => &pattern.predicate,
+        _ => &pattern.object,
+    }
+}
+
+fn shared_variables(pattern: &TriplePattern, bound: &BTreeSet<String>) -> Vec<String> {
+    let mut shared = Vec::new();
+    for position in [&pattern.subject, &pattern.predicate, &pattern.object].iter() {
+        if let Some(v) = position.as_variable() {
+            if bound.contains(v) && !shared.iter().any(|s| s == v) {
+                shared.push(v.to_owned());
+            }
+        }
+    }
+    shared
+}
+
+/// The variables bound in every solution of `pattern`.
+pub fn certain_variables(pattern: &GraphPattern) -> BTreeSet<String> {
+    match pattern {
+        &GraphPattern::Bgp(_) => pattern.variables().into_iter().collect(),
+        &GraphPattern::Join(ref a, ref b) => certain_variables(a).union(&certain_variables(b)).cloned().collect(),
+        &GraphPattern::Union(ref a, ref b) => certain_variables(a).intersection(&certain_variables(b)).cloned().collect(),
+        &GraphPattern::LeftJoin(ref a, _, _) | &GraphPattern::Minus(ref a, _) | &GraphPattern::Filter(_, ref a) |
+        &GraphPattern::Extend(ref a, _, _) => certain_variables(a),
+        &GraphPattern::Graph(ref name, ref inner) => {
+            let mut variables = certain_variables(inner);
+            variables.extend(name.as_variable().map(|v| v.to_owned()));
+            variables
+        }
+        &GraphPattern::Values(ref variables, ref rows) => {
+            variables.iter().enumerate().filter(|&(i, _)| rows.iter().all(|row| row[i].is_some())).map(|(_, v)| v.clone()).collect()
+        }
+        &GraphPattern::Path(..) => pattern.variables().into_iter().collect(),
+        &GraphPattern::Service(_, _, true) => BTreeSet::new(),
+        &GraphPattern::Service(_, ref inner, false) => certain_variables(inner),
+    }
+}
+
+/// How to join the solutions of two group patterns: by hashing on the variables both always bind,
+/// or by comparing every pair when they have none.
+pub fn choose_join(left: &GraphPattern, right: &GraphPattern) -> (JoinAlgorithm, Vec<String>) {
+    let variables: Vec<String> = certain_variables(left).intersection(&certain_variables(right)).cloned().collect();
+    if variables.is_empty() {
+        (JoinAlgorithm::NestedLoop, variables)
+    } else {
+        (JoinAlgorithm::Hash, variables)
+    }
+}
@@ -0,0 +1,473 @@
+use std::io::{self, Write};
+use json::{self, Json};
+use lexer::{Token, TokenStream};
+use namespace::NamespaceManager;
+use nquads::Term;
+use sparql::QueryResult;
+use turtle;
+use vocab;
+use xml;
+
+/// An encoding of query results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResultFormat {
+    Json,
+    Xml,
+    Csv,
+    Tsv,
+    Turtle,
+    NTriples,
+}
+
+static SOLUTION_FORMATS: &'static [ResultFormat] = &[ResultFormat::Json, ResultFormat::Xml, ResultFormat::Csv, ResultFormat::Tsv];
+static BOOLEAN_FORMATS: &'static [ResultFormat] = &[ResultFormat::Json, ResultFormat::Xml];
+/// The formats RDF graphs can be written in, preferred first.
+pub static GRAPH_FORMATS: &'static [ResultFormat] = &[ResultFormat::Turtle, ResultFormat::NTriples];
+
+static SPARQL_RESULTS_NAMESPACE: &'static str = "http://www.w3.org/2005/sparql-results#";
+
+impl ResultFormat {
+    pub fn media_type(&self) -> &'static str {
+        match *self {
+            ResultFormat::Json => "application/sparql-results+json",
+            ResultFormat::Xml => "application/sparql-results+xml",
+            ResultFormat::Csv => "text/csv",
+            ResultFormat::Tsv => "text/tab-separated-values",
+            ResultFormat::Turtle => "text/turtle",
+            ResultFormat::NTriples => "application/n-triples",
+        }
+    }
+
+    /* Media types accepted for this format. */
+    fn aliases(&self) -> &'static [&'static str] {
+        match *self {
+            ResultFormat::Json => &["application/sparql-results+json", "application/json"],
+            ResultFormat::Xml => &["application/sparql-results+xml", "application/xml", "text/xml"],
+            ResultFormat::Csv => &["text/csv"],
+            ResultFormat::Tsv => &["text/tab-separated-values"],
+            ResultFormat::Turtle => &["text/turtle", "application/x-turtle"],
+            ResultFormat::NTriples => &["application/n-triples", "text/plain"],
+        }
+    }
+
+    /// The format a media type (such as a `Content-Type` without parameters) names.
+    pub fn from_media_type(media_type: &str) -> Option<ResultFormat> {
+        let media_type = media_type.trim().to_lowercase();
+        [ResultFormat::Json, ResultFormat::Xml, ResultFormat::Csv, ResultFormat::Tsv, ResultFormat::Turtle, ResultFormat::NTriples]
+            .iter().find(|f| f.aliases().iter().any(|a| *a == media_type)).cloned()
+    }
+
+    /// The format with a short name such as `json` or `ttl`, as given on a command line.
+    pub fn from_name(name: &str) -> Option<ResultFormat> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(ResultFormat::Json),
+            "xml" => Some(ResultFormat::Xml),
+            "csv" => Some(ResultFormat::Csv),
+            "tsv" => Some(ResultFormat::Tsv),
+            "turtle" | "ttl" => Some(ResultFormat::Turtle),
+            "ntriples" | "nt" => Some(ResultFormat::NTriples),
+            _ => None,
+        }
+    }
+
+    /// The formats `result` can be written in, preferred first.
+    pub fn available(result: &QueryResult) -> &'static [ResultFormat] {
+        match result {
+            &QueryResult::Solutions { .. } => SOLUTION_FORMATS,
+            &QueryResult::Boolean(_) => BOOLEAN_FORMATS,
+            &QueryResult::Graph(_) => GRAPH_FORMATS,
+        }
+    }
+
+    /// Picks the format for `result` that an HTTP `Accept` header prefers, or the default format
+    /// when there is no header. `None` when nothing acceptable is available.
+    pub fn negotiate(accept: Option<&str>, result: &QueryResult) -> Option<ResultFormat> {
+        ResultFormat::choose(accept, ResultFormat::available(result))
+    }
+
+    /// Picks the one of `formats` that an HTTP `Accept` header prefers, or the first without a header.
+    pub fn choose(accept: Option<&str>, formats: &[ResultFormat]) -> Option<ResultFormat> {
+        let accept = match accept {
+            Some(a) if !a.trim().is_empty() => a,
+            _ => return formats.first().cloned(),
+        };
+        let mut best: Option<(f64, usize, ResultFormat)> = None;
+        for range in accept.split(',') {
+            let mut parts = range.split(';');
+            let media_range = parts.next().unwrap_or("").trim().to_lowercase();
+            let quality = parts.filter_map(|p| {
+                let p = p.trim();
+                p.strip_prefix("q=").and_then(|q| q.parse::<f64>().ok())
+            }).next().unwrap_or(1.0);
+            if quality <= 0.0 {
+                continue;
+            }
+            for (rank, format) in formats.iter().enumerate() {
+                let matches = media_range == "*/*" || format.aliases().iter().any(|alias| {
+                    *alias == media_range || (media_range.ends_with("/*") && alias.starts_with(&media_range[..media_range.len() - 1]))
+                });
+                let better = best.map(|(q, r, _)| quality > q || (quality == q && rank < r)).unwrap_or(true);
+                if matches && better {
+                    best = Some((quality, rank, *format));
+                }
+            }
+        }
+        best.map(|(_, _, format)| format)
+    }
+}
+
+fn json_term(term: &Term) -> String {
+    match term {
+        &Term::Iri(ref iri) => format!("{{\"type\":\"uri\",\"value\":{}}}", json::quote(iri)),
+        &Term::Blank(ref label) => format!("{{\"type\":\"bnode\",\"value\":{}}}", json::quote(label)),
+        &Term::Literal(ref value, _, Some(ref lang)) =>
+            format!("{{\"type\":\"literal\",\"value\":{},\"xml:lang\":{}}}", json::quote(value), json::quote(lang)),
+        &Term::Literal(ref value, Some(ref datatype), None) =>
+            format!("{{\"type\":\"literal\",\"value\":{},\"datatype\":{}}}", json::quote(value), json::quote(datatype)),
+        &Term::Literal(ref value, None, None) => format!("{{\"type\":\"literal\",\"value\":{}}}", json::quote(value)),
+    }
+}
+
+fn xml_term(term: &Term) -> String {
+    match term {
+        &Term::Iri(ref iri) => format!("<uri>{}</uri>", xml::escape(iri)),
+        &Term::Blank(ref label) => format!("<bnode>{}</bnode>", xml::escape(label)),
+        &Term::Literal(ref value, _, Some(ref lang)) => format!("<literal xml:lang=\"{}\">{}</literal>", xml::escape(lang), xml::escape(value)),
+        &Term::Literal(ref value, Some(ref datatype), None) =>
+            format!("<literal datatype=\"{}\">{}</literal>", xml::escape(datatype), xml::escape(value)),
+        &Term::Literal(ref value, None, None) => format!("<literal>{}</literal>", xml::escape(value)),
+    }
+}
+
+/* A CSV field: IRIs and literals by their text alone, quoted when they hold separators or quotes. */
+fn csv_field(term: &Term) -> String {
+    let text = match term {
+        &Term::Iri(ref iri) => iri.clone(),
+        &Term::Blank(ref label) => format!("_:{}", label),
+        &Term::Literal(ref value, _, _) => value.clone(),
+    };
+    if text.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Writes a solution sequence in `format`, encoding each row as `rows` yields it rather than
+/// building the whole document first. `format` must be one of the solution formats.
+pub fn write_solutions<W, I>(variables: &[String], rows: I, format: ResultFormat, out: &mut W) -> io::Result<()>
+    where W: Write, I: Iterator<Item=Vec<Option<Term>>> {
+    match format {
+        ResultFormat::Json => {
+            let vars: Vec<String> = variables.iter().map(|v| json::quote(v)).collect();
+            write!(out, "{{\"head\":{{\"vars\":[{}]}},\"results\":{{\"bindings\":[", vars.join(","))?;
+            for (i, row) in rows.enumerate() {
+                let bindings: Vec<String> = variables.iter().zip(row.iter())
+                    .filter_map(|(v, t)| t.as_ref().map(|t| format!("{}:{}", json::quote(v), json_term(t))))
+                    .collect();
+                write!(out, "{}\n{{{}}}", if i == 0 { "" } else { "," }, bindings.join(","))?;
+            }
+            writeln!(out, "]}}}}")
+        }
+        ResultFormat::Xml => {
+            write!(out, "<?xml version=\"1.0\"?>\n<sparql xmlns=\"{}\">\n  <head>\n", SPARQL_RESULTS_NAMESPACE)?;
+            for v in variables.iter() {
+                writeln!(out, "    <variable name=\"{}\"/>", xml::escape(v))?;
+            }
+            write!(out, "  </head>\n  <results>\n")?;
+            for row in rows {
+                writeln!(out, "    <result>")?;
+                for (v, t) in variables.iter().zip(row.iter()) {
+                    if let Some(ref t) = *t {
+                        writeln!(out, "      <binding name=\"{}\">{}</binding>", xml::escape(v), xml_term(t))?;
+                    }
+                }
+                writeln!(out, "    </result>")?;
+            }
+            write!(out, "  </results>\n</sparql>\n")
+        }
+        ResultFormat::Csv => {
+            let header: Vec<&str> = variables.iter().map(|v| v.as_str()).collect();
+            write!(out, "{}\r\n", header.join(","))?;
+            for row in rows {
+                let fields: Vec<String> = row.iter().map(|t| t.as_ref().map(csv_field).unwrap_or_default()).collect();
+                write!(out, "{}\r\n", fields.join(","))?;
+            }
+            Ok(())
+        }
+        ResultFormat::Tsv => {
+            let header: Vec<String> = variables.iter().map(|v| format!("?{}", v)).collect();
+            writeln!(out, "{}", header.join("\t"))?;
+            for row in rows {
+                let fields: Vec<String> = row.iter().map(|t| t.as_ref().map(|t| t.to_ntriples()).unwrap_or_default()).collect();
+                writeln!(out, "{}", fields.join("\t"))?;
+            }
+            Ok(())
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} cannot encode solutions.", format.media_type()))),
+    }
+}
+
+/// Writes `result` in `format`, which must be one of `ResultFormat::available(result)`.
+pub fn write<W: Write>(result: &QueryResult, format: ResultFormat, out: &mut W) -> io::Result<()> {
+    match (format, result) {
+        (_, &QueryResult::Solutions { ref variables, ref rows }) => write_solutions(variables, rows.iter().cloned(), format, out),
+        (ResultFormat::Json, &QueryResult::Boolean(value)) => writeln!(out, "{{\"head\":{{}},\"boolean\":{}}}", value),
+        (ResultFormat::Xml, &QueryResult::Boolean(value)) =>
+            write!(out, "<?xml version=\"1.0\"?>\n<sparql xmlns=\"{}\">\n  <head/>\n  <boolean>{}</boolean>\n</sparql>\n", SPARQL_RESULTS_NAMESPACE, value),
+        (ResultFormat::Turtle, &QueryResult::Graph(ref triples)) =>
+            out.write_all(turtle::serialize(triples, &NamespaceManager::default()).as_bytes()),
+        (ResultFormat::NTriples, &QueryResult::Graph(ref triples)) => {
+            for &(ref s, ref p, ref o) in triples.iter() {
+                writeln!(out, "{} {} {} .", s.to_ntriples(), p.to_ntriples(), o.to_ntriples())?;
+            }
+            Ok(())
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} cannot encode this result.", format.media_type()))),
+    }
+}
+
+/* Adds `name` to the variables if it is new, returning its column. */
+fn column(variables: &mut Vec<String>, name: &str) -> usize {
+    match variables.iter().position(|v| v == name) {
+        Some(i) => i,
+        None => {
+            variables.push(name.to_owned());
+            variables.len() - 1
+        }
+    }
+}
+
+/* Pads every row to the final number of variables. */
+fn solutions(variables: Vec<String>, mut rows: Vec<Vec<Option<Term>>>) -> QueryResult {
+    for row in rows.iter_mut() {
+        row.resize(variables.len(), None);
+    }
+    QueryResult::Solutions { variables: variables, rows: rows }
+}
+
+fn literal(value: String, datatype: Option<&str>, lang: Option<&str>) -> Term {
+    match (datatype, lang) {
+        (_, Some(lang)) => Term::Literal(value, None, Some(lang.to_owned())),
+        (Some(datatype), None) if datatype != vocab::XSD_STRING => Term::Literal(value, Some(datatype.to_owned()), None),
+        _ => Term::Literal(value, None, None),
+    }
+}
+
+fn read_json(text: &str) -> Result<QueryResult, String> {
+    let document = json::parse(text)?;
+    if let Some(boolean) = document.get("boolean") {
+        return match *boolean {
+            Json::Bool(value) => Ok(QueryResult::Boolean(value)),
+            _ => Err("The \"boolean\" member must be true or false.".to_owned()),
+        };
+    }
+    let mut variables = Vec::new();
+    for v in document.get("head").and_then(|h| h.get("vars")).and_then(|v| v.as_array()).unwrap_or(&[]) {
+        column(&mut variables, v.as_str().ok_or("Variable names must be strings.")?);
+    }
+    let bindings = document.get("results").and_then(|r| r.get("bindings")).and_then(|b| b.as_array())
+        .ok_or("Expected \"results\" with a \"bindings\" array, or \"boolean\".")?;
+    let mut rows = Vec::new();
+    for binding in bindings.iter() {
+        let members = match *binding {
+            Json::Object(ref members) => members,
+            _ => return Err("Each binding must be an object.".to_owned()),
+        };
+        let mut row = vec![None; variables.len()];
+        for (name, term) in members.iter() {
+            let value = term.get("value").and_then(|v| v.as_str()).ok_or_else(|| format!("The binding of {} has no value.", name))?.to_owned();
+            let term = match term.get("type").and_then(|t| t.as_str()) {
+                Some("uri") => Term::Iri(value),
+                Some("bnode") => Term::Blank(value),
+                Some("literal") | Some("typed-literal") => literal(value, term.get("datatype").and_then(|d| d.as_str()),
+                    term.get("xml:lang").and_then(|l| l.as_str())),
+                other => return Err(format!("Unsupported term type {:?} for {}.", other, name)),
+            };
+            let i = column(&mut variables, name);
+            row.resize(variables.len(), None);
+            row[i] = Some(term);
+        }
+        rows.push(row);
+    }
+    Ok(solutions(variables, rows))
+}
+
+fn read_xml(text: &str) -> Result<QueryResult, String> {
+    let root = xml::parse(text)?;
+    if root.local_name() != "sparql" {
+        return Err(format!("Expected a <sparql> document, found <{}>.", root.name));
+    }
+    if let Some(boolean) = root.child("boolean") {
+        return match boolean.text().trim() {
+            "true" => Ok(QueryResult::Boolean(true)),
+            "false" => Ok(QueryResult::Boolean(false)),
+            other => Err(format!("Invalid boolean result {:?}.", other)),
+        };
+    }
+    let mut variables = Vec::new();
+    if let Some(head) = root.child("head") {
+        for variable in head.elements().filter(|e| e.local_name() == "variable") {
+            column(&mut variables, variable.attribute("name").ok_or("A <variable> has no name.")?);
+        }
+    }
+    let results = root.child("results").ok_or("Expected <results> or <boolean>.")?;
+    let mut rows = Vec::new();
+    for result in results.elements().filter(|e| e.local_name() == "result") {
+        let mut row = vec![None; variables.len()];
+        for binding in result.elements().filter(|e| e.local_name() == "binding") {
+            let name = binding.attribute("name").ok_or("A <binding> has no name.")?;
+            let value = binding.elements().next().ok_or_else(|| format!("The binding of {} is empty.", name))?;
+            let term = match value.local_name() {
+                "uri" => Term::Iri(value.text()),
+                "bnode" => Term::Blank(value.text()),
+                "literal" => literal(value.text(), value.attribute("datatype"), value.attribute("xml:lang")),
+                other => return Err(format!("Unsupported term <{}> for {}.", other, name)),
+            };
+            let i = column(&mut variables, name);
+            row.resize(variables.len(), None);
+            row[i] = Some(term);
+        }
+        rows.push(row);
+    }
+    Ok(solutions(variables, rows))
+}
+
+/* Splits CSV text into records of fields, following RFC 4180 quoting. */
+fn csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut quoted = false;
+    let mut at_field_start = true;
+    while let Some(c) = chars.next() {
+        if quoted {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            } else {
+                field.push(c);
+            }
+            continue;
+        }
+        match c {
+            '"' if at_field_start => {
+                quoted = true;
+                at_field_start = false;
+            }
+            ',' => {
+                record.push(field.clone());
+                field.clear();
+                at_field_start = true;
+            }
+            '\r' | '\n' => {
+                if c == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                record.push(field.clone());
+                field.clear();
+                records.push(record.clone());
+                record.clear();
+                at_field_start = true;
+            }
+            other => {
+                field.push(other);
+                at_field_start = false;
+            }
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field in CSV results.".to_owned());
+    }
+    if !at_field_start || !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/* CSV does not record term types: `_:` values are read as blank nodes and all others as plain literals. */
+fn read_csv(text: &str) -> Result<QueryResult, String> {
+    let mut records = csv_records(text)?.into_iter();
+    let variables = records.next().ok_or("CSV results have no header row.")?;
+    let mut rows = Vec::new();
+    for (i, record) in records.enumerate() {
+        if record.len() != variables.len() {
+            return Err(format!("CSV row {} has {} fields, expected {}.", i + 1, record.len(), variables.len()));
+        }
+        rows.push(record.into_iter().map(|field| {
+            if field.is_empty() {
+                None
+            } else if let Some(label) = field.strip_prefix("_:") {
+                Some(Term::Blank(label.to_owned()))
+            } else {
+                Some(Term::Literal(field, None, None))
+            }
+        }).collect());
+    }
+    Ok(solutions(variables, rows))
+}
+
+/* One TSV field: an N-Triples term, or a Turtle number or boolean. */
+fn tsv_term(field: &str) -> Result<Term, String> {
+    let mut tokens = TokenStream::new(field)?;
+    let sign = if tokens.eat_punct("-") { "-" } else if tokens.eat_punct("+") { "+" } else { "" };
+    let term = match tokens.peek().cloned() {
+        Some(Token::Integer(n)) => Term::Literal(format!("{}{}", sign, n), Some(vocab::XSD_INTEGER.to_owned()), None),
+        Some(Token::Decimal(n)) => Term::Literal(format!("{}{}", sign, n), Some(vocab::XSD_DECIMAL.to_owned()), None),
+        Some(Token::Double(n)) => Term::Literal(format!("{}{}", sign, n), Some(vocab::XSD_DOUBLE.to_owned()), None),
+        Some(Token::Word(ref w)) if sign.is_empty() && (w == "true" || w == "false") =>
+            Term::Literal(w.clone(), Some(vocab::XSD_BOOLEAN.to_owned()), None),
+        _ if sign.is_empty() => {
+            let term = Term::read(&mut tokens)?;
+            return if tokens.is_at_end() { Ok(term) } else { Err(format!("Unexpected text after the term in {:?}.", field)) };
+        }
+        _ => return Err(format!("Invalid TSV term {:?}.", field)),
+    };
+    tokens.next();
+    if tokens.is_at_end() { Ok(term) } else { Err(format!("Unexpected text after the term in {:?}.", field)) }
+}
+
+fn read_tsv(text: &str) -> Result<QueryResult, String> {
+    let mut lines: Vec<&str> = text.split('\n').map(|l| l.trim_end_matches('\r')).collect();
+    if lines.len() > 1 && lines.last() == Some(&"") {
+        lines.pop();
+    }
+    let mut variables = Vec::new();
+    for name in lines[0].split('\t').filter(|n| !n.is_empty()) {
+        if !name.starts_with('?') && !name.starts_with('$') {
+            return Err(format!("TSV variable {:?} does not start with '?'.", name));
+        }
+        variables.push(name[1..].to_owned());
+    }
+    let mut rows = Vec::new();
+    for (i, line) in lines[1..].iter().enumerate() {
+        let fields: Vec<&str> = if variables.is_empty() { Vec::new() } else { line.split('\t').collect() };
+        if fields.len() != variables.len() {
+            return Err(format!("TSV row {} has {} fields, expected {}.", i + 1, fields.len(), variables.len()));
+        }
+        let mut row = Vec::new();
+        for field in fields {
+            row.push(if field.is_empty() { None } else { Some(tsv_term(field).map_err(|e| format!("TSV row {}: {}", i + 1, e))?) });
+        }
+        rows.push(row);
+    }
+    Ok(solutions(variables, rows))
+}
+
+/// Reads results written in `format`. CSV keeps only the text of terms, so IRIs and typed
+/// literals come back as plain literals.
+pub fn read(text: &str, format: ResultFormat) -> Result<QueryResult, String> {
+    match format {
+        ResultFormat::Json => read_json(text),
+        ResultFormat::Xml => read_xml(text),
+        ResultFormat::Csv => read_csv(text),
+        ResultFormat::Tsv => read_tsv(text),
+        ResultFormat::Turtle | ResultFormat::NTriples => turtle::parse(text, None).map(QueryResult::Graph),
+    }
+}
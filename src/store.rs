@@ -1,13 +1,34 @@
 
-use std::borrow::Borrow;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::iter;
 use identifiers::{InternalID, InternalUriID, ThirtyTwoBitID, SixtyFourBitID};
 use uri::RDFUri;
-use literal::Literal;
+use literal::{self, Literal};
 use blank::BlankNode;
 use indexed_hash_map::{IndexedIDHashMap};
-use indexed_quad_set::{test_me, IndexedQuadSet, SearchableIndex, IndexOrder, SPOG, GSPO, POSG, OSPG, SPOGIndex, GSPOIndex, POSGIndex, OSPGIndex};
+use fulltext::{self, FullTextIndex};
+use iri::IriRef;
+use uri_split::{UriSplitter, LastDelimiterSplitter};
+use namespace::NamespaceManager;
+use inference::{InferenceRule, InferenceState, Inconsistency, Justification, Proof, ProofStep};
+use rdfs::{RdfsVocabulary, rdfs_rules};
+use owl_rl::{OwlVocabulary, owl_rl_rules};
+use vocab;
+use datalog::{RuleProgram, DEFAULT_RULES_GRAPH_URI};
+use equality::EqualityState;
+use shacl::{self, ValidationReport};
+use shex::{Schema, ShapeMapEntry};
+use canonicalize::{Canonicalizer, CanonicalDataset};
+use isomorphism::{self, Comparison};
+use nquads;
+use patch::{self, Patch};
+use http::{HttpClient, TcpHttpClient};
+use limits::{Limits, OperationError};
+use sparql::{self, QueryResult};
+use sparql::entailment::EntailmentRegime;
+use sparql::explain::PlanNode;
+use indexed_quad_set::{QuadIndexes, QuadStatistics, IndexKind, SearchableIndex, IndexOrder};
 
 #[derive(PartialEq, PartialOrd, Clone, Hash)]
 pub enum StoreNode {
@@ -17,6 +38,24 @@ pub enum StoreNode {
 }
 
 impl StoreNode {
+    /// The node in canonical N-Triples syntax. Plain `xsd:string` literals are written without a datatype.
+    pub fn to_ntriples(&self, store: &StorageEngine) -> String {
+        match self {
+            &StoreNode::URIRef(ref u) => format!("<{}>", u.to_string(store)),
+            &StoreNode::Blank(ref b) => format!("_:{}", b.borrow_label()),
+            &StoreNode::Literal(ref l) => {
+                let escaped = nquads::escape_string(l.borrow_lexical_form());
+                let datatype = l.borrow_datatype_uri().to_string(store);
+                if let Some(lang) = l.borrow_lang() {
+                    format!("\"{}\"@{}", escaped, lang)
+                } else if datatype == literal::STRING_URI {
+                    format!("\"{}\"", escaped)
+                } else {
+                    format!("\"{}\"^^<{}>", escaped, datatype)
+                }
+            }
+        }
+    }
 }
 
 pub type SubjectID = InternalID;
@@ -24,6 +63,8 @@ pub type PredicateID = InternalID;
 pub type ObjectID = InternalID;
 pub type GraphID = InternalID;
 pub type InternalQuad = (SubjectID, PredicateID, ObjectID, GraphID);
+/// Quads of store nodes, in graph, subject, predicate, object order, as `search_nodes` finds them.
+pub type NodeQuads<'a> = Box<Iterator<Item=(&'a StoreNode, &'a StoreNode, &'a StoreNode, &'a StoreNode)>+'a>;
 
 type PrefixMap = IndexedIDHashMap<String, ThirtyTwoBitID>;
 type SuffixMap = IndexedIDHashMap<String, ThirtyTwoBitID>;
@@ -31,82 +72,157 @@ type ObjectMap = IndexedIDHashMap<StoreNode, SixtyFourBitID>;
 
 pub static DEFAULT_GRAPH_URI: &'static str = "http://internal/graph";
 
+/// Dictionary and index sizes, as reported by `StorageEngine::statistics`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StoreStatistics {
+    pub iri_count: usize,
+    pub blank_node_count: usize,
+    pub literal_count: usize,
+    /* Node IDs freed by removals and not yet reused. */
+    pub free_node_ids: usize,
+    pub iri_prefix_count: usize,
+    pub iri_suffix_count: usize,
+    pub namespace_count: usize,
+    pub graph_count: usize,
+    pub quad_count: usize,
+    pub inferred_quad_count: usize,
+    /* Entries in each ordering of the asserted quad indexes. */
+    pub index_entries: Vec<(&'static str, usize)>,
+    /* Indexed literals and distinct terms, when the full-text index is enabled. */
+    pub fulltext_index: Option<(usize, usize)>,
+}
+
 pub struct StorageEngine {
     object_map: ObjectMap,
     prefix_map: PrefixMap,
     suffix_map: SuffixMap,
-    spog_index: SPOGIndex,
-    gspo_index: GSPOIndex,
-    posg_index: POSGIndex,
-    ospg_index: OSPGIndex
+    quad_indexes: QuadIndexes,
+    fulltext_index: Option<FullTextIndex>,
+    normalize_iris: bool,
+    uri_splitter: Box<UriSplitter>,
+    namespace_manager: NamespaceManager,
+    inference: Option<InferenceState>,
+    equality: Option<EqualityState>,
+    http_client: Box<HttpClient>,
+    allowed_services: Option<Vec<String>>,
+    revision: u64,
 }
 
 impl Default for StorageEngine {
     fn default() -> StorageEngine {
+        StorageEngine::with_uri_splitter(Box::new(LastDelimiterSplitter))
+    }
+}
+
+
+impl StorageEngine {
+    /// Creates a store whose IRI dictionary uses the given prefix/suffix splitting strategy.
+    /// The strategy cannot be swapped later, because existing IRIs must keep splitting the same way.
+    pub fn with_uri_splitter(uri_splitter: Box<UriSplitter>) -> StorageEngine {
         let mut fresh = StorageEngine {
             object_map: ObjectMap::default(),
             prefix_map: PrefixMap::default(),
             suffix_map: SuffixMap::default(),
-            spog_index: IndexedQuadSet::default(),
-            gspo_index: IndexedQuadSet::default(),
-            posg_index: IndexedQuadSet::default(),
-            ospg_index: IndexedQuadSet::default(),
-
+            quad_indexes: QuadIndexes::default(),
+            fulltext_index: None,
+            normalize_iris: false,
+            uri_splitter,
+            namespace_manager: NamespaceManager::default(),
+            inference: None,
+            equality: None,
+            http_client: Box::new(TcpHttpClient::default()),
+            allowed_services: Some(Vec::new()),
+            revision: 0,
         };
         let default_graph_id = fresh.uri_str_to_internal_id(DEFAULT_GRAPH_URI).unwrap();
         if 0u64 != default_graph_id.0.into() { panic!("Default graph ID should always be 0."); }
         fresh
     }
-}
 
-
-impl StorageEngine {
-    pub fn uri_to_internal_uri_id_if_exist(&self, uri: &str) -> Result<InternalUriID, String> {
-        let prefix_str;
-        let suffix_str;
-        let hash_index = uri.rfind('#');
-        if hash_index.is_some() {
-            let split_str = uri.split_at(hash_index.unwrap()+1);
-            prefix_str = split_str.0;
-            suffix_str = split_str.1;
+    /* Validates the IRI, and normalizes it when IRI normalization is enabled on this store. */
+    fn prepare_uri<'u>(&self, uri: &'u str) -> Result<Cow<'u, str>, String> {
+        let parsed = IriRef::parse_absolute(uri)?;
+        if self.normalize_iris {
+            Ok(Cow::Owned(parsed.normalize().to_string()))
         } else {
-            let slash_index = uri.rfind('/');
-            if slash_index.is_some() {
-                let split_str = uri.split_at(slash_index.unwrap()+1);
-                prefix_str = split_str.0;
-                suffix_str = split_str.1;
-            } else {
-                return Result::Err(
-                    "Uri must contain either a /, or a # to separate prefix from suffix."
-                        .to_string());
-            }
+            Ok(Cow::Borrowed(uri))
         }
+    }
+
+    fn split_uri<'u>(&self, uri: &'u str) -> Result<(&'u str, &'u str), String> {
+        let split_index = self.uri_splitter.split_index(uri);
+        if split_index > uri.len() || !uri.is_char_boundary(split_index) {
+            return Err("The URI splitter returned an index that is not a char boundary of the URI.".to_string());
+        }
+        Ok(uri.split_at(split_index))
+    }
+
+    /// When enabled, IRIs are normalized (case, percent-encoding and dot segments) before interning
+    /// and lookup. Changing this does not rewrite IRIs already in the store.
+    pub fn set_iri_normalization(&mut self, enabled: bool) {
+        self.normalize_iris = enabled;
+    }
+
+    pub fn is_iri_normalization_enabled(&self) -> bool {
+        self.normalize_iris
+    }
+
+    /// The client that SPARQL `SERVICE` patterns send their sub-queries with.
+    pub fn borrow_http_client(&self) -> &HttpClient {
+        &*self.http_client
+    }
+
+    pub fn set_http_client(&mut self, client: Box<HttpClient>) {
+        self.http_client = client;
+    }
+
+    /// The endpoints `SERVICE` patterns may query, or `None` when any may be.
+    pub fn borrow_allowed_services(&self) -> Option<&[String]> {
+        self.allowed_services.as_deref()
+    }
+
+    /// Restricts `SERVICE` patterns to the endpoints in `endpoints`, compared as written, or lifts
+    /// the restriction with `None`. A service that is not allowed fails without being contacted.
+    /// A new store allows none, so that queries cannot make it contact other hosts.
+    pub fn set_allowed_services(&mut self, endpoints: Option<Vec<String>>) {
+        self.allowed_services = endpoints;
+    }
+
+    /// Counts changes to the asserted quads and namespaces. When it is the same before and after
+    /// an operation, the operation changed nothing worth saving.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn borrow_namespace_manager<'a>(&'a self) -> &'a NamespaceManager {
+        &self.namespace_manager
+    }
+
+    pub fn borrow_namespace_manager_mut<'a>(&'a mut self) -> &'a mut NamespaceManager {
+        self.revision += 1;
+        &mut self.namespace_manager
+    }
+
+    pub fn prefix_count(&self) -> usize {
+        self.prefix_map.len()
+    }
+
+    pub fn suffix_count(&self) -> usize {
+        self.suffix_map.len()
+    }
+
+    pub fn uri_to_internal_uri_id_if_exist(&self, uri: &str) -> Result<InternalUriID, String> {
+        let prepared_uri = self.prepare_uri(uri)?;
+        let (prefix_str, suffix_str) = self.split_uri(&prepared_uri)?;
         let prefix_id: ThirtyTwoBitID = if let Some(pid) = self.prefix_map.get_id_by_key(prefix_str).cloned() { pid }
             else { return Result::Err("That URI prefix does not exist in the store.".to_string()); };
         let suffix_id: ThirtyTwoBitID = if let Some(sid) = self.suffix_map.get_id_by_key(suffix_str).cloned() { sid }
             else { return Result::Err("That URI suffix does not exist in the store.".to_string()); };
-        return Ok(InternalUriID(prefix_id, suffix_id))
+        Ok(InternalUriID(prefix_id, suffix_id))
     }
     pub fn uri_to_internal_uri_id(&mut self, uri: &str) -> Result<InternalUriID, String> {
-        let prefix_str;
-        let suffix_str;
-        let hash_index = uri.rfind('#');
-        if hash_index.is_some() {
-            let split_str = uri.split_at(hash_index.unwrap()+1);
-            prefix_str = split_str.0;
-            suffix_str = split_str.1;
-        } else {
-            let slash_index = uri.rfind('/');
-            if slash_index.is_some() {
-                let split_str = uri.split_at(slash_index.unwrap()+1);
-                prefix_str = split_str.0;
-                suffix_str = split_str.1;
-            } else {
-                return Result::Err(
-                    "Uri must contain either a /, or a # to separate prefix from suffix."
-                        .to_string());
-            }
-        }
+        let prepared_uri = self.prepare_uri(uri)?;
+        let (prefix_str, suffix_str) = self.split_uri(&prepared_uri)?;
         let prefix_id: ThirtyTwoBitID = self.prefix_map.get_id_by_key(prefix_str).cloned().unwrap_or_else(||
             self.prefix_map.insert_unchecked(prefix_str.to_owned()).unwrap()
         );
@@ -114,18 +230,20 @@ impl StorageEngine {
         let suffix_id: ThirtyTwoBitID = self.suffix_map.get_id_by_key(suffix_str).cloned().unwrap_or_else(||
             self.suffix_map.insert_unchecked(suffix_str.to_owned()).unwrap()
         );
-        return Ok(InternalUriID(prefix_id, suffix_id))
+        Ok(InternalUriID(prefix_id, suffix_id))
     }
+    /* Err(()) means the node was never added, which is all callers branch on. */
+    #[allow(clippy::result_unit_err)]
     pub fn find_internal_id(&self, node: &StoreNode) -> Result<InternalID, ()> {
         let internal_id: SixtyFourBitID = if let Some(i) = self.object_map.get_id_by_key(node).cloned() { i }
             else { return Result::Err(()); };
-        return Ok(InternalID(internal_id))
+        Ok(InternalID(internal_id))
     }
     pub fn find_or_add_internal_id(&mut self, node: StoreNode) -> Result<InternalID, String> {
         let internal_id: SixtyFourBitID = self.object_map.get_id_by_key(&node).cloned().unwrap_or_else(||
             self.object_map.insert_unchecked(node).unwrap()
         );
-        return Ok(InternalID(internal_id))
+        Ok(InternalID(internal_id))
     }
     pub fn uri_str_to_internal_id(&mut self, uri: &str) -> Result<InternalID, String> {
         let internal_uri_id = self.uri_to_internal_uri_id(uri)?;
@@ -143,10 +261,38 @@ impl StorageEngine {
     }
 
     pub fn add_internal_quad(&mut self, graph: GraphID, subject: SubjectID, predicate: PredicateID, object: ObjectID) {
-        self.spog_index.add_entry(SPOG::build_from_ref_parts(&subject, &predicate, &object, &graph));
-        self.gspo_index.add_entry(GSPO::build_from_ref_parts(&graph, &subject, &predicate, &object));
-        self.posg_index.add_entry(POSG::build_from_ref_parts(&predicate, &object, &subject, &graph));
-        self.ospg_index.add_entry(OSPG::build_from_ref_parts(&object, &subject, &predicate, &graph));
+        let (subject, predicate, object) = if self.equality.is_some() {
+            let (cs, cp, co) = self.equate_and_canonicalize(&graph, subject.clone(), predicate.clone(), object.clone());
+            let stored = (cs.clone(), cp.clone(), co.clone(), graph.clone());
+            let mut originals = if self.quad_indexes.contains_quad(&graph, &cs, &cp, &co) {
+                self.equality.as_ref().unwrap().originals(&stored)
+            } else {
+                BTreeSet::new()
+            };
+            originals.insert((subject, predicate, object, graph.clone()));
+            self.equality.as_mut().unwrap().set_originals(stored, originals);
+            (cs, cp, co)
+        } else {
+            (subject, predicate, object)
+        };
+        let added = self.quad_indexes.add_quad(&graph, &subject, &predicate, &object);
+        if added || self.equality.is_some() {
+            self.revision += 1;
+        }
+        if self.fulltext_index.is_some() {
+            self.fulltext_index_object(&object);
+        }
+        if added && self.inference.is_some() {
+            let was_inferred = {
+                let state = self.inference.as_mut().unwrap();
+                state.forget_justification(&(subject.clone(), predicate.clone(), object.clone(), graph.clone()));
+                state.borrow_inferred_mut().remove_quad(&graph, &subject, &predicate, &object)
+            };
+            /* A quad that was already inferred has had its consequences derived. */
+            if !was_inferred {
+                self.materialize(vec![(subject, predicate, object, graph)]);
+            }
+        }
     }
 
     pub fn add_internal_triple(&mut self, subject: SubjectID, predicate: PredicateID, object: ObjectID) {
@@ -155,10 +301,32 @@ impl StorageEngine {
     }
 
     pub fn remove_internal_quad(&mut self, graph: GraphID, subject: SubjectID, predicate: PredicateID, object: ObjectID) {
-        self.spog_index.remove_entry(&SPOG::build_from_ref_parts(&subject, &predicate, &object, &graph));
-        self.gspo_index.remove_entry(&GSPO::build_from_ref_parts(&graph, &subject, &predicate, &object));
-        self.posg_index.remove_entry(&POSG::build_from_ref_parts(&predicate, &object, &subject, &graph));
-        self.ospg_index.remove_entry(&OSPG::build_from_ref_parts(&object, &subject, &predicate, &graph));
+        if self.equality.is_some() {
+            if self.unlink_equal(&graph, &subject, &predicate, &object) {
+                self.revision += 1;
+                return;
+            }
+            if !self.forget_original(&(subject.clone(), predicate.clone(), object.clone(), graph.clone())) {
+                return;
+            }
+        }
+        let (subject, predicate, object) = (self.canonical_id(&subject), self.canonical_id(&predicate), self.canonical_id(&object));
+        let quad = (subject, predicate, object, graph);
+        let removed = if self.inference.is_some() && self.quad_indexes.contains_quad(&quad.3, &quad.0, &quad.1, &quad.2) {
+            self.retract(vec![quad.clone()]);
+            true
+        } else {
+            self.quad_indexes.remove_quad(&quad.3, &quad.0, &quad.1, &quad.2)
+        };
+        if removed {
+            self.revision += 1;
+        }
+        let object = quad.2;
+        if self.quad_indexes.search(None, None, None, Some(object.clone())).next().is_none() {
+            if let Some(ref mut index) = self.fulltext_index {
+                index.unindex_literal(&object);
+            }
+        }
     }
 
     pub fn remove_internal_triple(&mut self, subject: SubjectID, predicate: PredicateID, object: ObjectID) {
@@ -170,6 +338,8 @@ impl StorageEngine {
         iter::empty::<(GraphID, SubjectID, PredicateID, ObjectID)>()
     }
 
+    /* Err(()) means no node carries this id. */
+    #[allow(clippy::result_unit_err)]
     pub fn lookup_node_by_iid<'a>(&'a self, iid: &InternalID) -> Result<&'a StoreNode, ()> {
         let indexed_id: SixtyFourBitID = iid.clone().into();
         if let Some(n) = self.object_map.get_key_ref_by_id(&indexed_id) {
@@ -179,7 +349,19 @@ impl StorageEngine {
         }
     }
 
-    pub fn search_nodes<'a>(&'a self, graph: Option<StoreNode>, subject: Option<StoreNode>, predicate: Option<StoreNode>, object: Option<StoreNode>) -> Result<Box<Iterator<Item=(&'a StoreNode, &'a StoreNode, &'a StoreNode, &'a StoreNode)>+'a>, String>
+    pub fn search_nodes<'a>(&'a self, graph: Option<StoreNode>, subject: Option<StoreNode>, predicate: Option<StoreNode>, object: Option<StoreNode>) -> Result<NodeQuads<'a>, String>
+    {
+        self.search_nodes_with_inferred(graph, subject, predicate, object, false)
+    }
+
+    pub fn search_nodes_with_inferred<'a>(&'a self, graph: Option<StoreNode>, subject: Option<StoreNode>, predicate: Option<StoreNode>, object: Option<StoreNode>, include_inferred: bool) -> Result<NodeQuads<'a>, String>
+    {
+        self.search_nodes_with_options(graph, subject, predicate, object, include_inferred, false)
+    }
+
+    /// `expand_aliases` returns a result for every owl:sameAs alias of each unbound term, and implies
+    /// `include_inferred`. It only makes a difference in equality mode.
+    pub fn search_nodes_with_options<'a>(&'a self, graph: Option<StoreNode>, subject: Option<StoreNode>, predicate: Option<StoreNode>, object: Option<StoreNode>, include_inferred: bool, expand_aliases: bool) -> Result<NodeQuads<'a>, String>
     {
         let gid = if let Some(g) = graph {
             if let Some(gi) = self.object_map.get_id_by_key(&g).cloned() { Some(InternalID(gi)) } else { return Err("That graph identifier does not exist in the store.".to_string()) }
@@ -193,7 +375,13 @@ impl StorageEngine {
         let oid = if let Some(o) = object {
             if let Some(oi) = self.object_map.get_id_by_key(&o).cloned() { Some(InternalID(oi)) } else { return Err("That object identifier does not exist in the store.".to_string()) }
         } else { None };
-        let internal_results = self.search_engine_internal(gid, sid, pid, oid);
+        let internal_results = if expand_aliases {
+            self.search_engine_expanded(gid, sid, pid, oid)
+        } else if include_inferred {
+            self.search_engine_entailed(gid, sid, pid, oid)
+        } else {
+            self.search_engine_internal(gid, sid, pid, oid)
+        };
         let node_results = internal_results.map(|res| {
             let (gid,sid,pid,oid) = res;
             let graphnode = self.object_map.get_key_ref_by_id(&gid.into()).unwrap();
@@ -202,79 +390,681 @@ impl StorageEngine {
             let objnode = self.object_map.get_key_ref_by_id(&oid.into()).unwrap();
             (graphnode, subjnode, prednode, objnode)
         }).collect::<Vec<(&'a StoreNode, &'a StoreNode, &'a StoreNode, &'a StoreNode)>>();
-        return Ok(Box::new(node_results.into_iter()))
+        Ok(Box::new(node_results.into_iter()))
     }
 
+    /// In equality mode, bound terms are looked up by their canonical representative.
     pub fn search_engine_internal<'a>(&'a self, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
                                       -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
-        match (graph, subject, predicate, object) {
-            (Some(g), Some(s), Some(p), Some(o)) => {
-                if let Some(f) = self.gspo_index.find_exact_match(&g, &s, &p, &o) {
-                    Box::new(iter::once((g, s, p, o)))
+        if self.equality.is_some() {
+            let canonical = |t: Option<InternalID>| t.map(|id| self.canonical_id(&id));
+            return self.quad_indexes.search(graph, canonical(subject), canonical(predicate), canonical(object));
+        }
+        self.quad_indexes.search(graph, subject, predicate, object)
+    }
+
+    /// Searches only the quads materialized by the enabled inference rules.
+    pub fn search_engine_inferred<'a>(&'a self, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
+                                      -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
+        if let Some(ref state) = self.inference {
+            let canonical = |t: Option<InternalID>| t.map(|id| self.canonical_id(&id));
+            state.borrow_inferred().search(graph, canonical(subject), canonical(predicate), canonical(object))
+        } else {
+            Box::new(Self::empty_iter())
+        }
+    }
+
+    /// Searches asserted and inferred quads together. A quad is never both, so there are no duplicates.
+    pub fn search_engine_entailed<'a>(&'a self, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
+                                      -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
+        let asserted = self.search_engine_internal(graph.clone(), subject.clone(), predicate.clone(), object.clone());
+        Box::new(asserted.chain(self.search_engine_inferred(graph, subject, predicate, object)))
+    }
+
+    /// Searches asserted and inferred quads like `search_engine_entailed`, reading both from the `index` ordering.
+    pub fn search_engine_entailed_by_index<'a>(&'a self, index: IndexKind, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
+                                               -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
+        let canonical = |t: Option<InternalID>| t.map(|id| self.canonical_id(&id));
+        let (subject, predicate, object) = (canonical(subject), canonical(predicate), canonical(object));
+        let asserted = self.quad_indexes.scan(index, graph.clone(), subject.clone(), predicate.clone(), object.clone());
+        match self.inference {
+            Some(ref state) => Box::new(asserted.chain(state.borrow_inferred().scan(index, graph, subject, predicate, object))),
+            None => asserted,
+        }
+    }
+
+    /// Searches asserted and inferred quads like `search_engine_entailed`, then expands every unbound
+    /// subject, predicate and object to each of its owl:sameAs aliases.
+    pub fn search_engine_expanded<'a>(&'a self, graph: Option<GraphID>, subject: Option<SubjectID>, predicate: Option<PredicateID>, object: Option<ObjectID>)
+                                      -> Box<Iterator<Item=(GraphID, SubjectID, PredicateID, ObjectID)>+'a> {
+        let results = self.search_engine_entailed(graph, subject.clone(), predicate.clone(), object.clone());
+        if self.equality.is_none() {
+            return results;
+        }
+        let terms = move |bound: &Option<InternalID>, found: &InternalID| bound.clone().map(|b| vec![b]).unwrap_or_else(|| self.aliases(found));
+        Box::new(results.flat_map(move |(g, s, p, o)| {
+            let mut expanded = Vec::new();
+            let (subjects, predicates, objects) = (terms(&subject, &s), terms(&predicate, &p), terms(&object, &o));
+            for es in subjects.iter() {
+                for ep in predicates.iter() {
+                    for eo in objects.iter() {
+                        expanded.push((g.clone(), es.clone(), ep.clone(), eo.clone()));
+                    }
+                }
+            }
+            expanded.into_iter()
+        }))
+    }
+
+    /// The IDs of graphs holding at least one asserted or inferred quad, the default graph included.
+    pub fn graph_ids(&self) -> Vec<GraphID> {
+        let graphs: BTreeSet<GraphID> = self.search_engine_entailed(None, None, None, None).map(|q| q.0).collect();
+        graphs.into_iter().collect()
+    }
+
+    pub fn quad_count(&self) -> usize {
+        self.quad_indexes.len()
+    }
+
+    pub fn statistics(&self) -> StoreStatistics {
+        let mut statistics = StoreStatistics::default();
+        for node in self.object_map.keys() {
+            match node {
+                &StoreNode::URIRef(_) => statistics.iri_count += 1,
+                &StoreNode::Blank(_) => statistics.blank_node_count += 1,
+                &StoreNode::Literal(_) => statistics.literal_count += 1,
+            }
+        }
+        statistics.free_node_ids = self.object_map.free_id_count();
+        statistics.iri_prefix_count = self.prefix_count();
+        statistics.iri_suffix_count = self.suffix_count();
+        statistics.namespace_count = self.namespace_manager.len();
+        statistics.graph_count = self.graph_ids().len();
+        statistics.quad_count = self.quad_count();
+        statistics.inferred_quad_count = self.inferred_quad_count();
+        statistics.index_entries = vec![
+            ("GSPO", self.quad_indexes.borrow_gspo_index().len()),
+            ("SPOG", self.quad_indexes.borrow_spog_index().len()),
+            ("POSG", self.quad_indexes.borrow_posg_index().len()),
+            ("OSPG", self.quad_indexes.borrow_ospg_index().len()),
+        ];
+        statistics.fulltext_index = self.fulltext_index.as_ref().map(|index| (index.literal_count(), index.term_count()));
+        statistics
+    }
+
+    /// Per-graph, per-predicate and per-term quad counts of the asserted quads, followed by those of
+    /// the inferred quads when inference is enabled.
+    pub fn quad_statistics<'a>(&'a self) -> Vec<&'a QuadStatistics> {
+        let mut statistics = vec![self.quad_indexes.borrow_statistics()];
+        statistics.extend(self.inference.as_ref().map(|state| state.borrow_inferred().borrow_statistics()));
+        statistics
+    }
+
+    pub fn inferred_quad_count(&self) -> usize {
+        self.inference.as_ref().map(|state| state.borrow_inferred().len()).unwrap_or(0)
+    }
+
+    /* Runs every rule over each quad on the agenda, and over each new quad those rules derive. */
+    fn materialize(&mut self, seeds: Vec<InternalQuad>) {
+        let mut agenda = seeds;
+        while let Some(delta) = agenda.pop() {
+            let mut derived = Vec::new();
+            let mut violations = Vec::new();
+            if let Some(ref state) = self.inference {
+                for rule in state.borrow_rules() {
+                    let mut by_rule = Vec::new();
+                    rule.apply(&delta, self, &mut by_rule);
+                    derived.extend(by_rule.into_iter().map(|d| (rule.name(), d)));
+                    rule.check(&delta, self, &mut violations);
+                }
+            }
+            let state = self.inference.as_mut().unwrap();
+            for violation in violations {
+                state.record_inconsistency(violation);
+            }
+            for (rule, derivation) in derived {
+                let (s, p, o, g) = derivation.quad;
+                if self.quad_indexes.contains_quad(&g, &s, &p, &o) {
+                    continue;
+                }
+                if state.borrow_inferred_mut().add_quad(&g, &s, &p, &o) {
+                    state.record_justification((s.clone(), p.clone(), o.clone(), g.clone()), Justification { rule, premises: derivation.premises });
+                    agenda.push((s, p, o, g));
+                }
+            }
+        }
+    }
+
+    /* Removes asserted quads and updates the inferred ones by delete and rederive: every inferred
+       quad with a derivation that used a removed quad is dropped, then those that still follow in
+       one step from what is left are derived again, along with their consequences. */
+    fn retract(&mut self, removed: Vec<InternalQuad>) {
+        let mut agenda = removed.clone();
+        let mut dropped: BTreeSet<InternalQuad> = BTreeSet::new();
+        while let Some(delta) = agenda.pop() {
+            let mut derived = Vec::new();
+            if let Some(ref state) = self.inference {
+                for rule in state.borrow_rules() {
+                    rule.apply(&delta, self, &mut derived);
+                }
+            }
+            let state = self.inference.as_ref().unwrap();
+            for derivation in derived {
+                let (s, p, o, g) = derivation.quad;
+                if state.borrow_inferred().contains_quad(&g, &s, &p, &o) && dropped.insert((s.clone(), p.clone(), o.clone(), g.clone())) {
+                    agenda.push((s, p, o, g));
+                }
+            }
+        }
+        for &(ref s, ref p, ref o, ref g) in removed.iter() {
+            self.quad_indexes.remove_quad(g, s, p, o);
+        }
+        {
+            let state = self.inference.as_mut().unwrap();
+            for quad in dropped.iter() {
+                let &(ref s, ref p, ref o, ref g) = quad;
+                state.borrow_inferred_mut().remove_quad(g, s, p, o);
+                state.forget_justification(quad);
+            }
+            state.forget_inconsistencies(|premise| removed.contains(premise) || dropped.contains(premise));
+        }
+        /* A removed quad may itself still follow from others. */
+        let candidates: Vec<InternalQuad> = removed.into_iter().chain(dropped).collect();
+        let mut rederived = Vec::new();
+        for quad in candidates {
+            let state = self.inference.as_ref().unwrap();
+            let derivation = state.borrow_rules().iter()
+                .filter_map(|rule| rule.rederive(&quad, self).map(|d| (rule.name(), d)))
+                .next();
+            if let Some((rule, derivation)) = derivation {
+                rederived.push((rule, derivation));
+            }
+        }
+        let mut seeds = Vec::new();
+        {
+            let state = self.inference.as_mut().unwrap();
+            for (rule, derivation) in rederived {
+                let (s, p, o, g) = derivation.quad;
+                if state.borrow_inferred_mut().add_quad(&g, &s, &p, &o) {
+                    state.record_justification((s.clone(), p.clone(), o.clone(), g.clone()), Justification { rule, premises: derivation.premises });
+                    seeds.push((s, p, o, g));
+                }
+            }
+        }
+        self.materialize(seeds);
+    }
+
+    /// Turns on owl:sameAs canonicalization. From then on, sameAs links between distinct terms merge
+    /// their equivalence classes, and quads are stored only against each class's representative.
+    /// A sameAs link itself is stored as the representative's reflexive sameAs quad in its graph.
+    /// Quads already in the store are rewritten to canonical form.
+    pub fn enable_equality_mode(&mut self) -> Result<(), String> {
+        if self.equality.is_some() {
+            return Ok(());
+        }
+        let same_as = self.uri_str_to_internal_id(vocab::OWL_SAME_AS)?;
+        let links: Vec<InternalQuad> = self.quad_indexes.search(None, None, Some(same_as.clone()), None)
+            .filter(|&(_, ref s, _, ref o)| s != o)
+            .map(|(g, s, p, o)| (s, p, o, g)).collect();
+        let mut state = EqualityState::new(same_as);
+        for &(ref s, _, ref o, ref g) in links.iter() {
+            state.add_link(g, s, o);
+        }
+        let merged: Vec<InternalID> = links.iter()
+            .flat_map(|&(ref s, _, ref o, _)| vec![s.clone(), o.clone()])
+            .filter(|id| state.borrow_classes().find(id) != *id)
+            .collect();
+        self.equality = Some(state);
+        let affected = self.stored_mentions(&merged);
+        self.recanonicalize(affected, &[]);
+        Ok(())
+    }
+
+    /// Stops canonicalizing. Quads stay stored against the representatives they were merged into.
+    pub fn disable_equality_mode(&mut self) {
+        self.equality = None;
+    }
+
+    pub fn is_equality_mode_enabled(&self) -> bool {
+        self.equality.is_some()
+    }
+
+    pub fn canonical_id(&self, id: &InternalID) -> InternalID {
+        if let Some(ref state) = self.equality {
+            state.borrow_classes().find(id)
+        } else {
+            id.clone()
+        }
+    }
+
+    /// Every term known to be owl:sameAs `id`, including `id` itself.
+    pub fn aliases(&self, id: &InternalID) -> Vec<InternalID> {
+        if let Some(ref state) = self.equality {
+            state.borrow_classes().aliases(id)
+        } else {
+            vec![id.clone()]
+        }
+    }
+
+    fn equate_and_canonicalize(&mut self, graph: &GraphID, subject: SubjectID, predicate: PredicateID, object: ObjectID) -> (SubjectID, PredicateID, ObjectID) {
+        let merged = {
+            let state = self.equality.as_mut().unwrap();
+            if predicate == *state.borrow_same_as() && subject != object {
+                state.add_link(graph, &subject, &object)
+            } else {
+                None
+            }
+        };
+        if let Some((absorbed, _)) = merged {
+            let affected = self.stored_mentions(&[absorbed]);
+            self.recanonicalize(affected, &[]);
+        }
+        (self.canonical_id(&subject), self.canonical_id(&predicate), self.canonical_id(&object))
+    }
+
+    /* Removes an asserted sameAs link, splitting its class if nothing else holds it together. The
+       quads stored against the old representative go back to the terms they were asserted with,
+       canonicalized within the new classes. Returns false when the quad is not a sameAs link
+       between distinct terms. */
+    fn unlink_equal(&mut self, graph: &GraphID, subject: &SubjectID, predicate: &PredicateID, object: &ObjectID) -> bool {
+        let root = self.canonical_id(subject);
+        let unlinked = {
+            let state = self.equality.as_mut().unwrap();
+            if *predicate != *state.borrow_same_as() || subject == object {
+                return false;
+            }
+            state.remove_link(graph, subject, object)
+        };
+        if !unlinked {
+            return true;
+        }
+        let affected = self.stored_mentions(&[root]);
+        let dropped = [
+            (subject.clone(), predicate.clone(), object.clone(), graph.clone()),
+            (object.clone(), predicate.clone(), subject.clone(), graph.clone()),
+        ];
+        self.recanonicalize(affected, &dropped);
+        true
+    }
+
+    /* Forgets `quad` as asserted in equality mode. Returns false if the quad it is stored as still
+       stands for another asserted form, and so has to stay. */
+    fn forget_original(&mut self, quad: &InternalQuad) -> bool {
+        let &(ref s, ref p, ref o, ref g) = quad;
+        let stored = (self.canonical_id(s), self.canonical_id(p), self.canonical_id(o), g.clone());
+        if !self.quad_indexes.contains_quad(&stored.3, &stored.0, &stored.1, &stored.2) {
+            return true;
+        }
+        let state = self.equality.as_mut().unwrap();
+        let mut originals = state.take_originals(&stored);
+        if originals.remove(quad) && !originals.is_empty() {
+            state.set_originals(stored, originals);
+            return false;
+        }
+        true
+    }
+
+    /* The asserted quads with one of `terms` as subject, predicate or object. */
+    fn stored_mentions(&self, terms: &[InternalID]) -> Vec<InternalQuad> {
+        let mut mentions: BTreeSet<InternalQuad> = BTreeSet::new();
+        for term in terms.iter() {
+            mentions.extend(self.quad_indexes.search(None, Some(term.clone()), None, None).map(|(g, s, p, o)| (s, p, o, g)));
+            mentions.extend(self.quad_indexes.search(None, None, Some(term.clone()), None).map(|(g, s, p, o)| (s, p, o, g)));
+            mentions.extend(self.quad_indexes.search(None, None, None, Some(term.clone())).map(|(g, s, p, o)| (s, p, o, g)));
+        }
+        mentions.into_iter().collect()
+    }
+
+    /* Stores the quads asserted as the `affected` stored quads, less `dropped`, under their current
+       canonical forms. Only the inferences that depend on the quads that move are updated. */
+    fn recanonicalize(&mut self, affected: Vec<InternalQuad>, dropped: &[InternalQuad]) {
+        let mut regrouped: BTreeMap<InternalQuad, BTreeSet<InternalQuad>> = BTreeMap::new();
+        for stored in affected.iter() {
+            let originals = self.equality.as_mut().unwrap().take_originals(stored);
+            for original in originals.into_iter().filter(|q| !dropped.contains(q)) {
+                let canonical = {
+                    let &(ref s, ref p, ref o, ref g) = &original;
+                    (self.canonical_id(s), self.canonical_id(p), self.canonical_id(o), g.clone())
+                };
+                regrouped.entry(canonical).or_default().insert(original);
+            }
+        }
+        let mut added = Vec::new();
+        for (canonical, originals) in regrouped.iter() {
+            let mut originals = originals.clone();
+            let &(ref s, ref p, ref o, ref g) = canonical;
+            if !affected.contains(canonical) {
+                if self.quad_indexes.contains_quad(g, s, p, o) {
+                    originals.extend(self.equality.as_ref().unwrap().originals(canonical));
                 } else {
-                    Box::new(Self::empty_iter())
+                    added.push(canonical.clone());
+                }
+            }
+            self.equality.as_mut().unwrap().set_originals(canonical.clone(), originals);
+        }
+        let removed: Vec<InternalQuad> = affected.into_iter().filter(|q| !regrouped.contains_key(q)).collect();
+        self.replace_asserted(removed, added);
+    }
+
+    /* Removes and adds asserted quads as one change, updating the inferred quads for both. */
+    fn replace_asserted(&mut self, removed: Vec<InternalQuad>, added: Vec<InternalQuad>) {
+        if self.inference.is_some() && !removed.is_empty() {
+            self.retract(removed);
+        } else {
+            for &(ref s, ref p, ref o, ref g) in removed.iter() {
+                self.quad_indexes.remove_quad(g, s, p, o);
+            }
+        }
+        let mut seeds = Vec::new();
+        for (s, p, o, g) in added {
+            self.quad_indexes.add_quad(&g, &s, &p, &o);
+            if let Some(ref mut state) = self.inference {
+                state.forget_justification(&(s.clone(), p.clone(), o.clone(), g.clone()));
+                /* A quad that was already inferred has had its consequences derived. */
+                if !state.borrow_inferred_mut().remove_quad(&g, &s, &p, &o) {
+                    seeds.push((s, p, o, g));
+                }
+            }
+        }
+        if self.inference.is_some() {
+            self.materialize(seeds);
+        }
+    }
+
+    /// Parses N3-style `rules` and writes their conclusions into `output_graph`, or the default
+    /// rules graph. Everything previously in that graph is removed; see `RuleProgram::evaluate`.
+    pub fn apply_rules(&mut self, rules: &str, output_graph: Option<GraphID>) -> Result<usize, String> {
+        let program = RuleProgram::parse(self, rules)?;
+        let graph = if let Some(g) = output_graph { g } else { self.uri_str_to_internal_id(DEFAULT_RULES_GRAPH_URI)? };
+        program.evaluate(self, &graph)
+    }
+
+    /// Enables the given forward-chaining rules and materializes everything they derive from the
+    /// quads already in the store. Inferred quads are then kept up to date as quads are added and removed.
+    /// Rules join quads within one graph and derive into that graph, so a schema in one named graph
+    /// does not apply to the data in another.
+    pub fn add_inference_rules(&mut self, rules: Vec<Box<InferenceRule>>) {
+        if self.inference.is_none() {
+            self.inference = Some(InferenceState::default());
+        }
+        self.inference.as_mut().unwrap().add_rules(rules);
+        self.rematerialize(None);
+    }
+
+    pub fn enable_rdfs_reasoning(&mut self) -> Result<(), String> {
+        let vocabulary = RdfsVocabulary::intern(self)?;
+        self.add_inference_rules(rdfs_rules(&vocabulary));
+        Ok(())
+    }
+
+    /// Enables the OWL 2 RL rules. Inconsistencies they detect are collected rather than failing
+    /// the update that caused them; see `check_consistency`.
+    pub fn enable_owl_rl_reasoning(&mut self) -> Result<(), String> {
+        let rdfs_vocabulary = RdfsVocabulary::intern(self)?;
+        let owl_vocabulary = OwlVocabulary::intern(self)?;
+        self.add_inference_rules(owl_rl_rules(&rdfs_vocabulary, &owl_vocabulary));
+        Ok(())
+    }
+
+    pub fn inconsistencies<'a>(&'a self) -> &'a [Inconsistency] {
+        self.inference.as_ref().map(|state| state.borrow_inconsistencies()).unwrap_or(&[])
+    }
+
+    pub fn check_consistency(&self) -> Result<(), Vec<Inconsistency>> {
+        let found = self.inconsistencies();
+        if found.is_empty() { Ok(()) } else { Err(found.to_vec()) }
+    }
+
+    /// Validates the data graph against the SHACL shapes in the shapes graph. When a report graph
+    /// is given, the report is also written into it as `sh:ValidationReport` quads.
+    pub fn validate_shacl(&mut self, shapes_graph: GraphID, data_graph: GraphID, report_graph: Option<GraphID>) -> Result<ValidationReport, String> {
+        let report = shacl::validate(self, &shapes_graph, &data_graph)?;
+        if let Some(graph) = report_graph {
+            shacl::write_report(self, &graph, &report)?;
+        }
+        Ok(report)
+    }
+
+    /// Checks the nodes selected by a ShEx query shape map against a ShExC schema, reading the
+    /// given graph or the default graph, and returns the result shape map.
+    pub fn validate_shex(&mut self, schema: &str, shape_map: &str, graph: Option<GraphID>) -> Result<Vec<ShapeMapEntry>, String> {
+        let schema = Schema::parse(self, schema)?;
+        let map = schema.parse_shape_map(self, shape_map)?;
+        let graph = graph.unwrap_or(InternalID(0.into()));
+        schema.validate(self, &graph, &map)
+    }
+
+    /// Canonicalizes `graph`, or the whole dataset, with RDFC-1.0. `work_limit` bounds the effort
+    /// spent on blank nodes that are hard to tell apart, defaulting to `canonicalize::DEFAULT_WORK_LIMIT`.
+    pub fn canonicalize(&self, graph: Option<GraphID>, work_limit: Option<usize>) -> Result<CanonicalDataset, String> {
+        let mut canonicalizer = Canonicalizer::new();
+        if let Some(limit) = work_limit {
+            canonicalizer = canonicalizer.with_work_limit(limit);
+        }
+        canonicalizer.canonicalize(self, graph.as_ref())
+    }
+
+    /// Compares `graph` of this store, or the whole dataset, with `other_graph` of `other`, which may
+    /// be this store. Blank node labels are not significant; see `isomorphism::compare`.
+    pub fn compare_graphs(&self, graph: Option<GraphID>, other: &StorageEngine, other_graph: Option<GraphID>) -> Result<Comparison, String> {
+        isomorphism::compare(self, graph.as_ref(), other, other_graph.as_ref())
+    }
+
+    /// The patch that turns `graph` of this store into `other_graph` of `other`, or this dataset
+    /// into the other when both are `None`. See `patch::diff`.
+    pub fn diff(&self, graph: Option<GraphID>, other: &StorageEngine, other_graph: Option<GraphID>) -> Result<Patch, String> {
+        patch::diff(self, graph.as_ref(), other, other_graph.as_ref())
+    }
+
+    /// Applies `patch` atomically: on error the store is left unchanged.
+    pub fn apply_patch(&mut self, patch: &Patch, strict: bool) -> Result<usize, String> {
+        patch.apply(self, strict)
+    }
+
+    /// Applies `patch` atomically within `limits`. See `Patch::apply_with_limits`.
+    pub fn apply_patch_with_limits(&mut self, patch: &Patch, strict: bool, limits: &Limits) -> Result<usize, OperationError> {
+        patch.apply_with_limits(self, strict, limits)
+    }
+
+    /// Runs a SPARQL 1.1 query. See `sparql::query`.
+    pub fn query(&self, text: &str) -> Result<QueryResult, String> {
+        sparql::query(self, text)
+    }
+
+    /// Runs a SPARQL query under an entailment regime. See `sparql::query_with_entailment`.
+    pub fn query_with_entailment(&self, text: &str, regime: EntailmentRegime) -> Result<QueryResult, String> {
+        sparql::query_with_entailment(self, text, regime)
+    }
+
+    /// Runs a SPARQL query within `limits`, failing with `OperationError::Limit` if one is hit.
+    pub fn query_with_limits(&self, text: &str, limits: &Limits) -> Result<QueryResult, OperationError> {
+        sparql::query_with_limits(self, text, limits)
+    }
+
+    /// Runs a SPARQL query and returns the plan it was evaluated with, with row counts and timings.
+    pub fn explain_query(&self, text: &str) -> Result<PlanNode, String> {
+        sparql::explain(self, text)
+    }
+
+    /// Runs a SPARQL 1.1 update request, returning the number of quads added and deleted.
+    pub fn update(&mut self, text: &str) -> Result<usize, String> {
+        sparql::update(self, text)
+    }
+
+    /// Runs a SPARQL update request within `limits`. See `sparql::update_with_limits`.
+    pub fn update_with_limits(&mut self, text: &str, limits: &Limits) -> Result<usize, OperationError> {
+        sparql::update_with_limits(self, text, limits)
+    }
+
+    /// Explains why the quad holds: asserted quads are leaves, and inferred ones are broken down
+    /// through the rule and premises that first derived them. `None` if the quad does not hold.
+    pub fn explain(&self, graph: GraphID, subject: SubjectID, predicate: PredicateID, object: ObjectID) -> Option<Proof> {
+        let quad = (self.canonical_id(&subject), self.canonical_id(&predicate), self.canonical_id(&object), graph);
+        let mut branch = Vec::new();
+        self.prove(quad, &mut branch)
+    }
+
+    fn prove(&self, quad: InternalQuad, branch: &mut Vec<InternalQuad>) -> Option<Proof> {
+        if branch.contains(&quad) {
+            return Some(Proof { quad, step: ProofStep::Circular });
+        }
+        if self.quad_indexes.contains_quad(&quad.3, &quad.0, &quad.1, &quad.2) {
+            return Some(Proof { quad, step: ProofStep::Asserted });
+        }
+        let justification = self.inference.as_ref()?.justification(&quad)?.clone();
+        branch.push(quad.clone());
+        let premises = justification.premises.into_iter().filter_map(|p| self.prove(p, branch)).collect();
+        branch.pop();
+        Some(Proof { quad, step: ProofStep::Inferred { rule: justification.rule, premises } })
+    }
+
+    pub fn disable_reasoning(&mut self) {
+        self.inference = None;
+    }
+
+    pub fn is_reasoning_enabled(&self) -> bool {
+        self.inference.is_some()
+    }
+
+    /// Drops the quads inferred in `graph` (or in every graph) and derives them again from the
+    /// asserted quads.
+    pub fn rematerialize(&mut self, graph: Option<GraphID>) {
+        if self.inference.is_none() {
+            return;
+        }
+        let stale: Vec<(GraphID, SubjectID, PredicateID, ObjectID)> = self.search_engine_inferred(graph.clone(), None, None, None).collect();
+        {
+            let state = self.inference.as_mut().unwrap();
+            state.clear_inconsistencies(graph.as_ref());
+            for (g, s, p, o) in stale {
+                state.borrow_inferred_mut().remove_quad(&g, &s, &p, &o);
+                state.forget_justification(&(s, p, o, g));
+            }
+        }
+        let seeds: Vec<InternalQuad> = self.search_engine_internal(graph, None, None, None)
+            .map(|(g, s, p, o)| (s, p, o, g)).collect();
+        self.materialize(seeds);
+    }
+
+    fn fulltext_index_object(&mut self, object: &ObjectID) {
+        let indexed_id: SixtyFourBitID = object.clone().into();
+        if let Some(&StoreNode::Literal(ref lit)) = self.object_map.get_key_ref_by_id(&indexed_id) {
+            if let Some(ref mut index) = self.fulltext_index {
+                index.index_literal(object, lit);
+            }
+        }
+    }
+
+    pub fn enable_fulltext_index(&mut self) {
+        if self.fulltext_index.is_some() {
+            return;
+        }
+        self.fulltext_index = Some(FullTextIndex::default());
+        let mut objects: Vec<ObjectID> = Vec::new();
+        for r in self.quad_indexes.borrow_ospg_index().full_range() {
+            let o = r.object_refs().3;
+            if objects.last() != Some(o) {
+                objects.push(o.clone());
+            }
+        }
+        for o in objects.iter() {
+            self.fulltext_index_object(o);
+        }
+    }
+
+    pub fn disable_fulltext_index(&mut self) {
+        self.fulltext_index = None;
+    }
+
+    pub fn borrow_fulltext_index<'a>(&'a self) -> Option<&'a FullTextIndex> {
+        self.fulltext_index.as_ref()
+    }
+
+    pub fn fulltext_search(&self, query: &str, lang: Option<&str>) -> Result<Vec<(ObjectID, f64)>, String> {
+        if let Some(ref index) = self.fulltext_index {
+            Ok(index.search(query, lang))
+        } else {
+            Err("The full-text index is not enabled on this store.".to_string())
+        }
+    }
+
+    /// Ranks subjects by the best score among their matching literal objects, optionally restricted
+    /// to a single predicate and graph.
+    pub fn fulltext_search_subjects(&self, query: &str, lang: Option<&str>, predicate: Option<PredicateID>, graph: Option<GraphID>) -> Result<Vec<(SubjectID, f64)>, String> {
+        let literal_hits = self.fulltext_search(query, lang)?;
+        let mut scores: BTreeMap<SubjectID, f64> = BTreeMap::new();
+        for (oid, score) in literal_hits {
+            for (_, sid, _, _) in self.search_engine_internal(graph.clone(), None, predicate.clone(), Some(oid)) {
+                let best = scores.entry(sid).or_insert(0.0);
+                if score > *best {
+                    *best = score;
                 }
-            },
-            (None, None, None, None) => {
-                Box::new(self.gspo_index.full_range().map(|r: &GSPO| { r.clone().deconstruct() }))
-            },
-            (Some(g), Some(s), Some(p), None) => {
-                Box::new(self.gspo_index.find_by_first_three(&g, &s, &p).map(|r: &GSPO| { r.clone().deconstruct() }))
-            },
-            (Some(g), Some(s), None, None) => {
-                Box::new(self.gspo_index.find_by_first_two(&g, &s).map(|r: &GSPO| { r.clone().deconstruct() }))
-            },
-            (Some(g), None, None, None) => {
-                Box::new(self.gspo_index.find_by_first_one(&g).map(|r: &GSPO| { r.clone().deconstruct() }))
-            },
-            (Some(g), Some(s), None, Some(o)) => {
-                let o_copy = o.clone();
-                Box::new(self.gspo_index.find_by_first_two(&g, &s)
-                    .filter(move |&r| r.object_refs().3.eq(&o_copy))
-                    .map(|r: &GSPO|  r.clone().deconstruct() ))
-            },
-            (Some(g), None, Some(p), Some(o)) => {
-                let g_copy = g.clone();
-                Box::new(self.posg_index.find_by_first_two(&p, &o)
-                    .filter(move |&r| r.object_refs().3.eq(&g_copy))
-                    .map(|r: &POSG|  r.clone().deconstruct() ))
-            },
-            (Some(g), None, Some(p), None) => {
-                /* This is assuming there are more different p's in the graph than different g's */
-                let g_copy = g.clone();
-                Box::new(self.posg_index.find_by_first_one(&p)
-                    .filter(move |&r| r.object_refs().3.eq(&g_copy))
-                    .map(|r: &POSG|  r.clone().deconstruct() ))
-            },
-            (Some(g), None, None, Some(o)) => {
-                /* This is assuming there are more different o's in the graph than different g's */
-                let g_copy = g.clone();
-                Box::new(self.ospg_index.find_by_first_one(&o)
-                    .filter(move |&r| r.object_refs().3.eq(&g_copy))
-                    .map(|r: &OSPG|  r.clone().deconstruct() ))
-            },
-            (None, Some(s), Some(p), Some(o)) => {
-                Box::new(self.spog_index.find_by_first_three(&s, &p, &o).map(|r: &SPOG| { r.clone().deconstruct() }))
-            },
-            (None, Some(s), Some(p), None) => {
-                Box::new(self.spog_index.find_by_first_two(&s, &p).map(|r: &SPOG| { r.clone().deconstruct() }))
-            },
-            (None, Some(s), None, None) => {
-                Box::new(self.spog_index.find_by_first_one(&s).map(|r: &SPOG| { r.clone().deconstruct() }))
-            },
-            (None, None, Some(p), Some(o)) => {
-                Box::new(self.posg_index.find_by_first_two(&p, &o).map(|r: &POSG| { r.clone().deconstruct() }))
-            },
-            (None, None, Some(p), None) => {
-                Box::new(self.posg_index.find_by_first_one(&p).map(|r: &POSG| { r.clone().deconstruct() }))
-            },
-            (None, Some(s), None, Some(o)) => {
-                Box::new(self.ospg_index.find_by_first_two(&o, &s).map(|r: &OSPG| { r.clone().deconstruct() }))
-            },
-            (None, None, None, Some(o)) => {
-                Box::new(self.ospg_index.find_by_first_one(&o).map(|r: &OSPG| { r.clone().deconstruct() }))
-            },
-            //_ => unimplemented!()
+            }
+        }
+        Ok(fulltext::rank_subjects(scores))
+    }
+
+    fn skolem_replacement(&mut self, id: &InternalID, authority: &str, cache: &mut BTreeMap<InternalID, InternalID>) -> Result<Option<InternalID>, String> {
+        if let Some(r) = cache.get(id) {
+            return Ok(Some(r.clone()));
         }
+        let skolem_iri = match self.lookup_node_by_iid(id) {
+            Ok(&StoreNode::Blank(ref b)) => b.to_skolem_iri(authority),
+            _ => return Ok(None)
+        };
+        let new_id = self.uri_str_to_internal_id(&skolem_iri)?;
+        cache.insert(id.clone(), new_id.clone());
+        Ok(Some(new_id))
+    }
+
+    fn deskolem_replacement(&mut self, id: &InternalID, cache: &mut BTreeMap<InternalID, InternalID>) -> Result<Option<InternalID>, String> {
+        if let Some(r) = cache.get(id) {
+            return Ok(Some(r.clone()));
+        }
+        let bnode = match self.lookup_node_by_iid(id) {
+            Ok(&StoreNode::URIRef(ref u)) => BlankNode::from_skolem_iri(&u.to_string(self)),
+            _ => None
+        };
+        let bnode = if let Some(b) = bnode { b } else { return Ok(None); };
+        let new_id = self.find_or_add_internal_id(StoreNode::Blank(bnode))?;
+        cache.insert(id.clone(), new_id.clone());
+        Ok(Some(new_id))
+    }
+
+    /* Rewrites subjects and objects through `replace`, returning how many quads changed. Every
+       replacement is found before any quad changes, so a failure leaves the quads as they were. */
+    fn rewrite_quad_terms<F>(&mut self, graph: Option<GraphID>, mut replace: F) -> Result<usize, String>
+        where F: FnMut(&mut StorageEngine, &InternalID) -> Result<Option<InternalID>, String> {
+        let quads: Vec<InternalQuad> = self.search_engine_internal(graph, None, None, None)
+            .map(|(g, s, p, o)| (s, p, o, g)).collect();
+        let mut rewrites = Vec::new();
+        for (s, p, o, g) in quads {
+            let new_s = replace(self, &s)?;
+            let new_o = replace(self, &o)?;
+            if new_s.is_some() || new_o.is_some() {
+                rewrites.push(((s, p, o, g), new_s, new_o));
+            }
+        }
+        for &((ref s, ref p, ref o, ref g), _, _) in rewrites.iter() {
+            self.remove_internal_quad(g.clone(), s.clone(), p.clone(), o.clone());
+        }
+        for ((s, p, o, g), new_s, new_o) in rewrites.iter().cloned() {
+            self.add_internal_quad(g, new_s.unwrap_or(s), p, new_o.unwrap_or(o));
+        }
+        Ok(rewrites.len())
+    }
+
+    /// Replaces blank node subjects and objects in `graph` (or every graph) with skolem IRIs
+    /// minted under `authority`, eg `http://example.org/.well-known/genid/<label>`.
+    pub fn skolemize(&mut self, graph: Option<GraphID>, authority: &str) -> Result<usize, String> {
+        IriRef::parse_absolute(authority)?;
+        let mut cache = BTreeMap::new();
+        self.rewrite_quad_terms(graph, |store, id| store.skolem_replacement(id, authority, &mut cache))
+    }
+
+    /// Replaces skolem IRIs in `graph` (or every graph) with the blank nodes they stand for.
+    pub fn deskolemize(&mut self, graph: Option<GraphID>) -> Result<usize, String> {
+        let mut cache = BTreeMap::new();
+        self.rewrite_quad_terms(graph, |store, id| store.deskolem_replacement(id, &mut cache))
     }
 }
\ No newline at end of file
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, BTreeSet};
+use iri::{self, IriRef};
+use lexer::{self, Token, TokenStream};
+use namespace::NamespaceManager;
+use nquads::Term;
+use store::{GraphID, StorageEngine};
+use vocab;
+
+pub type Triple = (Term, Term, Term);
+
+struct TurtleParser {
+    tokens: TokenStream,
+    prefixes: BTreeMap<String, String>,
+    base: Option<String>,
+    /* Labels written in the document, which generated blank nodes must not reuse. */
+    labels: BTreeSet<String>,
+    next_blank: usize,
+    triples: Vec<Triple>,
+}
+
+/// Parses a Turtle document (N-Triples is a subset) into triples, resolving relative IRIs against
+/// `base`. Blank nodes keep their labels; `[]` and collections get fresh ones.
+pub fn parse(text: &str, base: Option<&str>) -> Result<Vec<Triple>, String> {
+    let labels = lexer::lex(text)?.into_iter().filter_map(|(_, t)| if let Token::Blank(l) = t { Some(l) } else { None }).collect();
+    let mut parser = TurtleParser {
+        tokens: TokenStream::new(text)?,
+        prefixes: BTreeMap::new(),
+        base: base.map(|b| b.to_owned()),
+        labels: labels,
+        next_blank: 0,
+        triples: Vec::new(),
+    };
+    parser.document()?;
+    Ok(parser.triples)
+}
+
+/// Reads a literal from a parser's tokens: a signed number, a boolean, or a string with a language
+/// tag or a datatype, whose IRI `iri` reads. `None` if the next token does not start a literal.
+/// The SPARQL parser reads its literals with it too.
+pub fn parse_literal<P, T, I>(parser: &mut P, tokens: T, iri: I) -> Result<Option<Term>, String>
+    where T: Fn(&mut P) -> &mut TokenStream, I: FnOnce(&mut P) -> Result<String, String> {
+    let sign = {
+        let stream = tokens(parser);
+        match (stream.peek(), stream.peek_at(1)) {
+            (Some(&Token::Punct(s)), Some(&Token::Integer(_))) | (Some(&Token::Punct(s)), Some(&Token::Decimal(_))) |
+            (Some(&Token::Punct(s)), Some(&Token::Double(_))) if s == "-" || s == "+" => {
+                stream.next();
+                s
+            }
+            _ => "",
+        }
+    };
+    let token = if let Some(t) = tokens(parser).peek().cloned() { t } else { return Ok(None); };
+    let term = match token {
+        Token::Integer(n) => Term::Literal(format!("{}{}", sign, n), Some(vocab::XSD_INTEGER.to_owned()), None),
+        Token::Decimal(n) => Term::Literal(format!("{}{}", sign, n), Some(vocab::XSD_DECIMAL.to_owned()), None),
+        Token::Double(n) => Term::Literal(format!("{}{}", sign, n), Some(vocab::XSD_DOUBLE.to_owned()), None),
+        Token::Word(ref w) if w == "true" || w == "false" => Term::Literal(w.clone(), Some(vocab::XSD_BOOLEAN.to_owned()), None),
+        Token::Str(value) => {
+            tokens(parser).next();
+            if let Some(Token::LangTag(lang)) = tokens(parser).peek().cloned() {
+                tokens(parser).next();
+                return Ok(Some(Term::Literal(value, None, Some(lang))));
+            }
+            if tokens(parser).eat_punct("^^") {
+                let datatype = iri(parser)?;
+                let datatype = if datatype == vocab::XSD_STRING { None } else { Some(datatype) };
+                return Ok(Some(Term::Literal(value, datatype, None)));
+            }
+            return Ok(Some(Term::Literal(value, None, None)));
+        }
+        _ => return Ok(None),
+    };
+    tokens(parser).next();
+    Ok(Some(term))
+}
+
+impl TurtleParser {
+    fn document(&mut self) -> Result<(), String> {
+        while !self.tokens.is_at_end() {
+            if let Some(Token::LangTag(directive)) = self.tokens.peek().cloned() {
+                self.tokens.next();
+                match directive.as_str() {
+                    "prefix" => self.prefix()?,
+                    "base" => self.base()?,
+                    _ => return self.tokens.error(&format!("Unknown directive @{}", directive)),
+                }
+                self.tokens.expect_punct(".")?;
+            } else if self.tokens.eat_word("prefix") {
+                self.prefix()?;
+            } else if self.tokens.eat_word("base") {
+                self.base()?;
+            } else {
+                self.triples()?;
+                self.tokens.expect_punct(".")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn prefix(&mut self) -> Result<(), String> {
+        let prefix = match self.tokens.next() {
+            Some(Token::PrefixedName(ref prefix, ref local)) if local.is_empty() => prefix.clone(),
+            _ => return self.tokens.error("Expected a prefix name"),
+        };
+        let namespace = match self.tokens.next() {
+            Some(Token::IriRef(iri)) => self.resolve_relative(iri)?,
+            _ => return self.tokens.error("Expected a namespace IRI"),
+        };
+        self.prefixes.insert(prefix, namespace);
+        Ok(())
+    }
+
+    fn base(&mut self) -> Result<(), String> {
+        let base = match self.tokens.next() {
+            Some(Token::IriRef(iri)) => self.resolve_relative(iri)?,
+            _ => return self.tokens.error("Expected a base IRI"),
+        };
+        self.base = Some(base);
+        Ok(())
+    }
+
+    fn resolve_relative(&self, iri: String) -> Result<String, String> {
+        match self.base {
+            Some(ref base) => iri::resolve_iri(base, &iri),
+            None if IriRef::parse(&iri)?.is_absolute() => Ok(iri),
+            None => Err(format!("Relative IRI <{}> without a base IRI.", iri)),
+        }
+    }
+
+    fn fresh_blank(&mut self) -> Term {
+        loop {
+            self.next_blank += 1;
+            let label = format!("b{}", self.next_blank);
+            if !self.labels.contains(&label) {
+                return Term::Blank(label);
+            }
+        }
+    }
+
+    fn iri(&mut self) -> Result<Option<String>, String> {
+        match self.tokens.peek().cloned() {
+            Some(Token::IriRef(iri)) => {
+                self.tokens.next();
+                self.resolve_relative(iri).map(Some)
+            }
+            Some(Token::PrefixedName(prefix, local)) => {
+                self.tokens.next();
+                match self.prefixes.get(&prefix) {
+                    Some(namespace) => Ok(Some(format!("{}{}", namespace, local))),
+                    None => Err(format!("Prefix {:?} is not declared.", prefix)),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn triples(&mut self) -> Result<(), String> {
+        let bracketed = self.tokens.is_punct("[");
+        let subject = self.term(false)?;
+        /* `[ :p :o ] .` may stand alone without further predicates. */
+        if !(bracketed && self.tokens.is_punct(".")) {
+            self.predicate_objects(&subject)?;
+        }
+        Ok(())
+    }
+
+    fn predicate_objects(&mut self, subject: &Term) -> Result<(), String> {
+        loop {
+            let predicate = if self.tokens.eat_word("a") {
+                Term::Iri(vocab::RDF_TYPE.to_owned())
+            } else {
+                match self.iri()? {
+                    Some(iri) => Term::Iri(iri),
+                    None => return self.tokens.error("Expected a predicate"),
+                }
+            };
+            loop {
+                let object = self.term(true)?;
+                self.triples.push((subject.clone(), predicate.clone(), object));
+                if !self.tokens.eat_punct(",") {
+                    break;
+                }
+            }
+            if !self.tokens.eat_punct(";") {
+                return Ok(());
+            }
+            while self.tokens.eat_punct(";") {}
+            if self.tokens.is_punct(".") || self.tokens.is_punct("]") {
+                return Ok(());
+            }
+        }
+    }
+
+    /* A subject or object, adding the triples of blank node property lists and collections. */
+    fn term(&mut self, allow_literal: bool) -> Result<Term, String> {
+        if let Some(iri) = self.iri()? {
+            return Ok(Term::Iri(iri));
+        }
+        if let Some(Token::Blank(label)) = self.tokens.peek().cloned() {
+            self.tokens.next();
+            return Ok(Term::Blank(label));
+        }
+        if self.tokens.eat_punct("[") {
+            let node = self.fresh_blank();
+            if !self.tokens.eat_punct("]") {
+                self.predicate_objects(&node)?;
+                self.tokens.expect_punct("]")?;
+            }
+            return Ok(node);
+        }
+        if self.tokens.eat_punct("(") {
+            let mut items = Vec::new();
+            while !self.tokens.eat_punct(")") {
+                items.push(self.term(true)?);
+            }
+            let mut list = Term::Iri(vocab::RDF_NIL.to_owned());
+            for item in items.into_iter().rev() {
+                let node = self.fresh_blank();
+                self.triples.push((node.clone(), Term::Iri(vocab::RDF_FIRST.to_owned()), item));
+                self.triples.push((node.clone(), Term::Iri(vocab::RDF_REST.to_owned()), list));
+                list = node;
+            }
+            return Ok(list);
+        }
+        if allow_literal {
+            if let Some(literal) = self.literal()? {
+                return Ok(literal);
+            }
+        }
+        self.tokens.error("Expected an RDF term")
+    }
+
+    fn literal(&mut self) -> Result<Option<Term>, String> {
+        parse_literal(self, |p| &mut p.tokens, |p| match p.iri()? {
+            Some(iri) => Ok(iri),
+            None => p.tokens.error("Expected a datatype IRI"),
+        })
+    }
+}
+
+/* Whether a literal can be written bare, as Turtle reads it back with the same datatype. */
+fn is_shorthand(value: &str, datatype: &str) -> bool {
+    let digits = value.trim_start_matches(['+', '-']);
+    let all_digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    if datatype == vocab::XSD_BOOLEAN {
+        value == "true" || value == "false"
+    } else if datatype == vocab::XSD_INTEGER {
+        digits.len() + 1 >= value.len() && all_digits(digits)
+    } else if datatype == vocab::XSD_DECIMAL {
+        let mut parts = digits.splitn(2, '.');
+        let whole = parts.next().unwrap_or("");
+        digits.len() + 1 >= value.len() && (whole.is_empty() || all_digits(whole)) && parts.next().map(all_digits).unwrap_or(false)
+    } else {
+        false
+    }
+}
+
+/* Writes `term`, compacting IRIs with `namespaces` and recording the prefixes used. */
+fn write_term(term: &Term, namespaces: &NamespaceManager, used: &mut BTreeSet<String>) -> String {
+    let mut compact = |iri: &str| -> Option<String> {
+        namespaces.compact_iri(iri).map(|curie| {
+            used.insert(curie[..curie.find(':').unwrap_or(0)].to_owned());
+            curie
+        })
+    };
+    match term {
+        &Term::Iri(ref iri) => compact(iri).unwrap_or_else(|| term.to_ntriples()),
+        &Term::Literal(ref value, Some(ref datatype), None) if is_shorthand(value, datatype) => value.clone(),
+        &Term::Literal(ref value, Some(ref datatype), None) => match compact(datatype) {
+            Some(curie) => format!("{}^^{}", Term::Literal(value.clone(), None, None).to_ntriples(), curie),
+            None => term.to_ntriples(),
+        },
+        _ => term.to_ntriples(),
+    }
+}
+
+/// Writes `triples` as Turtle, grouped by subject, declaring the prefixes of `namespaces` it uses.
+pub fn serialize(triples: &[Triple], namespaces: &NamespaceManager) -> String {
+    let is_type = |p: &Term| if let &Term::Iri(ref p) = p { p == vocab::RDF_TYPE } else { false };
+    let mut sorted: Vec<&Triple> = triples.iter().collect();
+    /* Subjects in term order, with `a` leading each subject's predicates. */
+    sorted.sort_by(|a, b| (&a.0, !is_type(&a.1), &a.1, &a.2).cmp(&(&b.0, !is_type(&b.1), &b.1, &b.2)));
+    sorted.dedup();
+    let mut used = BTreeSet::new();
+    let mut body = String::new();
+    let mut i = 0;
+    while i < sorted.len() {
+        let subject = &sorted[i].0;
+        body.push_str(&write_term(subject, namespaces, &mut used));
+        let mut first_predicate = true;
+        while i < sorted.len() && sorted[i].0 == *subject {
+            let predicate = &sorted[i].1;
+            body.push_str(if first_predicate { " " } else { " ;\n    " });
+            first_predicate = false;
+            if is_type(predicate) {
+                body.push('a');
+            } else {
+                body.push_str(&write_term(predicate, namespaces, &mut used));
+            }
+            let mut first_object = true;
+            while i < sorted.len() && sorted[i].0 == *subject && sorted[i].1 == *predicate {
+                body.push_str(if first_object { " " } else { ", " });
+                first_object = false;
+                body.push_str(&write_term(&sorted[i].2, namespaces, &mut used));
+                i += 1;
+            }
+        }
+        body.push_str(" .\n");
+    }
+    let mut out = String::new();
+    for prefix in used.iter() {
+        if let Some(namespace) = namespaces.namespace(prefix) {
+            out.push_str(&format!("@prefix {}: {} .\n", prefix, Term::Iri(namespace.to_owned()).to_ntriples()));
+        }
+    }
+    if !out.is_empty() && !body.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&body);
+    out
+}
+
+/// The asserted triples of `graph`.
+pub fn graph_triples(store: &StorageEngine, graph: &GraphID) -> Result<Vec<Triple>, String> {
+    let mut triples = Vec::new();
+    for (_, s, p, o) in store.search_engine_internal(Some(graph.clone()), None, None, None) {
+        triples.push((Term::from_node(store, &s)?, Term::from_node(store, &p)?, Term::from_node(store, &o)?));
+    }
+    Ok(triples)
+}
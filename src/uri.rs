@@ -1,5 +1,6 @@
 use identifiers::InternalUriID;
 use store::StorageEngine;
+use iri::resolve_iri;
 use std::hash::{Hash, Hasher};
 
 static RDFURI_HASH_PREFIX: &'static str = "U:";
@@ -21,11 +22,21 @@ impl RDFUri {
         let iid = store.uri_to_internal_uri_id(uri_string).unwrap();
         RDFUri {id: iid}
     }
+    /// Like `from_string`, but an IRI the store cannot hold (such as a relative one) is an error.
+    pub fn try_from_string(store: &mut StorageEngine, uri_string: &str) -> Result<RDFUri, String> {
+        let iid = store.uri_to_internal_uri_id(uri_string)?;
+        Ok(RDFUri {id: iid})
+    }
+    /* Err(()) means the IRI was never interned; callers need nothing more. */
+    #[allow(clippy::result_unit_err)]
     pub fn from_string_if_exist(store: &StorageEngine, uri_string: &str) -> Result<RDFUri, ()> {
-        let iid = match store.uri_to_internal_uri_id_if_exist(uri_string) {
-            Ok(i) => i,
-            Err(s) => { println!("{}", s); return Result::Err(()) }
-        };
+        let iid = store.uri_to_internal_uri_id_if_exist(uri_string).map_err(|_| ())?;
+        Ok(RDFUri {id: iid})
+    }
+
+    pub fn from_relative_string(store: &mut StorageEngine, base_uri: &str, reference: &str) -> Result<RDFUri, String> {
+        let resolved = resolve_iri(base_uri, reference)?;
+        let iid = store.uri_to_internal_uri_id(&resolved)?;
         Ok(RDFUri {id: iid})
     }
 
@@ -36,7 +47,7 @@ impl RDFUri {
     pub fn to_string(&self, store: &StorageEngine) -> String {
         if let Ok(uristr) = store.internal_uri_id_to_uri(&self.id) { uristr }
             else {
-            panic!(format!("Cannot extract string representation of RDFUri with internal uri id {:?}.", self.id))
+            panic!("Cannot extract string representation of RDFUri with internal uri id {:?}.", self.id)
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+/// Decides where an IRI is cut into the prefix and suffix parts interned by the store.
+/// Returning a byte index (rather than two strings) guarantees `prefix + suffix` round-trips exactly.
+pub trait UriSplitter {
+    fn split_index(&self, uri: &str) -> usize;
+}
+
+/// The default strategy: cut after the last '#', else the last '/', else the last ':'.
+#[derive(Default, Clone, Debug)]
+pub struct LastDelimiterSplitter;
+
+impl UriSplitter for LastDelimiterSplitter {
+    fn split_index(&self, uri: &str) -> usize {
+        uri.rfind('#')
+            .or_else(|| uri.rfind('/'))
+            .or_else(|| uri.rfind(':'))
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    }
+}
+
+/// Cuts after the last occurrence of any of the given delimiters, so `delimiters = "/#?=&:"` turns
+/// `http://ex.org/item?id=42` into `http://ex.org/item?id=` + `42`.
+#[derive(Clone, Debug)]
+pub struct DelimiterSetSplitter {
+    delimiters: Vec<char>,
+}
+
+impl DelimiterSetSplitter {
+    pub fn new(delimiters: &str) -> DelimiterSetSplitter {
+        DelimiterSetSplitter { delimiters: delimiters.chars().collect() }
+    }
+}
+
+impl UriSplitter for DelimiterSetSplitter {
+    fn split_index(&self, uri: &str) -> usize {
+        let delimiters = &self.delimiters;
+        uri.rfind(|c: char| delimiters.contains(&c))
+            .map(|i| i + uri[i..].chars().next().unwrap().len_utf8())
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Default)]
+struct TrieNode {
+    children: BTreeMap<char, TrieNode>,
+    terminal: bool,
+}
+
+/// Cuts at the longest registered namespace that prefixes the IRI, falling back to another
+/// strategy for IRIs outside every registered namespace. Namespaces are fixed at construction
+/// so that interning and lookup always agree on the split.
+pub struct NamespaceSplitter {
+    root: TrieNode,
+    fallback: Box<UriSplitter>,
+}
+
+impl NamespaceSplitter {
+    pub fn new(namespaces: &[&str], fallback: Box<UriSplitter>) -> NamespaceSplitter {
+        let mut root = TrieNode::default();
+        for ns in namespaces {
+            let mut node = &mut root;
+            for c in ns.chars() {
+                node = {node}.children.entry(c).or_insert_with(TrieNode::default);
+            }
+            node.terminal = true;
+        }
+        NamespaceSplitter { root, fallback }
+    }
+
+    /* Byte length of the longest registered namespace prefixing `uri`, if any. */
+    fn longest_namespace(&self, uri: &str) -> Option<usize> {
+        let mut node = &self.root;
+        let mut longest = None;
+        for (i, c) in uri.char_indices() {
+            node = if let Some(n) = node.children.get(&c) { n } else { break; };
+            if node.terminal {
+                longest = Some(i + c.len_utf8());
+            }
+        }
+        longest
+    }
+}
+
+impl UriSplitter for NamespaceSplitter {
+    fn split_index(&self, uri: &str) -> usize {
+        match self.longest_namespace(uri) {
+            Some(i) if i < uri.len() => i,
+            _ => self.fallback.split_index(uri)
+        }
+    }
+}
@@ -0,0 +1,50 @@
+pub static RDF_TYPE: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub static RDF_PROPERTY: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#Property";
+pub static RDF_FIRST: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+pub static RDF_REST: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+pub static RDF_NIL: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+
+pub static RDFS_SUB_CLASS_OF: &'static str = "http://www.w3.org/2000/01/rdf-schema#subClassOf";
+pub static RDFS_SUB_PROPERTY_OF: &'static str = "http://www.w3.org/2000/01/rdf-schema#subPropertyOf";
+pub static RDFS_DOMAIN: &'static str = "http://www.w3.org/2000/01/rdf-schema#domain";
+pub static RDFS_RANGE: &'static str = "http://www.w3.org/2000/01/rdf-schema#range";
+pub static RDFS_CLASS: &'static str = "http://www.w3.org/2000/01/rdf-schema#Class";
+pub static RDFS_RESOURCE: &'static str = "http://www.w3.org/2000/01/rdf-schema#Resource";
+pub static RDFS_LITERAL: &'static str = "http://www.w3.org/2000/01/rdf-schema#Literal";
+pub static RDFS_DATATYPE: &'static str = "http://www.w3.org/2000/01/rdf-schema#Datatype";
+pub static RDFS_MEMBER: &'static str = "http://www.w3.org/2000/01/rdf-schema#member";
+pub static RDFS_CONTAINER_MEMBERSHIP_PROPERTY: &'static str = "http://www.w3.org/2000/01/rdf-schema#ContainerMembershipProperty";
+
+pub static OWL_SAME_AS: &'static str = "http://www.w3.org/2002/07/owl#sameAs";
+pub static OWL_DIFFERENT_FROM: &'static str = "http://www.w3.org/2002/07/owl#differentFrom";
+pub static OWL_INVERSE_OF: &'static str = "http://www.w3.org/2002/07/owl#inverseOf";
+pub static OWL_TRANSITIVE_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#TransitiveProperty";
+pub static OWL_SYMMETRIC_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#SymmetricProperty";
+pub static OWL_ASYMMETRIC_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#AsymmetricProperty";
+pub static OWL_IRREFLEXIVE_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#IrreflexiveProperty";
+pub static OWL_FUNCTIONAL_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#FunctionalProperty";
+pub static OWL_INVERSE_FUNCTIONAL_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#InverseFunctionalProperty";
+pub static OWL_EQUIVALENT_CLASS: &'static str = "http://www.w3.org/2002/07/owl#equivalentClass";
+pub static OWL_EQUIVALENT_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#equivalentProperty";
+pub static OWL_DISJOINT_WITH: &'static str = "http://www.w3.org/2002/07/owl#disjointWith";
+pub static OWL_PROPERTY_DISJOINT_WITH: &'static str = "http://www.w3.org/2002/07/owl#propertyDisjointWith";
+pub static OWL_PROPERTY_CHAIN_AXIOM: &'static str = "http://www.w3.org/2002/07/owl#propertyChainAxiom";
+pub static OWL_THING: &'static str = "http://www.w3.org/2002/07/owl#Thing";
+pub static OWL_NOTHING: &'static str = "http://www.w3.org/2002/07/owl#Nothing";
+pub static OWL_HAS_VALUE: &'static str = "http://www.w3.org/2002/07/owl#hasValue";
+pub static OWL_ON_PROPERTY: &'static str = "http://www.w3.org/2002/07/owl#onProperty";
+pub static OWL_SOME_VALUES_FROM: &'static str = "http://www.w3.org/2002/07/owl#someValuesFrom";
+pub static OWL_ALL_VALUES_FROM: &'static str = "http://www.w3.org/2002/07/owl#allValuesFrom";
+pub static OWL_INTERSECTION_OF: &'static str = "http://www.w3.org/2002/07/owl#intersectionOf";
+pub static OWL_UNION_OF: &'static str = "http://www.w3.org/2002/07/owl#unionOf";
+
+pub static XSD_STRING: &'static str = "http://www.w3.org/2001/XMLSchema#string";
+pub static XSD_BOOLEAN: &'static str = "http://www.w3.org/2001/XMLSchema#boolean";
+pub static XSD_INTEGER: &'static str = "http://www.w3.org/2001/XMLSchema#integer";
+pub static XSD_DECIMAL: &'static str = "http://www.w3.org/2001/XMLSchema#decimal";
+pub static XSD_DOUBLE: &'static str = "http://www.w3.org/2001/XMLSchema#double";
+pub static XSD_DATE: &'static str = "http://www.w3.org/2001/XMLSchema#date";
+pub static XSD_DATE_TIME: &'static str = "http://www.w3.org/2001/XMLSchema#dateTime";
+pub static XSD_TIME: &'static str = "http://www.w3.org/2001/XMLSchema#time";
+
+pub static SHACL_NAMESPACE: &'static str = "http://www.w3.org/ns/shacl#";
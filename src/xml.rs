@@ -0,0 +1,239 @@
+use std::char;
+
+/// An XML element with its attributes and children, as read from SPARQL XML results. Names are
+/// kept as written, prefix included; DTDs, comments and processing instructions are skipped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    /// The name without its namespace prefix.
+    pub fn local_name(&self) -> &str {
+        match self.name.find(':') {
+            Some(i) => &self.name[i + 1..],
+            None => &self.name,
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| v.as_str())
+    }
+
+    pub fn elements<'a>(&'a self) -> Box<Iterator<Item=&'a Element> + 'a> {
+        Box::new(self.children.iter().filter_map(|n| if let &Node::Element(ref e) = n { Some(e) } else { None }))
+    }
+
+    /// The first child element with local name `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.local_name() == name)
+    }
+
+    /// The concatenated text of this element's direct text children.
+    pub fn text(&self) -> String {
+        self.children.iter().filter_map(|n| if let &Node::Text(ref t) = n { Some(t.as_str()) } else { None }).collect()
+    }
+}
+
+/// Escapes text for element content or a double-quoted attribute value.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\r' => escaped.push_str("&#13;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+struct XmlParser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+/// Parses a document, returning its root element.
+pub fn parse(text: &str) -> Result<Element, String> {
+    let mut parser = XmlParser { text: text, position: 0 };
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+    if parser.position < text.len() {
+        return parser.error("Unexpected content after the root element");
+    }
+    Ok(root)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == ':' || c == '_' || c == '-' || c == '.'
+}
+
+impl<'a> XmlParser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("{} at offset {}.", message, self.position))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<(), String> {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.position += i + end.len();
+                Ok(())
+            }
+            None => self.error(&format!("Expected '{}'", end)),
+        }
+    }
+
+    /* Skips whitespace, comments, processing instructions and doctype declarations. */
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("<!DOCTYPE") {
+                /* An internal subset is not expanded, only skipped. */
+                let end = match self.rest().find('[') {
+                    Some(bracket) if bracket < self.rest().find('>').unwrap_or(0) => "]>",
+                    _ => ">",
+                };
+                self.skip_past(end)?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let length: usize = self.rest().chars().take_while(|c| is_name_char(*c)).map(|c| c.len_utf8()).sum();
+        if length == 0 {
+            return self.error("Expected a name");
+        }
+        let name = self.rest()[..length].to_owned();
+        self.position += length;
+        Ok(name)
+    }
+
+    fn unescape(&self, text: &str) -> Result<String, String> {
+        let mut value = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(amp) = rest.find('&') {
+            value.push_str(&rest[..amp]);
+            let semicolon = match rest[amp..].find(';') {
+                Some(i) => amp + i,
+                None => return self.error("Unterminated entity reference"),
+            };
+            let entity = &rest[amp + 1..semicolon];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            match c {
+                Some(c) => value.push(c),
+                None => return self.error(&format!("Unknown entity &{};", entity)),
+            }
+            rest = &rest[semicolon + 1..];
+        }
+        value.push_str(rest);
+        Ok(value)
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        if !self.rest().starts_with('<') {
+            return self.error("Expected an element");
+        }
+        self.position += 1;
+        let name = self.name()?;
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok(Element { name: name, attributes: attributes, children: Vec::new() });
+            }
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                break;
+            }
+            let attribute = self.name()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return self.error("Expected '='");
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(q) if q == '"' || q == '\'' => q,
+                _ => return self.error("Expected a quoted attribute value"),
+            };
+            self.position += 1;
+            let end = match self.rest().find(quote) {
+                Some(end) => end,
+                None => return self.error("Unterminated attribute value"),
+            };
+            let value = self.unescape(&self.rest()[..end])?;
+            self.position += end + 1;
+            attributes.push((attribute, value));
+        }
+        let mut children = Vec::new();
+        loop {
+            if self.rest().starts_with("</") {
+                self.position += 2;
+                let closing = self.name()?;
+                if closing != name {
+                    return self.error(&format!("Expected </{}>, found </{}>", name, closing));
+                }
+                self.skip_whitespace();
+                if !self.rest().starts_with('>') {
+                    return self.error("Expected '>'");
+                }
+                self.position += 1;
+                return Ok(Element { name: name, attributes: attributes, children: children });
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<![CDATA[") {
+                let start = self.position + 9;
+                self.skip_past("]]>")?;
+                children.push(Node::Text(self.text[start..self.position - 3].to_owned()));
+            } else if self.rest().starts_with('<') {
+                children.push(Node::Element(self.element()?));
+            } else if self.rest().is_empty() {
+                return self.error(&format!("Unclosed element <{}>", name));
+            } else {
+                let end = self.rest().find('<').unwrap_or(self.rest().len());
+                let text = self.unescape(&self.rest()[..end])?;
+                self.position += end;
+                children.push(Node::Text(text));
+            }
+        }
+    }
+}
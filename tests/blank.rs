@@ -0,0 +1,61 @@
+extern crate qstore;
+
+use qstore::blank::{BlankNode, BlankNodeScope};
+use qstore::nquads;
+use qstore::store::StorageEngine;
+
+#[test]
+fn scopes_labels_per_document() {
+    let mut scope = BlankNodeScope::new();
+    let a = scope.blank_node("b0");
+    assert_eq!(scope.blank_node("b0"), a);
+    assert!(scope.blank_node("b1") != a);
+    assert!(BlankNodeScope::new().blank_node("b0") != a);
+    assert_eq!(scope.len(), 2);
+}
+
+#[test]
+fn loads_keep_their_blank_nodes_apart() {
+    let mut store = StorageEngine::default();
+    let text = "_:b0 <http://e/p> \"1\" .\n_:b0 <http://e/q> \"2\" .\n";
+    nquads::load(&mut store, text, None).unwrap();
+    nquads::load(&mut store, text, None).unwrap();
+    let serialized = nquads::serialize(&store, None).unwrap();
+    let subjects: std::collections::BTreeSet<&str> = serialized.lines().map(|l| l.split(' ').next().unwrap()).collect();
+    assert_eq!(store.quad_count(), 4);
+    assert_eq!(subjects.len(), 2);
+}
+
+#[test]
+fn skolem_iris_round_trip() {
+    let blank = BlankNode::new(Some("x1"));
+    let iri = blank.to_skolem_iri("http://example.org/");
+    assert_eq!(iri, "http://example.org/.well-known/genid/x1");
+    assert_eq!(BlankNode::from_skolem_iri(&iri), Some(blank));
+    assert_eq!(BlankNode::from_skolem_iri("http://example.org/.well-known/genid/"), None);
+    assert_eq!(BlankNode::from_skolem_iri("http://example.org/data/.well-known/genid/x1"), None);
+    assert_eq!(BlankNode::from_skolem_iri("http://example.org/a?next=/.well-known/genid/x1"), None);
+    assert_eq!(BlankNode::from_skolem_iri("http://example.org/.well-known/genid/x1#frag"), None);
+}
+
+#[test]
+fn skolemize_and_deskolemize() {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "_:a <http://e/p> _:b .\n<http://e/s> <http://e/p> _:a .\n", None).unwrap();
+    assert_eq!(store.skolemize(None, "http://example.org").unwrap(), 2);
+    let skolemized = nquads::serialize(&store, None).unwrap();
+    assert!(!skolemized.contains("_:"));
+    assert_eq!(skolemized.matches("/.well-known/genid/").count(), 3);
+    assert_eq!(store.deskolemize(None).unwrap(), 2);
+    let deskolemized = nquads::serialize(&store, None).unwrap();
+    assert_eq!(deskolemized.matches("_:").count(), 3);
+    assert_eq!(store.quad_count(), 2);
+}
+
+#[test]
+fn skolemize_rejects_a_relative_authority() {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "_:a <http://e/p> \"1\" .\n", None).unwrap();
+    assert!(store.skolemize(None, "example.org").is_err());
+    assert!(nquads::serialize(&store, None).unwrap().starts_with("_:"));
+}
@@ -0,0 +1,69 @@
+extern crate qstore;
+
+use qstore::canonicalize::Canonicalizer;
+use qstore::nquads;
+use qstore::sha256::sha256_hex;
+use qstore::store::StorageEngine;
+
+
+fn canonical(data: &str) -> (String, String) {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, data, None).unwrap();
+    let dataset = store.canonicalize(None, None).unwrap();
+    (dataset.borrow_nquads().to_owned(), dataset.borrow_hash().to_owned())
+}
+
+#[test]
+fn labels_blank_nodes_as_the_specification_example_does() {
+    let (nquads, hash) = canonical("<http://example.com/#p> <http://example.com/#q> _:e0 .\n\
+                                    <http://example.com/#p> <http://example.com/#r> _:e1 .\n\
+                                    _:e0 <http://example.com/#s> <http://example.com/#u> .\n\
+                                    _:e1 <http://example.com/#t> <http://example.com/#u> .\n");
+    assert_eq!(nquads, "<http://example.com/#p> <http://example.com/#q> _:c14n0 .\n\
+                        <http://example.com/#p> <http://example.com/#r> _:c14n1 .\n\
+                        _:c14n0 <http://example.com/#s> <http://example.com/#u> .\n\
+                        _:c14n1 <http://example.com/#t> <http://example.com/#u> .\n");
+    assert_eq!(hash, sha256_hex(nquads.as_bytes()));
+}
+
+#[test]
+fn output_does_not_depend_on_labels_or_order() {
+    let first = canonical("_:a <http://e/p> _:b .\n_:b <http://e/p> _:c .\n_:c <http://e/q> \"end\" .\n");
+    let second = canonical("_:z <http://e/q> \"end\" .\n_:y <http://e/p> _:z .\n_:x <http://e/p> _:y .\n");
+    assert_eq!(first, second);
+}
+
+#[test]
+fn different_datasets_hash_differently() {
+    let first = canonical("_:a <http://e/p> _:b .\n");
+    let second = canonical("_:a <http://e/p> _:a .\n");
+    assert!(first.1 != second.1);
+}
+
+#[test]
+fn named_graphs_are_kept_in_the_dataset() {
+    let (nquads, _) = canonical("_:a <http://e/p> \"x\" <http://e/g> .\n");
+    assert_eq!(nquads, "_:c14n0 <http://e/p> \"x\" <http://e/g> .\n");
+}
+
+#[test]
+fn a_single_graph_hashes_independently_of_its_name() {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "_:a <http://e/p> \"x\" <http://e/g1> .\n_:b <http://e/p> \"x\" <http://e/g2> .\n", None).unwrap();
+    let (g1, g2) = (store.uri_str_to_internal_id("http://e/g1").unwrap(), store.uri_str_to_internal_id("http://e/g2").unwrap());
+    let first = store.canonicalize(Some(g1), None).unwrap();
+    let second = store.canonicalize(Some(g2), None).unwrap();
+    assert_eq!(first.borrow_hash(), second.borrow_hash());
+    assert_eq!(first.borrow_nquads(), "_:c14n0 <http://e/p> \"x\" .\n");
+}
+
+#[test]
+fn symmetric_blank_nodes_are_bounded_by_the_work_limit() {
+    let ring: String = (0..8).map(|i| format!("_:n{} <http://e/next> _:n{} .\n", i, (i + 1) % 8)).collect();
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &ring, None).unwrap();
+    let dataset = store.canonicalize(None, None).unwrap();
+    assert_eq!(dataset.borrow_labels().len(), 8);
+    let limited = Canonicalizer::new().with_work_limit(3).canonicalize(&store, None);
+    assert!(limited.unwrap_err().contains("work limit"));
+}
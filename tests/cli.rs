@@ -0,0 +1,101 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/* A scratch directory for one test, removed when it ends. */
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Scratch {
+        let dir = env::temp_dir().join(format!("qstore-cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    fn file(&self, name: &str, text: &str) -> String {
+        let path = self.0.join(name);
+        fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_str().unwrap().to_owned()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn qstore(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_qstore")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn load_then_list_and_dump_graphs() {
+    let scratch = Scratch::new("load");
+    let store = scratch.path("store.nq");
+    let people = scratch.file("people.ttl", "@prefix e: <http://e/> . e:ann e:knows e:bob, e:cat .");
+    let places = scratch.file("places.nt", "<http://e/ann> <http://e/lives> \"Paris\"@fr .\n");
+    stdout(&qstore(&["load", &store, &people]));
+    stdout(&qstore(&["load", &store, &places, "--graph", "http://e/places"]));
+    assert_eq!(stdout(&qstore(&["graphs", &store])), "1\t<http://e/places>\n2\tDEFAULT\n");
+    let dumped = stdout(&qstore(&["dump", &store, "--graph", "http://e/places", "--format", "ntriples"]));
+    assert_eq!(dumped, "<http://e/ann> <http://e/lives> \"Paris\"@fr .\n");
+    assert!(stdout(&qstore(&["stats", &store])).starts_with("quads\t3\n"));
+}
+
+#[test]
+fn query_and_update_a_store() {
+    let scratch = Scratch::new("query");
+    let store = scratch.path("store.nq");
+    stdout(&qstore(&["update", &store, "INSERT DATA { <http://e/a> <http://e/n> 1 . <http://e/b> <http://e/n> \"two\" }"]));
+    let rows = stdout(&qstore(&["query", &store, "SELECT ?s ?n WHERE { ?s <http://e/n> ?n } ORDER BY ?s"]));
+    assert_eq!(rows, "?s\t?n\n<http://e/a>\t\"1\"^^<http://www.w3.org/2001/XMLSchema#integer>\n<http://e/b>\t\"two\"\n");
+    let query = scratch.file("ask.rq", "ASK { <http://e/a> ?p ?o }");
+    assert_eq!(stdout(&qstore(&["query", &store, &format!("@{}", query)])).trim(), "{\"head\":{},\"boolean\":true}");
+    let output = qstore(&["query", &store, "ASK {}", "--format", "csv"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn validate_reports_each_file() {
+    let scratch = Scratch::new("validate");
+    let good = scratch.file("good.nt", "<http://e/a> <http://e/p> <http://e/b> .\n");
+    let bad = scratch.file("bad.ttl", "<http://e/a> <http://e/p> .");
+    assert_eq!(stdout(&qstore(&["validate", &good])), format!("{}: valid, 1 statements\n", good));
+    let output = qstore(&["validate", &good, &bad]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("{}: invalid", bad)));
+    assert!(!std::path::Path::new(&scratch.path("store.nq")).exists());
+}
+
+#[test]
+fn misuse_is_reported() {
+    let scratch = Scratch::new("misuse");
+    assert_eq!(qstore(&["query", &scratch.path("missing.nq"), "ASK {}"]).status.code(), Some(1));
+    assert_eq!(qstore(&["frobnicate"]).status.code(), Some(2));
+    assert_eq!(qstore(&["query", "--timeout", "soon"]).status.code(), Some(2));
+    assert!(stdout(&qstore(&["help"])).starts_with("Usage: qstore"));
+}
+
+#[test]
+fn explain_prints_the_plan() {
+    let scratch = Scratch::new("explain");
+    let store = scratch.file("store.nq", "<http://e/a> <http://e/p> <http://e/b> .\n");
+    let text = stdout(&qstore(&["explain", &store, "ASK { ?x <http://e/p> ?y }"]));
+    assert!(text.starts_with("ask  (rows: 1"), "{}", text);
+    assert!(text.contains("scan ?x <http://e/p> ?y using "));
+    let json = stdout(&qstore(&["explain", &store, "ASK { ?x <http://e/p> ?y }", "--format", "json"]));
+    assert!(json.starts_with("{\"operator\":\"ask\""), "{}", json);
+    assert_eq!(qstore(&["explain", &store, "ASK {}", "--format", "yaml"]).status.code(), Some(1));
+}
@@ -0,0 +1,37 @@
+/* Fixtures shared by the test crates, each of which uses only some of them. */
+#![allow(dead_code)]
+
+use qstore::identifiers::InternalID;
+use qstore::store::StorageEngine;
+
+pub fn id(store: &mut StorageEngine, iri: &str) -> InternalID {
+    store.uri_str_to_internal_id(iri).unwrap()
+}
+
+/// Whether the default graph holds the triple, asserted or entailed.
+pub fn holds(store: &mut StorageEngine, s: &str, p: &str, o: &str) -> bool {
+    let (s, p, o) = (id(store, s), id(store, p), id(store, o));
+    store.search_engine_entailed(Some(InternalID(0.into())), Some(s), Some(p), Some(o)).next().is_some()
+}
+
+/// Whether the triple is stored in the default graph, as asserted or canonicalized.
+pub fn stored(store: &mut StorageEngine, s: &str, p: &str, o: &str) -> bool {
+    let (s, p, o) = (id(store, s), id(store, p), id(store, o));
+    store.search_engine_internal(Some(InternalID(0.into())), Some(s), Some(p), Some(o)).next().is_some()
+}
+
+/// Whether the default graph holds the triple once equal resources are expanded to their aliases.
+pub fn expanded(store: &mut StorageEngine, s: &str, p: &str, o: &str) -> bool {
+    let (s, p, o) = (id(store, s), id(store, p), id(store, o));
+    store.search_engine_expanded(Some(InternalID(0.into())), Some(s), Some(p), Some(o)).next().is_some()
+}
+
+pub fn add(store: &mut StorageEngine, s: &str, p: &str, o: &str) {
+    let (s, p, o) = (id(store, s), id(store, p), id(store, o));
+    store.add_internal_triple(s, p, o);
+}
+
+pub fn remove(store: &mut StorageEngine, s: &str, p: &str, o: &str) {
+    let (s, p, o) = (id(store, s), id(store, p), id(store, o));
+    store.remove_internal_triple(s, p, o);
+}
@@ -0,0 +1,62 @@
+extern crate qstore;
+
+use qstore::identifiers::InternalID;
+use qstore::nquads;
+use qstore::store::StorageEngine;
+
+fn store() -> StorageEngine {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "<http://e/ann> <http://e/parent> <http://e/bob> .\n\
+                              <http://e/bob> <http://e/parent> <http://e/cal> .\n\
+                              <http://e/cal> <http://e/age> \"7\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n", None).unwrap();
+    store
+}
+
+fn output(store: &mut StorageEngine) -> String {
+    let graph = store.uri_str_to_internal_id("http://internal/graph/rules").unwrap();
+    nquads::serialize(store, Some(&graph)).unwrap()
+}
+
+#[test]
+fn recursive_rules_reach_a_fixpoint() {
+    let mut store = store();
+    let rules = "@prefix e: <http://e/> .\n\
+                 { ?x e:parent ?y } => { ?x e:ancestor ?y } .\n\
+                 { ?x e:parent ?y . ?y e:ancestor ?z } => { ?x e:ancestor ?z } .\n";
+    assert_eq!(store.apply_rules(rules, None).unwrap(), 3);
+    assert!(output(&mut store).contains("<http://e/ann> <http://e/ancestor> <http://e/cal> ."));
+}
+
+#[test]
+fn negation_is_stratified() {
+    let mut store = store();
+    let rules = "@prefix e: <http://e/> .\n\
+                 { ?x e:parent ?y } => { ?y e:hasParent true } .\n\
+                 { ?x e:parent ?y . not { ?x e:hasParent true } } => { ?x e:root true } .\n";
+    store.apply_rules(rules, None).unwrap();
+    let derived = output(&mut store);
+    assert!(derived.contains("<http://e/ann> <http://e/root> \"true\"^^<http://www.w3.org/2001/XMLSchema#boolean> ."));
+    assert!(!derived.contains("<http://e/bob> <http://e/root>"));
+    let unstratifiable = "@prefix e: <http://e/> .\n{ ?x e:parent ?y . not { ?x e:p ?y } } => { ?x e:p ?y } .\n";
+    assert!(store.apply_rules(unstratifiable, None).is_err());
+}
+
+#[test]
+fn literals_are_validated() {
+    let mut store = store();
+    assert!(store.apply_rules("{ ?x <http://e/age> \"7\"^^<integer> } => { ?x <http://e/young> true } .", None).is_err());
+    assert!(store.apply_rules("{ ?x <http://e/age> \"7\"^^<http://www.w3.org/2001/XMLSchema#integer> } => { ?x <http://e/young> true } .", None).is_ok());
+    assert!(output(&mut store).contains("<http://e/cal> <http://e/young>"));
+}
+
+#[test]
+fn output_graph_is_replaced_and_never_the_default_graph() {
+    let mut store = store();
+    store.apply_rules("{ ?x <http://e/parent> ?y } => { ?y <http://e/child> ?x } .", None).unwrap();
+    store.apply_rules("{ ?x <http://e/parent> ?y } => { ?x <http://e/hasChild> true } .", None).unwrap();
+    let derived = output(&mut store);
+    assert!(!derived.contains("<http://e/child>"));
+    assert!(derived.contains("<http://e/hasChild>"));
+    assert!(store.apply_rules("{ ?x <http://e/parent> ?y } => { ?y <http://e/child> ?x } .", Some(InternalID(0.into()))).is_err());
+    assert_eq!(store.quad_count(), 5);
+}
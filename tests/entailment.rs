@@ -0,0 +1,144 @@
+extern crate qstore;
+
+use qstore::http::Request;
+use qstore::limits::{CancellationToken, LimitKind, Limits, OperationError};
+use qstore::nquads::{self, Term};
+use qstore::server::{Endpoint, SPARQL_PATH};
+use qstore::sparql::QueryResult;
+use qstore::sparql::entailment::EntailmentRegime;
+use qstore::sparql::eval::Evaluator;
+use qstore::sparql::parser;
+use qstore::store::StorageEngine;
+
+const PREFIXES: &str = "PREFIX e: <http://e/> PREFIX rdf: <http://www.w3.org/1999/02/22-rdf-syntax-ns#> \
+    PREFIX rdfs: <http://www.w3.org/2000/01/rdf-schema#> PREFIX owl: <http://www.w3.org/2002/07/owl#> ";
+
+fn store(turtle: &str) -> StorageEngine {
+    let mut store = StorageEngine::default();
+    store.update(&format!("{}INSERT DATA {{ {} }}", PREFIXES, turtle)).unwrap();
+    store
+}
+
+/* The values of the single variable a query selects, sorted. */
+fn answers(store: &StorageEngine, query: &str, regime: EntailmentRegime) -> Vec<String> {
+    let mut values: Vec<String> = match store.query_with_entailment(&format!("{}{}", PREFIXES, query), regime).unwrap() {
+        QueryResult::Solutions { rows, .. } => rows.into_iter().map(|row| match row[0] {
+            Some(Term::Iri(ref iri)) => iri.trim_start_matches("http://e/").to_owned(),
+            ref other => format!("{:?}", other),
+        }).collect(),
+        other => panic!("{:?}", other),
+    };
+    values.sort();
+    values
+}
+
+#[test]
+fn rdfs_answers_follow_the_schema() {
+    let store = store("e:Dog rdfs:subClassOf e:Mammal . e:Mammal rdfs:subClassOf e:Animal . e:rex a e:Dog . \
+        e:owns rdfs:domain e:Person ; rdfs:range e:Thing . e:ann e:owns e:rex . \
+        e:hasPet rdfs:subPropertyOf e:owns . e:bob e:hasPet e:tom .");
+    let animals = "SELECT ?x WHERE { ?x a e:Animal }";
+    assert_eq!(answers(&store, animals, EntailmentRegime::Simple), Vec::<String>::new());
+    assert_eq!(answers(&store, animals, EntailmentRegime::Rdfs), vec!["rex"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { ?x a e:Person }", EntailmentRegime::Rdfs), vec!["ann", "bob"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { ?x a e:Thing }", EntailmentRegime::Rdfs), vec!["rex", "tom"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { e:bob e:owns ?x }", EntailmentRegime::Rdfs), vec!["tom"]);
+}
+
+#[test]
+fn answers_are_not_materialized() {
+    let store = store("e:Dog rdfs:subClassOf e:Animal . e:rex a e:Dog .");
+    let before = store.quad_count();
+    answers(&store, "SELECT ?x WHERE { ?x a e:Animal }", EntailmentRegime::Rdfs);
+    assert_eq!(store.quad_count(), before);
+    assert_eq!(store.inferred_quad_count(), 0);
+}
+
+#[test]
+fn cyclic_schemas_terminate() {
+    let store = store("e:A rdfs:subClassOf e:B . e:B rdfs:subClassOf e:C . e:C rdfs:subClassOf e:A . e:x a e:B .");
+    for class in ["A", "B", "C"].iter() {
+        assert_eq!(answers(&store, &format!("SELECT ?x WHERE {{ ?x a e:{} }}", class), EntailmentRegime::Rdfs), vec!["x"]);
+    }
+    assert_eq!(answers(&store, "SELECT ?c WHERE { e:A rdfs:subClassOf ?c }", EntailmentRegime::Rdfs), vec!["A", "B", "C"]);
+}
+
+#[test]
+fn owl_rl_answers_use_property_characteristics() {
+    let store = store("e:ancestor a owl:TransitiveProperty . e:a e:ancestor e:b . e:b e:ancestor e:c . \
+        e:parent owl:inverseOf e:child . e:a e:parent e:d . e:spouse a owl:SymmetricProperty . e:a e:spouse e:s . \
+        e:Human owl:equivalentClass e:Person . e:a a e:Human .");
+    assert_eq!(answers(&store, "SELECT ?x WHERE { e:a e:ancestor ?x }", EntailmentRegime::OwlRl), vec!["b", "c"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { e:d e:child ?x }", EntailmentRegime::OwlRl), vec!["a"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { e:s e:spouse ?x }", EntailmentRegime::OwlRl), vec!["a"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { ?x a e:Person }", EntailmentRegime::OwlRl), vec!["a"]);
+    assert_eq!(answers(&store, "SELECT ?x WHERE { e:a e:ancestor ?x }", EntailmentRegime::Rdfs), vec!["b"]);
+}
+
+#[test]
+fn owl_rl_answers_use_class_restrictions() {
+    let store = store("e:Parent owl:equivalentClass [ a owl:Restriction ; owl:onProperty e:hasChild ; owl:someValuesFrom e:Person ] . \
+        e:a e:hasChild e:b . e:b a e:Person . e:c e:hasChild e:d .");
+    assert_eq!(answers(&store, "SELECT ?x WHERE { ?x a e:Parent }", EntailmentRegime::OwlRl), vec!["a"]);
+}
+
+#[test]
+fn rules_join_quads_within_one_graph() {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "<http://e/Dog> <http://www.w3.org/2000/01/rdf-schema#subClassOf> <http://e/Animal> <http://e/g1> .\n\
+        <http://e/rex> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/Dog> <http://e/g1> .\n\
+        <http://e/fido> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/Dog> <http://e/g2> .\n", None).unwrap();
+    let query = "SELECT ?x WHERE { GRAPH ?g { ?x a e:Animal } }";
+    assert_eq!(answers(&store, query, EntailmentRegime::Rdfs), vec!["rex"]);
+}
+
+#[test]
+fn entailment_obeys_limits() {
+    let store = store("e:A rdfs:subClassOf e:B . e:x a e:A .");
+    let token = CancellationToken::new();
+    token.cancel();
+    let query = parser::parse_query(&format!("{}SELECT ?x WHERE {{ ?x a e:B }}", PREFIXES), store.borrow_namespace_manager()).unwrap();
+    let evaluator = Evaluator::new(&store).with_limits(Limits::new().with_cancellation(token)).with_entailment(EntailmentRegime::Rdfs);
+    let error = evaluator.execute(&query).map(|_| ()).map_err(|e| evaluator.borrow_guard().error(e));
+    match error {
+        Err(OperationError::Limit(limit)) => assert_eq!(limit.kind, LimitKind::Cancelled),
+        other => panic!("{:?}", other),
+    }
+}
+
+fn request(query: &[(&str, &str)]) -> Request {
+    Request {
+        method: "GET".to_owned(),
+        path: SPARQL_PATH.to_owned(),
+        query: query.iter().map(|&(n, v)| (n.to_owned(), v.to_owned())).collect(),
+        headers: vec![("accept".to_owned(), "text/tab-separated-values".to_owned())],
+        body: Vec::new(),
+    }
+}
+
+#[test]
+fn endpoints_choose_a_regime_per_request() {
+    let store = store("e:Dog rdfs:subClassOf e:Animal . e:rex a e:Dog .");
+    let query = format!("{}SELECT ?x WHERE {{ ?x a e:Animal }}", PREFIXES);
+    let rows = |endpoint: &mut Endpoint, params: &[(&str, &str)]| {
+        let response = endpoint.handle(&request(params));
+        assert_eq!(response.status, 200);
+        String::from_utf8(response.body).unwrap().lines().count() - 1
+    };
+    let mut endpoint = Endpoint::new(store);
+    assert_eq!(rows(&mut endpoint, &[("query", &query)]), 0);
+    assert_eq!(rows(&mut endpoint, &[("query", &query), ("entailment", "rdfs")]), 1);
+    let mut endpoint = endpoint.with_entailment(EntailmentRegime::Rdfs);
+    assert_eq!(rows(&mut endpoint, &[("query", &query)]), 1);
+    assert_eq!(rows(&mut endpoint, &[("query", &query), ("entailment", "simple")]), 0);
+    assert_eq!(endpoint.handle(&request(&[("query", &query), ("entailment", "rif")])).status, 400);
+}
+
+#[test]
+fn regimes_are_named() {
+    for regime in [EntailmentRegime::Simple, EntailmentRegime::Rdfs, EntailmentRegime::OwlRl].iter() {
+        assert_eq!(EntailmentRegime::from_name(regime.name()), Some(*regime));
+    }
+    assert_eq!(EntailmentRegime::from_name("OWL2-RL"), Some(EntailmentRegime::OwlRl));
+    assert_eq!(EntailmentRegime::from_name("rif"), None);
+}
@@ -0,0 +1,114 @@
+extern crate qstore;
+
+mod common;
+
+use common::{add, expanded as holds, id, remove, stored};
+use qstore::store::StorageEngine;
+use qstore::vocab::{OWL_SAME_AS as SAME_AS, RDF_TYPE as TYPE, RDFS_SUB_CLASS_OF as SUB_CLASS_OF};
+
+
+fn equality_store() -> StorageEngine {
+    let mut store = StorageEngine::default();
+    store.enable_equality_mode().unwrap();
+    store
+}
+
+#[test]
+fn a_link_merges_quads_onto_one_representative() {
+    let mut store = equality_store();
+    add(&mut store, "http://e/a", "http://e/p", "http://e/x");
+    add(&mut store, "http://e/b", "http://e/p", "http://e/y");
+    add(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    let (a, b) = (id(&mut store, "http://e/a"), id(&mut store, "http://e/b"));
+    assert_eq!(store.canonical_id(&a), store.canonical_id(&b));
+    assert!(holds(&mut store, "http://e/a", "http://e/p", "http://e/y"));
+    assert!(holds(&mut store, "http://e/b", "http://e/p", "http://e/x"));
+    assert_eq!(store.quad_count(), 3);
+}
+
+#[test]
+fn a_split_restores_the_asserted_terms() {
+    let mut store = equality_store();
+    add(&mut store, "http://e/a", "http://e/p", "http://e/x");
+    add(&mut store, "http://e/b", "http://e/p", "http://e/y");
+    add(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    remove(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    let (a, b) = (id(&mut store, "http://e/a"), id(&mut store, "http://e/b"));
+    assert!(store.canonical_id(&a) != store.canonical_id(&b));
+    assert!(stored(&mut store, "http://e/a", "http://e/p", "http://e/x"));
+    assert!(stored(&mut store, "http://e/b", "http://e/p", "http://e/y"));
+    assert!(!holds(&mut store, "http://e/a", "http://e/p", "http://e/y"));
+    assert!(!holds(&mut store, "http://e/b", "http://e/p", "http://e/x"));
+    assert_eq!(store.quad_count(), 2);
+}
+
+#[test]
+fn a_split_keeps_the_parts_that_stay_linked() {
+    let mut store = equality_store();
+    add(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    add(&mut store, "http://e/b", SAME_AS, "http://e/c");
+    add(&mut store, "http://e/c", "http://e/p", "http://e/x");
+    remove(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    let (a, b, c) = (id(&mut store, "http://e/a"), id(&mut store, "http://e/b"), id(&mut store, "http://e/c"));
+    assert_eq!(store.canonical_id(&b), store.canonical_id(&c));
+    assert!(store.canonical_id(&a) != store.canonical_id(&b));
+    assert!(holds(&mut store, "http://e/b", "http://e/p", "http://e/x"));
+    assert!(!holds(&mut store, "http://e/a", "http://e/p", "http://e/x"));
+    assert!(holds(&mut store, "http://e/b", SAME_AS, "http://e/c"));
+    assert!(!holds(&mut store, "http://e/a", SAME_AS, "http://e/b"));
+}
+
+#[test]
+fn a_redundant_link_keeps_the_class_together() {
+    let mut store = equality_store();
+    add(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    add(&mut store, "http://e/b", SAME_AS, "http://e/c");
+    add(&mut store, "http://e/a", SAME_AS, "http://e/c");
+    add(&mut store, "http://e/a", "http://e/p", "http://e/x");
+    remove(&mut store, "http://e/a", SAME_AS, "http://e/c");
+    let (a, c) = (id(&mut store, "http://e/a"), id(&mut store, "http://e/c"));
+    assert_eq!(store.canonical_id(&a), store.canonical_id(&c));
+    assert!(holds(&mut store, "http://e/c", "http://e/p", "http://e/x"));
+}
+
+#[test]
+fn deleting_one_asserted_form_keeps_the_others() {
+    let mut store = equality_store();
+    add(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    add(&mut store, "http://e/a", "http://e/p", "http://e/x");
+    add(&mut store, "http://e/b", "http://e/p", "http://e/x");
+    assert_eq!(store.quad_count(), 2);
+    remove(&mut store, "http://e/a", "http://e/p", "http://e/x");
+    assert!(holds(&mut store, "http://e/a", "http://e/p", "http://e/x"));
+    remove(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    assert!(stored(&mut store, "http://e/b", "http://e/p", "http://e/x"));
+    assert!(!holds(&mut store, "http://e/a", "http://e/p", "http://e/x"));
+}
+
+#[test]
+fn enabling_canonicalizes_existing_quads_and_remembers_them() {
+    let mut store = StorageEngine::default();
+    add(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    add(&mut store, "http://e/a", "http://e/p", "http://e/x");
+    add(&mut store, "http://e/b", "http://e/p", "http://e/y");
+    store.enable_equality_mode().unwrap();
+    assert!(holds(&mut store, "http://e/a", "http://e/p", "http://e/y"));
+    remove(&mut store, "http://e/a", SAME_AS, "http://e/b");
+    assert!(stored(&mut store, "http://e/a", "http://e/p", "http://e/x"));
+    assert!(stored(&mut store, "http://e/b", "http://e/p", "http://e/y"));
+    assert!(!holds(&mut store, "http://e/a", "http://e/p", "http://e/y"));
+}
+
+#[test]
+fn inferences_follow_a_split() {
+    let mut store = equality_store();
+    store.enable_rdfs_reasoning().unwrap();
+    add(&mut store, "http://e/Dog", SUB_CLASS_OF, "http://e/Animal");
+    add(&mut store, "http://e/rex", TYPE, "http://e/Dog");
+    add(&mut store, "http://e/rex", SAME_AS, "http://e/fido");
+    assert!(holds(&mut store, "http://e/fido", TYPE, "http://e/Animal"));
+    remove(&mut store, "http://e/rex", SAME_AS, "http://e/fido");
+    assert!(holds(&mut store, "http://e/rex", TYPE, "http://e/Animal"));
+    assert!(!holds(&mut store, "http://e/fido", TYPE, "http://e/Animal"));
+    assert!(!holds(&mut store, "http://e/fido", TYPE, "http://e/Dog"));
+}
@@ -0,0 +1,79 @@
+extern crate qstore;
+
+mod common;
+
+use common::{add, id};
+use qstore::identifiers::InternalID;
+use qstore::inference::{Proof, ProofStep};
+use qstore::store::StorageEngine;
+use qstore::vocab::{RDF_TYPE as TYPE, RDFS_SUB_CLASS_OF as SUB_CLASS_OF};
+
+
+fn explain(store: &mut StorageEngine, s: &str, p: &str, o: &str) -> Option<Proof> {
+    let (s, p, o) = (id(store, s), id(store, p), id(store, o));
+    store.explain(InternalID(0.into()), s, p, o)
+}
+
+fn leaves(proof: &Proof, out: &mut Vec<(InternalID, InternalID, InternalID)>) {
+    match proof.step {
+        ProofStep::Asserted => out.push((proof.quad.0.clone(), proof.quad.1.clone(), proof.quad.2.clone())),
+        ProofStep::Inferred { ref premises, .. } => for premise in premises.iter() { leaves(premise, out) },
+        ProofStep::Circular => {}
+    }
+}
+
+#[test]
+fn asserted_quads_are_leaves() {
+    let mut store = StorageEngine::default();
+    store.enable_rdfs_reasoning().unwrap();
+    add(&mut store, "http://e/rex", TYPE, "http://e/Dog");
+    let proof = explain(&mut store, "http://e/rex", TYPE, "http://e/Dog").unwrap();
+    assert_eq!(proof.step, ProofStep::Asserted);
+}
+
+#[test]
+fn inferred_quads_trace_back_to_asserted_premises() {
+    let mut store = StorageEngine::default();
+    store.enable_rdfs_reasoning().unwrap();
+    add(&mut store, "http://e/Dog", SUB_CLASS_OF, "http://e/Mammal");
+    add(&mut store, "http://e/Mammal", SUB_CLASS_OF, "http://e/Animal");
+    add(&mut store, "http://e/rex", TYPE, "http://e/Dog");
+    let proof = explain(&mut store, "http://e/rex", TYPE, "http://e/Animal").unwrap();
+    match proof.step {
+        ProofStep::Inferred { rule, ref premises } => {
+            assert!(!rule.is_empty());
+            assert_eq!(premises.len(), 2);
+        }
+        ref other => panic!("expected an inferred step, got {:?}", other),
+    }
+    let mut found = Vec::new();
+    leaves(&proof, &mut found);
+    let asserted = [
+        (id(&mut store, "http://e/Dog"), id(&mut store, SUB_CLASS_OF), id(&mut store, "http://e/Mammal")),
+        (id(&mut store, "http://e/Mammal"), id(&mut store, SUB_CLASS_OF), id(&mut store, "http://e/Animal")),
+        (id(&mut store, "http://e/rex"), id(&mut store, TYPE), id(&mut store, "http://e/Dog")),
+    ];
+    assert!(!found.is_empty());
+    assert!(found.iter().all(|leaf| asserted.contains(leaf)));
+    assert!(found.contains(&asserted[2]));
+}
+
+#[test]
+fn quads_that_do_not_hold_have_no_proof() {
+    let mut store = StorageEngine::default();
+    store.enable_rdfs_reasoning().unwrap();
+    add(&mut store, "http://e/rex", TYPE, "http://e/Dog");
+    assert!(explain(&mut store, "http://e/rex", TYPE, "http://e/Cat").is_none());
+}
+
+#[test]
+fn a_removed_premise_takes_the_proof_with_it() {
+    let mut store = StorageEngine::default();
+    store.enable_rdfs_reasoning().unwrap();
+    add(&mut store, "http://e/Dog", SUB_CLASS_OF, "http://e/Animal");
+    add(&mut store, "http://e/rex", TYPE, "http://e/Dog");
+    assert!(explain(&mut store, "http://e/rex", TYPE, "http://e/Animal").is_some());
+    let (s, p, o) = (id(&mut store, "http://e/Dog"), id(&mut store, SUB_CLASS_OF), id(&mut store, "http://e/Animal"));
+    store.remove_internal_triple(s, p, o);
+    assert!(explain(&mut store, "http://e/rex", TYPE, "http://e/Animal").is_none());
+}
@@ -0,0 +1,169 @@
+#![allow(bare_trait_objects)]
+
+extern crate qstore;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use qstore::http::{HttpClient, Request, Response};
+use qstore::limits::Limits;
+use qstore::nquads;
+use qstore::server::{Endpoint, LocalClient, SPARQL_PATH};
+use qstore::sparql::QueryResult;
+use qstore::store::StorageEngine;
+
+const REMOTE: &str = "http://remote.example/sparql";
+
+/* Each request sent: its Accept header and the timeout it was given, if any. */
+type Log = Rc<RefCell<Vec<(String, Option<Duration>)>>>;
+
+/* Answers with a stand-in endpoint, logging what it is sent. */
+struct Recording {
+    inner: Box<HttpClient>,
+    log: Log,
+}
+
+impl Recording {
+    fn send_logged(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8], timeout: Option<Duration>) -> Result<Response, String> {
+        let accept = headers.iter().find(|h| h.0.eq_ignore_ascii_case("accept")).map(|h| h.1.clone()).unwrap_or_default();
+        self.log.borrow_mut().push((accept, timeout));
+        self.inner.send(method, url, headers, body)
+    }
+}
+
+impl HttpClient for Recording {
+    fn send(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8]) -> Result<Response, String> {
+        self.send_logged(method, url, headers, body, None)
+    }
+
+    fn send_with_timeout(&self, method: &str, url: &str, headers: &[(String, String)], body: &[u8], timeout: Duration) -> Result<Response, String> {
+        self.send_logged(method, url, headers, body, Some(timeout))
+    }
+}
+
+/* Answers every request with the same response. */
+struct Fixed(Response);
+
+impl HttpClient for Fixed {
+    fn send(&self, _: &str, _: &str, _: &[(String, String)], _: &[u8]) -> Result<Response, String> {
+        Ok(self.0.clone())
+    }
+}
+
+/* The remote endpoint names 300 people; the local store knows the ages of the first 250. */
+fn remote() -> Box<HttpClient> {
+    let mut text = String::new();
+    for i in 0..300 {
+        text.push_str(&format!("<http://e/p{}> <http://e/name> \"P{}\" .\n", i, i));
+    }
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &text, None).unwrap();
+    Box::new(LocalClient::new().with_endpoint("http://remote.example", Endpoint::new(store)))
+}
+
+fn local(client: Box<HttpClient>) -> StorageEngine {
+    let mut text = String::new();
+    for i in 0..250 {
+        text.push_str(&format!("<http://e/p{}> <http://e/age> \"{}\" .\n", i, i));
+    }
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &text, None).unwrap();
+    store.set_http_client(client);
+    store.set_allowed_services(Some(vec![REMOTE.to_owned()]));
+    store
+}
+
+fn recording() -> (StorageEngine, Log) {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    (local(Box::new(Recording { inner: remote(), log: log.clone() })), log)
+}
+
+fn row_count(result: QueryResult) -> usize {
+    match result {
+        QueryResult::Solutions { rows, .. } => rows.len(),
+        other => panic!("{:?}", other),
+    }
+}
+
+const JOIN: &str = "SELECT * WHERE { ?p <http://e/age> ?age SERVICE <http://remote.example/sparql> { ?p <http://e/name> ?name } }";
+
+#[test]
+fn service_joins_remote_solutions_in_batches() {
+    let (store, log) = recording();
+    assert_eq!(row_count(store.query(JOIN).unwrap()), 250);
+    assert_eq!(log.borrow().len(), 3);
+    let accept = &log.borrow()[0].0;
+    assert!(accept.contains("application/sparql-results+json"));
+    assert!(!accept.contains("text/csv"));
+}
+
+#[test]
+fn silent_services_keep_the_solutions_when_they_fail() {
+    let store = local(Box::new(LocalClient::new()));
+    let silent = "SELECT * WHERE { ?p <http://e/age> ?age SERVICE SILENT <http://down.example/sparql> { ?p <http://e/name> ?name } }";
+    assert_eq!(row_count(store.query(silent).unwrap()), 250);
+    assert!(store.query(&silent.replace("SILENT ", "")).is_err());
+}
+
+#[test]
+fn csv_answers_are_refused() {
+    let csv = Response::new(200).with_header("Content-Type", "text/csv").with_body(b"p,name\r\nhttp://e/p1,P1\r\n".to_vec());
+    let store = local(Box::new(Fixed(csv)));
+    let error = store.query(JOIN).unwrap_err();
+    assert!(error.contains("unsupported format"), "{}", error);
+}
+
+#[test]
+fn remote_requests_wait_no_longer_than_the_query_may_run() {
+    let (store, log) = recording();
+    store.query_with_limits(JOIN, &Limits::new().with_timeout(Duration::from_secs(30))).unwrap();
+    assert!(log.borrow().iter().all(|&(_, timeout)| timeout.map(|t| t <= Duration::from_secs(30)).unwrap_or(false)));
+    log.borrow_mut().clear();
+    store.query(JOIN).unwrap();
+    assert!(log.borrow().iter().all(|&(_, timeout)| timeout.is_none()));
+}
+
+#[test]
+fn stores_allow_no_services_by_default() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut store = StorageEngine::default();
+    store.set_http_client(Box::new(Recording { inner: remote(), log: log.clone() }));
+    let names = "SELECT * WHERE { SERVICE <http://remote.example/sparql> { ?p <http://e/name> ?name } }";
+    assert!(store.query(names).is_err());
+    assert!(log.borrow().is_empty());
+    store.set_allowed_services(None);
+    assert_eq!(row_count(store.query(names).unwrap()), 300);
+}
+
+#[test]
+fn services_outside_the_allow_list_are_not_contacted() {
+    let (mut store, log) = recording();
+    store.set_allowed_services(Some(Vec::new()));
+    assert!(store.query(JOIN).is_err());
+    assert_eq!(row_count(store.query(&JOIN.replace("SERVICE", "SERVICE SILENT")).unwrap()), 250);
+    assert!(log.borrow().is_empty());
+    store.set_allowed_services(Some(vec![REMOTE.to_owned()]));
+    assert_eq!(row_count(store.query(JOIN).unwrap()), 250);
+}
+
+fn ask_server(endpoint: &mut Endpoint) -> u16 {
+    let request = Request {
+        method: "POST".to_owned(),
+        path: SPARQL_PATH.to_owned(),
+        query: Vec::new(),
+        headers: vec![("content-type".to_owned(), "application/sparql-query".to_owned())],
+        body: JOIN.as_bytes().to_vec(),
+    };
+    endpoint.handle(&request).status
+}
+
+#[test]
+fn endpoints_allow_no_services_by_default() {
+    let (store, log) = recording();
+    let mut endpoint = Endpoint::new(store);
+    assert_eq!(ask_server(&mut endpoint), 500);
+    assert!(log.borrow().is_empty());
+    let (store, _) = recording();
+    let mut endpoint = Endpoint::new(store).with_allowed_services(vec![REMOTE.to_owned()]);
+    assert_eq!(ask_server(&mut endpoint), 200);
+}
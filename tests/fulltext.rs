@@ -0,0 +1,74 @@
+extern crate qstore;
+
+use qstore::fulltext::{analyze, stem};
+use qstore::nquads::{self, Term};
+use qstore::store::StorageEngine;
+
+fn store(data: &str) -> StorageEngine {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, data, None).unwrap();
+    store.enable_fulltext_index();
+    store
+}
+
+fn subjects(store: &StorageEngine, query: &str, lang: Option<&str>) -> Vec<String> {
+    store.fulltext_search_subjects(query, lang, None, None).unwrap().into_iter()
+        .map(|(s, _)| Term::from_node(store, &s).unwrap().to_ntriples())
+        .collect()
+}
+
+#[test]
+fn stems_by_language() {
+    assert_eq!(stem("running", Some("en")), "run");
+    assert_eq!(stem("falling", Some("en-GB")), "fall");
+    assert_eq!(stem("running", None), "running");
+    assert_eq!(analyze("Quick, Foxes!", Some("en")), vec!["quick", "fox"]);
+}
+
+#[test]
+fn query_is_stemmed_in_the_language_of_each_literal() {
+    let store = store("<http://e/a> <http://e/p> \"The dogs were running\"@en .\n\
+                       <http://e/b> <http://e/p> \"running\" .\n\
+                       <http://e/c> <http://e/p> \"nothing here\"@en .\n");
+    let mut found = subjects(&store, "runs", None);
+    found.sort();
+    assert_eq!(found, vec!["<http://e/a>"]);
+    let mut found = subjects(&store, "running", None);
+    found.sort();
+    assert_eq!(found, vec!["<http://e/a>", "<http://e/b>"]);
+}
+
+#[test]
+fn language_restricts_matches() {
+    let store = store("<http://e/a> <http://e/p> \"running\"@en .\n\
+                       <http://e/b> <http://e/p> \"running\" .\n");
+    assert_eq!(subjects(&store, "runs", Some("en")), vec!["<http://e/a>"]);
+    assert!(subjects(&store, "running", Some("fr")).is_empty());
+}
+
+#[test]
+fn ranks_and_unindexes() {
+    let mut store = store("<http://e/a> <http://e/p> \"apple apple pie\" .\n\
+                           <http://e/b> <http://e/p> \"apple crumble with custard and cream\" .\n");
+    assert_eq!(subjects(&store, "apple", None), vec!["<http://e/a>", "<http://e/b>"]);
+    store.update("DELETE DATA { <http://e/a> <http://e/p> \"apple apple pie\" }").unwrap();
+    assert_eq!(subjects(&store, "apple", None), vec!["<http://e/b>"]);
+}
+
+#[test]
+fn search_needs_the_index() {
+    let store = StorageEngine::default();
+    assert!(store.fulltext_search("anything", None).is_err());
+}
+
+#[test]
+fn match_predicate_in_queries() {
+    let store = store("<http://e/a> <http://e/p> \"walked home\"@en .\n<http://e/b> <http://e/p> \"stayed in\"@en .\n");
+    let result = store.query("SELECT ?s WHERE { ?s <http://qstore.internal/fulltext#match> \"walking\" }").unwrap();
+    match result {
+        qstore::sparql::QueryResult::Solutions { rows, .. } => {
+            assert_eq!(rows, vec![vec![Some(Term::Iri("http://e/a".to_owned()))]]);
+        }
+        _ => panic!("expected solutions"),
+    }
+}
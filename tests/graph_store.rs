@@ -0,0 +1,107 @@
+extern crate qstore;
+
+use qstore::http::{self, Request, Response};
+use qstore::server::{Endpoint, GRAPH_STORE_PATH};
+use qstore::store::StorageEngine;
+
+fn request(method: &str, target: &str, content_type: Option<&str>, body: &str) -> Request {
+    let mut headers = vec![("accept".to_owned(), "application/n-triples".to_owned())];
+    if let Some(content_type) = content_type {
+        headers.push(("content-type".to_owned(), content_type.to_owned()));
+    }
+    Request {
+        method: method.to_owned(),
+        path: GRAPH_STORE_PATH.to_owned(),
+        query: http::parse_form(target),
+        headers,
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+fn send(endpoint: &mut Endpoint, method: &str, target: &str, body: &str) -> Response {
+    let content_type = if body.is_empty() { None } else { Some("text/turtle") };
+    endpoint.handle(&request(method, target, content_type, body))
+}
+
+fn body(response: &Response) -> String {
+    String::from_utf8(response.body.clone()).unwrap()
+}
+
+const GRAPH: &str = "graph=http%3A%2F%2Fe%2Fg";
+
+#[test]
+fn put_creates_then_replaces_a_graph() {
+    let mut endpoint = Endpoint::new(StorageEngine::default());
+    assert_eq!(send(&mut endpoint, "PUT", GRAPH, "<http://e/a> <http://e/p> <http://e/b> .").status, 201);
+    assert_eq!(send(&mut endpoint, "PUT", GRAPH, "<http://e/c> <http://e/p> \"x\" .").status, 204);
+    let response = send(&mut endpoint, "GET", GRAPH, "");
+    assert_eq!(response.status, 200);
+    assert_eq!(response.content_type().as_deref(), Some("application/n-triples"));
+    assert_eq!(body(&response).trim(), "<http://e/c> <http://e/p> \"x\" .");
+    assert_eq!(endpoint.borrow_store().quad_count(), 1);
+}
+
+#[test]
+fn post_adds_to_a_graph() {
+    let mut endpoint = Endpoint::new(StorageEngine::default());
+    assert_eq!(send(&mut endpoint, "POST", GRAPH, "@prefix e: <http://e/> . e:a e:p e:b .").status, 201);
+    assert_eq!(send(&mut endpoint, "POST", GRAPH, "<http://e/a> <http://e/p> <http://e/c> .").status, 204);
+    let text = body(&send(&mut endpoint, "GET", GRAPH, ""));
+    assert!(text.contains("<http://e/a> <http://e/p> <http://e/b> ."));
+    assert!(text.contains("<http://e/a> <http://e/p> <http://e/c> ."));
+}
+
+#[test]
+fn blank_nodes_are_scoped_to_each_upload() {
+    let mut endpoint = Endpoint::new(StorageEngine::default());
+    send(&mut endpoint, "POST", GRAPH, "_:b <http://e/p> \"1\" .");
+    send(&mut endpoint, "POST", GRAPH, "_:b <http://e/p> \"2\" .");
+    let text = body(&send(&mut endpoint, "GET", GRAPH, ""));
+    let subjects: Vec<&str> = text.lines().map(|line| line.split(' ').next().unwrap()).collect();
+    assert_eq!(subjects.len(), 2);
+    assert!(subjects[0] != subjects[1]);
+}
+
+#[test]
+fn missing_graphs_are_not_found() {
+    let mut endpoint = Endpoint::new(StorageEngine::default());
+    assert_eq!(send(&mut endpoint, "GET", GRAPH, "").status, 404);
+    assert_eq!(send(&mut endpoint, "HEAD", GRAPH, "").status, 404);
+    assert_eq!(send(&mut endpoint, "DELETE", GRAPH, "").status, 404);
+    assert_eq!(send(&mut endpoint, "GET", "default", "").status, 200);
+}
+
+#[test]
+fn delete_removes_a_graph() {
+    let mut endpoint = Endpoint::new(StorageEngine::default());
+    send(&mut endpoint, "PUT", GRAPH, "<http://e/a> <http://e/p> <http://e/b> .");
+    send(&mut endpoint, "PUT", "default", "<http://e/a> <http://e/p> <http://e/d> .");
+    assert_eq!(send(&mut endpoint, "HEAD", GRAPH, "").status, 200);
+    assert_eq!(send(&mut endpoint, "DELETE", GRAPH, "").status, 204);
+    assert_eq!(send(&mut endpoint, "GET", GRAPH, "").status, 404);
+    assert_eq!(body(&send(&mut endpoint, "GET", "default", "")).trim(), "<http://e/a> <http://e/p> <http://e/d> .");
+}
+
+#[test]
+fn bad_requests_are_refused() {
+    let mut endpoint = Endpoint::new(StorageEngine::default());
+    assert_eq!(send(&mut endpoint, "GET", "", "").status, 400);
+    assert_eq!(send(&mut endpoint, "GET", &format!("default&{}", GRAPH), "").status, 400);
+    assert_eq!(send(&mut endpoint, "GET", "graph=not%20an%20iri", "").status, 400);
+    assert_eq!(send(&mut endpoint, "PUT", GRAPH, "<http://e/a> <http://e/p> .").status, 400);
+    assert_eq!(endpoint.handle(&request("PUT", GRAPH, Some("application/json"), "{}")).status, 415);
+    assert_eq!(endpoint.handle(&request("PUT", GRAPH, None, "")).status, 415);
+    assert_eq!(send(&mut endpoint, "PATCH", GRAPH, "").status, 405);
+    let mut accept_json = request("GET", "default", None, "");
+    accept_json.headers[0].1 = "application/json".to_owned();
+    assert_eq!(endpoint.handle(&accept_json).status, 406);
+    assert_eq!(endpoint.borrow_store().quad_count(), 0);
+}
+
+#[test]
+fn a_read_only_store_refuses_writes() {
+    let mut endpoint = Endpoint::new(StorageEngine::default()).with_read_only(true);
+    assert_eq!(send(&mut endpoint, "PUT", GRAPH, "<http://e/a> <http://e/p> <http://e/b> .").status, 403);
+    assert_eq!(send(&mut endpoint, "DELETE", "default", "").status, 403);
+    assert_eq!(send(&mut endpoint, "GET", "default", "").status, 200);
+}
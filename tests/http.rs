@@ -0,0 +1,105 @@
+extern crate qstore;
+
+use std::io::{Cursor, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use qstore::http::{self, HttpClient, Request, TcpHttpClient};
+use qstore::server::Endpoint;
+use qstore::store::StorageEngine;
+
+fn read(text: &str, max_body: usize) -> Result<Request, u16> {
+    http::read_request(&mut Cursor::new(text.as_bytes().to_vec()), max_body).map(|r| r.unwrap()).map_err(|(status, _)| status)
+}
+
+fn chunked(body: &str) -> String {
+    format!("POST /sparql HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n{}", body)
+}
+
+#[test]
+fn chunks_are_joined() {
+    let request = read(&chunked("5\r\nhello\r\n6;name=x\r\n world\r\n0\r\nTrailer: y\r\n\r\n"), 100).unwrap();
+    assert_eq!(request.body, b"hello world".to_vec());
+}
+
+#[test]
+fn an_overflowing_chunk_size_is_too_large() {
+    assert_eq!(read(&chunked("ffffffffffffffffffff\r\nx\r\n0\r\n\r\n"), 100).unwrap_err(), 413);
+    assert_eq!(read(&chunked("5\r\nhello\r\nffffffffffffffff\r\nx\r\n0\r\n\r\n"), 100).unwrap_err(), 413);
+}
+
+#[test]
+fn chunks_beyond_the_limit_are_too_large() {
+    assert_eq!(read(&chunked("5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"), 8).unwrap_err(), 413);
+}
+
+#[test]
+fn chunk_data_must_end_with_a_line_break() {
+    assert_eq!(read(&chunked("5\r\nhelloX\r\n0\r\n\r\n"), 100).unwrap_err(), 400);
+    assert_eq!(read(&chunked("5\r\nhel"), 100).unwrap_err(), 400);
+}
+
+#[test]
+fn many_small_chunks_are_not_limited_by_the_head_size() {
+    let body = "1\r\nx\r\n".repeat(http::MAX_HEAD_SIZE / 4);
+    let request = read(&chunked(&format!("{}0\r\n\r\n", body)), http::MAX_HEAD_SIZE).unwrap();
+    assert_eq!(request.body.len(), http::MAX_HEAD_SIZE / 4);
+}
+
+#[test]
+fn a_long_chunk_size_line_is_refused() {
+    let extension = "x".repeat(http::MAX_CHUNK_LINE_SIZE);
+    assert_eq!(read(&chunked(&format!("5;{}\r\nhello\r\n0\r\n\r\n", extension)), 100).unwrap_err(), 400);
+}
+
+#[test]
+fn an_overflowing_content_length_is_too_large() {
+    let text = "POST /sparql HTTP/1.1\r\nContent-Length: 99999999999999999999999\r\n\r\n";
+    assert_eq!(read(text, 100).unwrap_err(), 413);
+    assert_eq!(read("POST /sparql HTTP/1.1\r\nContent-Length: -1\r\n\r\n", 100).unwrap_err(), 400);
+}
+
+#[test]
+fn a_slow_client_cannot_hold_the_endpoint() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut endpoint = Endpoint::new(StorageEngine::default()).with_request_timeout(Duration::from_millis(300));
+        let _ = endpoint.serve(&listener, 1000);
+    });
+    let started = Instant::now();
+    let mut slow = TcpStream::connect(address).unwrap();
+    slow.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+    let mut reply = Vec::new();
+    for byte in b"GET /sparql?query=ASK%7B%7D HTTP/1.1\r\n".iter().cycle() {
+        assert!(started.elapsed() < Duration::from_secs(5), "the slow request was never cut off");
+        if slow.write_all(&[*byte]).is_err() || slow.read_to_end(&mut reply).map(|n| n > 0).unwrap_or(false) {
+            break;
+        }
+    }
+    assert!(String::from_utf8_lossy(&reply).starts_with("HTTP/1.1 408"));
+    let mut fast = TcpStream::connect(address).unwrap();
+    fast.write_all(b"GET /sparql?query=ASK%7B%7D HTTP/1.1\r\nAccept: application/sparql-results+json\r\n\r\n").unwrap();
+    let mut reply = String::new();
+    fast.read_to_string(&mut reply).unwrap();
+    assert!(reply.starts_with("HTTP/1.1 200"), "{}", reply);
+}
+
+#[test]
+fn a_trickling_response_cannot_outlast_the_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/sparql", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        for byte in b"HTTP/1.1 200 OK\r\n".iter().cycle().take(100) {
+            thread::sleep(Duration::from_millis(50));
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+        }
+    });
+    let started = Instant::now();
+    let result = TcpHttpClient::default().send_with_timeout("GET", &url, &[], b"", Duration::from_millis(300));
+    assert!(result.is_err());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
@@ -0,0 +1,51 @@
+extern crate qstore;
+
+use qstore::iri::{resolve_iri, IriRef};
+use qstore::store::StorageEngine;
+
+#[test]
+fn resolves_rfc3986_examples() {
+    let base = "http://a/b/c/d;p?q";
+    let examples = [
+        ("g:h", "g:h"), ("g", "http://a/b/c/g"), ("./g", "http://a/b/c/g"), ("g/", "http://a/b/c/g/"),
+        ("/g", "http://a/g"), ("//g", "http://g"), ("?y", "http://a/b/c/d;p?y"), ("g?y", "http://a/b/c/g?y"),
+        ("#s", "http://a/b/c/d;p?q#s"), ("g#s", "http://a/b/c/g#s"), ("", "http://a/b/c/d;p?q"),
+        (".", "http://a/b/c/"), ("./", "http://a/b/c/"), ("..", "http://a/b/"), ("../g", "http://a/b/g"),
+        ("../..", "http://a/"), ("../../g", "http://a/g"), ("../../../g", "http://a/g"), ("/./g", "http://a/g"),
+        ("/../g", "http://a/g"), ("g.", "http://a/b/c/g."), ("..g", "http://a/b/c/..g"),
+        ("./g/.", "http://a/b/c/g/"), ("g/./h", "http://a/b/c/g/h"), ("g/../h", "http://a/b/c/h"),
+        ("g;x=1/../y", "http://a/b/c/y"),
+    ];
+    for &(reference, expected) in examples.iter() {
+        assert_eq!(resolve_iri(base, reference).unwrap(), expected, "resolving {:?}", reference);
+    }
+}
+
+#[test]
+fn validates_iris() {
+    assert!(IriRef::parse_absolute("http://example.org/caf\u{e9}?q=1#f").is_ok());
+    assert!(IriRef::parse_absolute("relative/path").is_err());
+    assert!(IriRef::parse("1http://example.org/").is_err());
+    assert!(IriRef::parse("http://example.org/a b").is_err());
+    assert!(IriRef::parse("http://example.org/%zz").is_err());
+    assert!(resolve_iri("relative", "g").is_err());
+}
+
+#[test]
+fn normalizes_case_percent_encoding_and_dots() {
+    let iri = IriRef::parse("HTTP://Example.ORG/a/./b/../c/%7euser/%2f?%7e#%7E").unwrap();
+    assert_eq!(iri.normalize().to_string(), "http://example.org/a/c/~user/%2F?~#~");
+    assert_eq!(iri.borrow_scheme(), Some("HTTP"));
+    assert_eq!(iri.borrow_authority(), Some("Example.ORG"));
+}
+
+#[test]
+fn store_normalizes_when_enabled() {
+    let mut store = StorageEngine::default();
+    assert!(store.uri_str_to_internal_id("not an iri").is_err());
+    let plain = store.uri_str_to_internal_id("http://Example.org/a/../b").unwrap();
+    store.set_iri_normalization(true);
+    let normalized = store.uri_str_to_internal_id("HTTP://example.ORG/b").unwrap();
+    assert_eq!(store.uri_str_to_internal_id("http://example.org/./b").unwrap(), normalized);
+    assert!(plain != normalized);
+}
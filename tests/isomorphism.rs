@@ -0,0 +1,71 @@
+extern crate qstore;
+
+use qstore::isomorphism::Comparison;
+use qstore::nquads;
+use qstore::store::StorageEngine;
+
+
+fn compare(first: &str, second: &str) -> Comparison {
+    let (mut a, mut b) = (StorageEngine::default(), StorageEngine::default());
+    nquads::load(&mut a, first, None).unwrap();
+    nquads::load(&mut b, second, None).unwrap();
+    a.compare_graphs(None, &b, None).unwrap()
+}
+
+/* Every ordered pair of distinct blank nodes among `n`, which canonicalization cannot tell apart cheaply. */
+fn complete(n: usize, label: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for i in 0..n {
+        for j in (0..n).filter(|&j| j != i) {
+            lines.push(format!("_:{}{} <http://e/p> _:{}{} .\n", label, i, label, j));
+        }
+    }
+    lines
+}
+
+#[test]
+fn relabelled_graphs_are_isomorphic() {
+    let comparison = compare("_:a <http://e/p> _:b .\n_:b <http://e/q> \"x\" .\n", "_:y <http://e/q> \"x\" .\n_:z <http://e/p> _:y .\n");
+    assert_eq!(comparison.isomorphic, Some(true));
+    assert_eq!(comparison.mapping.len(), 2);
+}
+
+#[test]
+fn differences_are_listed_per_side() {
+    let comparison = compare("_:a <http://e/p> \"x\" .\n_:a <http://e/q> \"y\" .\n", "_:b <http://e/p> \"x\" .\n_:b <http://e/q> \"z\" .\n");
+    assert_eq!(comparison.isomorphic, Some(false));
+    assert_eq!(comparison.only_in_first.len(), 1);
+    assert_eq!(comparison.only_in_second.len(), 1);
+}
+
+#[test]
+fn a_full_match_decides_isomorphism_past_the_work_limit() {
+    let first: String = complete(7, "a").concat();
+    let mut second = complete(7, "b");
+    second.reverse();
+    let comparison = compare(&first, &second.concat());
+    assert_eq!(comparison.isomorphic, Some(true));
+}
+
+#[test]
+fn differing_sizes_decide_past_the_work_limit() {
+    let first: String = complete(7, "a").concat();
+    let mut second = complete(7, "b");
+    second.pop();
+    let comparison = compare(&first, &second.concat());
+    assert_eq!(comparison.isomorphic, Some(false));
+}
+
+#[test]
+fn isomorphism_is_left_open_past_the_work_limit() {
+    let first: String = complete(7, "a").concat();
+    let mut second = complete(7, "b");
+    second.pop();
+    second.push("_:b0 <http://e/p> _:b0 .\n".to_owned());
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &first, None).unwrap();
+    assert!(store.canonicalize(None, None).is_err());
+    let comparison = compare(&first, &second.concat());
+    assert_eq!(comparison.isomorphic, None);
+    assert!(!comparison.only_in_first.is_empty());
+}
@@ -0,0 +1,101 @@
+extern crate qstore;
+
+use std::thread;
+use std::time::Duration;
+use qstore::limits::{CancellationToken, LimitKind, Limits, OperationError};
+use qstore::nquads;
+use qstore::patch::Patch;
+use qstore::store::StorageEngine;
+
+const CROSS_JOIN: &str = "SELECT * WHERE { ?a ?p ?b . ?c ?q ?d . ?e ?r ?f }";
+
+/* 200 quads, so a three-way cross join of them has eight million solutions. */
+fn store() -> StorageEngine {
+    let mut text = String::new();
+    for i in 0..200 {
+        text.push_str(&format!("<http://e/s{}> <http://e/p> <http://e/o{}> .\n", i, i));
+    }
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &text, None).unwrap();
+    store
+}
+
+fn stopped_by(result: Result<usize, OperationError>) -> Option<LimitKind> {
+    match result {
+        Err(OperationError::Limit(limit)) => Some(limit.kind),
+        _ => None,
+    }
+}
+
+#[test]
+fn intermediate_rows_are_bounded() {
+    let store = store();
+    let result = store.query_with_limits(CROSS_JOIN, &Limits::new().with_max_rows(1000)).map(|_| 0);
+    assert_eq!(stopped_by(result), Some(LimitKind::Rows));
+    let result = store.query_with_limits("SELECT * WHERE { ?s ?p ?o }", &Limits::new().with_max_rows(1000));
+    assert!(result.is_ok());
+}
+
+#[test]
+fn memory_is_bounded() {
+    let store = store();
+    let result = store.query_with_limits(CROSS_JOIN, &Limits::new().with_max_memory(1 << 20)).map(|_| 0);
+    assert_eq!(stopped_by(result), Some(LimitKind::Memory));
+}
+
+#[test]
+fn a_timeout_stops_a_runaway_join() {
+    let store = store();
+    let result = store.query_with_limits(CROSS_JOIN, &Limits::new().with_timeout(Duration::from_millis(50))).map(|_| 0);
+    assert_eq!(stopped_by(result), Some(LimitKind::Timeout));
+}
+
+#[test]
+fn a_token_cancels_from_another_thread() {
+    let store = store();
+    let token = CancellationToken::new();
+    let canceller = token.clone();
+    let handle = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        canceller.cancel();
+    });
+    let result = store.query_with_limits(CROSS_JOIN, &Limits::new().with_cancellation(token)).map(|_| 0);
+    handle.join().unwrap();
+    assert_eq!(stopped_by(result), Some(LimitKind::Cancelled));
+}
+
+#[test]
+fn a_stopped_update_changes_nothing() {
+    let mut store = store();
+    let result = store.update_with_limits("INSERT { ?s <http://e/q> ?o } WHERE { ?s <http://e/p> ?o }", &Limits::new().with_max_rows(100));
+    assert_eq!(stopped_by(result), Some(LimitKind::Rows));
+    assert_eq!(store.quad_count(), 200);
+    let token = CancellationToken::new();
+    token.cancel();
+    let result = store.update_with_limits("DELETE WHERE { ?s ?p ?o }", &Limits::new().with_cancellation(token));
+    assert_eq!(stopped_by(result), Some(LimitKind::Cancelled));
+    assert_eq!(store.quad_count(), 200);
+}
+
+#[test]
+fn bulk_patches_are_bounded() {
+    let mut store = StorageEngine::default();
+    let mut text = String::new();
+    for i in 0..10 {
+        text.push_str(&format!("A <http://e/s{}> <http://e/p> \"{}\" .\n", i, i));
+    }
+    let patch = Patch::parse(&text).unwrap();
+    let result = store.apply_patch_with_limits(&patch, false, &Limits::new().with_max_rows(5));
+    assert_eq!(stopped_by(result), Some(LimitKind::Rows));
+    assert_eq!(store.quad_count(), 0);
+    assert_eq!(store.apply_patch_with_limits(&patch, false, &Limits::new().with_max_rows(10)), Ok(10));
+}
+
+#[test]
+fn other_failures_are_not_limits() {
+    let store = store();
+    match store.query_with_limits("SELECT WHERE", &Limits::new().with_max_rows(10)) {
+        Err(ref e) => assert!(!e.is_limit()),
+        Ok(_) => panic!("an invalid query ran"),
+    }
+}
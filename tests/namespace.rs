@@ -0,0 +1,60 @@
+extern crate qstore;
+
+use std::env;
+use std::fs;
+use qstore::namespace::NamespaceManager;
+use qstore::nquads;
+
+#[test]
+fn bind_keeps_existing_binding_without_replace() {
+    let mut namespaces = NamespaceManager::default();
+    namespaces.bind("ex", "http://example.org/", false).unwrap();
+    assert!(namespaces.bind("ex", "http://other.org/", false).is_err());
+    assert_eq!(namespaces.namespace("ex"), Some("http://example.org/"));
+    namespaces.bind("ex", "http://other.org/", true).unwrap();
+    assert_eq!(namespaces.namespace("ex"), Some("http://other.org/"));
+    assert_eq!(namespaces.prefix("http://example.org/"), None);
+    assert!(namespaces.bind("1x", "http://example.org/", true).is_err());
+    assert!(namespaces.bind("x", "relative/", true).is_err());
+}
+
+#[test]
+fn namespace_under_several_prefixes() {
+    let mut namespaces = NamespaceManager::default();
+    namespaces.bind("a", "http://example.org/", true).unwrap();
+    namespaces.bind("b", "http://example.org/", false).unwrap();
+    assert_eq!(namespaces.namespace("a"), Some("http://example.org/"));
+    assert_eq!(namespaces.namespace("b"), Some("http://example.org/"));
+    assert_eq!(namespaces.prefix("http://example.org/"), Some("a"));
+    namespaces.bind("c", "http://example.org/", true).unwrap();
+    assert_eq!(namespaces.prefix("http://example.org/"), Some("c"));
+    namespaces.unbind("c").unwrap();
+    assert!(namespaces.prefix("http://example.org/").is_some());
+    assert_eq!(namespaces.len(), 2);
+}
+
+#[test]
+fn curies() {
+    let mut namespaces = NamespaceManager::default();
+    namespaces.bind("ex", "http://example.org/", true).unwrap();
+    namespaces.bind("exv", "http://example.org/vocab#", true).unwrap();
+    assert_eq!(namespaces.expand_curie("ex:thing").unwrap(), "http://example.org/thing");
+    assert!(namespaces.expand_curie("nope:thing").is_err());
+    assert_eq!(namespaces.compact_iri("http://example.org/vocab#name"), Some("exv:name".to_owned()));
+    assert_eq!(namespaces.compact_iri("http://example.org/a/b"), None);
+}
+
+#[test]
+fn prefixes_persist_with_the_store() {
+    let path = env::temp_dir().join(format!("qstore-namespace-{}.nq", std::process::id()));
+    let mut store = nquads::open_file(&path).unwrap();
+    nquads::load(&mut store, "<http://example.org/a> <http://example.org/p> \"x\" .\n", None).unwrap();
+    store.borrow_namespace_manager_mut().bind("ex", "http://example.org/", true).unwrap();
+    store.borrow_namespace_manager_mut().bind("", "http://example.org/#", true).unwrap();
+    nquads::save_file(&store, &path).unwrap();
+    let reopened = nquads::open_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(reopened.borrow_namespace_manager().namespace("ex"), Some("http://example.org/"));
+    assert_eq!(reopened.borrow_namespace_manager().namespace(""), Some("http://example.org/#"));
+    assert_eq!(reopened.quad_count(), 1);
+}
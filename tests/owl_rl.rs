@@ -0,0 +1,88 @@
+extern crate qstore;
+
+mod common;
+
+use common::holds;
+use qstore::nquads;
+use qstore::store::StorageEngine;
+use qstore::vocab::RDF_TYPE;
+
+static CHAIN: &str = "<http://e/hasUncle> <http://www.w3.org/2002/07/owl#propertyChainAxiom> _:l1 .\n\
+                      _:l1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#first> <http://e/hasParent> .\n\
+                      _:l1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#rest> _:l2 .\n\
+                      _:l2 <http://www.w3.org/1999/02/22-rdf-syntax-ns#first> <http://e/hasBrother> .\n\
+                      _:l2 <http://www.w3.org/1999/02/22-rdf-syntax-ns#rest> <http://www.w3.org/1999/02/22-rdf-syntax-ns#nil> .\n";
+
+fn reasoner(text: &str) -> StorageEngine {
+    let mut store = StorageEngine::default();
+    store.enable_owl_rl_reasoning().unwrap();
+    store.update(&format!("INSERT DATA {{ {} }}", text)).unwrap();
+    store
+}
+
+fn reasoner_in_order(lines: &[&str]) -> StorageEngine {
+    let mut store = StorageEngine::default();
+    store.enable_owl_rl_reasoning().unwrap();
+    let text: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+    /* One load keeps the blank node labels joined, and adds the quads in the order given. */
+    nquads::load(&mut store, &text, None).unwrap();
+    store
+}
+
+#[test]
+fn property_chains() {
+    let mut store = reasoner(&format!("{}<http://e/ann> <http://e/hasParent> <http://e/bob> .\n<http://e/bob> <http://e/hasBrother> <http://e/cal> .\n", CHAIN));
+    assert!(holds(&mut store, "http://e/ann", "http://e/hasUncle", "http://e/cal"));
+}
+
+#[test]
+fn chain_list_completed_after_the_data() {
+    let mut lines: Vec<&str> = vec!["<http://e/ann> <http://e/hasParent> <http://e/bob> .", "<http://e/bob> <http://e/hasBrother> <http://e/cal> ."];
+    lines.extend(CHAIN.lines());
+    let mut store = reasoner_in_order(&lines);
+    assert!(holds(&mut store, "http://e/ann", "http://e/hasUncle", "http://e/cal"));
+}
+
+#[test]
+fn intersection_and_union_lists_in_any_order() {
+    let lines = [
+        "<http://e/ann> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/Parent> .",
+        "<http://e/ann> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/Woman> .",
+        "<http://e/bea> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/Mother> .",
+        "_:i2 <http://www.w3.org/1999/02/22-rdf-syntax-ns#rest> <http://www.w3.org/1999/02/22-rdf-syntax-ns#nil> .",
+        "_:i2 <http://www.w3.org/1999/02/22-rdf-syntax-ns#first> <http://e/Woman> .",
+        "<http://e/Mother> <http://www.w3.org/2002/07/owl#intersectionOf> _:i1 .",
+        "_:i1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#first> <http://e/Parent> .",
+        "_:i1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#rest> _:i2 .",
+        "<http://e/Person> <http://www.w3.org/2002/07/owl#unionOf> _:u1 .",
+        "_:u1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#first> <http://e/Woman> .",
+        "_:u1 <http://www.w3.org/1999/02/22-rdf-syntax-ns#rest> <http://www.w3.org/1999/02/22-rdf-syntax-ns#nil> .",
+    ];
+    let mut store = reasoner_in_order(&lines);
+    assert!(holds(&mut store, "http://e/ann", RDF_TYPE, "http://e/Mother"));
+    assert!(holds(&mut store, "http://e/bea", RDF_TYPE, "http://e/Parent"));
+    assert!(holds(&mut store, "http://e/bea", RDF_TYPE, "http://e/Woman"));
+    assert!(holds(&mut store, "http://e/ann", RDF_TYPE, "http://e/Person"));
+    assert!(holds(&mut store, "http://e/bea", RDF_TYPE, "http://e/Person"));
+}
+
+#[test]
+fn deleting_a_list_member_retracts_the_chain() {
+    let mut store = reasoner(&format!("{}<http://e/ann> <http://e/hasParent> <http://e/bob> .\n<http://e/bob> <http://e/hasBrother> <http://e/cal> .\n", CHAIN));
+    store.update("DELETE DATA { <http://e/bob> <http://e/hasBrother> <http://e/cal> }").unwrap();
+    assert!(!holds(&mut store, "http://e/ann", "http://e/hasUncle", "http://e/cal"));
+}
+
+#[test]
+fn equality_and_inconsistency() {
+    let mut store = reasoner("<http://e/hasMother> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://www.w3.org/2002/07/owl#FunctionalProperty> .\n\
+                              <http://e/ann> <http://e/hasMother> <http://e/bea> .\n\
+                              <http://e/ann> <http://e/hasMother> <http://e/beatrice> .\n");
+    assert!(holds(&mut store, "http://e/bea", "http://www.w3.org/2002/07/owl#sameAs", "http://e/beatrice"));
+    assert!(store.check_consistency().is_ok());
+    store.update("INSERT DATA { <http://e/bea> <http://www.w3.org/2002/07/owl#differentFrom> <http://e/beatrice> }").unwrap();
+    let inconsistencies = store.check_consistency().unwrap_err();
+    assert!(inconsistencies.iter().any(|i| i.rule == "eq-diff1"));
+    store.update("DELETE DATA { <http://e/bea> <http://www.w3.org/2002/07/owl#differentFrom> <http://e/beatrice> }").unwrap();
+    assert!(store.check_consistency().is_ok());
+}
@@ -0,0 +1,72 @@
+extern crate qstore;
+
+use qstore::nquads::{self, Term};
+use qstore::patch::{Patch, PatchQuad, PatchRow};
+use qstore::store::StorageEngine;
+
+
+fn store_with(data: &str) -> StorageEngine {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, data, None).unwrap();
+    store
+}
+
+fn iri(value: &str) -> Term {
+    Term::Iri(value.to_owned())
+}
+
+fn add_row(subject: Term, predicate: Term, object: Term) -> PatchRow {
+    PatchRow::Add(PatchQuad { subject, predicate, object, graph: None })
+}
+
+#[test]
+fn diff_then_apply_reproduces_the_target() {
+    let old = "<http://e/a> <http://e/p> \"1\" .\n<http://e/a> <http://e/p> \"2\" .\n";
+    let new = "<http://e/a> <http://e/p> \"2\" .\n<http://e/b> <http://e/p> \"3\" <http://e/g> .\n";
+    let mut before = store_with(old);
+    let after = store_with(new);
+    let patch = before.diff(None, &after, None).unwrap();
+    assert_eq!(patch.added().len(), 1);
+    assert_eq!(patch.removed().len(), 1);
+    let reparsed = Patch::parse(&patch.serialize()).unwrap();
+    assert_eq!(before.apply_patch(&reparsed, true).unwrap(), 2);
+    assert_eq!(before.compare_graphs(None, &after, None).unwrap().isomorphic, Some(true));
+}
+
+#[test]
+fn strict_mode_rejects_deleting_absent_quads() {
+    let mut store = store_with("<http://e/a> <http://e/p> \"1\" .\n");
+    let patch = Patch::parse("TX .\nA <http://e/a> <http://e/p> \"2\" .\nD <http://e/a> <http://e/p> \"9\" .\nTC .\n").unwrap();
+    assert!(store.apply_patch(&patch, true).is_err());
+    assert_eq!(store.quad_count(), 1);
+    assert_eq!(store.apply_patch(&patch, false).unwrap(), 1);
+    assert_eq!(store.quad_count(), 2);
+}
+
+#[test]
+fn aborted_transactions_are_skipped() {
+    let mut store = StorageEngine::default();
+    let patch = Patch::parse("TX .\nA <http://e/a> <http://e/p> \"1\" .\nTA .\nTX .\nA <http://e/a> <http://e/p> \"2\" .\nTC .\n").unwrap();
+    assert_eq!(store.apply_patch(&patch, true).unwrap(), 1);
+}
+
+#[test]
+fn invalid_rows_are_rejected_before_anything_changes() {
+    let valid = add_row(iri("http://e/a"), iri("http://e/p"), iri("http://e/o"));
+    let invalid = vec![
+        add_row(iri("relative"), iri("http://e/p"), iri("http://e/o")),
+        add_row(Term::Literal("x".to_owned(), None, None), iri("http://e/p"), iri("http://e/o")),
+        add_row(iri("http://e/a"), Term::Blank("b".to_owned()), iri("http://e/o")),
+        add_row(iri("http://e/a"), iri("http://e/p"), Term::Literal("1".to_owned(), Some("integer".to_owned()), None)),
+        add_row(iri("http://e/a"), iri("http://e/p"), Term::Literal("1".to_owned(), Some("http://e/t".to_owned()), Some("en".to_owned()))),
+        PatchRow::Add(PatchQuad { subject: iri("http://e/a"), predicate: iri("http://e/p"), object: iri("http://e/o"), graph: Some(Term::Literal("g".to_owned(), None, None)) }),
+    ];
+    for row in invalid {
+        let mut patch = Patch::new();
+        patch.push(valid.clone());
+        patch.push(row);
+        let mut store = StorageEngine::default();
+        assert!(store.apply_patch(&patch, true).is_err());
+        assert_eq!(store.quad_count(), 0);
+    }
+}
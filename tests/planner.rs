@@ -0,0 +1,86 @@
+extern crate qstore;
+
+use qstore::identifiers::InternalID;
+use qstore::indexed_quad_set::IndexKind;
+use qstore::nquads::{self, Term};
+use qstore::sparql::algebra::{TermPattern, TriplePattern};
+use qstore::sparql::planner::{Binding, JoinAlgorithm, Planner};
+use qstore::store::StorageEngine;
+
+fn term(text: &str) -> TermPattern {
+    match text.strip_prefix('?') {
+        Some(variable) => TermPattern::Variable(variable.to_owned()),
+        None => TermPattern::Term(Term::Iri(format!("http://e/{}", text))),
+    }
+}
+
+fn pattern(s: &str, p: &str, o: &str) -> TriplePattern {
+    TriplePattern { subject: term(s), predicate: term(p), object: term(o) }
+}
+
+/* 100 people, one of them named, each knowing the next, and each working for one of 5 companies. */
+fn people() -> StorageEngine {
+    let mut text = String::new();
+    for i in 0..100 {
+        text.push_str(&format!("<http://e/p{}> <http://e/type> <http://e/Person> .\n", i));
+        text.push_str(&format!("<http://e/p{}> <http://e/knows> <http://e/p{}> .\n", i, (i + 1) % 100));
+        text.push_str(&format!("<http://e/p{}> <http://e/worksFor> <http://e/c{}> .\n", i, i % 5));
+    }
+    text.push_str("<http://e/p7> <http://e/name> \"Ann\" .\n");
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &text, None).unwrap();
+    store
+}
+
+fn constant(store: &StorageEngine, name: &str) -> Binding {
+    Binding::Constant(Term::Iri(format!("http://e/{}", name)).find(store))
+}
+
+#[test]
+fn estimates_follow_predicate_statistics() {
+    let store = people();
+    let planner = Planner::new(&store);
+    let cardinalities = planner.borrow_cardinalities();
+    let default = InternalID(0.into());
+    assert_eq!(cardinalities.quads(), 301.0);
+    assert_eq!(cardinalities.estimate(Some(&default), &Binding::Free, &constant(&store, "type"), &Binding::Free), 100.0);
+    assert_eq!(cardinalities.estimate(Some(&default), &Binding::Free, &constant(&store, "name"), &Binding::Free), 1.0);
+    assert_eq!(cardinalities.estimate(None, &Binding::Free, &constant(&store, "worksFor"), &constant(&store, "c0")), 20.0);
+    assert_eq!(cardinalities.estimate(None, &Binding::Free, &constant(&store, "missing"), &Binding::Free), 0.0);
+    let all_bound = cardinalities.estimate(None, &constant(&store, "p1"), &constant(&store, "type"), &constant(&store, "Person"));
+    assert!(all_bound <= 1.0);
+}
+
+#[test]
+fn the_most_selective_pattern_goes_first() {
+    let store = people();
+    let plan = Planner::new(&store).plan_bgp(&[pattern("?x", "type", "Person"), pattern("?x", "knows", "?y"), pattern("?x", "name", "?n")],
+                                             &InternalID(0.into()));
+    let order: Vec<TriplePattern> = plan.steps.iter().map(|s| s.pattern.clone()).collect();
+    assert_eq!(order[0], pattern("?x", "name", "?n"));
+    assert_eq!(plan.steps[0].index, Some(IndexKind::POSG));
+    assert_eq!(plan.steps[0].estimated_rows, 1.0);
+    for step in plan.steps[1..].iter() {
+        assert_eq!(step.join, JoinAlgorithm::NestedLoop);
+        assert_eq!(step.join_variables, vec!["x".to_owned()]);
+        assert_eq!(step.index, Some(IndexKind::GSPO));
+    }
+}
+
+#[test]
+fn connected_patterns_are_preferred_to_cross_products() {
+    let store = people();
+    let plan = Planner::new(&store).plan_bgp(&[pattern("?x", "name", "?n"), pattern("?c", "worksFor", "c1"), pattern("?x", "knows", "?c")],
+                                             &InternalID(0.into()));
+    assert_eq!(plan.steps[1].pattern, pattern("?x", "knows", "?c"));
+    assert!(plan.steps.iter().skip(1).all(|s| !s.join_variables.is_empty()));
+}
+
+#[test]
+fn large_joins_scan_instead_of_probing() {
+    let store = people();
+    let plan = Planner::new(&store).plan_bgp(&[pattern("?x", "worksFor", "?c"), pattern("?y", "worksFor", "?c")], &InternalID(0.into()));
+    assert!(plan.steps[1].join != JoinAlgorithm::NestedLoop, "{:?}", plan.steps[1]);
+    let rows = store.query("SELECT (COUNT(*) AS ?n) WHERE { ?x <http://e/worksFor> ?c . ?y <http://e/worksFor> ?c }").unwrap();
+    assert_eq!(rows, store.query("SELECT (2000 AS ?n) WHERE {}").unwrap());
+}
@@ -0,0 +1,107 @@
+extern crate qstore;
+
+use std::collections::BTreeSet;
+use qstore::identifiers::InternalID;
+use qstore::limits::{CancellationToken, Guard, LimitKind, Limits, OperationError};
+use qstore::nquads;
+use qstore::property_path::PropertyPath;
+use qstore::store::StorageEngine;
+
+/* a -broader-> b -broader-> c -broader-> a, with d -broader-> c and labels on a and d. */
+fn store() -> StorageEngine {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "<http://e/a> <http://e/broader> <http://e/b> .\n<http://e/b> <http://e/broader> <http://e/c> .\n\
+        <http://e/c> <http://e/broader> <http://e/a> .\n<http://e/d> <http://e/broader> <http://e/c> .\n\
+        <http://e/a> <http://e/label> \"A\" .\n<http://e/d> <http://e/label> \"D\" .\n", None).unwrap();
+    store.borrow_namespace_manager_mut().bind("e", "http://e/", true).unwrap();
+    store
+}
+
+/* A chain n0 -next-> n1 -next-> ... of `length` links. */
+fn chain(length: usize) -> StorageEngine {
+    let mut text = String::new();
+    for i in 0..length {
+        text.push_str(&format!("<http://e/n{}> <http://e/next> <http://e/n{}> .\n", i, i + 1));
+    }
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, &text, None).unwrap();
+    store
+}
+
+fn id(store: &mut StorageEngine, name: &str) -> InternalID {
+    store.uri_str_to_internal_id(&format!("http://e/{}", name)).unwrap()
+}
+
+fn reached(store: &mut StorageEngine, path: &str, start: &str) -> BTreeSet<InternalID> {
+    let start = id(store, start);
+    PropertyPath::parse(path, store).unwrap().evaluate(store, None, &start)
+}
+
+fn ids(store: &mut StorageEngine, names: &[&str]) -> BTreeSet<InternalID> {
+    names.iter().map(|n| id(store, n)).collect()
+}
+
+#[test]
+fn repetition_terminates_on_cycles() {
+    let mut store = store();
+    assert_eq!(reached(&mut store, "e:broader+", "a"), ids(&mut store, &["a", "b", "c"]));
+    assert_eq!(reached(&mut store, "e:broader*", "d"), ids(&mut store, &["a", "b", "c", "d"]));
+    assert_eq!(reached(&mut store, "e:broader?", "d"), ids(&mut store, &["c", "d"]));
+    assert_eq!(reached(&mut store, "^e:broader+", "c"), ids(&mut store, &["a", "b", "c", "d"]));
+}
+
+#[test]
+fn sequences_alternatives_and_negated_sets() {
+    let mut store = store();
+    assert_eq!(reached(&mut store, "e:broader/e:broader", "d"), ids(&mut store, &["a"]));
+    assert_eq!(reached(&mut store, "^e:broader/e:broader", "c"), ids(&mut store, &["c"]));
+    assert_eq!(reached(&mut store, "e:broader|e:label", "d").len(), 2);
+    assert_eq!(reached(&mut store, "!e:broader", "a").len(), 1);
+    assert_eq!(reached(&mut store, "!(e:label|^e:broader)", "c"), ids(&mut store, &["a"]));
+    assert!(reached(&mut store, "e:missing*", "a").contains(&id(&mut store, "a")));
+}
+
+#[test]
+fn pairs_match_evaluation_from_every_node() {
+    let mut store = store();
+    let nodes = ids(&mut store, &["a", "b", "c", "d"]);
+    for text in ["e:broader+", "e:broader/e:label", "^e:broader/e:label", "e:broader?", "(e:broader|^e:broader)/e:label"].iter() {
+        let path = PropertyPath::parse(text, &store).unwrap();
+        let mut expected = BTreeSet::new();
+        for node in nodes.iter() {
+            for end in path.evaluate(&store, None, node) {
+                expected.insert((node.clone(), end));
+            }
+        }
+        let pairs = path.pairs(&store, None, &Guard::unlimited()).unwrap();
+        assert_eq!(pairs.iter().filter(|p| nodes.contains(&p.0)).cloned().collect::<BTreeSet<_>>(), expected, "{}", text);
+    }
+}
+
+#[test]
+fn closures_are_bounded_by_the_guard() {
+    let mut store = chain(3000);
+    let path = PropertyPath::parse("<http://e/next>*", &store).unwrap();
+    let start = id(&mut store, "n0");
+    let guard = Guard::new(Limits::new().with_max_rows(100));
+    assert!(path.evaluate_guarded(&store, None, &start, &guard).is_err());
+    assert_eq!(guard.exceeded().map(|e| e.kind), Some(LimitKind::Rows));
+    let token = CancellationToken::new();
+    token.cancel();
+    let guard = Guard::new(Limits::new().with_cancellation(token));
+    assert!(path.pairs(&store, None, &guard).is_err());
+    assert_eq!(guard.exceeded().map(|e| e.kind), Some(LimitKind::Cancelled));
+    assert_eq!(path.evaluate(&store, None, &start).len(), 3001);
+}
+
+#[test]
+fn queries_over_paths_obey_limits() {
+    let store = chain(300);
+    let query = "SELECT * WHERE { ?x <http://e/next>+ ?y }";
+    match store.query_with_limits(query, &Limits::new().with_max_rows(1000)) {
+        Err(OperationError::Limit(limit)) => assert_eq!(limit.kind, LimitKind::Rows),
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+    let bound = store.query_with_limits("SELECT * WHERE { <http://e/n290> <http://e/next>+ ?y }", &Limits::new().with_max_rows(1000));
+    assert!(bound.is_ok());
+}
@@ -0,0 +1,75 @@
+extern crate qstore;
+
+use qstore::json::{self, Json};
+use qstore::nquads;
+use qstore::sparql::explain::PlanNode;
+use qstore::store::StorageEngine;
+
+fn store() -> StorageEngine {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "<http://e/a> <http://e/p> <http://e/b> .\n<http://e/b> <http://e/p> <http://e/c> .\n\
+        <http://e/a> <http://e/name> \"A\" .\n<http://e/b> <http://e/q> \"B\" <http://e/g1> .\n\
+        <http://e/c> <http://e/q> \"C\" <http://e/g2> .\n", None).unwrap();
+    store
+}
+
+/* Each operator with its detail, indented by depth. */
+fn outline(node: &PlanNode, depth: usize, out: &mut Vec<String>) {
+    out.push(format!("{}{} {}", "  ".repeat(depth), node.operator, node.detail).trim_end().to_owned());
+    for child in node.children.iter() {
+        outline(child, depth + 1, out);
+    }
+}
+
+#[test]
+fn the_plan_tree_shows_operators_indexes_and_rows() {
+    let plan = store().explain_query("SELECT ?x ?n WHERE { ?x <http://e/p> ?y . ?x <http://e/name> ?n } ORDER BY ?x LIMIT 5").unwrap();
+    let mut lines = Vec::new();
+    outline(&plan, 0, &mut lines);
+    assert_eq!(lines, vec![
+        "select order by ?x, limit 5",
+        "  bgp 2 patterns",
+        "    scan ?x <http://e/name> ?n using POSG",
+        "    nested loop join ?x <http://e/p> ?y using GSPO on ?x",
+    ]);
+    assert_eq!(plan.actual_rows, 1);
+    let bgp = &plan.children[0];
+    assert_eq!(bgp.children[0].actual_rows, 1);
+    assert!(bgp.children.iter().all(|step| step.estimated_rows.is_some()));
+    assert!(plan.estimated_rows.is_none());
+    assert!(plan.time >= bgp.time);
+}
+
+#[test]
+fn repeated_operators_total_their_loops() {
+    let plan = store().explain_query("SELECT * WHERE { GRAPH ?g { ?s <http://e/q> ?o } }").unwrap();
+    let graph = &plan.children[0];
+    assert_eq!(graph.operator, "graph");
+    assert_eq!(graph.actual_rows, 2);
+    let bgp = &graph.children[0];
+    assert_eq!(bgp.loops, 2);
+    assert_eq!(bgp.actual_rows, 2);
+}
+
+#[test]
+fn text_and_json_render_the_same_tree() {
+    let plan = store().explain_query("ASK { ?x <http://e/p> ?y }").unwrap();
+    let text = plan.to_text();
+    assert!(text.starts_with("ask  (rows: 1, time: "), "{}", text);
+    assert!(text.contains("\n  bgp 1 pattern  (estimated rows: "));
+    let scan = text.lines().nth(2).unwrap();
+    assert!(scan.starts_with("    scan ?x <http://e/p> ?y using POSG  (estimated rows: "), "{}", scan);
+    assert!(scan.contains(", rows: 2, time: "));
+    let document = json::parse(&plan.to_json()).unwrap();
+    assert_eq!(document.get("operator").and_then(Json::as_str), Some("ask"));
+    assert_eq!(document.get("estimated_rows"), Some(&Json::Null));
+    let bgp = &document.get("children").and_then(Json::as_array).unwrap()[0];
+    let scan = &bgp.get("children").and_then(Json::as_array).unwrap()[0];
+    assert_eq!(scan.get("detail").and_then(Json::as_str), Some("?x <http://e/p> ?y using POSG"));
+    assert_eq!(scan.get("actual_rows"), Some(&Json::Number("2".to_owned())));
+}
+
+#[test]
+fn invalid_queries_are_errors() {
+    assert!(store().explain_query("SELECT WHERE").is_err());
+}
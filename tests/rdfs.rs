@@ -0,0 +1,113 @@
+extern crate qstore;
+
+mod common;
+
+use common::{add, holds, id, remove};
+use qstore::identifiers::InternalID;
+use qstore::nquads::{self, Term};
+use qstore::store::StorageEngine;
+use qstore::vocab::{RDF_TYPE as TYPE, RDFS_DOMAIN, RDFS_SUB_CLASS_OF as SUB_CLASS_OF, RDFS_SUB_PROPERTY_OF};
+
+fn reasoner() -> StorageEngine {
+    let mut store = StorageEngine::default();
+    store.enable_rdfs_reasoning().unwrap();
+    store
+}
+
+#[test]
+fn materializes_existing_and_added_quads() {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "<http://e/Dog> <http://www.w3.org/2000/01/rdf-schema#subClassOf> <http://e/Animal> .\n\
+                              <http://e/rex> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/Dog> .\n", None).unwrap();
+    store.enable_rdfs_reasoning().unwrap();
+    assert!(holds(&mut store, "http://e/rex", TYPE, "http://e/Animal"));
+    add(&mut store, "http://e/Animal", SUB_CLASS_OF, "http://e/Thing");
+    assert!(holds(&mut store, "http://e/rex", TYPE, "http://e/Thing"));
+    assert!(holds(&mut store, "http://e/Dog", SUB_CLASS_OF, "http://e/Thing"));
+    assert_eq!(store.quad_count(), 3);
+    assert!(store.inferred_quad_count() >= 3);
+}
+
+#[test]
+fn deleting_a_premise_retracts_its_consequences() {
+    let mut store = reasoner();
+    add(&mut store, "http://e/A", SUB_CLASS_OF, "http://e/B");
+    add(&mut store, "http://e/B", SUB_CLASS_OF, "http://e/C");
+    add(&mut store, "http://e/x", TYPE, "http://e/A");
+    assert!(holds(&mut store, "http://e/x", TYPE, "http://e/C"));
+    remove(&mut store, "http://e/B", SUB_CLASS_OF, "http://e/C");
+    assert!(holds(&mut store, "http://e/x", TYPE, "http://e/B"));
+    assert!(!holds(&mut store, "http://e/x", TYPE, "http://e/C"));
+    assert!(!holds(&mut store, "http://e/A", SUB_CLASS_OF, "http://e/C"));
+}
+
+#[test]
+fn quads_with_other_support_survive_a_delete() {
+    let mut store = reasoner();
+    add(&mut store, "http://e/A", SUB_CLASS_OF, "http://e/C");
+    add(&mut store, "http://e/B", SUB_CLASS_OF, "http://e/C");
+    add(&mut store, "http://e/x", TYPE, "http://e/A");
+    add(&mut store, "http://e/x", TYPE, "http://e/B");
+    add(&mut store, "http://e/C", SUB_CLASS_OF, "http://e/D");
+    remove(&mut store, "http://e/x", TYPE, "http://e/A");
+    assert!(holds(&mut store, "http://e/x", TYPE, "http://e/C"));
+    assert!(holds(&mut store, "http://e/x", TYPE, "http://e/D"));
+    let (x, t, c) = (id(&mut store, "http://e/x"), id(&mut store, TYPE), id(&mut store, "http://e/C"));
+    assert!(store.explain(InternalID(0.into()), x, t, c).is_some());
+}
+
+#[test]
+fn removed_assertion_that_still_follows_becomes_inferred() {
+    let mut store = reasoner();
+    add(&mut store, "http://e/A", SUB_CLASS_OF, "http://e/B");
+    add(&mut store, "http://e/x", TYPE, "http://e/A");
+    add(&mut store, "http://e/x", TYPE, "http://e/B");
+    remove(&mut store, "http://e/x", TYPE, "http://e/B");
+    assert!(holds(&mut store, "http://e/x", TYPE, "http://e/B"));
+    assert_eq!(store.quad_count(), 2);
+}
+
+#[test]
+fn cycles_do_not_keep_themselves_alive() {
+    let mut store = reasoner();
+    add(&mut store, "http://e/A", SUB_CLASS_OF, "http://e/B");
+    add(&mut store, "http://e/B", SUB_CLASS_OF, "http://e/A");
+    add(&mut store, "http://e/x", TYPE, "http://e/A");
+    assert!(holds(&mut store, "http://e/x", TYPE, "http://e/B"));
+    remove(&mut store, "http://e/x", TYPE, "http://e/A");
+    assert!(!holds(&mut store, "http://e/x", TYPE, "http://e/A"));
+    assert!(!holds(&mut store, "http://e/x", TYPE, "http://e/B"));
+}
+
+#[test]
+fn incremental_matches_rematerialization() {
+    let mut store = reasoner();
+    let (domain, sub_property_of) = (RDFS_DOMAIN, RDFS_SUB_PROPERTY_OF);
+    add(&mut store, "http://e/hasPet", domain, "http://e/Owner");
+    add(&mut store, "http://e/hasDog", sub_property_of, "http://e/hasPet");
+    add(&mut store, "http://e/Owner", SUB_CLASS_OF, "http://e/Person");
+    add(&mut store, "http://e/ann", "http://e/hasDog", "http://e/rex");
+    add(&mut store, "http://e/bob", "http://e/hasPet", "http://e/tom");
+    remove(&mut store, "http://e/hasDog", sub_property_of, "http://e/hasPet");
+    let incremental = nquads_of_inferred(&store);
+    store.rematerialize(None);
+    assert_eq!(incremental, nquads_of_inferred(&store));
+    assert!(!holds(&mut store, "http://e/ann", TYPE, "http://e/Person"));
+    assert!(holds(&mut store, "http://e/bob", TYPE, "http://e/Person"));
+}
+
+fn nquads_of_inferred(store: &StorageEngine) -> Vec<String> {
+    let mut lines: Vec<String> = store.search_engine_inferred(None, None, None, None)
+        .map(|(_, s, p, o)| [s, p, o].iter().map(|t| Term::from_node(store, t).unwrap().to_ntriples()).collect::<Vec<String>>().join(" "))
+        .collect();
+    lines.sort();
+    lines
+}
+
+#[test]
+fn rules_stay_within_a_graph() {
+    let mut store = reasoner();
+    nquads::load(&mut store, "<http://e/A> <http://www.w3.org/2000/01/rdf-schema#subClassOf> <http://e/B> <http://e/schema> .\n\
+                              <http://e/x> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://e/A> <http://e/data> .\n", None).unwrap();
+    assert_eq!(store.inferred_quad_count(), 0);
+}
@@ -0,0 +1,36 @@
+extern crate qstore;
+
+use qstore::regex::Regex;
+
+
+#[test]
+fn counted_quantifiers_match() {
+    let regex = Regex::new("^a{2,3}$", "").unwrap();
+    assert!(!regex.is_match("a"));
+    assert!(regex.is_match("aa"));
+    assert!(regex.is_match("aaa"));
+    assert!(!regex.is_match("aaaa"));
+    assert!(Regex::new("^a{2,}$", "").unwrap().is_match("aaaaaa"));
+    assert!(Regex::new("^(ab){2}$", "").unwrap().is_match("abab"));
+}
+
+#[test]
+fn counts_out_of_order_are_rejected() {
+    assert!(Regex::new("a{3,2}", "").is_err());
+    assert!(Regex::new("a{2,2}", "").is_ok());
+}
+
+#[test]
+fn large_counts_are_rejected() {
+    assert!(Regex::new("a{1000}", "").is_ok());
+    assert!(Regex::new("a{1001}", "").is_err());
+    assert!(Regex::new("a{1,99999999999999999999999}", "").is_err());
+    assert!(Regex::new("((a{1000}){1000}){1000}", "").is_err());
+}
+
+#[test]
+fn flags_are_checked() {
+    assert!(Regex::new("A", "i").unwrap().is_match("a"));
+    assert!(Regex::new("a+", "q").unwrap().is_match("xa+"));
+    assert!(Regex::new("a", "z").is_err());
+}
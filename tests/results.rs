@@ -0,0 +1,85 @@
+extern crate qstore;
+
+use qstore::namespace::NamespaceManager;
+use qstore::nquads::Term;
+use qstore::sparql::QueryResult;
+use qstore::sparql::algebra::{Expression, QueryForm};
+use qstore::sparql::explain::expression_text;
+use qstore::sparql::parser;
+use qstore::sparql::results::{self, ResultFormat};
+
+fn solutions() -> QueryResult {
+    let literal = |value: &str, datatype: Option<&str>, lang: Option<&str>|
+        Some(Term::Literal(value.to_owned(), datatype.map(|d| d.to_owned()), lang.map(|l| l.to_owned())));
+    QueryResult::Solutions {
+        variables: vec!["s".to_owned(), "o".to_owned()],
+        rows: vec![
+            vec![Some(Term::Iri("http://e/a?x=1&y=2".to_owned())), literal("say \"hi\",\n\tthen <go>", None, None)],
+            vec![Some(Term::Blank("b0".to_owned())), literal("42", Some("http://www.w3.org/2001/XMLSchema#integer"), None)],
+            vec![None, literal("chat", None, Some("fr"))],
+            vec![Some(Term::Iri("http://e/é".to_owned())), None],
+        ],
+    }
+}
+
+fn round_trip(result: &QueryResult, format: ResultFormat) -> QueryResult {
+    let mut out = Vec::new();
+    results::write(result, format, &mut out).unwrap();
+    results::read(&String::from_utf8(out).unwrap(), format).unwrap()
+}
+
+#[test]
+fn solutions_survive_json_xml_and_tsv() {
+    for &format in [ResultFormat::Json, ResultFormat::Xml, ResultFormat::Tsv].iter() {
+        assert_eq!(round_trip(&solutions(), format), solutions(), "{:?}", format);
+    }
+}
+
+#[test]
+fn csv_keeps_the_text_of_terms() {
+    let text = |value: &str| Some(Term::Literal(value.to_owned(), None, None));
+    match round_trip(&solutions(), ResultFormat::Csv) {
+        QueryResult::Solutions { variables, rows } => {
+            assert_eq!(variables, vec!["s".to_owned(), "o".to_owned()]);
+            assert_eq!(rows[0], vec![text("http://e/a?x=1&y=2"), text("say \"hi\",\n\tthen <go>")]);
+            assert_eq!(rows[1][1], text("42"));
+            assert_eq!(rows[2][0], None);
+            assert_eq!(rows[3][1], None);
+        }
+        other => panic!("read {:?}", other),
+    }
+}
+
+#[test]
+fn booleans_survive_json_and_xml() {
+    for &format in [ResultFormat::Json, ResultFormat::Xml].iter() {
+        assert_eq!(round_trip(&QueryResult::Boolean(true), format), QueryResult::Boolean(true));
+        assert_eq!(round_trip(&QueryResult::Boolean(false), format), QueryResult::Boolean(false));
+    }
+}
+
+#[test]
+fn solution_writers_stream_rows() {
+    let rows = (0..3).map(|i| vec![Some(Term::Literal(i.to_string(), None, None))]);
+    let mut out = Vec::new();
+    results::write_solutions(&["n".to_owned()], rows, ResultFormat::Tsv, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "?n\n\"0\"\n\"1\"\n\"2\"\n");
+}
+
+/* The first projected expression of a SELECT query. */
+fn projected(text: &str) -> Expression {
+    match parser::parse_query(text, &NamespaceManager::default()).unwrap().form {
+        QueryForm::Select { projection: Some(mut items), .. } => items.remove(0).expression.unwrap(),
+        form => panic!("parsed {:?}", form),
+    }
+}
+
+#[test]
+fn expression_text_parses_back() {
+    for expression in ["GROUP_CONCAT(DISTINCT ?x; SEPARATOR=\", \")", "GROUP_CONCAT(?x; SEPARATOR=\"\\\"\\n\")", "GROUP_CONCAT(?x)",
+                       "COUNT(*)", "SAMPLE(?x)", "AVG(?x + 1)", "STRLEN(?x) > 2 && !BOUND(?y)"].iter() {
+        let original = projected(&format!("SELECT ({} AS ?v) WHERE {{ ?s ?p ?x }}", expression));
+        let rendered = expression_text(&original);
+        assert_eq!(projected(&format!("SELECT ({} AS ?v) WHERE {{ ?s ?p ?x }}", rendered)), original, "{}", rendered);
+    }
+}
@@ -0,0 +1,71 @@
+extern crate qstore;
+
+use qstore::identifiers::InternalID;
+use qstore::nquads;
+use qstore::shacl::ValidationReport;
+use qstore::store::StorageEngine;
+
+
+static SHAPE: &str = "<http://e/S> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <http://www.w3.org/ns/shacl#NodeShape> .\n\
+                      <http://e/S> <http://www.w3.org/ns/shacl#targetNode> <http://e/x> .\n\
+                      <http://e/S> <http://www.w3.org/ns/shacl#property> _:p .\n\
+                      _:p <http://www.w3.org/ns/shacl#path> <http://e/value> .\n";
+
+fn validate(constraint: &str, parameter: &str, value: &str) -> ValidationReport {
+    let mut store = StorageEngine::default();
+    let shapes = store.uri_str_to_internal_id("http://e/shapes").unwrap();
+    nquads::load(&mut store, &format!("{}_:p <http://www.w3.org/ns/shacl#{}> {} .\n", SHAPE, constraint, parameter), Some(&shapes)).unwrap();
+    nquads::load(&mut store, &format!("<http://e/x> <http://e/value> {} .\n", value), None).unwrap();
+    store.validate_shacl(shapes, InternalID(0.into()), None).unwrap()
+}
+
+fn components(report: &ValidationReport) -> Vec<&'static str> {
+    report.results.iter().map(|r| r.source_constraint_component).collect()
+}
+
+#[test]
+fn datatype_requires_a_well_typed_literal() {
+    let integer = "<http://www.w3.org/2001/XMLSchema#integer>";
+    assert!(validate("datatype", integer, &format!("\"42\"^^{}", integer)).conforms);
+    let report = validate("datatype", integer, &format!("\"forty-two\"^^{}", integer));
+    assert_eq!(components(&report), vec!["DatatypeConstraintComponent"]);
+    assert!(!validate("datatype", integer, "\"42\"").conforms);
+    let byte = "<http://www.w3.org/2001/XMLSchema#byte>";
+    assert!(!validate("datatype", byte, &format!("\"300\"^^{}", byte)).conforms);
+    let date = "<http://www.w3.org/2001/XMLSchema#date>";
+    assert!(validate("datatype", date, &format!("\"2024-02-29Z\"^^{}", date)).conforms);
+    assert!(!validate("datatype", date, &format!("\"2024-13-01\"^^{}", date)).conforms);
+    assert!(!validate("datatype", date, &format!("\"€€€x\"^^{}", date)).conforms);
+    assert!(!validate("datatype", date, &format!("\"2024-01-01+0€:00\"^^{}", date)).conforms);
+}
+
+#[test]
+fn numeric_ranges_compare_values_not_lexical_forms() {
+    let ten = "\"10\"^^<http://www.w3.org/2001/XMLSchema#integer>";
+    assert!(validate("minInclusive", ten, "\"9.5e1\"^^<http://www.w3.org/2001/XMLSchema#double>").conforms);
+    assert!(!validate("minInclusive", ten, "\"9\"^^<http://www.w3.org/2001/XMLSchema#integer>").conforms);
+}
+
+#[test]
+fn strings_that_look_like_numbers_are_not_numbers() {
+    let ten = "\"10\"^^<http://www.w3.org/2001/XMLSchema#integer>";
+    let report = validate("minInclusive", ten, "\"99\"");
+    assert_eq!(components(&report), vec!["MinInclusiveConstraintComponent"]);
+    let report = validate("maxExclusive", ten, "\"1\"^^<http://e/custom>");
+    assert_eq!(components(&report), vec!["MaxExclusiveConstraintComponent"]);
+}
+
+#[test]
+fn ill_typed_literals_are_incomparable() {
+    let ten = "\"10\"^^<http://www.w3.org/2001/XMLSchema#integer>";
+    assert!(!validate("minInclusive", ten, "\"1x1\"^^<http://www.w3.org/2001/XMLSchema#integer>").conforms);
+}
+
+#[test]
+fn same_typed_strings_and_dates_compare_by_value() {
+    assert!(validate("minExclusive", "\"apple\"", "\"banana\"").conforms);
+    assert!(!validate("minExclusive", "\"banana\"", "\"apple\"").conforms);
+    let date = "<http://www.w3.org/2001/XMLSchema#date>";
+    assert!(validate("maxInclusive", &format!("\"2020-01-01\"^^{}", date), &format!("\"2019-12-31\"^^{}", date)).conforms);
+    assert!(!validate("maxInclusive", &format!("\"2020-01-01\"^^{}", date), "\"2019-12-31\"").conforms);
+}
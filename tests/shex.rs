@@ -0,0 +1,63 @@
+extern crate qstore;
+
+use qstore::nquads;
+use qstore::store::StorageEngine;
+
+
+static PREFIXES: &str = "PREFIX ex: <http://e/>\nPREFIX xsd: <http://www.w3.org/2001/XMLSchema#>\n";
+
+fn validate(schema: &str, data: &str) -> Result<bool, String> {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, data, None).unwrap();
+    let entries = store.validate_shex(&format!("{}{}", PREFIXES, schema), "<http://e/x>@<http://e/S>", None)?;
+    Ok(entries.iter().all(|e| e.conformant))
+}
+
+fn value(object: &str) -> String {
+    format!("<http://e/x> <http://e/v> {} .\n", object)
+}
+
+#[test]
+fn numeric_facets_need_numeric_literals() {
+    let schema = "ex:S { ex:v MININCLUSIVE 10 }";
+    assert!(validate(schema, &value("\"11\"^^<http://www.w3.org/2001/XMLSchema#integer>")).unwrap());
+    assert!(!validate(schema, &value("\"9.5\"^^<http://www.w3.org/2001/XMLSchema#decimal>")).unwrap());
+    assert!(!validate(schema, &value("\"99\"")).unwrap());
+    assert!(!validate(schema, &value("\"1e2\"^^<http://www.w3.org/2001/XMLSchema#integer>")).unwrap());
+    assert!(validate(schema, &value("\"1e2\"^^<http://www.w3.org/2001/XMLSchema#double>")).unwrap());
+}
+
+#[test]
+fn digit_facets_only_apply_to_decimals() {
+    let schema = "ex:S { ex:v TOTALDIGITS 3 }";
+    assert!(validate(schema, &value("\"12.5\"^^<http://www.w3.org/2001/XMLSchema#decimal>")).unwrap());
+    assert!(!validate(schema, &value("\"12.55\"^^<http://www.w3.org/2001/XMLSchema#decimal>")).unwrap());
+    assert!(!validate(schema, &value("\"1.5E0\"^^<http://www.w3.org/2001/XMLSchema#double>")).unwrap());
+}
+
+#[test]
+fn datatype_constraints_reject_ill_typed_literals() {
+    let schema = "ex:S { ex:v xsd:integer }";
+    assert!(validate(schema, &value("\"7\"^^<http://www.w3.org/2001/XMLSchema#integer>")).unwrap());
+    assert!(!validate(schema, &value("\"seven\"^^<http://www.w3.org/2001/XMLSchema#integer>")).unwrap());
+}
+
+#[test]
+fn literals_with_invalid_datatypes_are_rejected() {
+    assert!(validate("ex:S { ex:v [\"a\"^^<rel>] }", &value("\"a\"")).is_err());
+}
+
+#[test]
+fn negation_through_recursion_is_rejected() {
+    assert!(validate("ex:S NOT @ex:S", "").is_err());
+    assert!(validate("ex:S { ex:p NOT @ex:T }\nex:T { ex:q @ex:S }", "").is_err());
+    assert!(validate("ex:S { ex:p NOT @ex:T }\nex:T { ex:q . }", "<http://e/x> <http://e/p> <http://e/y> .\n").unwrap());
+    assert!(validate("ex:S { ex:p @ex:S ? }", "").unwrap());
+}
+
+#[test]
+fn matching_work_is_bounded() {
+    let data: String = (0..40).map(|i| format!("<http://e/x> <http://e/p> <http://e/o{}> .\n", i)).collect();
+    let result = validate("ex:S { ex:p . {5} ; ex:p . {5} ; ex:q . }", &data);
+    assert!(result.unwrap_err().contains("work limit"));
+}
@@ -0,0 +1,135 @@
+extern crate qstore;
+
+use std::env;
+use std::fs;
+use qstore::limits::Limits;
+use qstore::nquads::{self, Term};
+use qstore::server::Endpoint;
+use qstore::sparql::QueryResult;
+use qstore::sparql::eval::Evaluator;
+use qstore::sparql::parser;
+use qstore::store::StorageEngine;
+
+fn select(store: &StorageEngine, text: &str) -> Vec<Vec<Option<Term>>> {
+    let query = parser::parse_query(text, store.borrow_namespace_manager()).unwrap();
+    match Evaluator::new(store).execute(&query).unwrap() {
+        QueryResult::Solutions { rows, .. } => rows,
+        _ => panic!("not a SELECT query"),
+    }
+}
+
+fn literal(lexical: &str, datatype: Option<&str>) -> Option<Term> {
+    Some(Term::Literal(lexical.to_owned(), datatype.map(|d| d.to_owned()), None))
+}
+
+#[test]
+fn exists_sees_the_outer_solution() {
+    let mut store = StorageEngine::default();
+    nquads::load(&mut store, "<http://e/a> <http://e/p> \"1\" .\n<http://e/a> <http://e/q> \"1\" .\n\
+        <http://e/b> <http://e/p> \"2\" .\n<http://e/b> <http://e/q> \"5\" .\n", None).unwrap();
+    let rows = select(&store, "SELECT ?s WHERE { ?s <http://e/p> ?v FILTER EXISTS { ?s <http://e/q> ?w FILTER(?w = ?v) } }");
+    assert_eq!(rows, vec![vec![Some(Term::Iri("http://e/a".to_owned()))]]);
+    let rows = select(&store, "SELECT ?s WHERE { ?s <http://e/p> ?v FILTER NOT EXISTS { ?s <http://e/q> ?w FILTER(?w = ?v) } }");
+    assert_eq!(rows, vec![vec![Some(Term::Iri("http://e/b".to_owned()))]]);
+}
+
+#[test]
+fn integer_overflow_leaves_the_result_unbound() {
+    let store = StorageEngine::default();
+    let rows = select(&store, "SELECT (ABS(?x) AS ?a) (-?x AS ?n) (SUBSTR(\"abc\", 2, ?x) AS ?s) \
+        WHERE { BIND(-9223372036854775807 - 1 AS ?x) }");
+    assert_eq!(rows, vec![vec![None, None, literal("", None)]]);
+    let rows = select(&store, "SELECT (SUBSTR(\"abc\", 2, 9223372036854775807) AS ?s) WHERE {}");
+    assert_eq!(rows, vec![vec![literal("bc", None)]]);
+}
+
+#[test]
+fn order_by_is_total_over_mixed_literals() {
+    let store = StorageEngine::default();
+    let xsd = |t: &str| format!("http://www.w3.org/2001/XMLSchema#{}", t);
+    let rows = select(&store, "SELECT ?x WHERE { VALUES ?x { \"b\" true 2 \"a\"@en \"NaN\"^^<http://www.w3.org/2001/XMLSchema#double> 1.5 false \"a\" } } ORDER BY ?x");
+    let expected = vec![
+        literal("NaN", Some(&xsd("double"))),
+        literal("1.5", Some(&xsd("decimal"))),
+        literal("2", Some(&xsd("integer"))),
+        literal("a", None),
+        literal("b", None),
+        Some(Term::Literal("a".to_owned(), None, Some("en".to_owned()))),
+        literal("false", Some(&xsd("boolean"))),
+        literal("true", Some(&xsd("boolean"))),
+    ];
+    assert_eq!(rows, expected.into_iter().map(|t| vec![t]).collect::<Vec<_>>());
+}
+
+#[test]
+fn date_times_compare_as_instants() {
+    let store = StorageEngine::default();
+    let compare = |a: &str, op: &str, b: &str| {
+        let text = format!("SELECT (\"{}\"^^<http://www.w3.org/2001/XMLSchema#dateTime> {} \"{}\"^^<http://www.w3.org/2001/XMLSchema#dateTime> AS ?r) WHERE {{}}", a, op, b);
+        select(&store, &text).remove(0).remove(0)
+    };
+    let yes = literal("true", Some("http://www.w3.org/2001/XMLSchema#boolean"));
+    assert_eq!(compare("2020-01-01T10:00:00+02:00", "<", "2020-01-01T09:00:00Z"), yes);
+    assert_eq!(compare("2020-01-01T08:00:00Z", "=", "2020-01-01T09:00:00+01:00"), yes);
+    assert_eq!(compare("2020-01-01T00:00:00.5Z", ">", "2020-01-01T00:00:00.25Z"), yes);
+    assert_eq!(compare("2020-01-01T00:00:00.50Z", "=", "2020-01-01T00:00:00.5Z"), yes);
+    assert_eq!(compare("1999-12-31T23:59:59-05:00", ">", "2000-01-01T01:00:00Z"), yes);
+    assert_eq!(compare("2020-01-01T00:00:00", "<", "2020-01-02T00:00:00Z"), None);
+}
+
+#[test]
+fn literals_read_as_in_turtle() {
+    let store = StorageEngine::default();
+    let rows = select(&store, "SELECT ?x WHERE { VALUES ?x { +1 -2.5 \"a\"^^<http://www.w3.org/2001/XMLSchema#string> } }");
+    let turtle = qstore::turtle::parse("<http://e/s> <http://e/p> +1, -2.5, \"a\"^^<http://www.w3.org/2001/XMLSchema#string> .", None).unwrap();
+    assert_eq!(rows, turtle.into_iter().map(|(_, _, o)| vec![Some(o)]).collect::<Vec<_>>());
+}
+
+#[test]
+fn a_regex_pattern_is_reused_across_solutions() {
+    let store = StorageEngine::default();
+    let rows = select(&store, "SELECT ?x WHERE { VALUES ?x { \"Apple\" \"banana\" \"apricot\" } FILTER REGEX(?x, \"^a\", \"i\") }");
+    assert_eq!(rows, vec![vec![literal("Apple", None)], vec![literal("apricot", None)]]);
+    let rows = select(&store, "SELECT ?x WHERE { VALUES (?x ?p) { (\"abc\" \"^a\") (\"abc\" \"^b\") } FILTER REGEX(?x, ?p) }");
+    assert_eq!(rows, vec![vec![literal("abc", None)]]);
+}
+
+fn update(text: &str) -> qstore::http::Request {
+    qstore::http::Request {
+        method: "POST".to_owned(),
+        path: "/sparql".to_owned(),
+        query: Vec::new(),
+        headers: vec![("content-type".to_owned(), "application/sparql-update".to_owned())],
+        body: text.as_bytes().to_vec(),
+    }
+}
+
+#[test]
+fn a_stopped_update_saves_what_it_applied() {
+    let path = env::temp_dir().join(format!("qstore-stopped-update-{}.nq", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut endpoint = Endpoint::open(&path).unwrap().with_limits(Limits::new().with_max_rows(1));
+    let request = update("INSERT DATA { <http://e/a> <http://e/p> <http://e/b> } ; \
+        INSERT DATA { <http://e/c> <http://e/p> <http://e/d> . <http://e/e> <http://e/p> <http://e/f> }");
+    assert_eq!(endpoint.handle(&request).status, 503);
+    assert_eq!(endpoint.borrow_store().quad_count(), 1);
+    assert_eq!(nquads::open_file(&path).unwrap().quad_count(), 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn only_updates_that_change_the_store_are_saved() {
+    let path = env::temp_dir().join(format!("qstore-unchanged-update-{}.nq", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut endpoint = Endpoint::open(&path).unwrap().with_limits(Limits::new().with_max_rows(1));
+    assert_eq!(endpoint.handle(&update("INSERT DATA { <http://e/a> <http://e/p> <http://e/b> }")).status, 204);
+    assert!(path.exists());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(endpoint.handle(&update("DELETE DATA { <http://e/x> <http://e/p> <http://e/y> }")).status, 204);
+    assert_eq!(endpoint.handle(&update("INSERT DATA { <http://e/a> <http://e/p> <http://e/b> }")).status, 204);
+    assert_eq!(endpoint.handle(&update("INSERT DATA { <http://e/c> <http://e/p> <http://e/d> . <http://e/e> <http://e/p> <http://e/f> }")).status, 503);
+    assert!(!path.exists());
+    assert_eq!(endpoint.handle(&update("DELETE DATA { <http://e/a> <http://e/p> <http://e/b> }")).status, 204);
+    assert_eq!(nquads::open_file(&path).unwrap().quad_count(), 0);
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,49 @@
+extern crate qstore;
+
+use qstore::store::StorageEngine;
+use qstore::uri_split::{DelimiterSetSplitter, LastDelimiterSplitter, NamespaceSplitter, UriSplitter};
+
+#[test]
+fn last_delimiter() {
+    let splitter = LastDelimiterSplitter;
+    assert_eq!(splitter.split_index("http://ex.org/a#b"), 16);
+    assert_eq!(splitter.split_index("http://ex.org/a/b"), 16);
+    assert_eq!(splitter.split_index("urn:isbn:123"), 9);
+    assert_eq!(splitter.split_index("nodelimiter"), 0);
+}
+
+#[test]
+fn delimiter_set() {
+    let splitter = DelimiterSetSplitter::new("/#?=&:");
+    let uri = "http://ex.org/item?id=42";
+    assert_eq!(&uri[splitter.split_index(uri)..], "42");
+    let splitter = DelimiterSetSplitter::new("\u{b7}");
+    let uri = "http://ex.org/a\u{b7}b";
+    assert_eq!(&uri[splitter.split_index(uri)..], "b");
+}
+
+#[test]
+fn longest_namespace_wins() {
+    let splitter = NamespaceSplitter::new(&["http://ex.org/", "http://ex.org/vocab/"], Box::new(LastDelimiterSplitter));
+    let uri = "http://ex.org/vocab/a/b";
+    assert_eq!(&uri[splitter.split_index(uri)..], "a/b");
+    let uri = "http://ex.org/thing";
+    assert_eq!(&uri[splitter.split_index(uri)..], "thing");
+    /* The namespace itself, and IRIs outside every namespace, fall back. */
+    let uri = "http://ex.org/vocab/";
+    assert_eq!(splitter.split_index(uri), LastDelimiterSplitter.split_index(uri));
+    let uri = "http://other.org/x/y";
+    assert_eq!(&uri[splitter.split_index(uri)..], "y");
+}
+
+#[test]
+fn store_round_trips_with_any_splitter() {
+    let mut store = StorageEngine::with_uri_splitter(Box::new(DelimiterSetSplitter::new("?=")));
+    let iris = ["http://ex.org/item?id=42", "http://ex.org/item?id=43", "http://ex.org/other"];
+    let ids: Vec<_> = iris.iter().map(|iri| store.uri_to_internal_uri_id(iri).unwrap()).collect();
+    for (iri, id) in iris.iter().zip(ids.iter()) {
+        assert_eq!(store.internal_uri_id_to_uri(id).unwrap(), *iri);
+        assert_eq!(store.uri_to_internal_uri_id_if_exist(iri).unwrap(), *id);
+    }
+    assert_eq!(ids[0].0, ids[1].0);
+}